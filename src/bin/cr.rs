@@ -9,9 +9,10 @@ use std::time::Instant;
 #[cfg(not(target_arch = "wasm32"))]
 mod injection;
 
-use calcit::calcit::LocatedWarning;
+use calcit::calcit::{Calcit, LocatedWarning};
 use calcit::call_stack::CallStackList;
-use calcit::cli_args::{CalcitCommand, ToplevelCalcit};
+use calcit::cli_args::{CalcitCommand, DepsCommand, ToplevelCalcit};
+use calcit::error_report::{self, ErrorFormat, ErrorReport, FailureKind};
 use calcit::snapshot::ChangesDict;
 use calcit::util::string::strip_shebang;
 use dirs::home_dir;
@@ -19,8 +20,8 @@ use notify::RecursiveMode;
 use notify_debouncer_mini::new_debouncer;
 
 use calcit::{
-  builtins, call_stack, cli_args, codegen, codegen::emit_js::gen_stack, codegen::COMPILE_ERRORS_FILE, program, runner, snapshot, util,
-  ProgramEntries,
+  builtins, call_stack, cli_args, codegen, codegen::emit_js::gen_stack, codegen::COMPILE_ERRORS_FILE, inspect, program, runner,
+  snapshot, util, ProgramEntries,
 };
 
 fn main() -> Result<(), String> {
@@ -30,12 +31,24 @@ fn main() -> Result<(), String> {
   #[cfg(not(target_arch = "wasm32"))]
   injection::inject_platform_apis();
 
-  let cli_args: ToplevelCalcit = argh::from_env();
+  let (cli_args, program_args) = cli_args::parse_cli_args();
 
   let mut eval_once = cli_args.once;
   let assets_watch = cli_args.watch_dir.to_owned();
 
-  println!("calcit version: {}", cli_args::CALCIT_VERSION);
+  if let Some(socket_path) = &cli_args.inspect {
+    let once = match &cli_args.subcommand {
+      Some(CalcitCommand::EmitJs(options)) => options.once,
+      Some(CalcitCommand::EmitIr(options)) => options.once,
+      _ => false,
+    };
+    if cli_args.once || once {
+      return Err(String::from("--inspect only works in watch mode, can not be used with -1 (--once)"));
+    }
+    inspect::check_socket_path(socket_path)?;
+  }
+
+  eprintln!("calcit version: {}", cli_args::CALCIT_VERSION);
 
   let core_snapshot = calcit::load_core_snapshot()?;
 
@@ -44,13 +57,18 @@ fn main() -> Result<(), String> {
   let module_folder = home_dir()
     .map(|buf| buf.as_path().join(".config/calcit/modules/"))
     .expect("failed to load $HOME");
-  println!("module folder: {}", module_folder.to_str().expect("extract path"));
+  eprintln!("module folder: {}", module_folder.to_str().expect("extract path"));
+
+  error_report::configure(ErrorFormat::parse(&cli_args.error_format)?, cli_args.error_path.to_owned());
 
   if cli_args.disable_stack {
     call_stack::set_using_stack(false);
-    println!("stack trace disabled.")
+    eprintln!("stack trace disabled.")
   }
 
+  // namespaces from the entry file, without modules and core, used by `cr check`
+  let mut app_namespaces: Vec<Arc<str>> = vec![];
+
   let input_path = PathBuf::from(&cli_args.input);
   let base_dir = input_path.parent().expect("extract parent");
 
@@ -84,7 +102,7 @@ fn main() -> Result<(), String> {
     // config in entry will overwrite default configs
     if let Some(entry) = cli_args.entry.to_owned() {
       if snapshot.entries.contains_key(entry.as_str()) {
-        println!("running entry: {entry}");
+        eprintln!("running entry: {entry}");
         snapshot.entries[entry.as_str()].clone_into(&mut snapshot.configs);
       } else {
         return Err(format!(
@@ -95,6 +113,12 @@ fn main() -> Result<(), String> {
       }
     }
 
+    for ns in snapshot.files.keys() {
+      if !ns.ends_with(".$meta") {
+        app_namespaces.push(ns.to_owned());
+      }
+    }
+
     // attach modules
     for module_path in &snapshot.configs.modules {
       let module_data = calcit::load_module(module_path, base_dir, &module_folder)?;
@@ -129,6 +153,10 @@ fn main() -> Result<(), String> {
     *prgm = program::extract_program_data(&snapshot)?;
   }
 
+  if let Some(CalcitCommand::Deps(deps_options)) = &cli_args.subcommand {
+    return run_deps(&app_namespaces, deps_options);
+  }
+
   let check_warnings: &RefCell<Vec<LocatedWarning>> = &RefCell::new(vec![]);
 
   // make sure builtin classes are touched
@@ -140,6 +168,25 @@ fn main() -> Result<(), String> {
   )
   .map_err(|e| e.msg)?;
 
+  if let Some(CalcitCommand::Check(check_options)) = &cli_args.subcommand {
+    let mut entry_fns: Vec<Arc<str>> = vec![entries.init_fn.to_owned(), entries.reload_fn.to_owned()];
+    for configs in snapshot.entries.values() {
+      entry_fns.push(configs.init_fn.to_owned());
+      entry_fns.push(configs.reload_fn.to_owned());
+    }
+    return run_check(&app_namespaces, &entry_fns, &check_options.format);
+  }
+
+  if let Some(CalcitCommand::Expand(expand_options)) = &cli_args.subcommand {
+    let (ns, def) = util::string::extract_ns_def(&expand_options.target)?;
+    let expansion = runner::expand::expand_def(&ns, &def, expand_options.steps)?;
+    for warning in &expansion.warnings {
+      eprintln!("{warning}");
+    }
+    println!("{}", expansion.format());
+    return Ok(());
+  }
+
   let task = if let Some(CalcitCommand::EmitJs(js_options)) = &cli_args.subcommand {
     if js_options.once {
       // redundant config, during watching mode, emit once
@@ -158,13 +205,18 @@ fn main() -> Result<(), String> {
   } else {
     let started_time = Instant::now();
 
-    let v = calcit::run_program(entries.init_ns.to_owned(), entries.init_def.to_owned(), &[]).map_err(|e| {
+    // only passed when `--` is used, so init functions without parameters still work
+    let params = match &program_args {
+      Some(xs) => vec![Calcit::from(xs.iter().map(|s| Calcit::Str(s.as_str().into())).collect::<Vec<_>>())],
+      None => vec![],
+    };
+    let v = calcit::run_program(entries.init_ns.to_owned(), entries.init_def.to_owned(), &params).map_err(|e| {
       LocatedWarning::print_list(&e.warnings);
       e.msg
     })?;
 
     let duration = Instant::now().duration_since(started_time);
-    println!("took {}ms: {v}", duration.as_micros() as f64 / 1000.0);
+    eprintln!("took {}ms: {v}", duration.as_micros() as f64 / 1000.0);
     Ok(())
   };
 
@@ -182,15 +234,28 @@ fn main() -> Result<(), String> {
 
   if !eval_once {
     runner::track::track_task_add();
+    if let Some(socket_path) = cli_args.inspect.to_owned() {
+      #[cfg(not(target_arch = "wasm32"))]
+      injection::install_ctrl_c_handler();
+      let entries = entries.to_owned();
+      let args = cli_args.clone();
+      std::thread::spawn(move || {
+        let reload = |changes: ChangesDict| recall_program(changes, &entries, &args);
+        if let Err(e) = inspect::serve(&socket_path, &reload) {
+          eprintln!("failed to inspect, {e}");
+        }
+      });
+    }
     let args = cli_args.clone();
     std::thread::spawn(move || watch_files(entries, args, assets_watch));
   }
   runner::track::exit_when_cleared();
+  inspect::remove_socket();
   Ok(())
 }
 
 pub fn watch_files(entries: ProgramEntries, settings: ToplevelCalcit, assets_watch: Option<String>) {
-  println!("\nRunning: in watch mode...\n");
+  eprintln!("\nRunning: in watch mode...\n");
   let (tx, rx) = channel();
   let mut debouncer = new_debouncer(Duration::from_millis(200), tx).expect("create watcher");
   let config = notify::Config::default();
@@ -214,9 +279,9 @@ pub fn watch_files(entries: ProgramEntries, settings: ToplevelCalcit, assets_wat
   if let Some(assets_folder) = assets_watch.as_ref() {
     match debouncer.watcher().watch(Path::new(assets_folder), RecursiveMode::Recursive) {
       Ok(_) => {
-        println!("assets to watch: {assets_folder}");
+        eprintln!("assets to watch: {assets_folder}");
       }
-      Err(e) => println!("failed to watch path `{assets_folder}`: {e}"),
+      Err(e) => eprintln!("failed to watch path `{assets_folder}`: {e}"),
     }
   };
 
@@ -230,11 +295,19 @@ pub fn watch_files(entries: ProgramEntries, settings: ToplevelCalcit, assets_wat
           eprintln!("failed re-compiling, got empty inc file");
           continue;
         }
-        if let Err(e) = recall_program(&content, &entries, &settings) {
-          eprintln!("error: {e}");
+        let changes = match cirru_edn::parse(&content).and_then(ChangesDict::try_from) {
+          Ok(changes) => changes,
+          Err(e) => {
+            eprintln!("error: {e}");
+            continue;
+          }
+        };
+        let _runtime = runner::event_loop::lock_runtime();
+        if let Err(e) = recall_program(changes, &entries, &settings) {
+          eprintln!("\nfailed to reload, {e}");
         };
       }
-      Ok(Err(e)) => println!("watch error: {e:?}"),
+      Ok(Err(e)) => eprintln!("watch error: {e:?}"),
       Err(e) => eprintln!("watch error: {e:?}"),
     }
   }
@@ -242,17 +315,14 @@ pub fn watch_files(entries: ProgramEntries, settings: ToplevelCalcit, assets_wat
 
 // overwrite previous state
 
-fn recall_program(content: &str, entries: &ProgramEntries, settings: &ToplevelCalcit) -> Result<(), String> {
-  println!("\n-------- file change --------\n");
+fn recall_program(changes: ChangesDict, entries: &ProgramEntries, settings: &ToplevelCalcit) -> Result<(), String> {
+  eprintln!("\n-------- file change --------\n");
 
   // Steps:
-  // 1. load changes file, and patch to program_code
+  // 1. patch changes to program_code
   // 2. clears evaled states, gensym counter
-  // 3. rerun program, and catch error
+  // 3. rerun program, and return error
 
-  let data = cirru_edn::parse(content)?;
-  // println!("\ndata: {}", &data);
-  let changes: ChangesDict = data.try_into()?;
   // println!("\nchanges: {:?}", changes);
   program::apply_code_changes(&changes)?;
   // println!("\nprogram code: {:?}", new_code);
@@ -260,7 +330,8 @@ fn recall_program(content: &str, entries: &ProgramEntries, settings: &ToplevelCa
   // clear data in evaled states
   program::clear_all_program_evaled_defs(entries.init_ns.to_owned(), entries.reload_ns.to_owned(), settings.reload_libs)?;
   builtins::meta::force_reset_gensym_index()?;
-  println!("cleared evaled states and reset gensym index.");
+  builtins::protocols::drop_changed_protocols(&changes);
+  eprintln!("cleared evaled states, reset gensym index and dropped protocol implementations from changed definitions.");
 
   let task = if let Some(CalcitCommand::EmitJs(_)) = settings.subcommand {
     run_codegen(entries, &settings.emit_path, false)
@@ -270,7 +341,7 @@ fn recall_program(content: &str, entries: &ProgramEntries, settings: &ToplevelCa
     // run from `reload_fn` after reload
     let started_time = Instant::now();
     let task_size = runner::track::count_pending_tasks();
-    println!("checking pending tasks: {task_size}");
+    eprintln!("checking pending tasks: {task_size}");
     if task_size > 1 {
       // when there's services, make sure their code get preprocessed too
       let check_warnings: &RefCell<Vec<LocatedWarning>> = &RefCell::new(vec![]);
@@ -280,7 +351,7 @@ fn recall_program(content: &str, entries: &ProgramEntries, settings: &ToplevelCa
         return Err(e.to_string());
       }
 
-      let warnings = check_warnings.borrow();
+      let warnings = LocatedWarning::take_blocking(&check_warnings.borrow());
       throw_on_warnings(&warnings)?;
     }
     let v = calcit::run_program(entries.reload_ns.to_owned(), entries.reload_def.to_owned(), &[]).map_err(|e| {
@@ -288,18 +359,11 @@ fn recall_program(content: &str, entries: &ProgramEntries, settings: &ToplevelCa
       e.msg
     })?;
     let duration = Instant::now().duration_since(started_time);
-    println!("took {}ms: {v}", duration.as_micros() as f64 / 1000.0);
+    eprintln!("took {}ms: {v}", duration.as_micros() as f64 / 1000.0);
     Ok(())
   };
 
-  match task {
-    Ok(_) => {}
-    Err(e) => {
-      eprintln!("\nfailed to reload, {e}")
-    }
-  }
-
-  Ok(())
+  task
 }
 
 fn run_codegen(entries: &ProgramEntries, emit_path: &str, ir_mode: bool) -> Result<(), String> {
@@ -327,7 +391,7 @@ fn run_codegen(entries: &ProgramEntries, emit_path: &str, ir_mode: bool) -> Resu
     Ok(_) => (),
     Err(failure) => {
       eprintln!("\nfailed preprocessing, {failure}");
-      call_stack::display_stack(&failure.msg, &failure.stack, failure.location.as_ref())?;
+      error_report::report_err(FailureKind::Preprocess, &failure);
 
       let _ = fs::write(
         &js_file_path,
@@ -345,12 +409,12 @@ fn run_codegen(entries: &ProgramEntries, emit_path: &str, ir_mode: bool) -> Resu
     Ok(_) => (),
     Err(failure) => {
       eprintln!("\nfailed preprocessing, {failure}");
-      call_stack::display_stack(&failure.msg, &failure.stack, failure.location.as_ref())?;
+      error_report::report_err(FailureKind::Preprocess, &failure);
       return Err(failure.msg);
     }
   }
 
-  let warnings = check_warnings.borrow();
+  let warnings = LocatedWarning::take_blocking(&check_warnings.borrow());
   throw_on_js_warnings(&warnings, &js_file_path)?;
 
   // clear if there are no errors
@@ -364,7 +428,7 @@ fn run_codegen(entries: &ProgramEntries, emit_path: &str, ir_mode: bool) -> Resu
       Ok(_) => (),
       Err(failure) => {
         eprintln!("\nfailed codegen, {failure}");
-        call_stack::display_stack(&failure, &gen_stack::get_gen_stack(), None)?;
+        error_report::report_failure(FailureKind::Codegen, &failure, &gen_stack::get_gen_stack());
         return Err(failure);
       }
     }
@@ -374,13 +438,61 @@ fn run_codegen(entries: &ProgramEntries, emit_path: &str, ir_mode: bool) -> Resu
       Ok(_) => (),
       Err(failure) => {
         eprintln!("\nfailed codegen, {failure}");
-        call_stack::display_stack(&failure, &gen_stack::get_gen_stack(), None)?;
+        error_report::report_failure(FailureKind::Codegen, &failure, &gen_stack::get_gen_stack());
         return Err(failure);
       }
     }
   }
   let duration = Instant::now().duration_since(started_time);
-  println!("took {}ms", duration.as_micros() as f64 / 1000.0);
+  eprintln!("took {}ms", duration.as_micros() as f64 / 1000.0);
+  Ok(())
+}
+
+/// exits with error when anything is reported
+fn run_check(app_namespaces: &[Arc<str>], entry_fns: &[Arc<str>], format: &str) -> Result<(), String> {
+  if format != "human" && format != "json" {
+    return Err(format!("unknown format `{format}` for check, expected `human` or `json`"));
+  }
+  let started_time = Instant::now();
+  let report = runner::lint::check_program(app_namespaces, entry_fns);
+  if format == "json" {
+    println!("{}", report.format_json()?);
+  } else {
+    println!("{}", report.format_human());
+  }
+  let duration = Instant::now().duration_since(started_time);
+  eprintln!(
+    "checked {} namespaces in {}ms",
+    app_namespaces.len(),
+    duration.as_micros() as f64 / 1000.0
+  );
+  if !report.is_empty() {
+    // a summary for humans, rather than the `Err` printed by `main` in debug format
+    eprintln!(
+      "check failed with {} errors and {} warnings",
+      report.errors.len(),
+      report.warnings.len()
+    );
+    std::process::exit(1);
+  }
+  Ok(())
+}
+
+/// prints the graph to stdout, cycles are reported but not treated as failures
+fn run_deps(app_namespaces: &[Arc<str>], options: &DepsCommand) -> Result<(), String> {
+  let code = { program::PROGRAM_CODE_DATA.read().expect("read program code").to_owned() };
+  let only = if options.app_only { Some(app_namespaces) } else { None };
+  let graph = match options.level.as_str() {
+    "ns" => runner::dep_graph::ns_graph(&code, only),
+    "def" => runner::dep_graph::def_graph(&code, only),
+    level => return Err(format!("unknown level `{level}` for deps, expected `ns` or `def`")),
+  };
+  match options.format.as_str() {
+    "human" => println!("{}", graph.format_human()),
+    "dot" => println!("{}", graph.format_dot()),
+    "json" => println!("{}", graph.format_json()?),
+    format => return Err(format!("unknown format `{format}` for deps, expected `human`, `dot` or `json`")),
+  }
   Ok(())
 }
 
@@ -388,11 +500,19 @@ fn throw_on_js_warnings(warnings: &[LocatedWarning], js_file_path: &Path) -> Res
   if !warnings.is_empty() {
     let mut content: String = String::from("");
     for warn in warnings {
-      println!("{warn}");
+      eprintln!("{warn}");
       content = format!("{content}\n{warn}");
     }
 
     let _ = fs::write(js_file_path, format!("export default \"{}\";", content.trim().escape_default()));
+    let message = format!("Found {} warnings, codegen blocked", warnings.len());
+    error_report::report(&ErrorReport {
+      kind: FailureKind::Warnings,
+      message: &message,
+      stack: &CallStackList::default(),
+      location: None,
+      warnings,
+    });
     Err(format!(
       "Found {} warnings, codegen blocked. errors in {}.mjs",
       warnings.len(),
@@ -407,11 +527,19 @@ fn throw_on_warnings(warnings: &[LocatedWarning]) -> Result<(), String> {
   if !warnings.is_empty() {
     let mut content: String = String::from("");
     for warn in warnings {
-      println!("{warn}");
+      eprintln!("{warn}");
       content = format!("{content}\n{warn}");
     }
 
-    Err(format!("Found {} warnings in preprocessing, re-run blocked.", warnings.len()))
+    let message = format!("Found {} warnings in preprocessing, re-run blocked.", warnings.len());
+    error_report::report(&ErrorReport {
+      kind: FailureKind::Warnings,
+      message: &message,
+      stack: &CallStackList::default(),
+      location: None,
+      warnings,
+    });
+    Err(message)
   } else {
     Ok(())
   }
@@ -7,8 +7,10 @@ use std::thread;
 use calcit::{
   builtins,
   calcit::{Calcit, CalcitErr},
-  call_stack::{display_stack, CallStackList},
+  call_stack::CallStackList,
   data::edn::{calcit_to_edn, edn_to_calcit},
+  error_report::{self, ErrorReport, FailureKind},
+  inspect,
   runner::track,
 };
 
@@ -45,11 +47,11 @@ pub fn inject_platform_apis() {
   builtins::register_import_proc("&blocking-dylib-edn-fn", blocking_dylib_edn_fn);
   builtins::register_import_proc("async-sleep", builtins::meta::async_sleep);
   builtins::register_import_proc("on-control-c", on_ctrl_c);
-  println!("registered platform APIs");
+  eprintln!("registered platform APIs");
 }
 
 // &call-dylib-edn
-pub fn call_dylib_edn(xs: Vec<Calcit>, _call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+pub fn call_dylib_edn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   if xs.len() < 2 {
     return CalcitErr::err_str(format!("&call-dylib-edn expected >2 arguments, got: {:?}", xs));
   }
@@ -77,7 +79,7 @@ pub fn call_dylib_edn(xs: Vec<Calcit>, _call_stack: &CallStackList) -> Result<Ca
   }
   let func: libloading::Symbol<EdnFfi> = unsafe { lib.get(method.as_bytes()).expect("dy function not found") };
   let ret = func(ys.to_owned())?;
-  Ok(edn_to_calcit(&ret, &Calcit::Nil))
+  edn_to_calcit(&ret, &Calcit::Nil, call_stack)
 }
 
 pub fn stdout_println(xs: Vec<Calcit>, _call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
@@ -155,13 +157,17 @@ pub fn call_dylib_edn_fn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<
         if let Calcit::Fn { info, .. } = &callback {
           let mut real_args: Vec<Calcit> = vec![];
           for p in ps {
-            real_args.push(edn_to_calcit(&p, &Calcit::Nil));
+            real_args.push(edn_to_calcit(&p, &Calcit::Nil, &copied_stack).map_err(|e| e.msg.to_string())?);
           }
           let r = runner::run_fn(&real_args, info, &copied_stack);
           match r {
             Ok(ret) => calcit_to_edn(&ret),
             Err(e) => {
-              display_stack(&format!("[Error] thread callback failed: {}", e.msg), &e.stack, e.location.as_ref())?;
+              let message = format!("[Error] thread callback failed: {}", e.msg);
+              error_report::report(&ErrorReport {
+                message: &message,
+                ..ErrorReport::from_err(FailureKind::Runtime, &e)
+              });
               Err(format!("Error: {e}"))
             }
           }
@@ -172,7 +178,7 @@ pub fn call_dylib_edn_fn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<
       }),
       Arc::new(track::track_task_release),
     ) {
-      Ok(ret) => edn_to_calcit(&ret, &Calcit::Nil),
+      Ok(ret) => edn_to_calcit(&ret, &Calcit::Nil, &copied_stack_1)?,
       Err(e) => {
         track::track_task_release();
         // let _ = display_stack(&format!("failed to call request: {}", e), &copied_stack_1);
@@ -238,13 +244,17 @@ pub fn blocking_dylib_edn_fn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Res
       if let Calcit::Fn { info, .. } = &callback {
         let mut real_args: Vec<Calcit> = vec![];
         for p in ps {
-          real_args.push(edn_to_calcit(&p, &Calcit::Nil));
+          real_args.push(edn_to_calcit(&p, &Calcit::Nil, &copied_stack).map_err(|e| e.msg.to_string())?);
         }
         let r = runner::run_fn(&real_args, info, &copied_stack);
         match r {
           Ok(ret) => calcit_to_edn(&ret),
           Err(e) => {
-            display_stack(&format!("[Error] thread callback failed: {}", e.msg), &e.stack, e.location.as_ref())?;
+            let message = format!("[Error] thread callback failed: {}", e.msg);
+            error_report::report(&ErrorReport {
+              message: &message,
+              ..ErrorReport::from_err(FailureKind::Runtime, &e)
+            });
             Err(format!("Error: {e}"))
           }
         }
@@ -255,11 +265,11 @@ pub fn blocking_dylib_edn_fn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Res
     }),
     Arc::new(track::track_task_release),
   ) {
-    Ok(ret) => edn_to_calcit(&ret, &Calcit::Nil),
+    Ok(ret) => edn_to_calcit(&ret, &Calcit::Nil, call_stack)?,
     Err(e) => {
       // TODO for more accurate tracking, need to place tracker inside foreign function
       // track::track_task_release();
-      let _ = display_stack(&format!("failed to call request: {e}"), call_stack, None);
+      error_report::report_failure(FailureKind::Runtime, &format!("failed to call request: {e}"), call_stack);
       return CalcitErr::err_str(e);
     }
   };
@@ -267,20 +277,38 @@ pub fn blocking_dylib_edn_fn(xs: Vec<Calcit>, call_stack: &CallStackList) -> Res
   Ok(Calcit::Nil)
 }
 
-/// need to put it here since the crate does not compile for dylib
-#[no_mangle]
-pub fn on_ctrl_c(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
-  if xs.len() == 1 {
-    let cb = Arc::new(xs[0].to_owned());
-    let copied_stack = Arc::new(call_stack.to_owned());
-    ctrlc::set_handler(move || {
-      if let Calcit::Fn { info, .. } = cb.as_ref() {
-        if let Err(e) = runner::run_fn(&[], info, &copied_stack) {
-          eprintln!("error: {e}");
+/// callback from `on-control-c`, replaced when it's called again after reloading
+static CTRL_C_CALLBACK: Mutex<Option<(Calcit, CallStackList)>> = Mutex::new(None);
+
+/// a process only has one handler of Ctrl-C, it calls the callback from program,
+/// or removes the inspecting socket and exits when there is no callback
+pub fn install_ctrl_c_handler() {
+  static INSTALLED: std::sync::Once = std::sync::Once::new();
+  INSTALLED.call_once(|| {
+    ctrlc::set_handler(|| {
+      let callback = CTRL_C_CALLBACK.lock().expect("lock ctrl-c callback").to_owned();
+      match callback {
+        Some((Calcit::Fn { info, .. }, call_stack)) => {
+          if let Err(e) = runner::run_fn(&[], &info, &call_stack) {
+            eprintln!("error: {e}");
+          }
+        }
+        _ => {
+          inspect::remove_socket();
+          std::process::exit(130);
         }
       }
     })
     .expect("Error setting Ctrl-C handler");
+  });
+}
+
+/// need to put it here since the crate does not compile for dylib
+#[no_mangle]
+pub fn on_ctrl_c(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.len() == 1 {
+    *CTRL_C_CALLBACK.lock().expect("lock ctrl-c callback") = Some((xs[0].to_owned(), call_stack.to_owned()));
+    install_ctrl_c_handler();
     Ok(Calcit::Nil)
   } else {
     CalcitErr::err_str(format!("on-control-c expected a callback function {xs:?}"))
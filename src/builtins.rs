@@ -1,14 +1,22 @@
+mod buffers;
 pub mod effects;
+mod files;
+mod lazy;
 mod lists;
 mod logics;
 mod maps;
 mod math;
 pub mod meta;
-mod records;
-mod refs;
+pub mod protocols;
+pub mod records;
+pub mod refs;
 mod sets;
 mod strings;
+mod subprocess;
 pub mod syntax;
+mod tasks;
+mod time;
+mod transients;
 
 use std::collections::HashMap;
 use std::sync::{Arc, LazyLock, RwLock};
@@ -71,8 +79,55 @@ fn handle_proc_internal(name: CalcitProc, args: &[Calcit], call_stack: &CallStac
     CalcitProc::NativeGetOs => meta::get_os(args),
     CalcitProc::NativeFormatTernaryTree => meta::format_ternary_tree(args),
     CalcitProc::NativeBuffer => meta::buffer(args),
+    CalcitProc::NativeBufferCount => buffers::count(args),
+    CalcitProc::NativeBufferNth => buffers::nth(args),
+    CalcitProc::NativeBufferSlice => buffers::slice(args),
+    CalcitProc::NativeBufferConcat => buffers::concat(args),
+    CalcitProc::NativeBufferAssoc => buffers::assoc(args),
+    CalcitProc::NativeBufferFindIndex => buffers::find_index(args),
+    CalcitProc::NativeBufferRead => buffers::read(args),
+    CalcitProc::NativeBufferWrite => buffers::write(args),
+    CalcitProc::NativeBufferToHex => buffers::to_hex(args),
+    CalcitProc::NativeBufferFromHex => buffers::from_hex(args),
+    CalcitProc::NativeBufferToBase64 => buffers::to_base64(args),
+    CalcitProc::NativeBufferFromBase64 => buffers::from_base64(args),
+    CalcitProc::NativeBufferToString => buffers::to_string(args),
+    CalcitProc::NativeBufferFromString => buffers::from_string(args),
+    CalcitProc::NativeBufferToList => buffers::to_list(args),
+    CalcitProc::NativeBufferFromList => buffers::from_list(args),
+    // transients
+    CalcitProc::Transient => transients::transient(args),
+    CalcitProc::IsTransient => transients::transient_ques(args),
+    CalcitProc::Persistent => transients::persistent(args),
+    CalcitProc::NativeTransientCount => transients::count(args),
+    CalcitProc::NativeTransientGet => transients::get(args),
+    CalcitProc::NativeTransientAppend => transients::append(args),
+    CalcitProc::NativeTransientAssoc => transients::assoc(args),
+    CalcitProc::NativeTransientDissoc => transients::dissoc(args),
+    CalcitProc::NativeTransientInclude => transients::include(args),
+    CalcitProc::NativeTransientExclude => transients::exclude(args),
+    // lazy sequences
+    CalcitProc::ToLazy => lazy::to_lazy(args),
+    CalcitProc::IsLazySeq => lazy::lazy_seq_ques(args),
+    CalcitProc::LazyRange => lazy::lazy_range(args),
+    CalcitProc::LazyIterate => lazy::lazy_iterate(args),
+    CalcitProc::LazyRepeat => lazy::lazy_repeat(args),
+    CalcitProc::LazyUnfold => lazy::lazy_unfold(args),
+    CalcitProc::ReadFileLines => lazy::read_file_lines(args),
+    CalcitProc::ReadStdinLines => lazy::read_stdin_lines(args),
+    CalcitProc::NativeLazyMap => lazy::map(args),
+    CalcitProc::NativeLazyFilter => lazy::filter(args),
+    CalcitProc::NativeLazyTake => lazy::take(args),
+    CalcitProc::NativeLazyDrop => lazy::drop(args),
+    CalcitProc::NativeLazyTakeWhile => lazy::take_while(args),
+    CalcitProc::NativeLazyConcat => lazy::concat(args),
+    CalcitProc::NativeLazyZip => lazy::zip(args),
+    CalcitProc::NativeLazyToList => lazy::to_list(args, call_stack),
+    CalcitProc::NativeLazyFirst => lazy::first(args, call_stack),
+    CalcitProc::NativeLazyEmpty => lazy::empty_ques(args, call_stack),
+    CalcitProc::NativeLazyCount => lazy::count(args, call_stack),
     CalcitProc::NativeHash => meta::hash(args),
-    CalcitProc::NativeExtractCodeIntoEdn => meta::extract_code_into_edn(args),
+    CalcitProc::NativeExtractCodeIntoEdn => meta::extract_code_into_edn(args, call_stack),
     CalcitProc::NativeDataToCode => meta::data_to_code(args),
     CalcitProc::NativeCirruNth => meta::cirru_nth(args),
     CalcitProc::NativeCirruType => meta::cirru_type(args),
@@ -89,20 +144,54 @@ fn handle_proc_internal(name: CalcitProc, args: &[Calcit], call_stack: &CallStac
     // effects
     CalcitProc::NativeDisplayStack => meta::display_stack(args, call_stack),
     CalcitProc::Raise => effects::raise(args),
+    CalcitProc::RaiseData => effects::raise_data(args),
     CalcitProc::Quit => effects::quit(args),
     CalcitProc::GetEnv => effects::get_env(args),
     CalcitProc::NativeGetCalcitBackend => effects::call_get_calcit_backend(args),
     CalcitProc::ReadFile => effects::read_file(args),
     CalcitProc::WriteFile => effects::write_file(args),
+    CalcitProc::ReadStdin => effects::read_stdin(args),
+    CalcitProc::ReadStdinLine => effects::read_stdin_line(args),
+    CalcitProc::EachStdinLine => effects::each_stdin_line(args, call_stack),
+    CalcitProc::ReadFileBuffer => files::read_file_buffer(args),
+    CalcitProc::WriteFileBuffer => files::write_file_buffer(args),
+    CalcitProc::ListDir => files::list_dir(args),
+    CalcitProc::MakeDir => files::make_dir(args),
+    CalcitProc::RemovePath => files::remove_path(args),
+    CalcitProc::RenamePath => files::rename_path(args),
+    CalcitProc::PathExists => files::path_exists(args),
+    CalcitProc::PathStat => files::path_stat(args),
+    CalcitProc::GlobPaths => files::glob_paths(args),
+    CalcitProc::RunProcess => subprocess::run_process(args, call_stack),
     // external data format
     CalcitProc::ParseCirru => meta::parse_cirru(args),
     CalcitProc::ParseCirruList => meta::parse_cirru_list(args),
     CalcitProc::FormatCirru => meta::format_cirru(args),
-    CalcitProc::ParseCirruEdn => meta::parse_cirru_edn(args),
+    CalcitProc::ParseCirruEdn => meta::parse_cirru_edn(args, call_stack),
     CalcitProc::FormatCirruEdn => meta::format_cirru_edn(args),
+    CalcitProc::ParseJson => meta::parse_json(args),
+    CalcitProc::FormatJson => meta::format_json(args),
+    CalcitProc::ParseBinary => meta::parse_binary(args),
+    CalcitProc::FormatBinary => meta::format_binary(args),
     CalcitProc::NativeCirruQuoteToList => meta::cirru_quote_to_list(args),
     // time
     CalcitProc::CpuTime => effects::cpu_time(args),
+    CalcitProc::Now => time::now(args),
+    CalcitProc::MonotonicNow => time::monotonic_now(args),
+    CalcitProc::FormatTime => time::format_time(args),
+    CalcitProc::ParseTime => time::parse_time(args),
+    CalcitProc::TimeAdd => time::time_add(args),
+    CalcitProc::TimeDiff => time::time_diff(args),
+    CalcitProc::TimeTruncate => time::time_truncate(args),
+    // async
+    CalcitProc::SetTimeout => tasks::set_timeout(args, call_stack),
+    CalcitProc::SetInterval => tasks::set_interval(args, call_stack),
+    CalcitProc::ClearTimer => tasks::clear_timer(args),
+    CalcitProc::NewPromise => tasks::new_promise(args),
+    CalcitProc::ResolvePromise => tasks::resolve_promise(args),
+    CalcitProc::RejectPromise => tasks::reject_promise(args),
+    CalcitProc::PromiseThen => tasks::promise_then(args, call_stack),
+    CalcitProc::Await => tasks::await_value(args, call_stack),
     // logics
     CalcitProc::NativeEquals => logics::binary_equal(args),
     CalcitProc::NativeLessThan => logics::binary_less(args),
@@ -204,6 +293,11 @@ fn handle_proc_internal(name: CalcitProc, args: &[Calcit], call_stack: &CallStac
     CalcitProc::NativeMapDiffNew => maps::diff_new(args),
     CalcitProc::NativeMapDiffKeys => maps::diff_keys(args),
     CalcitProc::NativeMapCommonKeys => maps::common_keys(args),
+    CalcitProc::SortedMap => maps::new_sorted_map(args),
+    CalcitProc::IsSortedMap => maps::sorted_ques(args),
+    CalcitProc::NativeMapFirst => maps::first(args),
+    CalcitProc::NativeMapLast => maps::last(args),
+    CalcitProc::NativeMapRange => maps::range(args),
     // sets
     CalcitProc::Set => sets::new_set(args),
     CalcitProc::NativeInclude => sets::call_include(args),
@@ -216,19 +310,31 @@ fn handle_proc_internal(name: CalcitProc, args: &[Calcit], call_stack: &CallStac
     CalcitProc::NativeSetEmpty => sets::empty_ques(args),
     CalcitProc::NativeSetIncludes => sets::includes_ques(args),
     CalcitProc::NativeSetDestruct => sets::destruct(args),
+    CalcitProc::SortedSet => sets::new_sorted_set(args),
+    CalcitProc::IsSortedSet => sets::sorted_ques(args),
+    CalcitProc::NativeSetFirst => sets::first(args),
+    CalcitProc::NativeSetLast => sets::last(args),
+    CalcitProc::NativeSetRange => sets::range(args),
     // refs
     CalcitProc::Atom => refs::atom(args),
     CalcitProc::AtomDeref => refs::atom_deref(args),
     CalcitProc::AddWatch => refs::add_watch(args),
     CalcitProc::RemoveWatch => refs::remove_watch(args),
+    CalcitProc::CompareAndSet => refs::compare_and_set(args, call_stack),
+    CalcitProc::NativeAtomSwap => refs::atom_swap(args, call_stack),
+    CalcitProc::Transact => refs::transact(args, call_stack),
+    CalcitProc::NativeRefsRegistered => refs::registered_refs(args),
+    CalcitProc::NativeRefsResetAll => refs::reset_all_refs(args),
     // records
     CalcitProc::NewRecord => records::new_record(args),
     CalcitProc::NewClassRecord => records::new_class_record(args),
-    CalcitProc::NativeRecord => records::call_record(args),
+    CalcitProc::NativeRecord => records::call_record(args, call_stack),
     CalcitProc::NativeRecordWith => records::record_with(args),
     CalcitProc::NativeRecordClass => records::get_class(args),
     CalcitProc::NativeRecordWithClass => records::with_class(args),
-    CalcitProc::NativeRecordFromMap => records::record_from_map(args),
+    CalcitProc::NativeRecordFromMap => records::record_from_map(args, call_stack),
+    CalcitProc::NativeRecordWithSchema => records::with_schema(args),
+    CalcitProc::NativeRecordSchema => records::get_schema(args),
     CalcitProc::NativeRecordGetName => records::get_record_name(args),
     CalcitProc::NativeRecordToMap => records::turn_map(args),
     CalcitProc::NativeRecordMatches => records::matches(args),
@@ -237,6 +343,10 @@ fn handle_proc_internal(name: CalcitProc, args: &[Calcit], call_stack: &CallStac
     CalcitProc::NativeRecordGet => records::get(args),
     CalcitProc::NativeRecordAssoc => records::assoc(args),
     CalcitProc::NativeRecordExtendAs => records::extend_as(args),
+    // protocols
+    CalcitProc::NewProtocol => protocols::new_protocol(args),
+    CalcitProc::ExtendProtocol => protocols::extend_protocol(args, call_stack),
+    CalcitProc::Satisfies => protocols::satisfies_ques(args),
   }
 }
 
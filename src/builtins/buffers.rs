@@ -0,0 +1,359 @@
+//! procs for `Buffer`, buffers are immutable and procs like `&buffer:assoc` return new buffers.
+//! kinds of numbers for `&buffer:read` and `&buffer:write` are tags like `:u8`, `:i16-le`, `:u32-be` and `:f64-le`.
+//! 64-bit integers are read as floats, and only integers in the safe range(`2^53 - 1`) are written.
+
+use crate::calcit::{Calcit, CalcitErr};
+use crate::util::number::f64_to_usize;
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberKind {
+  Unsigned,
+  Signed,
+  Float,
+}
+
+/// a kind of number stored in bytes
+#[derive(Debug, Clone, Copy)]
+struct ByteNumber {
+  kind: NumberKind,
+  size: usize,
+  little_endian: bool,
+}
+
+impl ByteNumber {
+  fn parse(x: &Calcit, proc_name: &str) -> Result<Self, CalcitErr> {
+    let name = match x {
+      Calcit::Tag(t) => t.ref_str().to_owned(),
+      a => return Err(CalcitErr::use_str(format!("{proc_name} expected a tag of number kind, got: {a}"))),
+    };
+    let (base, little_endian) = match name.split_once('-') {
+      Some((base, "le")) => (base, true),
+      Some((base, "be")) => (base, false),
+      Some(_) => return Err(CalcitErr::use_str(format!("{proc_name} unknown number kind: :{name}"))),
+      None => (&*name, false),
+    };
+    let (kind, size) = match base {
+      "u8" => (NumberKind::Unsigned, 1),
+      "i8" => (NumberKind::Signed, 1),
+      "u16" => (NumberKind::Unsigned, 2),
+      "i16" => (NumberKind::Signed, 2),
+      "u32" => (NumberKind::Unsigned, 4),
+      "i32" => (NumberKind::Signed, 4),
+      "u64" => (NumberKind::Unsigned, 8),
+      "i64" => (NumberKind::Signed, 8),
+      "f32" => (NumberKind::Float, 4),
+      "f64" => (NumberKind::Float, 8),
+      _ => return Err(CalcitErr::use_str(format!("{proc_name} unknown number kind: :{name}"))),
+    };
+    // endianness is required for numbers with more than 1 byte
+    if (size == 1) == name.contains('-') {
+      return Err(CalcitErr::use_str(format!("{proc_name} unknown number kind: :{name}")));
+    }
+    Ok(ByteNumber { kind, size, little_endian })
+  }
+
+  fn read(&self, bytes: &[u8]) -> f64 {
+    let mut raw = [0u8; 8];
+    if self.little_endian {
+      raw[..self.size].copy_from_slice(bytes);
+    } else {
+      for (idx, b) in bytes.iter().rev().enumerate() {
+        raw[idx] = *b;
+      }
+    }
+    // `raw` is little endian from here
+    let unsigned = u64::from_le_bytes(raw);
+    match self.kind {
+      NumberKind::Unsigned => unsigned as f64,
+      NumberKind::Signed => {
+        let shift = 64 - self.size * 8;
+        (((unsigned << shift) as i64) >> shift) as f64
+      }
+      NumberKind::Float if self.size == 4 => f32::from_bits(unsigned as u32) as f64,
+      NumberKind::Float => f64::from_bits(unsigned),
+    }
+  }
+
+  fn write(&self, n: f64, proc_name: &str) -> Result<Vec<u8>, CalcitErr> {
+    let raw: u64 = match self.kind {
+      NumberKind::Float if self.size == 4 => (n as f32).to_bits() as u64,
+      NumberKind::Float => n.to_bits(),
+      kind => {
+        let bits = self.size as i32 * 8;
+        let (min, max) = match kind {
+          NumberKind::Unsigned => (0.0, 2f64.powi(bits) - 1.0),
+          _ => (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1) - 1.0),
+        };
+        let (min, max) = (min.max(-MAX_SAFE_INTEGER), max.min(MAX_SAFE_INTEGER));
+        if n.fract() != 0.0 || n < min || n > max {
+          return Err(CalcitErr::use_str(format!(
+            "{proc_name} expected an integer within {min}~{max}, got: {n}"
+          )));
+        }
+        (n as i64) as u64
+      }
+    };
+    let bytes = raw.to_le_bytes();
+    let mut ys = bytes[..self.size].to_vec();
+    if !self.little_endian {
+      ys.reverse();
+    }
+    Ok(ys)
+  }
+}
+
+/// NaN and infinities have no integer parts, so they are rejected along with negative numbers and fractions
+fn read_index(x: &Calcit, proc_name: &str) -> Result<usize, CalcitErr> {
+  match x {
+    Calcit::Number(n) if n.fract() == 0.0 && *n >= 0.0 => {
+      f64_to_usize(*n).map_err(|e| CalcitErr::use_str(format!("{proc_name} expected an index, {e}")))
+    }
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected an index, got: {a}"))),
+  }
+}
+
+/// range of a number in bytes at the offset, fails when it's out of the buffer
+fn byte_range(offset: usize, kind: &ByteNumber, buf: &[u8], proc_name: &str) -> Result<std::ops::Range<usize>, CalcitErr> {
+  match offset.checked_add(kind.size) {
+    Some(end) if end <= buf.len() => Ok(offset..end),
+    _ => Err(CalcitErr::use_str(format!(
+      "{proc_name} {} bytes at {offset} out of buffer size {}",
+      kind.size,
+      buf.len()
+    ))),
+  }
+}
+
+fn read_byte(x: &Calcit, proc_name: &str) -> Result<u8, CalcitErr> {
+  match x {
+    Calcit::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => Ok(*n as u8),
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected a byte within 0~255, got: {a}"))),
+  }
+}
+
+pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => Ok(Calcit::Number(buf.len() as f64)),
+    [a] => CalcitErr::err_str(format!("&buffer:count expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:count expected 1 argument, got:", xs),
+  }
+}
+
+/// `nil` when index is out of range
+pub fn nth(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf), idx] => {
+      let idx = read_index(idx, "&buffer:nth")?;
+      Ok(buf.get(idx).map_or(Calcit::Nil, |b| Calcit::Number(*b as f64)))
+    }
+    [a, _] => CalcitErr::err_str(format!("&buffer:nth expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:nth expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn slice(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let (buf, from, to) = match xs {
+    [Calcit::Buffer(buf), from] | [Calcit::Buffer(buf), from, Calcit::Nil] => (buf, read_index(from, "&buffer:slice")?, buf.len()),
+    [Calcit::Buffer(buf), from, to] => (buf, read_index(from, "&buffer:slice")?, read_index(to, "&buffer:slice")?),
+    [a, ..] if xs.len() <= 3 => return CalcitErr::err_str(format!("&buffer:slice expected a buffer, got: {a}")),
+    _ => return CalcitErr::err_nodes("&buffer:slice expected 2~3 arguments, got:", xs),
+  };
+  if from > to || to > buf.len() {
+    return CalcitErr::err_str(format!("&buffer:slice expected range within 0~{}, got: {from}~{to}", buf.len()));
+  }
+  Ok(Calcit::Buffer(buf[from..to].to_vec()))
+}
+
+pub fn concat(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let mut ys: Vec<u8> = vec![];
+  for x in xs {
+    match x {
+      Calcit::Buffer(buf) => ys.extend_from_slice(buf),
+      a => return CalcitErr::err_str(format!("&buffer:concat expected buffers, got: {a}")),
+    }
+  }
+  Ok(Calcit::Buffer(ys))
+}
+
+pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf), idx, v] => {
+      let idx = read_index(idx, "&buffer:assoc")?;
+      let byte = read_byte(v, "&buffer:assoc")?;
+      if idx >= buf.len() {
+        return CalcitErr::err_str(format!("&buffer:assoc index {idx} out of range 0~{}", buf.len()));
+      }
+      let mut ys = buf.to_owned();
+      ys[idx] = byte;
+      Ok(Calcit::Buffer(ys))
+    }
+    [a, _, _] => CalcitErr::err_str(format!("&buffer:assoc expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:assoc expected 3 arguments, got:", xs),
+  }
+}
+
+/// finds a byte or a sequence of bytes, `-1` when not found
+pub fn find_index(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let found = match xs {
+    [Calcit::Buffer(buf), Calcit::Buffer(pattern)] => {
+      if pattern.is_empty() {
+        Some(0)
+      } else {
+        buf.windows(pattern.len()).position(|w| w == &pattern[..])
+      }
+    }
+    [Calcit::Buffer(buf), b] => {
+      let byte = read_byte(b, "&buffer:find-index")?;
+      buf.iter().position(|x| *x == byte)
+    }
+    [a, _] => return CalcitErr::err_str(format!("&buffer:find-index expected a buffer, got: {a}")),
+    _ => return CalcitErr::err_nodes("&buffer:find-index expected 2 arguments, got:", xs),
+  };
+  Ok(Calcit::Number(found.map_or(-1.0, |idx| idx as f64)))
+}
+
+pub fn read(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf), kind, offset] => {
+      let kind = ByteNumber::parse(kind, "&buffer:read")?;
+      let offset = read_index(offset, "&buffer:read")?;
+      let range = byte_range(offset, &kind, buf, "&buffer:read")?;
+      Ok(Calcit::Number(kind.read(&buf[range])))
+    }
+    [a, _, _] => CalcitErr::err_str(format!("&buffer:read expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:read expected 3 arguments, got:", xs),
+  }
+}
+
+pub fn write(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf), kind, offset, Calcit::Number(n)] => {
+      let kind = ByteNumber::parse(kind, "&buffer:write")?;
+      let offset = read_index(offset, "&buffer:write")?;
+      let range = byte_range(offset, &kind, buf, "&buffer:write")?;
+      let bytes = kind.write(*n, "&buffer:write")?;
+      let mut ys = buf.to_owned();
+      ys[range].copy_from_slice(&bytes);
+      Ok(Calcit::Buffer(ys))
+    }
+    [Calcit::Buffer(_), _, _, a] => CalcitErr::err_str(format!("&buffer:write expected a number, got: {a}")),
+    [a, _, _, _] => CalcitErr::err_str(format!("&buffer:write expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:write expected 4 arguments, got:", xs),
+  }
+}
+
+pub fn to_hex(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => Ok(Calcit::Str(hex::encode(buf).into())),
+    [a] => CalcitErr::err_str(format!("&buffer:to-hex expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:to-hex expected 1 argument, got:", xs),
+  }
+}
+
+pub fn from_hex(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Str(s)] => match hex::decode(&**s) {
+      Ok(buf) => Ok(Calcit::Buffer(buf)),
+      Err(e) => CalcitErr::err_str(format!("&buffer:from-hex failed, {e}")),
+    },
+    [a] => CalcitErr::err_str(format!("&buffer:from-hex expected a string, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:from-hex expected 1 argument, got:", xs),
+  }
+}
+
+/// standard alphabet with padding
+pub fn to_base64(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => {
+      let mut s = String::with_capacity(buf.len().div_ceil(3) * 4);
+      for chunk in buf.chunks(3) {
+        let n = chunk
+          .iter()
+          .enumerate()
+          .fold(0u32, |acc, (idx, b)| acc | (*b as u32) << (16 - idx * 8));
+        for idx in 0..4 {
+          if idx <= chunk.len() {
+            s.push(BASE64_CHARS[(n >> (18 - idx * 6)) as usize & 0x3f] as char);
+          } else {
+            s.push('=');
+          }
+        }
+      }
+      Ok(Calcit::Str(s.into()))
+    }
+    [a] => CalcitErr::err_str(format!("&buffer:to-base64 expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:to-base64 expected 1 argument, got:", xs),
+  }
+}
+
+/// standard alphabet, padding is optional
+pub fn from_base64(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let s = match xs {
+    [Calcit::Str(s)] => s,
+    [a] => return CalcitErr::err_str(format!("&buffer:from-base64 expected a string, got: {a}")),
+    _ => return CalcitErr::err_nodes("&buffer:from-base64 expected 1 argument, got:", xs),
+  };
+  let body = s.trim_end_matches('=');
+  if s.len() - body.len() > 2 || body.len() % 4 == 1 || (s.len() != body.len() && s.len() % 4 != 0) {
+    return CalcitErr::err_str(format!("&buffer:from-base64 invalid length of base64: {}", s.len()));
+  }
+  let mut buf: Vec<u8> = Vec::with_capacity(body.len() * 3 / 4);
+  for chunk in body.as_bytes().chunks(4) {
+    let mut n = 0u32;
+    for (idx, c) in chunk.iter().enumerate() {
+      match BASE64_CHARS.iter().position(|x| x == c) {
+        Some(v) => n |= (v as u32) << (18 - idx * 6),
+        None => return CalcitErr::err_str(format!("&buffer:from-base64 invalid character `{}`", *c as char)),
+      }
+    }
+    for idx in 0..(chunk.len() - 1) {
+      buf.push((n >> (16 - idx * 8)) as u8);
+    }
+  }
+  Ok(Calcit::Buffer(buf))
+}
+
+pub fn to_string(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => match std::str::from_utf8(buf) {
+      Ok(s) => Ok(Calcit::Str(s.into())),
+      Err(e) => CalcitErr::err_str(format!("&buffer:to-string invalid UTF-8 at byte {}", e.valid_up_to())),
+    },
+    [a] => CalcitErr::err_str(format!("&buffer:to-string expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:to-string expected 1 argument, got:", xs),
+  }
+}
+
+pub fn from_string(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Str(s)] => Ok(Calcit::Buffer(s.as_bytes().to_vec())),
+    [a] => CalcitErr::err_str(format!("&buffer:from-string expected a string, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:from-string expected 1 argument, got:", xs),
+  }
+}
+
+pub fn to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => Ok(Calcit::from(buf.iter().map(|b| Calcit::Number(*b as f64)).collect::<Vec<_>>())),
+    [a] => CalcitErr::err_str(format!("&buffer:to-list expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:to-list expected 1 argument, got:", xs),
+  }
+}
+
+pub fn from_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::List(ys)] => {
+      let mut buf: Vec<u8> = Vec::with_capacity(ys.len());
+      for y in ys.iter() {
+        buf.push(read_byte(y, "&buffer:from-list")?);
+      }
+      Ok(Calcit::Buffer(buf))
+    }
+    [a] => CalcitErr::err_str(format!("&buffer:from-list expected a list, got: {a}")),
+    _ => CalcitErr::err_nodes("&buffer:from-list expected 1 argument, got:", xs),
+  }
+}
@@ -1,12 +1,16 @@
 use std::env;
 use std::fs;
+use std::io::{self, BufRead, Read};
 use std::process::exit;
+use std::sync::Arc;
 use std::sync::LazyLock;
 use std::sync::RwLock;
 use std::time::Instant;
 
 use crate::{
   calcit::{Calcit, CalcitErr},
+  call_stack::CallStackList,
+  runner::call_callback,
   util::number::f64_to_i32,
 };
 
@@ -20,7 +24,19 @@ pub enum CliRunningMode {
 static STARTED_INSTANT: LazyLock<RwLock<Instant>> = LazyLock::new(|| RwLock::new(Instant::now()));
 static CLI_RUNNING_MODE: LazyLock<RwLock<CliRunningMode>> = LazyLock::new(|| RwLock::new(CliRunningMode::Eval));
 
+/// `(raise & xs)`, values are joined with spaces into the message.
+/// a single value that's not a string is also kept as payload, and an error record received in `try` is rethrown
 pub fn raise(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if let [x] = xs {
+    if let Some(e) = CalcitErr::from_error_record(x) {
+      return Err(e);
+    }
+    if !matches!(x, Calcit::Str(_)) {
+      let mut e = CalcitErr::use_str(x.turn_string());
+      e.payload = Some(Arc::new(x.to_owned()));
+      return Err(e);
+    }
+  }
   let mut s = String::from("");
   for (idx, x) in xs.iter().enumerate() {
     if idx > 0 {
@@ -31,6 +47,18 @@ pub fn raise(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   CalcitErr::err_str(s)
 }
 
+/// `(raise-data message payload)`, payload is passed to handlers of `try` in `:payload`
+pub fn raise_data(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [message, payload] => {
+      let mut e = CalcitErr::use_str(message.turn_string());
+      e.payload = Some(Arc::new(payload.to_owned()));
+      Err(e)
+    }
+    _ => CalcitErr::err_nodes("raise-data expected 2 arguments, got:", xs),
+  }
+}
+
 pub fn init_effects_states() {
   // trigger lazy instant
   let _eff = STARTED_INSTANT.read().expect("read instant");
@@ -78,7 +106,10 @@ pub fn call_get_calcit_backend(_xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 pub fn quit(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Number(n)) => match f64_to_i32(*n) {
-      Ok(code) => exit(code),
+      Ok(code) => {
+        crate::inspect::remove_socket();
+        exit(code)
+      }
       Err(e) => unreachable!("quit failed to get code from f64, {}", e),
     },
     Some(a) => CalcitErr::err_str(format!("quit expected i32 value, got: {a}")),
@@ -133,3 +164,60 @@ pub fn write_file(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     (a, b) => CalcitErr::err_str(format!("write-file expected 2 strings, got: {a:?} {b:?}")),
   }
 }
+
+/// reads stdin until EOF
+pub fn read_stdin(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("read-stdin expected 0 arguments, got:", xs);
+  }
+  let mut content = String::new();
+  match io::stdin().lock().read_to_string(&mut content) {
+    Ok(_) => Ok(Calcit::Str(content.into())),
+    Err(e) => CalcitErr::err_str(format!("read-stdin failed, {e}")),
+  }
+}
+
+pub fn read_line_from(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+  let mut line = String::new();
+  if reader.read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+  if line.ends_with('\n') {
+    line.pop();
+    if line.ends_with('\r') {
+      line.pop();
+    }
+  }
+  Ok(Some(line))
+}
+
+/// reads a line from stdin without the line ending, returns `nil` at EOF
+pub fn read_stdin_line(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("read-stdin-line expected 0 arguments, got:", xs);
+  }
+  match read_line_from(&mut io::stdin().lock()) {
+    Ok(Some(line)) => Ok(Calcit::Str(line.into())),
+    Ok(None) => Ok(Calcit::Nil),
+    Err(e) => CalcitErr::err_str(format!("read-stdin-line failed, {e}")),
+  }
+}
+
+/// `(each-stdin-line f)` calls `f` with every line of stdin until EOF, lines are read lazily
+pub fn each_stdin_line(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  let f = match xs {
+    [f @ (Calcit::Fn { .. } | Calcit::Proc(_))] => f,
+    _ => return CalcitErr::err_nodes("each-stdin-line expected a function, got:", xs),
+  };
+  loop {
+    // not holding the lock during callback, which may read stdin as well
+    let line = read_line_from(&mut io::stdin().lock())
+      .map_err(|e| CalcitErr::use_msg_stack(format!("each-stdin-line failed, {e}"), call_stack))?;
+    match line {
+      Some(line) => {
+        call_callback(f, &[Calcit::Str(line.into())], call_stack)?;
+      }
+      None => return Ok(Calcit::Nil),
+    }
+  }
+}
@@ -0,0 +1,153 @@
+//! filesystem procs, paths are passed as strings and errors carry the path
+
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::calcit::{Calcit, CalcitErr, CalcitList};
+
+fn check_arity(xs: &[Calcit], n: usize, proc_name: &str) -> Result<(), CalcitErr> {
+  if xs.len() == n {
+    return Ok(());
+  }
+  let expected = if n == 1 {
+    String::from("1 argument")
+  } else {
+    format!("{n} arguments")
+  };
+  Err(CalcitErr::use_str(format!(
+    "{proc_name} expected {expected}, got: {}",
+    CalcitList::from(xs)
+  )))
+}
+
+fn read_path<'a>(x: Option<&'a Calcit>, proc_name: &str) -> Result<&'a str, CalcitErr> {
+  match x {
+    Some(Calcit::Str(s)) => Ok(s),
+    Some(a) => Err(CalcitErr::use_str(format!("{proc_name} expected a path string, got: {a}"))),
+    None => Err(CalcitErr::use_str(format!("{proc_name} expected a path, got nothing"))),
+  }
+}
+
+pub fn read_file_buffer(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "read-file-buffer")?;
+  let path = read_path(xs.first(), "read-file-buffer")?;
+  match fs::read(path) {
+    Ok(content) => Ok(Calcit::Buffer(content)),
+    Err(e) => CalcitErr::err_str(format!("read-file-buffer failed at {path}: {e}")),
+  }
+}
+
+pub fn write_file_buffer(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 2, "write-file-buffer")?;
+  let path = read_path(xs.first(), "write-file-buffer")?;
+  match xs.get(1) {
+    Some(Calcit::Buffer(buf)) => match fs::write(path, buf) {
+      Ok(_) => Ok(Calcit::Nil),
+      Err(e) => CalcitErr::err_str(format!("write-file-buffer failed at {path}: {e}")),
+    },
+    Some(a) => CalcitErr::err_str(format!("write-file-buffer expected a buffer, got: {a}")),
+    None => CalcitErr::err_str("write-file-buffer expected a buffer, got nothing"),
+  }
+}
+
+/// names of entries in a directory, sorted
+pub fn list_dir(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "list-dir")?;
+  let path = read_path(xs.first(), "list-dir")?;
+  let entries = fs::read_dir(path).map_err(|e| CalcitErr::use_str(format!("list-dir failed at {path}: {e}")))?;
+  let mut names: Vec<String> = vec![];
+  for entry in entries {
+    let entry = entry.map_err(|e| CalcitErr::use_str(format!("list-dir failed at {path}: {e}")))?;
+    names.push(entry.file_name().to_string_lossy().into_owned());
+  }
+  names.sort();
+  Ok(Calcit::from(names.into_iter().map(|s| Calcit::Str(s.into())).collect::<Vec<_>>()))
+}
+
+/// like `mkdir -p`, parent directories are created as well
+pub fn make_dir(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "make-dir")?;
+  let path = read_path(xs.first(), "make-dir")?;
+  match fs::create_dir_all(path) {
+    Ok(_) => Ok(Calcit::Nil),
+    Err(e) => CalcitErr::err_str(format!("make-dir failed at {path}: {e}")),
+  }
+}
+
+/// removes a file, or a directory with everything inside
+pub fn remove_path(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "remove-path")?;
+  let path = read_path(xs.first(), "remove-path")?;
+  let meta = fs::symlink_metadata(path).map_err(|e| CalcitErr::use_str(format!("remove-path failed at {path}: {e}")))?;
+  let result = if meta.is_dir() {
+    fs::remove_dir_all(path)
+  } else {
+    fs::remove_file(path)
+  };
+  match result {
+    Ok(_) => Ok(Calcit::Nil),
+    Err(e) => CalcitErr::err_str(format!("remove-path failed at {path}: {e}")),
+  }
+}
+
+pub fn rename_path(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 2, "rename-path")?;
+  let from = read_path(xs.first(), "rename-path")?;
+  let to = read_path(xs.get(1), "rename-path")?;
+  match fs::rename(from, to) {
+    Ok(_) => Ok(Calcit::Nil),
+    Err(e) => CalcitErr::err_str(format!("rename-path failed from {from} to {to}: {e}")),
+  }
+}
+
+pub fn path_exists(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "path-exists?")?;
+  let path = read_path(xs.first(), "path-exists?")?;
+  Ok(Calcit::Bool(Path::new(path).exists()))
+}
+
+/// returns a map with `:kind` in `:file` `:dir` `:symlink`, `:size` in bytes, `:modified` in milliseconds, and `:readonly`.
+/// symlinks are not followed
+pub fn path_stat(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "path-stat")?;
+  let path = read_path(xs.first(), "path-stat")?;
+  let meta = fs::symlink_metadata(path).map_err(|e| CalcitErr::use_str(format!("path-stat failed at {path}: {e}")))?;
+  let kind = if meta.is_symlink() {
+    "symlink"
+  } else if meta.is_dir() {
+    "dir"
+  } else {
+    "file"
+  };
+  let modified = match meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+    Some(d) => Calcit::Number(d.as_micros() as f64 / 1000.0),
+    None => Calcit::Nil,
+  };
+  let mut m = rpds::HashTrieMap::new_sync();
+  m.insert_mut(Calcit::tag("kind"), Calcit::tag(kind));
+  m.insert_mut(Calcit::tag("size"), Calcit::Number(meta.len() as f64));
+  m.insert_mut(Calcit::tag("modified"), modified);
+  m.insert_mut(Calcit::tag("readonly"), Calcit::Bool(meta.permissions().readonly()));
+  Ok(Calcit::Map(m))
+}
+
+/// paths matching a pattern like `src/**/*.rs`, sorted
+pub fn glob_paths(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  check_arity(xs, 1, "glob-paths")?;
+  let pattern = match xs.first() {
+    Some(Calcit::Str(s)) => s,
+    Some(a) => return CalcitErr::err_str(format!("glob-paths expected a pattern string, got: {a}")),
+    None => return CalcitErr::err_str("glob-paths expected a pattern, got nothing"),
+  };
+  let paths = glob::glob(pattern).map_err(|e| CalcitErr::use_str(format!("glob-paths got invalid pattern `{pattern}`: {e}")))?;
+  let mut ys: Vec<String> = vec![];
+  for path in paths {
+    match path {
+      Ok(p) => ys.push(p.to_string_lossy().into_owned()),
+      Err(e) => return CalcitErr::err_str(format!("glob-paths failed: {e}")),
+    }
+  }
+  ys.sort();
+  Ok(Calcit::from(ys.into_iter().map(|s| Calcit::Str(s.into())).collect::<Vec<_>>()))
+}
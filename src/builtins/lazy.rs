@@ -0,0 +1,437 @@
+//! procs for lazy sequences. steps like `&lazy:map` only wrap sequences into new ones,
+//! items are pulled one by one through a `LazyCursor` when consumed by `foldl`, `&lazy:to-list` and alike.
+//! lists, sets and maps are turned into lazy sequences with `to-lazy`, maps produce pairs of entries.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+
+use crate::builtins::effects::read_line_from;
+use crate::calcit::{Calcit, CalcitErr, CalcitLazySeq, CalcitList, LazyNode};
+use crate::call_stack::CallStackList;
+use crate::runner::call_callback;
+use crate::util::number::f64_to_usize;
+
+/// state of consuming a lazy sequence, created from the sequence each time it is consumed
+pub enum LazyCursor {
+  List(Arc<CalcitList>, usize),
+  Range {
+    next: f64,
+    to: Option<f64>,
+    step: f64,
+  },
+  Iterate {
+    current: Calcit,
+    f: Calcit,
+    started: bool,
+  },
+  Repeat(Calcit),
+  /// seed is `None` after the function returned `nil`
+  Unfold(Option<Calcit>, Calcit),
+  FileLines(Arc<str>, BufReader<File>),
+  StdinLines,
+  Map(Box<LazyCursor>, Calcit),
+  Filter(Box<LazyCursor>, Calcit),
+  Take(Box<LazyCursor>, usize),
+  Drop(Box<LazyCursor>, usize),
+  /// becomes `None` once an item fails the predicate
+  TakeWhile(Option<Box<LazyCursor>>, Calcit),
+  Concat(Option<Box<LazyCursor>>, VecDeque<Arc<CalcitLazySeq>>),
+  Zip(Box<LazyCursor>, Box<LazyCursor>),
+}
+
+impl LazyCursor {
+  /// files are opened here, so errors of missing files are thrown when sequences are consumed
+  pub fn new(seq: &CalcitLazySeq, call_stack: &CallStackList) -> Result<Self, CalcitErr> {
+    let cursor = match &seq.node {
+      LazyNode::List(xs) => LazyCursor::List(xs.to_owned(), 0),
+      LazyNode::Range { from, to, step } => LazyCursor::Range {
+        next: *from,
+        to: *to,
+        step: *step,
+      },
+      LazyNode::Iterate { init, f } => LazyCursor::Iterate {
+        current: init.to_owned(),
+        f: f.to_owned(),
+        started: false,
+      },
+      LazyNode::Repeat(x) => LazyCursor::Repeat(x.to_owned()),
+      LazyNode::Unfold { seed, f } => LazyCursor::Unfold(Some(seed.to_owned()), f.to_owned()),
+      LazyNode::FileLines(path) => match File::open(&**path) {
+        Ok(file) => LazyCursor::FileLines(path.to_owned(), BufReader::new(file)),
+        Err(e) => {
+          return Err(CalcitErr::use_msg_stack(
+            format!("read-file-lines failed at {path}: {e}"),
+            call_stack,
+          ))
+        }
+      },
+      LazyNode::StdinLines => LazyCursor::StdinLines,
+      LazyNode::Map(xs, f) => LazyCursor::Map(Box::new(Self::new(xs, call_stack)?), f.to_owned()),
+      LazyNode::Filter(xs, f) => LazyCursor::Filter(Box::new(Self::new(xs, call_stack)?), f.to_owned()),
+      LazyNode::Take(xs, n) => LazyCursor::Take(Box::new(Self::new(xs, call_stack)?), *n),
+      LazyNode::Drop(xs, n) => LazyCursor::Drop(Box::new(Self::new(xs, call_stack)?), *n),
+      LazyNode::TakeWhile(xs, f) => LazyCursor::TakeWhile(Some(Box::new(Self::new(xs, call_stack)?)), f.to_owned()),
+      // later sequences are not opened until reached
+      LazyNode::Concat(xss) => LazyCursor::Concat(None, xss.iter().cloned().collect()),
+      LazyNode::Zip(xs, ys) => LazyCursor::Zip(Box::new(Self::new(xs, call_stack)?), Box::new(Self::new(ys, call_stack)?)),
+    };
+    Ok(cursor)
+  }
+
+  /// pulls next item, `None` when the sequence ends
+  pub fn next_item(&mut self, call_stack: &CallStackList) -> Result<Option<Calcit>, CalcitErr> {
+    match self {
+      LazyCursor::List(xs, idx) => {
+        let item = xs.get(*idx).cloned();
+        *idx += 1;
+        Ok(item)
+      }
+      LazyCursor::Range { next, to, step } => {
+        let ended = match to {
+          Some(bound) => (*step > 0.0 && *next >= *bound) || (*step < 0.0 && *next <= *bound),
+          None => false,
+        };
+        if ended {
+          return Ok(None);
+        }
+        let item = *next;
+        *next += *step;
+        Ok(Some(Calcit::Number(item)))
+      }
+      LazyCursor::Iterate { current, f, started } => {
+        if *started {
+          *current = call_callback(f, &[current.to_owned()], call_stack)?;
+        } else {
+          *started = true;
+        }
+        Ok(Some(current.to_owned()))
+      }
+      LazyCursor::Repeat(x) => Ok(Some(x.to_owned())),
+      LazyCursor::Unfold(seed, f) => {
+        let Some(s) = seed.take() else {
+          return Ok(None);
+        };
+        match call_callback(f, &[s], call_stack)? {
+          Calcit::Nil => Ok(None),
+          Calcit::List(pair) if pair.len() == 2 => {
+            *seed = Some(pair[1].to_owned());
+            Ok(Some(pair[0].to_owned()))
+          }
+          a => Err(CalcitErr::use_msg_stack(
+            format!("lazy-unfold expected nil or `[] value next-seed` from function, got: {a}"),
+            call_stack,
+          )),
+        }
+      }
+      LazyCursor::FileLines(path, reader) => match read_line_from(reader) {
+        Ok(line) => Ok(line.map(|s| Calcit::Str(s.into()))),
+        Err(e) => Err(CalcitErr::use_msg_stack(
+          format!("read-file-lines failed at {path}: {e}"),
+          call_stack,
+        )),
+      },
+      LazyCursor::StdinLines => match read_line_from(&mut io::stdin().lock()) {
+        Ok(line) => Ok(line.map(|s| Calcit::Str(s.into()))),
+        Err(e) => Err(CalcitErr::use_msg_stack(format!("read-stdin-lines failed, {e}"), call_stack)),
+      },
+      LazyCursor::Map(xs, f) => match xs.next_item(call_stack)? {
+        Some(x) => Ok(Some(call_callback(f, &[x], call_stack)?)),
+        None => Ok(None),
+      },
+      LazyCursor::Filter(xs, f) => {
+        while let Some(x) = xs.next_item(call_stack)? {
+          if truthy(&call_callback(f, &[x.to_owned()], call_stack)?) {
+            return Ok(Some(x));
+          }
+        }
+        Ok(None)
+      }
+      LazyCursor::Take(xs, remaining) => {
+        // not pulling from sources like stdin after enough items are taken
+        if *remaining == 0 {
+          return Ok(None);
+        }
+        *remaining -= 1;
+        xs.next_item(call_stack)
+      }
+      LazyCursor::Drop(xs, skipping) => {
+        while *skipping > 0 {
+          *skipping -= 1;
+          if xs.next_item(call_stack)?.is_none() {
+            return Ok(None);
+          }
+        }
+        xs.next_item(call_stack)
+      }
+      LazyCursor::TakeWhile(inner, f) => {
+        let Some(xs) = inner else {
+          return Ok(None);
+        };
+        match xs.next_item(call_stack)? {
+          Some(x) if truthy(&call_callback(f, &[x.to_owned()], call_stack)?) => Ok(Some(x)),
+          _ => {
+            *inner = None;
+            Ok(None)
+          }
+        }
+      }
+      LazyCursor::Concat(current, pending) => loop {
+        if let Some(xs) = current {
+          if let Some(x) = xs.next_item(call_stack)? {
+            return Ok(Some(x));
+          }
+        }
+        match pending.pop_front() {
+          Some(seq) => *current = Some(Box::new(Self::new(&seq, call_stack)?)),
+          None => return Ok(None),
+        }
+      },
+      LazyCursor::Zip(xs, ys) => match xs.next_item(call_stack)? {
+        Some(x) => match ys.next_item(call_stack)? {
+          Some(y) => Ok(Some(Calcit::from(CalcitList::from(&[x, y])))),
+          None => Ok(None),
+        },
+        None => Ok(None),
+      },
+    }
+  }
+}
+
+fn truthy(x: &Calcit) -> bool {
+  !matches!(x, Calcit::Nil | Calcit::Bool(false))
+}
+
+fn wrap(node: LazyNode) -> Calcit {
+  Calcit::LazySeq(Arc::new(CalcitLazySeq::new(node)))
+}
+
+/// lazy sequences are used directly, other collections are wrapped with their items
+fn read_seq(x: &Calcit, proc_name: &str) -> Result<Arc<CalcitLazySeq>, CalcitErr> {
+  let items: Vec<Calcit> = match x {
+    Calcit::LazySeq(s) => return Ok(s.to_owned()),
+    Calcit::List(xs) => return Ok(Arc::new(CalcitLazySeq::new(LazyNode::List(xs.to_owned())))),
+    Calcit::Nil => vec![],
+    Calcit::Set(xs) => xs.iter().cloned().collect(),
+    Calcit::SortedSet(xs) => xs.iter().cloned().collect(),
+    Calcit::Map(xs) => xs
+      .iter()
+      .map(|(k, v)| Calcit::from(CalcitList::from(&[k.to_owned(), v.to_owned()])))
+      .collect(),
+    Calcit::SortedMap(xs) => xs
+      .iter()
+      .map(|(k, v)| Calcit::from(CalcitList::from(&[k.to_owned(), v.to_owned()])))
+      .collect(),
+    a => {
+      return Err(CalcitErr::use_str(format!(
+        "{proc_name} expected a lazy-seq or a collection, got: {a}"
+      )))
+    }
+  };
+  Ok(Arc::new(CalcitLazySeq::new(LazyNode::List(Arc::new(CalcitList::Vector(items))))))
+}
+
+fn read_fn(x: &Calcit, proc_name: &str) -> Result<Calcit, CalcitErr> {
+  match x {
+    Calcit::Fn { .. } | Calcit::Proc(_) => Ok(x.to_owned()),
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected a function, got: {a}"))),
+  }
+}
+
+fn read_count(x: &Calcit, proc_name: &str) -> Result<usize, CalcitErr> {
+  match x {
+    Calcit::Number(n) => f64_to_usize(*n).map_err(|e| CalcitErr::use_str(format!("{proc_name} expected a count, {e}"))),
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected a count, got: {a}"))),
+  }
+}
+
+pub fn to_lazy(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => Ok(Calcit::LazySeq(read_seq(a, "to-lazy")?)),
+    _ => CalcitErr::err_nodes("to-lazy expected 1 argument, got:", xs),
+  }
+}
+
+pub fn lazy_seq_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => Ok(Calcit::Bool(matches!(a, Calcit::LazySeq(_)))),
+    _ => CalcitErr::err_nodes("lazy-seq? expected 1 argument, got:", xs),
+  }
+}
+
+/// `(lazy-range)` counts from 0 infinitely, otherwise takes arguments like `range`, and a `nil` bound for an infinite range
+pub fn lazy_range(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let (from, to) = match (xs.first(), xs.get(1)) {
+    (None, _) => (0.0, None),
+    (Some(Calcit::Number(to)), None) => (0.0, Some(*to)),
+    (Some(Calcit::Number(from)), Some(Calcit::Nil)) => (*from, None),
+    (Some(Calcit::Number(from)), Some(Calcit::Number(to))) => (*from, Some(*to)),
+    _ => return CalcitErr::err_nodes("lazy-range expected numbers for base and bound, got:", xs),
+  };
+  let step = match xs.get(2) {
+    Some(Calcit::Number(n)) => *n,
+    Some(a) => return CalcitErr::err_str(format!("lazy-range expected a number for step, got: {a}")),
+    None => 1.0,
+  };
+  if xs.len() > 3 {
+    return CalcitErr::err_nodes("lazy-range expected 0~3 arguments, got:", xs);
+  }
+  let wrong_direction = match to {
+    Some(to) => (to > from && step < 0.0) || (to < from && step > 0.0),
+    None => false,
+  };
+  if step == 0.0 || wrong_direction {
+    return CalcitErr::err_str(format!("lazy-range cannot reach the bound with step {step}"));
+  }
+  Ok(wrap(LazyNode::Range { from, to, step }))
+}
+
+pub fn lazy_iterate(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [init, f] => Ok(wrap(LazyNode::Iterate {
+      init: init.to_owned(),
+      f: read_fn(f, "lazy-iterate")?,
+    })),
+    _ => CalcitErr::err_nodes("lazy-iterate expected an initial value and a function, got:", xs),
+  }
+}
+
+pub fn lazy_repeat(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [x] => Ok(wrap(LazyNode::Repeat(x.to_owned()))),
+    _ => CalcitErr::err_nodes("lazy-repeat expected 1 argument, got:", xs),
+  }
+}
+
+/// `(lazy-unfold seed f)`, where `f` returns `nil` to stop or `[] value next-seed`
+pub fn lazy_unfold(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [seed, f] => Ok(wrap(LazyNode::Unfold {
+      seed: seed.to_owned(),
+      f: read_fn(f, "lazy-unfold")?,
+    })),
+    _ => CalcitErr::err_nodes("lazy-unfold expected a seed and a function, got:", xs),
+  }
+}
+
+/// lines without line endings, the file is read again every time the sequence is consumed
+pub fn read_file_lines(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Str(path)] => Ok(wrap(LazyNode::FileLines(path.to_owned()))),
+    [a] => CalcitErr::err_str(format!("read-file-lines expected a path string, got: {a}")),
+    _ => CalcitErr::err_nodes("read-file-lines expected 1 argument, got:", xs),
+  }
+}
+
+/// lines of stdin, taken away from stdin while consumed
+pub fn read_stdin_lines(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("read-stdin-lines expected 0 arguments, got:", xs);
+  }
+  Ok(wrap(LazyNode::StdinLines))
+}
+
+pub fn map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, f] => Ok(wrap(LazyNode::Map(read_seq(a, "&lazy:map")?, read_fn(f, "&lazy:map")?))),
+    _ => CalcitErr::err_nodes("&lazy:map expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn filter(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, f] => Ok(wrap(LazyNode::Filter(read_seq(a, "&lazy:filter")?, read_fn(f, "&lazy:filter")?))),
+    _ => CalcitErr::err_nodes("&lazy:filter expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn take(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, n] => Ok(wrap(LazyNode::Take(read_seq(a, "&lazy:take")?, read_count(n, "&lazy:take")?))),
+    _ => CalcitErr::err_nodes("&lazy:take expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn drop(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, n] => Ok(wrap(LazyNode::Drop(read_seq(a, "&lazy:drop")?, read_count(n, "&lazy:drop")?))),
+    _ => CalcitErr::err_nodes("&lazy:drop expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn take_while(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, f] => Ok(wrap(LazyNode::TakeWhile(
+      read_seq(a, "&lazy:take-while")?,
+      read_fn(f, "&lazy:take-while")?,
+    ))),
+    _ => CalcitErr::err_nodes("&lazy:take-while expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn concat(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let mut seqs = Vec::with_capacity(xs.len());
+  for x in xs {
+    seqs.push(read_seq(x, "&lazy:concat")?);
+  }
+  Ok(wrap(LazyNode::Concat(seqs)))
+}
+
+/// pairs items at same positions into lists, ends with the shorter sequence
+pub fn zip(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a, b] => Ok(wrap(LazyNode::Zip(read_seq(a, "&lazy:zip")?, read_seq(b, "&lazy:zip")?))),
+    _ => CalcitErr::err_nodes("&lazy:zip expected 2 arguments, got:", xs),
+  }
+}
+
+/// realizes all items, never returns for infinite sequences
+pub fn to_list(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => {
+      let mut cursor = LazyCursor::new(&*read_seq(a, "&lazy:to-list")?, call_stack)?;
+      let mut ys = vec![];
+      while let Some(y) = cursor.next_item(call_stack)? {
+        ys.push(y);
+      }
+      Ok(Calcit::from(ys))
+    }
+    _ => CalcitErr::err_nodes("&lazy:to-list expected 1 argument, got:", xs),
+  }
+}
+
+pub fn first(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => {
+      let mut cursor = LazyCursor::new(&*read_seq(a, "&lazy:first")?, call_stack)?;
+      Ok(cursor.next_item(call_stack)?.unwrap_or(Calcit::Nil))
+    }
+    _ => CalcitErr::err_nodes("&lazy:first expected 1 argument, got:", xs),
+  }
+}
+
+pub fn empty_ques(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => {
+      let mut cursor = LazyCursor::new(&*read_seq(a, "&lazy:empty?")?, call_stack)?;
+      Ok(Calcit::Bool(cursor.next_item(call_stack)?.is_none()))
+    }
+    _ => CalcitErr::err_nodes("&lazy:empty? expected 1 argument, got:", xs),
+  }
+}
+
+/// counts by realizing all items without keeping them
+pub fn count(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => {
+      let mut cursor = LazyCursor::new(&*read_seq(a, "&lazy:count")?, call_stack)?;
+      let mut size = 0;
+      while cursor.next_item(call_stack)?.is_some() {
+        size += 1;
+      }
+      Ok(Calcit::Number(size as f64))
+    }
+    _ => CalcitErr::err_nodes("&lazy:count expected 1 argument, got:", xs),
+  }
+}
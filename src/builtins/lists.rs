@@ -5,8 +5,10 @@ use crate::calcit::{Calcit, CalcitErr, CalcitList, CalcitTuple};
 use crate::util::number::f64_to_usize;
 
 use crate::builtins;
+use crate::builtins::lazy::LazyCursor;
 use crate::call_stack::CallStackList;
 use crate::runner;
+use crate::runner::call_callback;
 
 pub fn new_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   Ok(Calcit::List(Arc::new(xs.into())))
@@ -176,6 +178,24 @@ pub fn reverse(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
 }
 
+/// items of sets for folding, sorted sets are folded in order
+fn set_items(x: &Calcit) -> Box<dyn Iterator<Item = &Calcit> + '_> {
+  match x {
+    Calcit::Set(xs) => Box::new(xs.iter()),
+    Calcit::SortedSet(xs) => Box::new(xs.iter()),
+    _ => Box::new(std::iter::empty()),
+  }
+}
+
+/// entries of maps for folding, sorted maps are folded in order of keys
+fn map_entries(x: &Calcit) -> Box<dyn Iterator<Item = (&Calcit, &Calcit)> + '_> {
+  match x {
+    Calcit::Map(xs) => Box::new(xs.iter()),
+    Calcit::SortedMap(xs) => Box::new(xs.iter()),
+    _ => Box::new(std::iter::empty()),
+  }
+}
+
 /// foldl using syntax for performance, it's supposed to be a function
 pub fn foldl(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   if xs.len() == 3 {
@@ -197,22 +217,22 @@ pub fn foldl(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, Calcit
         Ok(ret)
       }
       // also handles set
-      (Calcit::Set(xs), Calcit::Fn { info, .. }) => {
-        for x in xs {
+      (Calcit::Set(_) | Calcit::SortedSet(_), Calcit::Fn { info, .. }) => {
+        for x in set_items(&xs[0]) {
           ret = runner::run_fn(&[ret, x.to_owned()], info, call_stack)?;
         }
         Ok(ret)
       }
-      (Calcit::Set(xs), Calcit::Proc(proc)) => {
-        for x in xs {
+      (Calcit::Set(_) | Calcit::SortedSet(_), Calcit::Proc(proc)) => {
+        for x in set_items(&xs[0]) {
           // println!("foldl args, {} {}", ret, x.to_owned());
           ret = builtins::handle_proc(*proc, &[ret, x.to_owned()], call_stack)?;
         }
         Ok(ret)
       }
       // also handles map
-      (Calcit::Map(xs), Calcit::Fn { info, .. }) => {
-        for (k, x) in xs {
+      (Calcit::Map(_) | Calcit::SortedMap(_), Calcit::Fn { info, .. }) => {
+        for (k, x) in map_entries(&xs[0]) {
           ret = runner::run_fn(
             &[ret, Calcit::from(CalcitList::from(&[k.to_owned(), x.to_owned()]))],
             info,
@@ -221,8 +241,8 @@ pub fn foldl(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, Calcit
         }
         Ok(ret)
       }
-      (Calcit::Map(xs), Calcit::Proc(proc)) => {
-        for (k, x) in xs {
+      (Calcit::Map(_) | Calcit::SortedMap(_), Calcit::Proc(proc)) => {
+        for (k, x) in map_entries(&xs[0]) {
           // println!("foldl args, {} {}", ret, x.to_owned());
           ret = builtins::handle_proc(
             *proc,
@@ -232,6 +252,14 @@ pub fn foldl(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, Calcit
         }
         Ok(ret)
       }
+      // items are pulled one by one, so sequences larger than memory can be folded
+      (Calcit::LazySeq(s), f @ (Calcit::Fn { .. } | Calcit::Proc(_))) => {
+        let mut cursor = LazyCursor::new(s, call_stack)?;
+        while let Some(x) = cursor.next_item(call_stack)? {
+          ret = call_callback(f, &[ret, x], call_stack)?;
+        }
+        Ok(ret)
+      }
 
       (a, b) => Err(CalcitErr::use_msg_stack_location(
         format!("foldl expected list and function, got: {a} {b}"),
@@ -292,9 +320,9 @@ pub fn foldl_shortcut(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calci
         Ok(default_value.to_owned())
       }
       // almost identical body, except for the type
-      (Calcit::Set(xs), Calcit::Fn { info, .. }) => {
+      (Calcit::Set(_) | Calcit::SortedSet(_), Calcit::Fn { info, .. }) => {
         let mut state = acc.to_owned();
-        for x in xs {
+        for x in set_items(&xs[0]) {
           let pair = runner::run_fn(&[state.to_owned(), x.to_owned()], info, call_stack)?;
           match pair {
             Calcit::Tuple(CalcitTuple { tag: x0, extra, .. }) => match &*x0 {
@@ -329,9 +357,9 @@ pub fn foldl_shortcut(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calci
         Ok(default_value.to_owned())
       }
       // almost identical body, escept for the type
-      (Calcit::Map(xs), Calcit::Fn { info, .. }) => {
+      (Calcit::Map(_) | Calcit::SortedMap(_), Calcit::Fn { info, .. }) => {
         let mut state = acc.to_owned();
-        for (k, x) in xs {
+        for (k, x) in map_entries(&xs[0]) {
           let pair = runner::run_fn(
             &[state.to_owned(), Calcit::from(CalcitList::from(&[k.to_owned(), x.to_owned()]))],
             info,
@@ -369,6 +397,44 @@ pub fn foldl_shortcut(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calci
         }
         Ok(default_value.to_owned())
       }
+      // stops pulling items from the sequence after a shortcut
+      (Calcit::LazySeq(s), f @ (Calcit::Fn { .. } | Calcit::Proc(_))) => {
+        let mut state = acc.to_owned();
+        let mut cursor = LazyCursor::new(s, call_stack)?;
+        while let Some(x) = cursor.next_item(call_stack)? {
+          let pair = call_callback(f, &[state.to_owned(), x], call_stack)?;
+          match pair {
+            Calcit::Tuple(CalcitTuple { tag: x0, extra, .. }) => match &*x0 {
+              Calcit::Bool(b) => {
+                let x1 = extra.first().ok_or(CalcitErr::use_msg_stack_location(
+                  "foldl lazy-seq expected value in tuple",
+                  call_stack,
+                  x0.get_location(),
+                ))?;
+                if *b {
+                  return Ok((*x1).to_owned());
+                } else {
+                  (*x1).clone_into(&mut state)
+                }
+              }
+              a => {
+                return Err(CalcitErr::use_msg_stack_location(
+                  format!("return value in foldl-shortcut should be a bool, got: {a}"),
+                  call_stack,
+                  a.get_location(),
+                ))
+              }
+            },
+            _ => {
+              return Err(CalcitErr::use_msg_stack(
+                format!("return value for foldl-shortcut should be `:: bool acc`, got: {pair}"),
+                call_stack,
+              ))
+            }
+          }
+        }
+        Ok(default_value.to_owned())
+      }
 
       (a, b) => Err(CalcitErr::use_msg_stack_location(
         format!("foldl-shortcut expected list... and fn, got: {a} {b}"),
@@ -464,7 +530,7 @@ pub fn sort(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitE
           let v = runner::run_fn(&[(*a).to_owned(), (*b).to_owned()], info, call_stack);
           match v {
             Ok(Calcit::Number(x)) if x < 0.0 => Ordering::Less,
-            Ok(Calcit::Number(x)) if x == 0.0 => Ordering::Equal,
+            Ok(Calcit::Number(0.0)) => Ordering::Equal,
             Ok(Calcit::Number(x)) if x > 0.0 => Ordering::Greater,
             Ok(a) => {
               eprintln!("expected number from sort comparator, got: {a}");
@@ -491,7 +557,7 @@ pub fn sort(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitE
           let v = builtins::handle_proc(*proc, &[(*a).to_owned(), (*b).to_owned()], call_stack);
           match v {
             Ok(Calcit::Number(x)) if x < 0.0 => Ordering::Less,
-            Ok(Calcit::Number(x)) if x == 0.0 => Ordering::Equal,
+            Ok(Calcit::Number(0.0)) => Ordering::Equal,
             Ok(Calcit::Number(x)) if x > 0.0 => Ordering::Greater,
             Ok(a) => {
               eprintln!("expected number from sort comparator, got: {a}");
@@ -1,9 +1,27 @@
+use std::ops::Bound;
 use std::sync::Arc;
 
 use crate::calcit::{Calcit, CalcitErr, CalcitList, CalcitRecord};
 
 use crate::util::number::is_even;
 
+/// entries of a hash map or a sorted map, sorted maps give entries in order
+fn entries_of(x: &Calcit) -> Box<dyn Iterator<Item = (&Calcit, &Calcit)> + '_> {
+  match x {
+    Calcit::Map(xs) => Box::new(xs.iter()),
+    Calcit::SortedMap(xs) => Box::new(xs.iter()),
+    _ => Box::new(std::iter::empty()),
+  }
+}
+
+fn has_key(x: &Calcit, k: &Calcit) -> bool {
+  match x {
+    Calcit::Map(xs) => xs.contains_key(k),
+    Calcit::SortedMap(xs) => xs.contains_key(k),
+    _ => false,
+  }
+}
+
 pub fn call_new_map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if is_even(xs.len()) {
     let n = xs.len() >> 1;
@@ -17,6 +35,28 @@ pub fn call_new_map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
 }
 
+pub fn new_sorted_map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if is_even(xs.len()) {
+    let mut ys = rpds::RedBlackTreeMap::new_sync();
+    for pair in xs.chunks(2) {
+      ys.insert_mut(pair[0].to_owned(), pair[1].to_owned());
+    }
+    Ok(Calcit::SortedMap(ys))
+  } else {
+    CalcitErr::err_str(format!(
+      "sorted-map expected even number of arguments, got: {}",
+      CalcitList::from(xs)
+    ))
+  }
+}
+
+pub fn sorted_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => Ok(Calcit::Bool(matches!(a, Calcit::SortedMap(_)))),
+    _ => CalcitErr::err_nodes("sorted-map? expected 1 argument, got:", xs),
+  }
+}
+
 pub fn dissoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.len() < 2 {
     return CalcitErr::err_nodes("map dissoc expected at least 2 arguments:", xs);
@@ -34,6 +74,13 @@ pub fn dissoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       }
       Ok(Calcit::Map(ys.to_owned()))
     }
+    Some(Calcit::SortedMap(base)) => {
+      let mut ys = base.to_owned();
+      for x in &xs[1..] {
+        ys.remove_mut(x);
+      }
+      Ok(Calcit::SortedMap(ys))
+    }
     Some(a) => CalcitErr::err_str(format!("map dissoc expected a map, got: {a}")),
     _ => CalcitErr::err_nodes("map dissoc expected 2 arguments, got:", xs),
   }
@@ -48,6 +95,7 @@ pub fn get(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
         None => Ok(Calcit::Nil),
       }
     }
+    (Some(Calcit::SortedMap(xs)), Some(a)) => Ok(xs.get(a).map_or(Calcit::Nil, ToOwned::to_owned)),
     (Some(a), ..) => CalcitErr::err_str(format!("map &get expected map, got: {a}")),
     (None, ..) => CalcitErr::err_nodes("map &get expected 2 arguments, got:", xs),
   }
@@ -57,13 +105,21 @@ pub fn call_merge(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.len() == 2 {
     match (&xs[0], &xs[1]) {
       (Calcit::Map(xs), Calcit::Nil) => Ok(Calcit::Map(xs.to_owned())),
-      (Calcit::Map(xs), Calcit::Map(ys)) => {
+      (Calcit::SortedMap(xs), Calcit::Nil) => Ok(Calcit::SortedMap(xs.to_owned())),
+      (Calcit::Map(xs), ys @ (Calcit::Map(_) | Calcit::SortedMap(_))) => {
         let mut zs: rpds::HashTrieMapSync<Calcit, Calcit> = xs.to_owned();
-        for (k, v) in ys {
+        for (k, v) in entries_of(ys) {
           zs.insert_mut(k.to_owned(), v.to_owned());
         }
         Ok(Calcit::Map(zs))
       }
+      (Calcit::SortedMap(xs), ys @ (Calcit::Map(_) | Calcit::SortedMap(_))) => {
+        let mut zs = xs.to_owned();
+        for (k, v) in entries_of(ys) {
+          zs.insert_mut(k.to_owned(), v.to_owned());
+        }
+        Ok(Calcit::SortedMap(zs))
+      }
       (
         Calcit::Record(
           record @ CalcitRecord {
@@ -71,12 +127,13 @@ pub fn call_merge(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
             fields,
             values,
             class,
+            ..
           },
         ),
-        Calcit::Map(ys),
+        ys @ (Calcit::Map(_) | Calcit::SortedMap(_)),
       ) => {
         let mut new_values = (**values).to_owned();
-        for (k, v) in ys {
+        for (k, v) in entries_of(ys) {
           match k {
             Calcit::Str(s) | Calcit::Symbol { sym: s, .. } => match record.index_of(s) {
               Some(pos) => v.clone_into(&mut new_values[pos]),
@@ -94,6 +151,7 @@ pub fn call_merge(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
           fields: fields.to_owned(),
           values: Arc::new(new_values),
           class: class.to_owned(),
+          schema: record.schema.to_owned(),
         }))
       }
       (a, b) => CalcitErr::err_str(format!("expected 2 maps, got: {a} {b}")),
@@ -115,6 +173,14 @@ pub fn to_pairs(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       }
       Ok(Calcit::Set(zs))
     }
+    // pairs are ordered by keys since keys are unique
+    Some(Calcit::SortedMap(ys)) => {
+      let mut zs: rpds::RedBlackTreeSetSync<Calcit> = rpds::RedBlackTreeSet::new_sync();
+      for (k, v) in ys {
+        zs.insert_mut(Calcit::from(vec![k.to_owned(), v.to_owned()]));
+      }
+      Ok(Calcit::SortedSet(zs))
+    }
     Some(Calcit::Record(CalcitRecord { fields, values, .. })) => {
       let mut zs: rpds::HashTrieSetSync<Calcit> = rpds::HashTrieSet::new_sync();
       for idx in 0..fields.len() {
@@ -130,26 +196,35 @@ pub fn to_pairs(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn call_merge_non_nil(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(xs)), Some(Calcit::Map(ys))) => {
+    (Some(Calcit::Map(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
       let mut zs: rpds::HashTrieMapSync<Calcit, Calcit> = xs.to_owned();
-      for (k, v) in ys {
+      for (k, v) in entries_of(ys) {
         if *v != Calcit::Nil {
           zs.insert_mut(k.to_owned(), v.to_owned());
         }
       }
       Ok(Calcit::Map(zs))
     }
+    (Some(Calcit::SortedMap(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
+      let mut zs = xs.to_owned();
+      for (k, v) in entries_of(ys) {
+        if *v != Calcit::Nil {
+          zs.insert_mut(k.to_owned(), v.to_owned());
+        }
+      }
+      Ok(Calcit::SortedMap(zs))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("expected 2 maps, got: {a} {b}")),
     (_, _) => CalcitErr::err_nodes("expected 2 arguments, got:", xs),
   }
 }
 
-/// out to list, but with a arbitrary order
+/// out to list, but with a arbitrary order, or in order of keys for sorted maps
 pub fn to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
-    Some(Calcit::Map(m)) => {
+    Some(m @ (Calcit::Map(_) | Calcit::SortedMap(_))) => {
       let mut ys = vec![];
-      for (k, v) in m {
+      for (k, v) in entries_of(m) {
         let zs = vec![k.to_owned(), v.to_owned()];
         ys.push(Calcit::from(zs));
       }
@@ -163,6 +238,7 @@ pub fn to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Map(ys)) => Ok(Calcit::Number(ys.size() as f64)),
+    Some(Calcit::SortedMap(ys)) => Ok(Calcit::Number(ys.size() as f64)),
     Some(a) => CalcitErr::err_str(format!("map count expected a map, got: {a}")),
     None => CalcitErr::err_str("map count expected 1 argument"),
   }
@@ -171,6 +247,7 @@ pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 pub fn empty_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Map(ys)) => Ok(Calcit::Bool(ys.is_empty())),
+    Some(Calcit::SortedMap(ys)) => Ok(Calcit::Bool(ys.is_empty())),
     Some(a) => CalcitErr::err_str(format!("map empty? expected some map, got: {a}")),
     None => CalcitErr::err_str("map empty? expected 1 argument"),
   }
@@ -178,7 +255,7 @@ pub fn empty_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn contains_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(xs)), Some(a)) => Ok(Calcit::Bool(xs.contains_key(a))),
+    (Some(m @ (Calcit::Map(_) | Calcit::SortedMap(_))), Some(a)) => Ok(Calcit::Bool(has_key(m, a))),
     (Some(a), ..) => CalcitErr::err_str(format!("map contains? expected a map, got: {a}")),
     (None, ..) => CalcitErr::err_nodes("map contains? expected 2 arguments, got:", xs),
   }
@@ -186,8 +263,8 @@ pub fn contains_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn includes_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(ys)), Some(a)) => {
-      for (_k, v) in ys {
+    (Some(m @ (Calcit::Map(_) | Calcit::SortedMap(_))), Some(a)) => {
+      for (_k, v) in entries_of(m) {
         if v == a {
           return Ok(Calcit::Bool(true));
         }
@@ -210,6 +287,18 @@ pub fn destruct(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       }
       None => Ok(Calcit::Nil),
     },
+    Some(Calcit::SortedMap(ys)) => match ys.first() {
+      Some((k0, v0)) => {
+        let mut zs = ys.to_owned();
+        zs.remove_mut(k0);
+        Ok(Calcit::from(CalcitList::from(&[
+          k0.to_owned(),
+          v0.to_owned(),
+          Calcit::SortedMap(zs),
+        ])))
+      }
+      None => Ok(Calcit::Nil),
+    },
     Some(a) => CalcitErr::err_str(format!("&map:destruct expected a map, got: {a}")),
     None => CalcitErr::err_nodes("&map:destruct expected 1 argument, got:", xs),
   }
@@ -229,6 +318,17 @@ pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
         Ok(Calcit::Map(ys))
       }
     }
+    Some(Calcit::SortedMap(base)) => {
+      if xs.len() % 2 != 1 {
+        CalcitErr::err_nodes("map:assoc expected odd number of arguments, got:", xs)
+      } else {
+        let mut ys = base.to_owned();
+        for pair in xs[1..].chunks(2) {
+          ys.insert_mut(pair[0].to_owned(), pair[1].to_owned());
+        }
+        Ok(Calcit::SortedMap(ys))
+      }
+    }
     Some(a) => CalcitErr::err_str(format!("map:assoc expected a map, got: {a}")),
     None => CalcitErr::err_nodes("map:assoc expected 3 arguments, got:", xs),
   }
@@ -236,15 +336,22 @@ pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn diff_new(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(xs)), Some(Calcit::Map(ys))) => {
+    (Some(Calcit::Map(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
       let zs = &mut xs.to_owned();
-      for k in ys.keys() {
+      for (k, _) in entries_of(ys) {
         if zs.contains_key(k) {
           zs.remove_mut(k);
         }
       }
       Ok(Calcit::Map(zs.to_owned()))
     }
+    (Some(Calcit::SortedMap(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
+      let mut zs = xs.to_owned();
+      for (k, _) in entries_of(ys) {
+        zs.remove_mut(k);
+      }
+      Ok(Calcit::SortedMap(zs))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("map:new_entries expected 2 maps, got: {a} {b}")),
     (..) => CalcitErr::err_nodes("map:diff-new expected 2 arguments, got", xs),
   }
@@ -252,15 +359,24 @@ pub fn diff_new(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn diff_keys(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(xs)), Some(Calcit::Map(ys))) => {
+    (Some(Calcit::Map(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
       let mut ks: rpds::HashTrieSetSync<Calcit> = rpds::HashTrieSet::new_sync();
       for k in xs.keys() {
-        if !ys.contains_key(k) {
+        if !has_key(ys, k) {
           ks.insert_mut(k.to_owned());
         }
       }
       Ok(Calcit::Set(ks))
     }
+    (Some(Calcit::SortedMap(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
+      let mut ks: rpds::RedBlackTreeSetSync<Calcit> = rpds::RedBlackTreeSet::new_sync();
+      for k in xs.keys() {
+        if !has_key(ys, k) {
+          ks.insert_mut(k.to_owned());
+        }
+      }
+      Ok(Calcit::SortedSet(ks))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("map:diff-keys expected 2 maps, got: {a} {b}")),
     (..) => CalcitErr::err_nodes("map:diff-keys expected 2 arguments, got:", xs),
   }
@@ -268,16 +384,85 @@ pub fn diff_keys(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn common_keys(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Map(xs)), Some(Calcit::Map(ys))) => {
+    (Some(Calcit::Map(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
       let mut ks: rpds::HashTrieSetSync<Calcit> = rpds::HashTrieSet::new_sync();
       for k in xs.keys() {
-        if ys.contains_key(k) {
+        if has_key(ys, k) {
           ks.insert_mut(k.to_owned());
         }
       }
       Ok(Calcit::Set(ks))
     }
+    (Some(Calcit::SortedMap(xs)), Some(ys @ (Calcit::Map(_) | Calcit::SortedMap(_)))) => {
+      let mut ks: rpds::RedBlackTreeSetSync<Calcit> = rpds::RedBlackTreeSet::new_sync();
+      for k in xs.keys() {
+        if has_key(ys, k) {
+          ks.insert_mut(k.to_owned());
+        }
+      }
+      Ok(Calcit::SortedSet(ks))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("map:diff-keys expected 2 maps, got: {a} {b}")),
     (..) => CalcitErr::err_nodes("map:common-keys expected 2 arguments, got:", xs),
   }
 }
+
+/// entry with the smallest key in a sorted map, as a pair
+pub fn first(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedMap(ys)] => Ok(
+      ys.first()
+        .map_or(Calcit::Nil, |(k, v)| Calcit::from(vec![k.to_owned(), v.to_owned()])),
+    ),
+    [a] => CalcitErr::err_str(format!("&map:first expected a sorted map, got: {a}")),
+    _ => CalcitErr::err_nodes("&map:first expected 1 argument, got:", xs),
+  }
+}
+
+/// entry with the largest key in a sorted map, as a pair
+pub fn last(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedMap(ys)] => Ok(
+      ys.last()
+        .map_or(Calcit::Nil, |(k, v)| Calcit::from(vec![k.to_owned(), v.to_owned()])),
+    ),
+    [a] => CalcitErr::err_str(format!("&map:last expected a sorted map, got: {a}")),
+    _ => CalcitErr::err_nodes("&map:last expected 1 argument, got:", xs),
+  }
+}
+
+/// bounds of ranges in sorted maps and sorted sets, from `from` inclusive to `to` exclusive, `nil` for no bound.
+/// `nil` is the smallest value so a `nil` for `from` still includes everything
+pub(crate) fn range_bounds<'a>(
+  from: &'a Calcit,
+  to: &'a Calcit,
+  proc_name: &str,
+) -> Result<(Bound<&'a Calcit>, Bound<&'a Calcit>), CalcitErr> {
+  let end = match to {
+    Calcit::Nil => Bound::Unbounded,
+    _ => Bound::Excluded(to),
+  };
+  if let Bound::Excluded(to) = end {
+    if from > to {
+      return Err(CalcitErr::use_str(format!(
+        "{proc_name} expected range from a smaller value, got: {from} {to}"
+      )));
+    }
+  }
+  Ok((Bound::Included(from), end))
+}
+
+/// entries in a sorted map with keys from `from` inclusive to `to` exclusive, `nil` for no bound
+pub fn range(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedMap(ys), from, to] => {
+      let mut zs = rpds::RedBlackTreeMap::new_sync();
+      for (k, v) in ys.range(range_bounds(from, to, "&map:range")?) {
+        zs.insert_mut(k.to_owned(), v.to_owned());
+      }
+      Ok(Calcit::SortedMap(zs))
+    }
+    [a, _, _] => CalcitErr::err_str(format!("&map:range expected a sorted map, got: {a}")),
+    _ => CalcitErr::err_nodes("&map:range expected 3 arguments, got:", xs),
+  }
+}
@@ -1,17 +1,19 @@
 use crate::{
-  builtins,
+  builtins::{self, protocols},
   calcit::{
     self, gen_core_id, Calcit, CalcitErr, CalcitImport, CalcitList, CalcitLocal, CalcitRecord, CalcitSymbolInfo, CalcitSyntax,
-    CalcitTuple, GENERATED_DEF, GEN_NS,
+    CalcitTuple, MethodInlineCache, NodeLocation, ReceiverClass, GENERATED_DEF, GEN_NS,
   },
   call_stack::{self, CallStackList},
   codegen::gen_ir::dump_code,
   data::{
+    binary,
     cirru::{self, cirru_to_calcit},
     data_to_calcit,
     edn::{self, edn_to_calcit},
+    json::{self, JsonBigNumber, JsonFormatOptions, JsonParseOptions},
   },
-  runner,
+  program, runner,
   util::number::f64_to_usize,
 };
 
@@ -28,8 +30,75 @@ use std::{
 
 static JS_SYMBOL_INDEX: AtomicUsize = AtomicUsize::new(0);
 
+/// counters of `gensym` evaluated at runtime, by namespace
 pub(crate) static NS_SYMBOL_DICT: LazyLock<Mutex<HashMap<Arc<str>, usize>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// seed of an expansion, and the count of the next symbol
+type GensymSeed = (Arc<str>, usize);
+
+/// seeds and counters of `gensym` for macros being expanded in preprocessing, innermost at the end.
+/// functions called by a macro share its counter, since they run inside the expansion
+static MACRO_GENSYM_SEEDS: LazyLock<Mutex<Vec<GensymSeed>>> = LazyLock::new(|| Mutex::new(vec![]));
+
+/// 64-bit FNV-1a, seeds stay the same across Rust versions and platforms
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+  bytes
+    .iter()
+    .fold(hash, |h, b| (h ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// pops the seed of an expansion when dropped
+pub struct GensymSeedGuard(());
+
+impl Drop for GensymSeedGuard {
+  fn drop(&mut self) {
+    MACRO_GENSYM_SEEDS.lock().expect("lock gensym seeds").pop();
+  }
+}
+
+/// enters a seed of `gensym` for a macro expanded during preprocessing. the seed is a hash of the macro,
+/// the call site and the call, so generated symbols only depend on code, not on which defs were preprocessed before.
+/// symbols are counted from 1 in each expansion, until the guard is dropped
+pub fn enter_gensym_seed(macro_ns: &str, macro_name: &str, location: &NodeLocation, call: &Calcit) -> GensymSeedGuard {
+  let call_text = call.to_string();
+  let coord = location.coord.as_slice();
+  let parts: [&[u8]; 6] = [
+    macro_ns.as_bytes(),
+    macro_name.as_bytes(),
+    location.ns.as_bytes(),
+    location.def.as_bytes(),
+    coord,
+    call_text.as_bytes(),
+  ];
+  // lengths are hashed before parts, so parts are not mixed with neighbours
+  let hash = parts.iter().fold(0xcbf2_9ce4_8422_2325, |h, part| {
+    fnv1a(fnv1a(h, &(part.len() as u64).to_le_bytes()), part)
+  });
+  MACRO_GENSYM_SEEDS
+    .lock()
+    .expect("lock gensym seeds")
+    .push((format!("{hash:016x}").into(), 1));
+  GensymSeedGuard(())
+}
+
+/// suffix of the next generated symbol, from the seed of the macro being expanded, or from the counter of the namespace at runtime
+pub(crate) fn next_gensym_suffix(file_ns: &str) -> String {
+  if let Some((seed, n)) = MACRO_GENSYM_SEEDS.lock().expect("lock gensym seeds").last_mut() {
+    let v = *n;
+    *n += 1;
+    return format!("{seed}_{v}");
+  }
+  let mut ns_sym_dict = NS_SYMBOL_DICT.lock().expect("open symbol dict");
+  if let Some(n) = ns_sym_dict.get_mut(file_ns) {
+    let v = n.to_owned();
+    *n += 1;
+    v.to_string()
+  } else {
+    ns_sym_dict.insert(file_ns.into(), 2);
+    String::from("1")
+  }
+}
+
 pub fn type_of(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.len() != 1 {
     return CalcitErr::err_nodes("type-of expected 1 argument, got:", xs);
@@ -48,8 +117,9 @@ pub fn type_of(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     Calcit::CirruQuote(..) => Ok(Calcit::tag("cirru-quote")),
     Calcit::Recur(..) => Ok(Calcit::tag("recur")),
     Calcit::List(..) => Ok(Calcit::tag("list")),
-    Calcit::Set(..) => Ok(Calcit::tag("set")),
-    Calcit::Map(..) => Ok(Calcit::tag("map")),
+    // sorted sets and sorted maps are still sets and maps, checked with `sorted-set?` and `sorted-map?`
+    Calcit::Set(..) | Calcit::SortedSet(..) => Ok(Calcit::tag("set")),
+    Calcit::Map(..) | Calcit::SortedMap(..) => Ok(Calcit::tag("map")),
     Calcit::Record { .. } => Ok(Calcit::tag("record")),
     Calcit::Proc(..) => Ok(Calcit::tag("fn")), // special kind proc, but also fn
     Calcit::Macro { .. } => Ok(Calcit::tag("macro")),
@@ -60,6 +130,9 @@ pub fn type_of(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     Calcit::Local { .. } => Ok(Calcit::tag("local")),
     Calcit::Import { .. } => Ok(Calcit::tag("import")),
     Calcit::Registered(..) => Ok(Calcit::tag("registered")),
+    Calcit::Promise(..) => Ok(Calcit::tag("promise")),
+    Calcit::Transient(..) => Ok(Calcit::tag("transient")),
+    Calcit::LazySeq(..) => Ok(Calcit::tag("lazy-seq")),
     Calcit::AnyRef(..) => Ok(Calcit::tag("any-ref")),
   }
 }
@@ -108,12 +181,14 @@ pub fn reset_gensym_index(_xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   Ok(Calcit::Nil)
 }
 
+/// resets counters of namespaces, symbols generated during preprocessing are not affected
 pub fn force_reset_gensym_index() -> Result<(), String> {
   let mut ns_symbol_dict = NS_SYMBOL_DICT.lock().expect("write symbols");
   ns_symbol_dict.clear();
   Ok(())
 }
 
+/// called before generating code of each def, so names in a def do not depend on other defs
 pub fn reset_js_gensym_index() {
   let _ = JS_SYMBOL_INDEX.swap(0, atomic::Ordering::SeqCst);
 }
@@ -201,12 +276,12 @@ pub fn format_cirru(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
 }
 
-pub fn parse_cirru_edn(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+pub fn parse_cirru_edn(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Str(s)) => match cirru_edn::parse(s) {
       Ok(nodes) => match xs.get(1) {
-        Some(options) => Ok(edn::edn_to_calcit(&nodes, options)),
-        None => Ok(edn::edn_to_calcit(&nodes, &Calcit::Nil)),
+        Some(options) => edn::edn_to_calcit(&nodes, options, call_stack),
+        None => edn::edn_to_calcit(&nodes, &Calcit::Nil, call_stack),
       },
       Err(e) => CalcitErr::err_str(format!("parse-cirru-edn failed, {e}")),
     },
@@ -222,6 +297,83 @@ pub fn format_cirru_edn(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
 }
 
+/// reads an option from a map like `{} (:pretty true)`
+fn read_option<'a>(options: Option<&'a Calcit>, name: &str, proc_name: &str) -> Result<Option<&'a Calcit>, CalcitErr> {
+  match options {
+    None | Some(Calcit::Nil) => Ok(None),
+    Some(Calcit::Map(m)) => Ok(m.get(&Calcit::tag(name)).filter(|v| !matches!(v, Calcit::Nil))),
+    Some(a) => Err(CalcitErr::use_str(format!("{proc_name} expected a map of options, got: {a}"))),
+  }
+}
+
+pub fn parse_json(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.is_empty() || xs.len() > 2 {
+    return CalcitErr::err_nodes("parse-json expected 1~2 arguments, got:", xs);
+  }
+  let mut options = JsonParseOptions::default();
+  match read_option(xs.get(1), "keys", "parse-json")? {
+    None => (),
+    Some(Calcit::Tag(t)) if t.ref_str() == "tag" => options.keys_as_tags = true,
+    Some(Calcit::Tag(t)) if t.ref_str() == "string" => options.keys_as_tags = false,
+    Some(a) => return CalcitErr::err_str(format!("parse-json expected :tag or :string for :keys, got: {a}")),
+  }
+  match read_option(xs.get(1), "big-number", "parse-json")? {
+    None => (),
+    Some(Calcit::Tag(t)) if t.ref_str() == "float" => options.big_number = JsonBigNumber::Float,
+    Some(Calcit::Tag(t)) if t.ref_str() == "error" => options.big_number = JsonBigNumber::Error,
+    Some(Calcit::Tag(t)) if t.ref_str() == "string" => options.big_number = JsonBigNumber::Str,
+    Some(a) => return CalcitErr::err_str(format!("parse-json expected :float, :error or :string for :big-number, got: {a}")),
+  }
+  match &xs[0] {
+    Calcit::Str(s) => json::parse_json(s, options).map_err(|e| CalcitErr::use_str(format!("parse-json {e}"))),
+    a => CalcitErr::err_str(format!("parse-json expected a string, got: {a}")),
+  }
+}
+
+pub fn format_json(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.is_empty() || xs.len() > 2 {
+    return CalcitErr::err_nodes("format-json expected 1~2 arguments, got:", xs);
+  }
+  let mut options = JsonFormatOptions::default();
+  match read_option(xs.get(1), "pretty", "format-json")? {
+    None | Some(Calcit::Bool(false)) => (),
+    Some(Calcit::Bool(true)) => options.indent = Some(2),
+    Some(Calcit::Number(n)) => match f64_to_usize(*n) {
+      Ok(size) if size <= 10 => options.indent = if size == 0 { None } else { Some(size) },
+      _ => return CalcitErr::err_str(format!("format-json expected indentation within 0~10, got: {n}")),
+    },
+    Some(a) => return CalcitErr::err_str(format!("format-json expected a bool or a number for :pretty, got: {a}")),
+  }
+  match read_option(xs.get(1), "non-finite", "format-json")? {
+    None => (),
+    Some(Calcit::Tag(t)) if t.ref_str() == "error" => options.non_finite_as_null = false,
+    Some(Calcit::Tag(t)) if t.ref_str() == "nil" => options.non_finite_as_null = true,
+    Some(a) => return CalcitErr::err_str(format!("format-json expected :error or :nil for :non-finite, got: {a}")),
+  }
+  match json::format_json(&xs[0], options) {
+    Ok(s) => Ok(Calcit::Str(s.into())),
+    Err(e) => CalcitErr::err_str(format!("format-json {e}")),
+  }
+}
+
+pub fn parse_binary(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Buffer(buf)] => binary::parse_binary(buf).map_err(|e| CalcitErr::use_str(format!("parse-binary {e}"))),
+    [a] => CalcitErr::err_str(format!("parse-binary expected a buffer, got: {a}")),
+    _ => CalcitErr::err_nodes("parse-binary expected 1 argument, got:", xs),
+  }
+}
+
+pub fn format_binary(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [x] => match binary::format_binary(x) {
+      Ok(buf) => Ok(Calcit::Buffer(buf)),
+      Err(e) => CalcitErr::err_str(format!("format-binary {e}")),
+    },
+    _ => CalcitErr::err_nodes("format-binary expected 1 argument, got:", xs),
+  }
+}
+
 pub fn cirru_quote_to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.len() != 1 {
     return CalcitErr::err_nodes("&cirru-quote:to-list expected 1 argument, got:", xs);
@@ -316,7 +468,32 @@ pub fn new_class_tuple(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
 }
 
-pub fn invoke_method(name: &str, method_args: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+/// builtin class of a value which is not a record or a tuple, named by its definition in `calcit.core`
+pub fn core_class_name(x: &Calcit) -> Option<&'static str> {
+  match x {
+    Calcit::List(..) => Some("&core-list-class"),
+    Calcit::Map(..) | Calcit::SortedMap(..) => Some("&core-map-class"),
+    Calcit::Number(..) => Some("&core-number-class"),
+    Calcit::Str(..) => Some("&core-string-class"),
+    Calcit::Set(..) | Calcit::SortedSet(..) => Some("&core-set-class"),
+    Calcit::Nil => Some("&core-nil-class"),
+    Calcit::Fn { .. } | Calcit::Proc(..) => Some("&core-fn-class"),
+    Calcit::Buffer(..) => Some("&core-buffer-class"),
+    Calcit::Transient(..) => Some("&core-transient-class"),
+    Calcit::LazySeq(..) => Some("&core-lazy-seq-class"),
+    _ => None,
+  }
+}
+
+/// methods found in a class are kept in the cache of the call site,
+/// calls on receivers of the same class skip looking up class and method again.
+/// methods missing in the class are looked up from implementations of protocols
+pub fn invoke_method(
+  name: &str,
+  cache: &MethodInlineCache,
+  method_args: &[Calcit],
+  call_stack: &CallStackList,
+) -> Result<Calcit, CalcitErr> {
   if method_args.is_empty() {
     return Err(CalcitErr::use_msg_stack(
       format!("expected operand for method invoking: {:?}", method_args),
@@ -324,111 +501,81 @@ pub fn invoke_method(name: &str, method_args: &[Calcit], call_stack: &CallStackL
     ));
   }
   let v0 = &method_args[0];
-  match v0 {
-    Calcit::Tuple(CalcitTuple { class, .. }) => match class {
-      Some(record) => method_record(record, v0, name, method_args, call_stack),
-      None => Err(CalcitErr::use_msg_stack(
-        format!("cannot find class for method invoking: {v0}"),
-        call_stack,
-      )),
-    },
-    Calcit::Record(CalcitRecord { class, .. }) => match class {
-      Some(record) => method_record(record, v0, name, method_args, call_stack),
-      None => Err(CalcitErr::use_msg_stack(
-        format!("cannot find class for method invoking: {v0}"),
-        call_stack,
-      )),
+  let receiver_class = match v0 {
+    Calcit::Tuple(CalcitTuple { class, .. }) | Calcit::Record(CalcitRecord { class, .. }) => class.as_ref().map(ReceiverClass::Record),
+    x => match core_class_name(x) {
+      Some(def) => Some(ReceiverClass::Core(def)),
+      None => {
+        return Err(CalcitErr::use_msg_stack_location(
+          format!("cannot decide a class from: {x}"),
+          call_stack,
+          x.get_location(),
+        ))
+      }
     },
+  };
 
-    // classed should already be preprocessed
-    Calcit::List(..) => {
-      let class = runner::evaluate_symbol_from_program("&core-list-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    Calcit::Map(..) => {
-      let class = runner::evaluate_symbol_from_program("&core-map-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    Calcit::Number(..) => {
-      let class = runner::evaluate_symbol_from_program("&core-number-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    Calcit::Str(..) => {
-      let class = runner::evaluate_symbol_from_program("&core-string-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    Calcit::Set(..) => {
-      let class = &runner::evaluate_symbol_from_program("&core-set-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(class, v0, name, method_args, call_stack)
+  // builtin classes are defined in program, so looked up again after code changes
+  let generation = program::program_generation();
+  if let Some(class) = receiver_class {
+    if let Some(method) = cache.lookup(class, generation) {
+      return call_method(&method, method_args, call_stack);
     }
-    Calcit::Nil => {
-      let class = runner::evaluate_symbol_from_program("&core-nil-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    Calcit::Fn { .. } | Calcit::Proc(..) => {
-      let class = runner::evaluate_symbol_from_program("&core-fn-class", calcit::CORE_NS, None, call_stack)?;
-      method_call(&class, v0, name, method_args, call_stack)
-    }
-    x => Err(CalcitErr::use_msg_stack_location(
-      format!("cannot decide a class from: {x}"),
-      call_stack,
-      x.get_location(),
-    )),
   }
-}
 
-fn method_call(
-  class: &Calcit,
-  v0: &Calcit,
-  name: &str,
-  method_args: &[Calcit],
-  call_stack: &CallStackList,
-) -> Result<Calcit, CalcitErr> {
-  // match &class {
-  //   Some(record) => method_record(record, v0, name, method_args, call_stack),
-  //   None => CalcitErr::err_str(format!("cannot find class for method invoking: {v0}")),
-  // }
+  let class = match receiver_class {
+    Some(ReceiverClass::Record(record)) => Some((**record).to_owned()),
+    Some(ReceiverClass::Core(def)) => match runner::evaluate_symbol_from_program(def, calcit::CORE_NS, None, call_stack)? {
+      Calcit::Record(record) => Some(record),
+      x => {
+        return Err(CalcitErr::use_msg_stack_location(
+          format!("cannot find class for method invoking: {v0}"),
+          call_stack,
+          x.get_location(),
+        ))
+      }
+    },
+    None => None,
+  };
+  if let (Some(receiver_class), Some(method)) = (receiver_class, class.as_ref().and_then(|c| c.get(name))) {
+    cache.store(receiver_class, generation, method.to_owned());
+    return call_method(method, method_args, call_stack);
+  }
+  // implementations could be registered any time, so they are not cached
+  if let Some(method) = protocols::find_impl(v0, name) {
+    return call_method(&method, method_args, call_stack);
+  }
   match class {
-    Calcit::Record(record) => method_record(record, v0, name, method_args, call_stack),
-    x => Err(CalcitErr::use_msg_stack_location(
+    Some(class) => Err(unknown_method(&class, v0, name, call_stack)),
+    None => Err(CalcitErr::use_msg_stack(
       format!("cannot find class for method invoking: {v0}"),
       call_stack,
-      x.get_location(),
     )),
   }
 }
 
-fn method_record(
-  class: &CalcitRecord,
-  v0: &Calcit,
-  name: &str,
-  method_args: &[Calcit],
-  call_stack: &CallStackList,
-) -> Result<Calcit, CalcitErr> {
-  match class.get(name) {
-    Some(v) => {
-      match v {
-        // dirty copy...
-        Calcit::Fn { info, .. } => runner::run_fn(method_args, info, call_stack),
-        Calcit::Proc(proc) => builtins::handle_proc(*proc, method_args, call_stack),
-        Calcit::Syntax(syn, _ns) => Err(CalcitErr::use_msg_stack(
-          format!("cannot get syntax here since instance is always evaluated, got: {syn}"),
-          call_stack,
-        )),
-        y => Err(CalcitErr::use_msg_stack_location(
-          format!("expected a function to invoke, got: {y}"),
-          call_stack,
-          y.get_location(),
-        )),
-      }
-    }
-    None => {
-      let content = class.fields.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
-      Err(CalcitErr::use_msg_stack(
-        format!("unknown method `.{name}` for {}: {}.\navailable methods: {content}", class.name, v0),
-        call_stack,
-      ))
-    }
+fn unknown_method(class: &CalcitRecord, v0: &Calcit, name: &str, call_stack: &CallStackList) -> CalcitErr {
+  let content = class.fields.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+  CalcitErr::use_msg_stack(
+    format!("unknown method `.{name}` for {}: {}.\navailable methods: {content}", class.name, v0),
+    call_stack,
+  )
+}
+
+fn call_method(method: &Calcit, method_args: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match method {
+    // dirty copy...
+    Calcit::Fn { info, .. } => runner::run_fn(method_args, info, call_stack),
+    Calcit::Proc(proc) => builtins::handle_proc(*proc, method_args, call_stack),
+    Calcit::Syntax(syn, _ns) => Err(CalcitErr::use_msg_stack(
+      format!("cannot get syntax here since instance is always evaluated, got: {syn}"),
+      call_stack,
+    )),
+    y => Err(CalcitErr::use_msg_stack_location(
+      format!("expected a function to invoke, got: {y}"),
+      call_stack,
+      y.get_location(),
+    )),
   }
 }
 
@@ -561,7 +708,6 @@ pub fn get_os(_xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 }
 
 pub fn async_sleep(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
-  use std::{thread, time};
   let sec = if xs.is_empty() {
     1.0
   } else if let Calcit::Number(n) = xs[0] {
@@ -570,17 +716,8 @@ pub fn async_sleep(xs: Vec<Calcit>, call_stack: &CallStackList) -> Result<Calcit
     return Err(CalcitErr::use_msg_stack("expected number", call_stack));
   };
 
-  runner::track::track_task_add();
-
-  let _handle = thread::spawn(move || {
-    let ten_secs = time::Duration::from_secs(sec.round() as u64);
-    // let _now = time::Instant::now();
-    thread::sleep(ten_secs);
-
-    runner::track::track_task_release();
-  });
-
-  // handle.join();
+  // a timer without callback, only keeps the event loop alive
+  runner::event_loop::add_timer(std::time::Duration::from_secs(sec.round() as u64), false, None, call_stack);
 
   Ok(Calcit::Nil)
 }
@@ -642,11 +779,11 @@ pub fn hash(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 }
 
 /// extract out calcit internal meta code
-pub fn extract_code_into_edn(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+pub fn extract_code_into_edn(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   if xs.len() != 1 {
     return CalcitErr::err_nodes("&extract-code-into-edn expected 1 argument, got:", xs);
   }
-  Ok(edn_to_calcit(&dump_code(&xs[0]), &Calcit::Nil))
+  edn_to_calcit(&dump_code(&xs[0]), &Calcit::Nil, call_stack)
 }
 
 /// turns data back into code in generating js
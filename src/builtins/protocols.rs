@@ -0,0 +1,266 @@
+//! open protocols, methods that can be implemented for builtin types, records and tuple classes after they are defined.
+//! a protocol is a record whose fields are its methods, declared with `defprotocol`, and known to the type checker once its definition is preprocessed.
+//! implementations are kept in a registry by types, `invoke_method` looks them up when the class of a value does not have the method.
+//! on code changes, implementations registered or defined by changed definitions are dropped, others are kept for the reloaded program.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use cirru_edn::EdnTag;
+
+use crate::builtins::meta::core_class_name;
+use crate::builtins::records;
+use crate::calcit::{Calcit, CalcitErr, CalcitProc, CalcitRecord, CalcitTuple};
+use crate::call_stack::{CallStackList, StackKind};
+use crate::program;
+use crate::snapshot::ChangesDict;
+
+/// builtin types are named by their class definitions. records are identified by names and fields,
+/// tuples by those of their classes, so records decoded from EDN or binary data share implementations with their definitions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ProtocolTarget {
+  Core(&'static str),
+  Record(EdnTag, Arc<Vec<EdnTag>>),
+}
+
+impl ProtocolTarget {
+  fn of_record(r: &CalcitRecord) -> Self {
+    ProtocolTarget::Record(r.name.to_owned(), r.fields.to_owned())
+  }
+
+  fn of_value(x: &Calcit) -> Option<Self> {
+    match x {
+      Calcit::Record(r) => Some(ProtocolTarget::of_record(r)),
+      Calcit::Tuple(CalcitTuple { class, .. }) => class.as_ref().map(|c| ProtocolTarget::of_record(c)),
+      _ => core_class_name(x).map(ProtocolTarget::Core),
+    }
+  }
+}
+
+/// namespace and name of a definition
+type DefKey = (Arc<str>, Arc<str>);
+
+/// methods of one protocol for a type, along with definitions they came from
+#[derive(Debug)]
+struct ProtocolImpl {
+  methods: HashMap<Arc<str>, Calcit>,
+  /// the definition calling `extend-protocol!` and definitions of methods, `None` when the caller is unknown
+  origins: Option<HashSet<DefKey>>,
+}
+
+/// implementations by protocol names, a method name is implemented by one protocol at most
+type TargetImpls = HashMap<EdnTag, ProtocolImpl>;
+
+static PROTOCOL_IMPLS: LazyLock<RwLock<HashMap<ProtocolTarget, TargetImpls>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// methods of protocols by namespaces and names of their definitions
+type DeclaredProtocols = HashMap<DefKey, Vec<Arc<str>>>;
+
+/// registered when definitions are preprocessed, since protocols may be used before their values are evaluated
+static DECLARED_PROTOCOLS: LazyLock<RwLock<DeclaredProtocols>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// types named by tags from `type-of`, others are records or classes of tuples
+fn read_target(x: &Calcit) -> Result<ProtocolTarget, String> {
+  match x {
+    Calcit::Tag(t) => {
+      let def = match t.ref_str() {
+        "list" => "&core-list-class",
+        "map" => "&core-map-class",
+        "number" => "&core-number-class",
+        "string" => "&core-string-class",
+        "set" => "&core-set-class",
+        "nil" => "&core-nil-class",
+        "fn" => "&core-fn-class",
+        "buffer" => "&core-buffer-class",
+        "transient" => "&core-transient-class",
+        "lazy-seq" => "&core-lazy-seq-class",
+        _ => return Err(format!("unknown type {x}")),
+      };
+      Ok(ProtocolTarget::Core(def))
+    }
+    Calcit::Record(r) => Ok(ProtocolTarget::of_record(r)),
+    a => Err(format!("expected a type tag or a record, got: {a}")),
+  }
+}
+
+/// implementation of a method registered for the type of a value
+pub fn find_impl(x: &Calcit, name: &str) -> Option<Calcit> {
+  let target = ProtocolTarget::of_value(x)?;
+  let impls = PROTOCOL_IMPLS.read().expect("read protocol impls");
+  impls.get(&target)?.values().find_map(|p| p.methods.get(name).cloned())
+}
+
+/// records methods when a definition is preprocessed into `new-protocol`, other definitions remove previous records
+pub fn declare_protocol(ns: &str, def: &str, resolved_code: &Calcit) {
+  let key: DefKey = (ns.into(), def.into());
+  let mut declared = DECLARED_PROTOCOLS.write().expect("write declared protocols");
+  match resolved_code {
+    Calcit::List(xs) if matches!(xs.first(), Some(Calcit::Proc(CalcitProc::NewProtocol))) => {
+      let methods = xs
+        .iter()
+        .skip(2)
+        .filter_map(|x| match x {
+          Calcit::Tag(t) => Some(t.arc_str()),
+          _ => None,
+        })
+        .collect();
+      declared.insert(key, methods);
+    }
+    _ => {
+      declared.remove(&key);
+    }
+  }
+}
+
+/// whether a method is declared by a protocol, implementations are only known at runtime
+pub fn is_protocol_method(name: &str) -> bool {
+  let declared = DECLARED_PROTOCOLS.read().expect("read declared protocols");
+  declared.values().any(|methods| methods.iter().any(|m| &**m == name))
+}
+
+/// drops implementations registered or defined by changed definitions, along with protocols whose definitions are cleared.
+/// others are kept since the program is not started again after code changes
+pub fn drop_changed_protocols(changes: &ChangesDict) {
+  let changed = |(ns, def): &DefKey| {
+    changes.removed.contains(ns)
+      || changes
+        .changed
+        .get(ns)
+        .is_some_and(|info| info.changed_defs.contains_key(def) || info.removed_defs.contains(def))
+  };
+  let mut registry = PROTOCOL_IMPLS.write().expect("write protocol impls");
+  for impls in registry.values_mut() {
+    impls.retain(|_, p| p.origins.as_ref().is_some_and(|origins| !origins.iter().any(changed)));
+  }
+  registry.retain(|_, impls| !impls.is_empty());
+  DECLARED_PROTOCOLS
+    .write()
+    .expect("write declared protocols")
+    .retain(|(ns, def), _| program::lookup_evaled_def(ns, def).is_some());
+}
+
+/// the closest function from a definition in the call stack, and definitions of the methods
+fn find_origins(methods: &HashMap<Arc<str>, Calcit>, call_stack: &CallStackList) -> Option<HashSet<DefKey>> {
+  let caller = call_stack
+    .0
+    .iter()
+    .find(|s| s.kind == StackKind::Fn && program::has_def_code(&s.ns, &s.def))?;
+  let mut origins: HashSet<DefKey> = HashSet::from([(caller.ns.to_owned(), caller.def.to_owned())]);
+  for f in methods.values() {
+    if let Calcit::Fn { info, .. } = f {
+      if program::has_def_code(&info.def_ns, &info.name) {
+        origins.insert((info.def_ns.to_owned(), info.name.to_owned()));
+      }
+    }
+  }
+  Some(origins)
+}
+
+fn extend_failed(protocol: &CalcitRecord, target: &Calcit, mut problems: Vec<String>) -> Result<Calcit, CalcitErr> {
+  problems.sort();
+  CalcitErr::err_str(format!(
+    "extend-protocol! failed for :{} on {target}\n  {}",
+    protocol.name,
+    problems.join("\n  ")
+  ))
+}
+
+/// `(new-protocol :Name :method ...)` creates a protocol, problems of methods are reported here rather than when it's extended
+pub fn new_protocol(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let name = match xs.first() {
+    Some(Calcit::Tag(t)) => t,
+    Some(a) => return CalcitErr::err_str(format!("new-protocol expected a name in tag, got: {a}")),
+    None => return CalcitErr::err_nodes("new-protocol expected a name and methods, got:", xs),
+  };
+  if xs.len() < 2 {
+    return CalcitErr::err_str(format!("new-protocol expected methods for :{name}"));
+  }
+  let mut methods: HashSet<&EdnTag> = HashSet::with_capacity(xs.len() - 1);
+  for x in &xs[1..] {
+    match x {
+      Calcit::Tag(t) => {
+        if !methods.insert(t) {
+          return CalcitErr::err_str(format!("new-protocol duplicated method .{t} for :{name}"));
+        }
+      }
+      a => return CalcitErr::err_str(format!("new-protocol expected methods in tags for :{name}, got: {a}")),
+    }
+  }
+  records::new_record(xs)
+}
+
+/// `(extend-protocol! Protocol target impls)` registers implementations of all methods for a type,
+/// missing and unknown methods are reported together
+pub fn extend_protocol(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 3 {
+    return CalcitErr::err_nodes("extend-protocol! expected 3 arguments, got:", xs);
+  }
+  let protocol = match &xs[0] {
+    Calcit::Record(p) => p,
+    a => return CalcitErr::err_str(format!("extend-protocol! expected a protocol, got: {a}")),
+  };
+  let target = read_target(&xs[1]).map_err(|e| CalcitErr::use_str(format!("extend-protocol! {e}")))?;
+  let impls = match &xs[2] {
+    Calcit::Map(m) => m,
+    a => return CalcitErr::err_str(format!("extend-protocol! expected a map of methods, got: {a}")),
+  };
+
+  let mut methods: HashMap<Arc<str>, Calcit> = HashMap::with_capacity(impls.size());
+  let mut problems: Vec<String> = vec![];
+  for (k, f) in impls {
+    let name: Arc<str> = match k {
+      Calcit::Tag(t) => t.arc_str(),
+      Calcit::Str(s) => s.to_owned(),
+      a => return CalcitErr::err_str(format!("extend-protocol! expected a method in tag, got: {a}")),
+    };
+    if protocol.index_of(&name).is_none() {
+      problems.push(format!("unknown method .{name}"));
+    } else if !matches!(f, Calcit::Fn { .. } | Calcit::Proc(..)) {
+      problems.push(format!("expected a function for .{name}, got: {f}"));
+    }
+    methods.insert(name, f.to_owned());
+  }
+  for field in protocol.fields.iter() {
+    if !methods.contains_key(field.ref_str()) {
+      problems.push(format!("missing method .{field}"));
+    }
+  }
+  if !problems.is_empty() {
+    return extend_failed(protocol, &xs[1], problems);
+  }
+
+  let origins = find_origins(&methods, call_stack);
+  let mut registry = PROTOCOL_IMPLS.write().expect("write protocol impls");
+  let entry = registry.entry(target).or_default();
+  for (other, p) in entry.iter() {
+    if other != &protocol.name {
+      for name in methods.keys().filter(|name| p.methods.contains_key(*name)) {
+        problems.push(format!("method .{name} is already implemented by :{other}"));
+      }
+    }
+  }
+  if !problems.is_empty() {
+    drop(registry);
+    return extend_failed(protocol, &xs[1], problems);
+  }
+  entry.insert(protocol.name.to_owned(), ProtocolImpl { methods, origins });
+  Ok(Calcit::Nil)
+}
+
+/// whether implementations of the protocol are registered for the type of a value
+pub fn satisfies_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match (xs.first(), xs.get(1)) {
+    (Some(Calcit::Record(protocol)), Some(x)) if xs.len() == 2 => {
+      let found = match ProtocolTarget::of_value(x) {
+        Some(target) => {
+          let registry = PROTOCOL_IMPLS.read().expect("read protocol impls");
+          registry.get(&target).is_some_and(|t| t.contains_key(&protocol.name))
+        }
+        None => false,
+      };
+      Ok(Calcit::Bool(found))
+    }
+    (Some(a), Some(_)) if xs.len() == 2 => CalcitErr::err_str(format!("satisfies? expected a protocol, got: {a}")),
+    _ => CalcitErr::err_nodes("satisfies? expected 2 arguments, got:", xs),
+  }
+}
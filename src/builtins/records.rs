@@ -3,7 +3,9 @@ use std::sync::Arc;
 
 use cirru_edn::EdnTag;
 
-use crate::calcit::{Calcit, CalcitErr, CalcitRecord, CalcitTuple};
+use crate::calcit::{Calcit, CalcitErr, CalcitRecord, CalcitTuple, RecordFieldSchema, RecordSchema};
+use crate::call_stack::CallStackList;
+use crate::runner::call_callback;
 
 pub fn new_record(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.is_empty() {
@@ -51,6 +53,7 @@ pub fn new_record(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     fields: Arc::new(fields),
     values: Arc::new(values),
     class: None,
+    schema: None,
   }))
 }
 
@@ -104,44 +107,39 @@ pub fn new_class_record(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     fields: Arc::new(fields),
     values: Arc::new(values),
     class: Some(Arc::new(class)),
+    schema: None,
   }))
 }
 
-pub fn call_record(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+pub fn call_record(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   let args_size = xs.len();
-  if args_size < 2 {
-    return CalcitErr::err_nodes("&%{{}} expected at least 2 arguments, got:", xs);
-  }
-  match &xs[0] {
-    Calcit::Record(
-      record @ CalcitRecord {
+  match xs.first() {
+    Some(Calcit::Record(record)) if record.schema.is_some() || args_size >= 2 => {
+      let CalcitRecord {
         name,
         fields: def_fields,
-        values: v0,
         class,
-      },
-    ) => {
+        schema,
+        ..
+      } = record;
       if (args_size - 1).rem(2) == 0 {
         let size = (args_size - 1) / 2;
-        if size != def_fields.len() {
+        // fields are filled by schema, otherwise all fields are required
+        if schema.is_none() && size != def_fields.len() {
           return CalcitErr::err_str(format!("unexpected size in &%{{}}, {size} .. {}", def_fields.len()));
         }
-        let mut values: Vec<Calcit> = (**v0).to_owned();
+        let mut values: Vec<Option<Calcit>> = vec![None; def_fields.len()];
 
         for idx in 0..size {
           let k_idx = idx * 2 + 1;
           let v_idx = k_idx + 1;
           match &xs[k_idx] {
             Calcit::Tag(s) => match record.index_of(s.ref_str()) {
-              Some(pos) => {
-                xs[v_idx].clone_into(&mut values[pos]);
-              }
+              Some(pos) => values[pos] = Some(xs[v_idx].to_owned()),
               None => return CalcitErr::err_str(format!("unexpected field {s} for {def_fields:?}")),
             },
             Calcit::Symbol { sym: s, .. } | Calcit::Str(s) => match record.index_of(s) {
-              Some(pos) => {
-                xs[v_idx].clone_into(&mut values[pos]);
-              }
+              Some(pos) => values[pos] = Some(xs[v_idx].to_owned()),
               None => return CalcitErr::err_str(format!("unexpected field {s} for {def_fields:?}")),
             },
             a => return CalcitErr::err_str(format!("expected field in string/tag, got: {a}")),
@@ -151,14 +149,59 @@ pub fn call_record(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
         Ok(Calcit::Record(CalcitRecord {
           name: name.to_owned(),
           fields: def_fields.to_owned(),
-          values: Arc::new(values),
+          values: Arc::new(fill_record_values(record, values, "&%{}", call_stack)?),
           class: class.to_owned(),
+          schema: schema.to_owned(),
         }))
       } else {
         CalcitErr::err_nodes("&%{{}} expected pairs, got:", xs)
       }
     }
-    a => CalcitErr::err_str(format!("&%{{}} expected a record as prototype, got: {a}")),
+    Some(Calcit::Record(..)) | None => CalcitErr::err_nodes("&%{{}} expected at least 2 arguments, got:", xs),
+    Some(a) => CalcitErr::err_str(format!("&%{{}} expected a record as prototype, got: {a}")),
+  }
+}
+
+/// takes values of fields in order, missing fields are filled by the schema of the record.
+/// all missing and invalid fields are reported together
+pub fn fill_record_values(
+  record: &CalcitRecord,
+  provided: Vec<Option<Calcit>>,
+  proc_name: &str,
+  call_stack: &CallStackList,
+) -> Result<Vec<Calcit>, CalcitErr> {
+  let mut values: Vec<Calcit> = Vec::with_capacity(provided.len());
+  let mut problems: Vec<String> = vec![];
+  for (idx, item) in provided.into_iter().enumerate() {
+    let field = &record.fields[idx];
+    let rule = record.schema.as_ref().and_then(|schema| schema.0.get(idx));
+    let value = match (item, rule) {
+      (Some(v), _) => v,
+      (None, Some(RecordFieldSchema { default: Some(v), .. })) => v.to_owned(),
+      (None, Some(RecordFieldSchema { optional: true, .. })) => {
+        values.push(Calcit::Nil);
+        continue;
+      }
+      (None, _) => {
+        problems.push(format!("missing field :{field}"));
+        values.push(Calcit::Nil);
+        continue;
+      }
+    };
+    if let Some(RecordFieldSchema { validator: Some(f), .. }) = rule {
+      if let Calcit::Nil | Calcit::Bool(false) = call_callback(f, &[value.to_owned()], call_stack)? {
+        problems.push(format!("invalid field :{field}, got: {value}"));
+      }
+    }
+    values.push(value);
+  }
+  if problems.is_empty() {
+    Ok(values)
+  } else {
+    Err(CalcitErr::use_msg_stack(
+      format!("{proc_name} failed to build record :{}\n  {}", record.name, problems.join("\n  ")),
+      call_stack,
+    ))
   }
 }
 
@@ -175,6 +218,7 @@ pub fn record_with(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
         fields: def_fields,
         values: v0,
         class,
+        ..
       },
     ) => {
       if (args_size - 1).rem(2) == 0 {
@@ -206,6 +250,7 @@ pub fn record_with(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
           fields: def_fields.to_owned(),
           values: Arc::new(values),
           class: class.to_owned(),
+          schema: record.schema.to_owned(),
         }))
       } else {
         CalcitErr::err_nodes("&record:with expected pairs, got:", xs)
@@ -240,30 +285,44 @@ pub fn with_class(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   }
   match (&xs[0], &xs[1]) {
     (
-      Calcit::Record(CalcitRecord {
-        name,
-        fields: def_fields,
-        values: v0,
-        ..
-      }),
+      Calcit::Record(
+        record @ CalcitRecord {
+          name,
+          fields: def_fields,
+          values: v0,
+          ..
+        },
+      ),
       Calcit::Record(class),
     ) => Ok(Calcit::Record(CalcitRecord {
       name: name.to_owned(),
       fields: def_fields.to_owned(),
       values: v0.to_owned(),
       class: Some(Arc::new(class.to_owned())),
+      schema: record.schema.to_owned(),
     })),
     (Calcit::Record { .. }, b) => CalcitErr::err_str(format!("&record:with-class expected a record as class, got: {b}")),
     (a, _b) => CalcitErr::err_str(format!("&record:with-class expected a record, got: {a}")),
   }
 }
 
-pub fn record_from_map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+pub fn record_from_map(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   if xs.len() != 2 {
     return CalcitErr::err_nodes("&record:from-map expected 2 arguments, got:", xs);
   }
   match (&xs[0], &xs[1]) {
-    (Calcit::Record(CalcitRecord { name, fields, class, .. }), Calcit::Map(ys)) => {
+    (
+      Calcit::Record(
+        record @ CalcitRecord {
+          name,
+          fields,
+          class,
+          schema,
+          ..
+        },
+      ),
+      Calcit::Map(ys),
+    ) => {
       let mut pairs: Vec<(EdnTag, Calcit)> = Vec::with_capacity(fields.len());
       for (k, v) in ys {
         match k {
@@ -276,30 +335,121 @@ pub fn record_from_map(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
           a => return CalcitErr::err_str(format!("unknown field {a}")),
         }
       }
-      if fields.len() != pairs.len() {
-        return CalcitErr::err_str(format!("invalid fields {pairs:?} for record {fields:?}"));
-      }
-      pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
-      let mut values: Vec<Calcit> = Vec::with_capacity(fields.len());
-      for idx in 0..fields.len() {
-        let (k, v) = &pairs[idx];
-        if &fields[idx] == k {
-          values.push(v.to_owned());
-        } else {
-          return CalcitErr::err_str(format!("field mismatch: {k} {} in {fields:?} {pairs:?}", fields[idx]));
+      let values: Vec<Calcit> = if schema.is_some() {
+        let mut values: Vec<Option<Calcit>> = vec![None; fields.len()];
+        for (k, v) in pairs {
+          match record.index_of(k.ref_str()) {
+            Some(pos) => values[pos] = Some(v),
+            None => return CalcitErr::err_str(format!("unexpected field {k} for {fields:?}")),
+          }
         }
-      }
+        fill_record_values(record, values, "&record:from-map", call_stack)?
+      } else {
+        if fields.len() != pairs.len() {
+          return CalcitErr::err_str(format!("invalid fields {pairs:?} for record {fields:?}"));
+        }
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut values: Vec<Calcit> = Vec::with_capacity(fields.len());
+        for idx in 0..fields.len() {
+          let (k, v) = &pairs[idx];
+          if &fields[idx] == k {
+            values.push(v.to_owned());
+          } else {
+            return CalcitErr::err_str(format!("field mismatch: {k} {} in {fields:?} {pairs:?}", fields[idx]));
+          }
+        }
+        values
+      };
       Ok(Calcit::Record(CalcitRecord {
         name: name.to_owned(),
         fields: fields.to_owned(),
         values: Arc::new(values),
         class: class.to_owned(),
+        schema: schema.to_owned(),
       }))
     }
     (a, b) => CalcitErr::err_str(format!("&record:from-map expected a record and a map, got: {a} {b}")),
   }
 }
 
+/// attaches rules for fields, taking a map from field to a map of `:default`, `:optional` and `:validate`
+pub fn with_schema(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 2 {
+    return CalcitErr::err_nodes("&record:with-schema expected 2 arguments, got:", xs);
+  }
+  match (&xs[0], &xs[1]) {
+    (Calcit::Record(record), Calcit::Map(rules)) => {
+      let mut schema = RecordSchema(vec![RecordFieldSchema::default(); record.fields.len()]);
+      for (k, options) in rules {
+        let pos = match k {
+          Calcit::Tag(s) => record.index_of(s.ref_str()),
+          Calcit::Str(s) => record.index_of(s),
+          a => return CalcitErr::err_str(format!("&record:with-schema expected a field in tag, got: {a}")),
+        };
+        let Some(pos) = pos else {
+          return CalcitErr::err_str(format!("&record:with-schema got unknown field {k} for {:?}", record.fields));
+        };
+        let Calcit::Map(options) = options else {
+          return CalcitErr::err_str(format!("&record:with-schema expected a map of options for {k}, got: {options}"));
+        };
+        let rule = &mut schema.0[pos];
+        for (option, v) in options {
+          match option {
+            Calcit::Tag(t) if t.ref_str() == "default" => rule.default = Some(v.to_owned()),
+            Calcit::Tag(t) if t.ref_str() == "optional" => match v {
+              Calcit::Bool(b) => rule.optional = *b,
+              a => return CalcitErr::err_str(format!("&record:with-schema expected a bool for :optional of {k}, got: {a}")),
+            },
+            Calcit::Tag(t) if t.ref_str() == "validate" => match v {
+              Calcit::Fn { .. } | Calcit::Proc(..) => rule.validator = Some(v.to_owned()),
+              a => return CalcitErr::err_str(format!("&record:with-schema expected a function for :validate of {k}, got: {a}")),
+            },
+            a => return CalcitErr::err_str(format!("&record:with-schema got unknown option {a} for {k}")),
+          }
+        }
+      }
+      Ok(Calcit::Record(CalcitRecord {
+        schema: Some(Arc::new(schema)),
+        ..record.to_owned()
+      }))
+    }
+    (Calcit::Record(..), b) => CalcitErr::err_str(format!("&record:with-schema expected a map of rules, got: {b}")),
+    (a, _) => CalcitErr::err_str(format!("&record:with-schema expected a record, got: {a}")),
+  }
+}
+
+/// rules of fields in the shape taken by `&record:with-schema`, `nil` for records without a schema
+pub fn get_schema(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 1 {
+    return CalcitErr::err_nodes("&record:schema expected 1 argument, got:", xs);
+  }
+  match &xs[0] {
+    Calcit::Record(CalcitRecord { fields, schema, .. }) => match schema {
+      Some(schema) => {
+        let mut rules = rpds::HashTrieMap::new_sync();
+        for (field, rule) in fields.iter().zip(schema.0.iter()) {
+          let mut options = rpds::HashTrieMap::new_sync();
+          if let Some(v) = &rule.default {
+            options.insert_mut(Calcit::tag("default"), v.to_owned());
+          }
+          if rule.optional {
+            options.insert_mut(Calcit::tag("optional"), Calcit::Bool(true));
+          }
+          if let Some(f) = &rule.validator {
+            options.insert_mut(Calcit::tag("validate"), f.to_owned());
+          }
+          if !options.is_empty() {
+            rules.insert_mut(Calcit::Tag(field.to_owned()), Calcit::Map(options));
+          }
+        }
+        Ok(Calcit::Map(rules))
+      }
+      None => Ok(Calcit::Nil),
+    },
+    a => CalcitErr::err_str(format!("&record:schema expected a record, got: {a}")),
+  }
+}
+
 pub fn get_record_name(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   if xs.len() != 1 {
     return CalcitErr::err_nodes("&record:get-name expected record, got:", xs);
@@ -394,6 +544,7 @@ pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
           fields,
           values,
           class,
+          ..
         },
       )),
       Some(a),
@@ -408,6 +559,7 @@ pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
             fields: fields.to_owned(),
             values: Arc::new(new_values),
             class: class.to_owned(),
+            schema: record.schema.to_owned(),
           }))
         }
         None => CalcitErr::err_str(format!("invalid field `{s}` for {fields:?}")),
@@ -421,6 +573,7 @@ pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
             fields: fields.to_owned(),
             values: Arc::new(new_values),
             class: class.to_owned(),
+            schema: record.schema.to_owned(),
           }))
         }
         None => CalcitErr::err_str(format!("invalid field `{s}` for {fields:?}")),
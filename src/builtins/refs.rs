@@ -1,6 +1,9 @@
 //! two kinds of atoms
 //! - defined with `defatom`, which is global atom that retains after hot swapping
 //! - defined with `atom`, which is barely a piece of local mutable state
+//!
+//! global atoms are registered by paths of their definitions, and dropped when definitions are removed in code changes,
+//! or when changed definitions no longer expand to `defatom`
 
 use std::collections::HashMap;
 use std::sync::atomic::AtomicUsize;
@@ -8,16 +11,25 @@ use std::sync::{Arc, LazyLock, Mutex};
 
 use cirru_edn::EdnTag;
 
-use crate::calcit::{Calcit, CalcitErr, CalcitImport, CalcitList, CalcitScope};
+use crate::calcit::{Calcit, CalcitErr, CalcitImport, CalcitList, CalcitScope, CalcitSyntax};
+use crate::program::ProgramCodeData;
 use crate::{call_stack::CallStackList, runner};
 
 pub(crate) type ValueAndListeners = (Calcit, HashMap<EdnTag, Calcit>);
 
-type RefListeners = HashMap<Arc<str>, Arc<Mutex<ValueAndListeners>>>;
+/// a global atom defined by `defatom`, initial value is kept for resetting
+struct RegisteredRef {
+  ns: Arc<str>,
+  def: Arc<str>,
+  initial: Calcit,
+  pair: Arc<Mutex<ValueAndListeners>>,
+}
+
+type RefListeners = HashMap<Arc<str>, RegisteredRef>;
 
 static REFS_DICT: LazyLock<Mutex<RefListeners>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
-fn modify_ref(locked_pair: Arc<Mutex<ValueAndListeners>>, v: Calcit, call_stack: &CallStackList) -> Result<(), CalcitErr> {
+pub(crate) fn modify_ref(locked_pair: Arc<Mutex<ValueAndListeners>>, v: Calcit, call_stack: &CallStackList) -> Result<(), CalcitErr> {
   let (listeners, prev) = {
     let mut pair = locked_pair.lock().expect("read ref");
     let prev = pair.0.to_owned();
@@ -31,6 +43,16 @@ fn modify_ref(locked_pair: Arc<Mutex<ValueAndListeners>>, v: Calcit, call_stack:
     (listeners, prev)
   };
 
+  trigger_listeners(&listeners, &v, &prev, call_stack)
+}
+
+/// listeners are called after locks released, so they are free to read and modify refs
+fn trigger_listeners(
+  listeners: &HashMap<EdnTag, Calcit>,
+  v: &Calcit,
+  prev: &Calcit,
+  call_stack: &CallStackList,
+) -> Result<(), CalcitErr> {
   for f in listeners.values() {
     match f {
       Calcit::Fn { info, .. } => {
@@ -51,66 +73,125 @@ fn modify_ref(locked_pair: Arc<Mutex<ValueAndListeners>>, v: Calcit, call_stack:
 /// syntax to prevent expr re-evaluating
 pub fn defatom(expr: &CalcitList, scope: &CalcitScope, file_ns: &str, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   match (expr.first(), expr.get(1)) {
-    (Some(Calcit::Symbol { sym, info, .. }), Some(code)) => {
-      let mut path: String = (*info.at_ns).to_owned();
-      path.push('/');
-      path.push_str(sym);
-
-      let path_info: Arc<str> = path.into();
+    (Some(Calcit::Symbol { sym, info, .. }), Some(code)) => define_ref(&info.at_ns, sym, code, scope, file_ns, call_stack),
+    (Some(Calcit::Import(CalcitImport { def, ns, .. })), Some(code)) => define_ref(ns, def, code, scope, file_ns, call_stack),
+    (Some(a), Some(b)) => Err(CalcitErr::use_msg_stack_location(
+      format!("defatom expected a symbol and an expression: {a} , {b}"),
+      call_stack,
+      a.get_location().or_else(|| b.get_location()),
+    )),
+    _ => Err(CalcitErr::use_msg_stack("defatom expected 2 nodes", call_stack)),
+  }
+}
 
-      // println!("defatom symbol {:?}", path_info);
+fn define_ref(
+  ns: &Arc<str>,
+  def: &Arc<str>,
+  code: &Calcit,
+  scope: &CalcitScope,
+  file_ns: &str,
+  call_stack: &CallStackList,
+) -> Result<Calcit, CalcitErr> {
+  let path_info: Arc<str> = format!("{ns}/{def}").into();
 
-      let defined = {
-        let dict = REFS_DICT.lock().expect("read refs");
-        dict.get(&path_info).map(ToOwned::to_owned)
-        // need to release lock before calling `evaluate_expr`
-      };
+  let defined = {
+    let dict = REFS_DICT.lock().expect("read refs");
+    dict.get(&path_info).map(|r| r.pair.to_owned())
+    // need to release lock before calling `evaluate_expr`
+  };
 
-      match defined {
-        Some(v) => Ok(Calcit::Ref(path_info, v.to_owned())),
-        None => {
-          let v = runner::evaluate_expr(code, scope, file_ns, call_stack)?;
-          let pair_value = Arc::new(Mutex::new((v, HashMap::new())));
-          let mut dict = REFS_DICT.lock().expect("read refs");
-          dict.insert(path_info.to_owned(), pair_value.to_owned());
-          Ok(Calcit::Ref(path_info, pair_value))
-        }
-      }
+  match defined {
+    Some(v) => Ok(Calcit::Ref(path_info, v)),
+    None => {
+      let v = runner::evaluate_expr(code, scope, file_ns, call_stack)?;
+      let pair_value = Arc::new(Mutex::new((v.to_owned(), HashMap::new())));
+      let mut dict = REFS_DICT.lock().expect("read refs");
+      dict.insert(
+        path_info.to_owned(),
+        RegisteredRef {
+          ns: ns.to_owned(),
+          def: def.to_owned(),
+          initial: v,
+          pair: pair_value.to_owned(),
+        },
+      );
+      Ok(Calcit::Ref(path_info, pair_value))
     }
+  }
+}
 
-    (Some(Calcit::Import(CalcitImport { def, ns, .. })), Some(code)) => {
-      let mut path: String = ns.to_string();
-      path.push('/');
-      path.push_str(def);
-
-      let path_info: Arc<str> = path.into();
+/// drops refs whose definitions are removed from program code, listeners are detached
+/// since they are likely closures from old code. returns paths of dropped refs
+pub fn drop_orphaned_refs(code: &ProgramCodeData) -> Vec<Arc<str>> {
+  let mut dict = REFS_DICT.lock().expect("read refs");
+  let orphaned: Vec<Arc<str>> = dict
+    .iter()
+    .filter(|(_, r)| !code.get(&r.ns).is_some_and(|file| file.defs.contains_key(&r.def)))
+    .map(|(path, _)| path.to_owned())
+    .collect();
+  for path in &orphaned {
+    if let Some(r) = dict.remove(path) {
+      r.pair.lock().expect("read ref").1.clear();
+    }
+  }
+  orphaned
+}
 
-      // println!("defatom import {:?}", path_info);
+/// called when a definition is preprocessed, drops its ref when the code no longer expands to `defatom`,
+/// atoms defined by macros are kept since their code is checked after expanding
+pub fn drop_replaced_ref(ns: &str, def: &str, resolved_code: &Calcit) {
+  let is_defatom = match resolved_code {
+    Calcit::List(xs) => matches!(xs.first(), Some(Calcit::Syntax(CalcitSyntax::Defatom, _))),
+    _ => false,
+  };
+  if !is_defatom {
+    if let Some(r) = REFS_DICT.lock().expect("read refs").remove(&*format!("{ns}/{def}")) {
+      r.pair.lock().expect("read ref").1.clear();
+    }
+  }
+}
 
-      let defined = {
-        let dict = REFS_DICT.lock().expect("read refs");
-        dict.get(&path_info).map(ToOwned::to_owned)
-        // need to release lock before calling `evaluate_expr`
-      };
+/// ref defined at `ns/def` path
+pub(crate) fn find_registered(path: &str) -> Option<Arc<Mutex<ValueAndListeners>>> {
+  let dict = REFS_DICT.lock().expect("read refs");
+  dict.get(path).map(|r| r.pair.to_owned())
+}
 
-      match defined {
-        Some(v) => Ok(Calcit::Ref(path_info, v.to_owned())),
-        None => {
-          let v = runner::evaluate_expr(code, scope, file_ns, call_stack)?;
-          let pair_value = Arc::new(Mutex::new((v, HashMap::new())));
-          let mut dict = REFS_DICT.lock().expect("read refs");
-          dict.insert(path_info.to_owned(), pair_value.to_owned());
-          Ok(Calcit::Ref(path_info, pair_value))
-        }
-      }
+/// `(&refs:registered)` lists global refs with paths and tags of their listeners, sorted by paths
+pub fn registered_refs(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("&refs:registered expected no arguments, got:", xs);
+  }
+  let dict = REFS_DICT.lock().expect("read refs");
+  let mut paths: Vec<&Arc<str>> = dict.keys().collect();
+  paths.sort();
+  let mut ys: Vec<Calcit> = Vec::with_capacity(paths.len());
+  for path in paths {
+    let r = &dict[path];
+    let mut watchers = rpds::HashTrieSet::new_sync();
+    for k in r.pair.lock().expect("read ref").1.keys() {
+      watchers.insert_mut(Calcit::Tag(k.to_owned()));
     }
-    (Some(a), Some(b)) => Err(CalcitErr::use_msg_stack_location(
-      format!("defatom expected a symbol and an expression: {a} , {b}"),
-      call_stack,
-      a.get_location().or_else(|| b.get_location()),
-    )),
-    _ => Err(CalcitErr::use_msg_stack("defatom expected 2 nodes", call_stack)),
+    let mut info = rpds::HashTrieMap::new_sync();
+    info.insert_mut(Calcit::tag("path"), Calcit::Str(path.to_owned()));
+    info.insert_mut(Calcit::tag("watchers"), Calcit::Set(watchers));
+    ys.push(Calcit::Map(info));
+  }
+  Ok(Calcit::from(ys))
+}
+
+/// `(&refs:reset-all!)` puts initial values back to all global refs and detaches their listeners, mostly for tests
+pub fn reset_all_refs(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("&refs:reset-all! expected no arguments, got:", xs);
   }
+  let dict = REFS_DICT.lock().expect("read refs");
+  for r in dict.values() {
+    let mut pair = r.pair.lock().expect("read ref");
+    r.initial.clone_into(&mut pair.0);
+    pair.1.clear();
+  }
+  Ok(Calcit::Nil)
 }
 
 /// dead simple counter for ID generator, better use nanoid in business
@@ -228,3 +309,162 @@ pub fn remove_watch(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
     (a, b) => CalcitErr::err_str(format!("remove-watch expected 2 arguments, got: {a:?} {b:?}")),
   }
 }
+
+/// `(compare-and-set! *a old new)` writes only when the ref still holds a value equal to `old`, returns whether it's written
+pub fn compare_and_set(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match (xs.first(), xs.get(1), xs.get(2)) {
+    (Some(Calcit::Ref(_path, locked_pair)), Some(old), Some(v)) if xs.len() == 3 => {
+      let listeners = {
+        let mut pair = locked_pair.lock().expect("read ref");
+        if &pair.0 != old {
+          return Ok(Calcit::Bool(false));
+        }
+        if &pair.0 == v {
+          return Ok(Calcit::Bool(true));
+        }
+        v.clone_into(&mut pair.0);
+        pair.1.to_owned()
+      };
+      trigger_listeners(&listeners, v, old, call_stack)?;
+      Ok(Calcit::Bool(true))
+    }
+    (Some(a), _, _) if xs.len() == 3 => Err(CalcitErr::use_msg_stack(
+      format!("compare-and-set! expected a ref, got: {a}"),
+      call_stack,
+    )),
+    _ => Err(CalcitErr::use_msg_stack(
+      format!("compare-and-set! expected 3 arguments, got: {}", CalcitList::from(xs)),
+      call_stack,
+    )),
+  }
+}
+
+/// a conflicting update is not likely to happen so many times, unless a function keeps modifying refs it reads
+const MAX_RETRIES: usize = 10000;
+
+/// `(&atom:swap! *a f & args)` calls `f` with current value outside the lock,
+/// and retries when the ref is modified before the result is written
+pub fn atom_swap(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  let (locked_pair, f) = match (xs.first(), xs.get(1)) {
+    (Some(Calcit::Ref(_path, locked_pair)), Some(f)) => (locked_pair, f),
+    (Some(a), Some(_)) => return Err(CalcitErr::use_msg_stack(format!("swap! expected a ref, got: {a}"), call_stack)),
+    _ => {
+      return Err(CalcitErr::use_msg_stack(
+        format!("swap! expected a ref and a function, got: {}", CalcitList::from(xs)),
+        call_stack,
+      ))
+    }
+  };
+  for _ in 0..MAX_RETRIES {
+    let prev = locked_pair.lock().expect("read ref").0.to_owned();
+    let mut args = Vec::with_capacity(xs.len() - 1);
+    args.push(prev.to_owned());
+    args.extend_from_slice(&xs[2..]);
+    let v = runner::call_callback(f, &args, call_stack)?;
+
+    let listeners = {
+      let mut pair = locked_pair.lock().expect("read ref");
+      if pair.0 != prev {
+        // modified by others during calling `f`
+        continue;
+      }
+      if v == prev {
+        return Ok(v);
+      }
+      v.clone_into(&mut pair.0);
+      pair.1.to_owned()
+    };
+    trigger_listeners(&listeners, &v, &prev, call_stack)?;
+    return Ok(v);
+  }
+  Err(CalcitErr::use_msg_stack(
+    format!("swap! gave up after {MAX_RETRIES} conflicting updates"),
+    call_stack,
+  ))
+}
+
+/// `(transact! ([] *a *b) f)` calls `f` with current values of refs and writes the list it returns to all refs at once.
+/// refs are locked in order of their addresses to prevent deadlocks, and the transaction retries if any of them changed during calling `f`.
+/// listeners of each modified ref are called once, after all values are written
+pub fn transact(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  let (refs, f) = match (xs.first(), xs.get(1)) {
+    (Some(Calcit::List(refs)), Some(f)) if xs.len() == 2 => (refs, f),
+    (Some(a), Some(_)) if xs.len() == 2 => {
+      return Err(CalcitErr::use_msg_stack(
+        format!("transact! expected a list of refs, got: {a}"),
+        call_stack,
+      ))
+    }
+    _ => {
+      return Err(CalcitErr::use_msg_stack(
+        format!("transact! expected refs and a function, got: {}", CalcitList::from(xs)),
+        call_stack,
+      ))
+    }
+  };
+  let mut pairs: Vec<&Arc<Mutex<ValueAndListeners>>> = Vec::with_capacity(refs.len());
+  for r in refs.iter() {
+    match r {
+      Calcit::Ref(_path, locked_pair) => {
+        if pairs.iter().any(|p| Arc::ptr_eq(p, locked_pair)) {
+          return Err(CalcitErr::use_msg_stack(format!("transact! got duplicated ref: {r}"), call_stack));
+        }
+        pairs.push(locked_pair);
+      }
+      a => return Err(CalcitErr::use_msg_stack(format!("transact! expected a ref, got: {a}"), call_stack)),
+    }
+  }
+  let mut lock_order: Vec<usize> = (0..pairs.len()).collect();
+  lock_order.sort_by_key(|i| Arc::as_ptr(pairs[*i]) as usize);
+
+  for _ in 0..MAX_RETRIES {
+    let prevs: Vec<Calcit> = {
+      let guards = lock_order
+        .iter()
+        .map(|i| (*i, pairs[*i].lock().expect("read ref")))
+        .collect::<Vec<_>>();
+      let mut values = vec![Calcit::Nil; pairs.len()];
+      for (i, guard) in &guards {
+        values[*i] = guard.0.to_owned();
+      }
+      values
+    };
+    let ret = runner::call_callback(f, &[Calcit::from(prevs.to_owned())], call_stack)?;
+    let values = match &ret {
+      Calcit::List(ys) if ys.len() == pairs.len() => ys.to_vec(),
+      a => {
+        return Err(CalcitErr::use_msg_stack(
+          format!("transact! expected a list of {} values from the function, got: {a}", pairs.len()),
+          call_stack,
+        ))
+      }
+    };
+
+    let changes = {
+      let mut guards = lock_order
+        .iter()
+        .map(|i| (*i, pairs[*i].lock().expect("read ref")))
+        .collect::<Vec<_>>();
+      if guards.iter().any(|(i, guard)| guard.0 != prevs[*i]) {
+        // modified by others during calling `f`
+        continue;
+      }
+      let mut changes: Vec<(usize, HashMap<EdnTag, Calcit>)> = vec![];
+      for (i, guard) in guards.iter_mut() {
+        if guard.0 != values[*i] {
+          values[*i].clone_into(&mut guard.0);
+          changes.push((*i, guard.1.to_owned()));
+        }
+      }
+      changes
+    };
+    for (i, listeners) in changes {
+      trigger_listeners(&listeners, &values[i], &prevs[i], call_stack)?;
+    }
+    return Ok(ret);
+  }
+  Err(CalcitErr::use_msg_stack(
+    format!("transact! gave up after {MAX_RETRIES} conflicting updates"),
+    call_stack,
+  ))
+}
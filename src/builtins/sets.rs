@@ -1,5 +1,23 @@
+use crate::builtins::maps::range_bounds;
 use crate::calcit::{Calcit, CalcitErr, CalcitList};
 
+/// items of a hash set or a sorted set, sorted sets give items in order
+fn items_of(x: &Calcit) -> Box<dyn Iterator<Item = &Calcit> + '_> {
+  match x {
+    Calcit::Set(xs) => Box::new(xs.iter()),
+    Calcit::SortedSet(xs) => Box::new(xs.iter()),
+    _ => Box::new(std::iter::empty()),
+  }
+}
+
+fn has_item(x: &Calcit, y: &Calcit) -> bool {
+  match x {
+    Calcit::Set(xs) => xs.contains(y),
+    Calcit::SortedSet(xs) => xs.contains(y),
+    _ => false,
+  }
+}
+
 pub fn new_set(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   let mut ys = rpds::HashTrieSet::new_sync();
   for x in xs {
@@ -8,6 +26,21 @@ pub fn new_set(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   Ok(Calcit::Set(ys))
 }
 
+pub fn new_sorted_set(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let mut ys = rpds::RedBlackTreeSet::new_sync();
+  for x in xs {
+    ys.insert_mut(x.to_owned());
+  }
+  Ok(Calcit::SortedSet(ys))
+}
+
+pub fn sorted_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => Ok(Calcit::Bool(matches!(a, Calcit::SortedSet(_)))),
+    _ => CalcitErr::err_nodes("sorted-set? expected 1 argument, got:", xs),
+  }
+}
+
 pub fn call_include(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
     (Some(Calcit::Set(xs)), Some(a)) => {
@@ -15,6 +48,11 @@ pub fn call_include(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       ys.insert_mut(a.to_owned());
       Ok(Calcit::Set(ys))
     }
+    (Some(Calcit::SortedSet(xs)), Some(a)) => {
+      let mut ys = xs.to_owned();
+      ys.insert_mut(a.to_owned());
+      Ok(Calcit::SortedSet(ys))
+    }
     (Some(a), _) => CalcitErr::err_str(format!("&include expect a set, but got: {a}")),
     (a, b) => CalcitErr::err_str(format!("invalid arguments for &include: {a:?} {b:?}")),
   }
@@ -27,60 +65,88 @@ pub fn call_exclude(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       ys.remove_mut(a);
       Ok(Calcit::Set(ys))
     }
+    (Some(Calcit::SortedSet(xs)), Some(a)) => {
+      let mut ys = xs.to_owned();
+      ys.remove_mut(a);
+      Ok(Calcit::SortedSet(ys))
+    }
     (Some(a), _) => CalcitErr::err_str(format!("&exclude expect a set, but got: {a}")),
     (a, b) => CalcitErr::err_str(format!("invalid arguments for &exclude: {a:?} {b:?}")),
   }
 }
 pub fn call_difference(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Set(a)), Some(Calcit::Set(b))) => {
+    (Some(Calcit::Set(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
       // rpds::HashTrieSetSync::difference has different semantics
       // https://docs.rs/im/12.2.0/im/struct.HashSet.html#method.difference
       let mut ys = a.to_owned();
-      for item in b {
+      for item in items_of(b) {
         ys.remove_mut(item);
       }
       Ok(Calcit::Set(ys))
     }
+    (Some(Calcit::SortedSet(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
+      let mut ys = a.to_owned();
+      for item in items_of(b) {
+        ys.remove_mut(item);
+      }
+      Ok(Calcit::SortedSet(ys))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("&difference expected 2 sets: {a} {b}")),
     (a, b) => CalcitErr::err_str(format!("&difference expected 2 arguments: {a:?} {b:?}")),
   }
 }
 pub fn call_union(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Set(a)), Some(Calcit::Set(b))) => {
+    (Some(Calcit::Set(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
       let mut c = a.to_owned();
-      for x in b.iter() {
+      for x in items_of(b) {
         c.insert_mut(x.to_owned());
       }
       Ok(Calcit::Set(c))
     }
+    (Some(Calcit::SortedSet(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
+      let mut c = a.to_owned();
+      for x in items_of(b) {
+        c.insert_mut(x.to_owned());
+      }
+      Ok(Calcit::SortedSet(c))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("&union expected 2 sets: {a} {b}")),
     (a, b) => CalcitErr::err_str(format!("&union expected 2 arguments: {a:?} {b:?}")),
   }
 }
 pub fn call_intersection(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Set(a)), Some(Calcit::Set(b))) => {
+    (Some(Calcit::Set(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
       let mut c: rpds::HashTrieSetSync<Calcit> = rpds::HashTrieSet::new_sync();
       for x in a.iter() {
-        if b.contains(x) {
+        if has_item(b, x) {
           c.insert_mut(x.to_owned())
         }
       }
       Ok(Calcit::Set(c))
     }
+    (Some(Calcit::SortedSet(a)), Some(b @ (Calcit::Set(_) | Calcit::SortedSet(_)))) => {
+      let mut c: rpds::RedBlackTreeSetSync<Calcit> = rpds::RedBlackTreeSet::new_sync();
+      for x in a.iter() {
+        if has_item(b, x) {
+          c.insert_mut(x.to_owned())
+        }
+      }
+      Ok(Calcit::SortedSet(c))
+    }
     (Some(a), Some(b)) => CalcitErr::err_str(format!("&set:intersection expected 2 sets: {a} {b}")),
     (a, b) => CalcitErr::err_str(format!("&set:intersection expected 2 arguments: {a:?} {b:?}")),
   }
 }
 
-/// turn hashset into list with a random order from internals
+/// turn hashset into list with a random order from internals, sorted sets keep the order
 pub fn set_to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
-    Some(Calcit::Set(xs)) => {
+    Some(xs @ (Calcit::Set(_) | Calcit::SortedSet(_))) => {
       let mut ys = vec![];
-      for x in xs {
+      for x in items_of(xs) {
         ys.push(x.to_owned());
       }
       Ok(Calcit::from(ys))
@@ -93,6 +159,7 @@ pub fn set_to_list(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Set(ys)) => Ok(Calcit::Number(ys.size() as f64)),
+    Some(Calcit::SortedSet(ys)) => Ok(Calcit::Number(ys.size() as f64)),
     Some(a) => CalcitErr::err_str(format!("set count expected a set, got: {a}")),
     None => CalcitErr::err_str("set count expected 1 argument, got nothing"),
   }
@@ -101,6 +168,7 @@ pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 pub fn empty_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match xs.first() {
     Some(Calcit::Set(ys)) => Ok(Calcit::Bool(ys.is_empty())),
+    Some(Calcit::SortedSet(ys)) => Ok(Calcit::Bool(ys.is_empty())),
     Some(a) => CalcitErr::err_str(format!("set empty? expected some set, got: {a}")),
     None => CalcitErr::err_str("set empty? expected 1 argument, got nothing"),
   }
@@ -108,7 +176,7 @@ pub fn empty_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
 
 pub fn includes_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
   match (xs.first(), xs.get(1)) {
-    (Some(Calcit::Set(xs)), Some(a)) => Ok(Calcit::Bool(xs.contains(a))),
+    (Some(xs @ (Calcit::Set(_) | Calcit::SortedSet(_))), Some(a)) => Ok(Calcit::Bool(has_item(xs, a))),
     (Some(a), ..) => CalcitErr::err_str(format!("sets `includes?` expected set, got: {a}")),
     (None, ..) => CalcitErr::err_nodes("sets `includes?` expected 2 arguments, got:", xs),
   }
@@ -126,7 +194,48 @@ pub fn destruct(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
       }
       None => Ok(Calcit::Nil),
     },
+    Some(Calcit::SortedSet(ys)) => match ys.first() {
+      Some(y0) => {
+        let mut zs = ys.to_owned();
+        zs.remove_mut(y0);
+        Ok(Calcit::from(CalcitList::from(&[y0.to_owned(), Calcit::SortedSet(zs)])))
+      }
+      None => Ok(Calcit::Nil),
+    },
     Some(a) => CalcitErr::err_str(format!("&set:destruct expected a set, got: {a}")),
     None => CalcitErr::err_str("&set:destruct expected 1 argument, got nothing"),
   }
 }
+
+/// smallest item in a sorted set
+pub fn first(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedSet(ys)] => Ok(ys.first().map_or(Calcit::Nil, ToOwned::to_owned)),
+    [a] => CalcitErr::err_str(format!("&set:first expected a sorted set, got: {a}")),
+    _ => CalcitErr::err_nodes("&set:first expected 1 argument, got:", xs),
+  }
+}
+
+/// largest item in a sorted set
+pub fn last(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedSet(ys)] => Ok(ys.last().map_or(Calcit::Nil, ToOwned::to_owned)),
+    [a] => CalcitErr::err_str(format!("&set:last expected a sorted set, got: {a}")),
+    _ => CalcitErr::err_nodes("&set:last expected 1 argument, got:", xs),
+  }
+}
+
+/// items in a sorted set from `from` inclusive to `to` exclusive, `nil` for no bound
+pub fn range(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::SortedSet(ys), from, to] => {
+      let mut zs = rpds::RedBlackTreeSet::new_sync();
+      for y in ys.range(range_bounds(from, to, "&set:range")?) {
+        zs.insert_mut(y.to_owned());
+      }
+      Ok(Calcit::SortedSet(zs))
+    }
+    [a, _, _] => CalcitErr::err_str(format!("&set:range expected a sorted set, got: {a}")),
+    _ => CalcitErr::err_nodes("&set:range expected 3 arguments, got:", xs),
+  }
+}
@@ -0,0 +1,160 @@
+//! running external commands, blocks until the process exits
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::calcit::{Calcit, CalcitErr};
+use crate::call_stack::CallStackList;
+use crate::runner::call_callback;
+
+fn read_option<'a>(options: Option<&'a Calcit>, name: &str) -> Result<Option<&'a Calcit>, CalcitErr> {
+  match options {
+    None | Some(Calcit::Nil) => Ok(None),
+    Some(Calcit::Map(m)) => Ok(m.get(&Calcit::tag(name)).filter(|v| !matches!(v, Calcit::Nil))),
+    Some(a) => Err(CalcitErr::use_str(format!("run-process expected a map of options, got: {a}"))),
+  }
+}
+
+/// sends chunks ending with `\n` to the channel, until EOF
+fn pipe_lines<R: Read + Send + 'static>(
+  source: R,
+  stream: &'static str,
+  tx: mpsc::Sender<(&'static str, Vec<u8>)>,
+) -> thread::JoinHandle<()> {
+  thread::spawn(move || {
+    let mut reader = BufReader::new(source);
+    loop {
+      let mut chunk: Vec<u8> = vec![];
+      match reader.read_until(b'\n', &mut chunk) {
+        Ok(0) | Err(_) => break,
+        Ok(_) => {
+          if tx.send((stream, chunk)).is_err() {
+            break;
+          }
+        }
+      }
+    }
+  })
+}
+
+/// `(run-process cmd args options)`, returns `{} (:code 0) (:stdout |) (:stderr |)`, `:code` is nil when killed by a signal.
+/// options:
+/// - `:cwd` working directory
+/// - `:env` map of extra environment variables
+/// - `:stdin` string sent to the process
+/// - `:on-line` callback `(fn (line stream))` called with each line, `stream` is `:stdout` or `:stderr`
+pub fn run_process(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.is_empty() || xs.len() > 3 {
+    return CalcitErr::err_nodes("run-process expected 1~3 arguments, got:", xs);
+  }
+  let program = match &xs[0] {
+    Calcit::Str(s) => s.to_owned(),
+    a => return CalcitErr::err_str(format!("run-process expected a command string, got: {a}")),
+  };
+  let mut command = Command::new(&*program);
+  match xs.get(1) {
+    None | Some(Calcit::Nil) => (),
+    Some(Calcit::List(args)) => {
+      for arg in args.iter() {
+        match arg {
+          Calcit::Str(s) => {
+            command.arg(&**s);
+          }
+          a => return CalcitErr::err_str(format!("run-process expected string arguments, got: {a}")),
+        }
+      }
+    }
+    Some(a) => return CalcitErr::err_str(format!("run-process expected a list of arguments, got: {a}")),
+  }
+
+  let options = xs.get(2);
+  match read_option(options, "cwd")? {
+    None => (),
+    Some(Calcit::Str(dir)) => {
+      command.current_dir(&**dir);
+    }
+    Some(a) => return CalcitErr::err_str(format!("run-process expected a string for :cwd, got: {a}")),
+  }
+  match read_option(options, "env")? {
+    None => (),
+    Some(Calcit::Map(env)) => {
+      for (k, v) in env {
+        match (k, v) {
+          (Calcit::Str(k), Calcit::Str(v)) => command.env(&**k, &**v),
+          (Calcit::Tag(k), Calcit::Str(v)) => command.env(k.ref_str(), &**v),
+          (k, v) => return CalcitErr::err_str(format!("run-process expected strings in :env, got: {k} {v}")),
+        };
+      }
+    }
+    Some(a) => return CalcitErr::err_str(format!("run-process expected a map for :env, got: {a}")),
+  }
+  let stdin_data = match read_option(options, "stdin")? {
+    None => None,
+    Some(Calcit::Str(s)) => Some(s.to_owned()),
+    Some(a) => return CalcitErr::err_str(format!("run-process expected a string for :stdin, got: {a}")),
+  };
+  let on_line = match read_option(options, "on-line")? {
+    None => None,
+    Some(f @ (Calcit::Fn { .. } | Calcit::Proc(_))) => Some(f.to_owned()),
+    Some(a) => return CalcitErr::err_str(format!("run-process expected a function for :on-line, got: {a}")),
+  };
+
+  command
+    .stdin(if stdin_data.is_some() { Stdio::piped() } else { Stdio::null() })
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  let mut child = command
+    .spawn()
+    .map_err(|e| CalcitErr::use_msg_stack(format!("run-process failed to spawn `{program}`: {e}"), call_stack))?;
+
+  if let (Some(data), Some(mut stdin)) = (stdin_data, child.stdin.take()) {
+    // written in a thread, in case the process does not read before output pipes are full
+    thread::spawn(move || {
+      let _ = stdin.write_all(data.as_bytes());
+    });
+  }
+
+  let (tx, rx) = mpsc::channel();
+  let stdout_handle = pipe_lines(child.stdout.take().expect("piped stdout"), "stdout", tx.to_owned());
+  let stderr_handle = pipe_lines(child.stderr.take().expect("piped stderr"), "stderr", tx);
+
+  let mut stdout: Vec<u8> = vec![];
+  let mut stderr: Vec<u8> = vec![];
+  // callbacks run on current thread, receiving ends when both pipes are closed
+  for (stream, chunk) in rx {
+    if let Some(f) = &on_line {
+      let text = String::from_utf8_lossy(&chunk);
+      let line = text.strip_suffix('\n').map(|s| s.strip_suffix('\r').unwrap_or(s)).unwrap_or(&text);
+      if let Err(e) = call_callback(f, &[Calcit::Str(line.into()), Calcit::tag(stream)], call_stack) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+      }
+    }
+    if stream == "stdout" {
+      stdout.extend(chunk);
+    } else {
+      stderr.extend(chunk);
+    }
+  }
+  let _ = stdout_handle.join();
+  let _ = stderr_handle.join();
+
+  let status = child
+    .wait()
+    .map_err(|e| CalcitErr::use_msg_stack(format!("run-process failed waiting for `{program}`: {e}"), call_stack))?;
+
+  let mut m = rpds::HashTrieMap::new_sync();
+  m.insert_mut(
+    Calcit::tag("code"),
+    match status.code() {
+      Some(code) => Calcit::Number(code as f64),
+      None => Calcit::Nil,
+    },
+  );
+  m.insert_mut(Calcit::tag("stdout"), Calcit::Str(String::from_utf8_lossy(&stdout).into()));
+  m.insert_mut(Calcit::tag("stderr"), Calcit::Str(String::from_utf8_lossy(&stderr).into()));
+  Ok(Calcit::Map(m))
+}
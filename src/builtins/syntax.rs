@@ -8,7 +8,7 @@ use std::sync::Arc;
 use std::vec;
 
 use crate::builtins;
-use crate::builtins::meta::NS_SYMBOL_DICT;
+use crate::builtins::meta::next_gensym_suffix;
 use crate::calcit::{
   self, CalcitArgLabel, CalcitFn, CalcitFnArgs, CalcitList, CalcitLocal, CalcitMacro, CalcitSymbolInfo, CalcitSyntax, LocatedWarning,
 };
@@ -403,7 +403,7 @@ pub fn call_try(expr: &CalcitList, scope: &CalcitScope, file_ns: &str, call_stac
       Ok(v) => Ok(v.to_owned()),
       Err(failure) => {
         let f = runner::evaluate_expr(&expr[1], scope, file_ns, call_stack)?;
-        let err_data = Calcit::Str(failure.msg.to_owned().into());
+        let err_data = failure.to_error_record(call_stack);
         match f {
           Calcit::Fn { info, .. } => runner::run_fn(&[err_data], &info, call_stack),
           Calcit::Proc(proc) => builtins::handle_proc(proc, &[err_data], call_stack),
@@ -417,22 +417,11 @@ pub fn call_try(expr: &CalcitList, scope: &CalcitScope, file_ns: &str, call_stac
 }
 
 pub fn gensym(xs: &CalcitList, _scope: &CalcitScope, file_ns: &str, _call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
-  let n = {
-    let mut ns_sym_dict = NS_SYMBOL_DICT.lock().expect("open symbol dict");
-    // println!("calling in ns: {}", file_ns);
-    if let Some(n) = ns_sym_dict.get_mut(file_ns) {
-      let v = n.to_owned();
-      *n += 1;
-      v
-    } else {
-      ns_sym_dict.insert(file_ns.into(), 2);
-      1
-    }
-  };
+  let n = next_gensym_suffix(file_ns);
 
   let s = if xs.is_empty() {
     let mut chunk = String::from("G__");
-    chunk.push_str(&n.to_string());
+    chunk.push_str(&n);
     chunk
   } else {
     match &xs[0] {
@@ -440,14 +429,14 @@ pub fn gensym(xs: &CalcitList, _scope: &CalcitScope, file_ns: &str, _call_stack:
         let mut chunk = (**s).to_string();
         chunk.push('_');
         chunk.push('_');
-        chunk.push_str(&n.to_string());
+        chunk.push_str(&n);
         chunk
       }
       Calcit::Tag(k) => {
         let mut chunk = k.to_string();
         chunk.push('_');
         chunk.push('_');
-        chunk.push_str(&n.to_string());
+        chunk.push_str(&n);
         chunk
       }
       a => return CalcitErr::err_str(format!("gensym expected a string, but got: {a}")),
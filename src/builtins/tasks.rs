@@ -0,0 +1,132 @@
+//! timers and promises, callbacks are scheduled on the event loop in `runner::event_loop`
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::calcit::{Calcit, CalcitErr, CalcitPromise, PromiseReaction};
+use crate::call_stack::CallStackList;
+use crate::runner::event_loop;
+use crate::util::number::f64_to_usize;
+
+fn read_delay(x: &Calcit, proc_name: &str) -> Result<Duration, CalcitErr> {
+  match x {
+    Calcit::Number(n) if n.is_finite() => Ok(Duration::from_micros((n.max(0.0) * 1000.0).round() as u64)),
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected delay in milliseconds, got: {a}"))),
+  }
+}
+
+fn check_callback(x: &Calcit, proc_name: &str) -> Result<(), CalcitErr> {
+  match x {
+    Calcit::Fn { .. } | Calcit::Proc(_) => Ok(()),
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected a callback function, got: {a}"))),
+  }
+}
+
+fn read_promise<'a>(x: Option<&'a Calcit>, proc_name: &str) -> Result<&'a Arc<CalcitPromise>, CalcitErr> {
+  match x {
+    Some(Calcit::Promise(p)) => Ok(p),
+    Some(a) => Err(CalcitErr::use_str(format!("{proc_name} expected a promise, got: {a}"))),
+    None => Err(CalcitErr::use_str(format!("{proc_name} expected a promise, got nothing"))),
+  }
+}
+
+/// `(set-timeout ms f)`, returns id of the timer
+pub fn set_timeout(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 2 {
+    return CalcitErr::err_nodes("set-timeout expected 2 arguments, got:", xs);
+  }
+  let delay = read_delay(&xs[0], "set-timeout")?;
+  check_callback(&xs[1], "set-timeout")?;
+  let id = event_loop::add_timer(delay, false, Some(xs[1].to_owned()), call_stack);
+  Ok(Calcit::Number(id as f64))
+}
+
+/// `(set-interval ms f)`, returns id of the timer
+pub fn set_interval(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 2 {
+    return CalcitErr::err_nodes("set-interval expected 2 arguments, got:", xs);
+  }
+  let delay = read_delay(&xs[0], "set-interval")?;
+  if delay.is_zero() {
+    return CalcitErr::err_str("set-interval expected a positive interval");
+  }
+  check_callback(&xs[1], "set-interval")?;
+  let id = event_loop::add_timer(delay, true, Some(xs[1].to_owned()), call_stack);
+  Ok(Calcit::Number(id as f64))
+}
+
+/// cancels timers from `set-timeout` and `set-interval`
+pub fn clear_timer(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Number(n)] => match f64_to_usize(*n) {
+      Ok(id) => {
+        event_loop::clear_timer(id);
+        Ok(Calcit::Nil)
+      }
+      Err(e) => CalcitErr::err_str(format!("clear-timer expected a timer id, {e}")),
+    },
+    _ => CalcitErr::err_nodes("clear-timer expected a timer id, got:", xs),
+  }
+}
+
+/// creates a pending promise, to be settled with `resolve-promise!` or `reject-promise!`
+pub fn new_promise(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if !xs.is_empty() {
+    return CalcitErr::err_nodes("new-promise expected 0 arguments, got:", xs);
+  }
+  Ok(Calcit::Promise(Arc::new(CalcitPromise::default())))
+}
+
+pub fn resolve_promise(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 2 {
+    return CalcitErr::err_nodes("resolve-promise! expected 2 arguments, got:", xs);
+  }
+  let p = read_promise(xs.first(), "resolve-promise!")?;
+  event_loop::settle_promise(p, Ok(xs[1].to_owned()));
+  Ok(Calcit::Nil)
+}
+
+pub fn reject_promise(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() != 2 {
+    return CalcitErr::err_nodes("reject-promise! expected 2 arguments, got:", xs);
+  }
+  let p = read_promise(xs.first(), "reject-promise!")?;
+  event_loop::settle_promise(p, Err(xs[1].to_owned()));
+  Ok(Calcit::Nil)
+}
+
+/// `(promise-then p on-fulfilled ?on-rejected)`, returns a promise settled by the handler
+pub fn promise_then(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  if xs.len() < 2 || xs.len() > 3 {
+    return CalcitErr::err_nodes("promise-then expected 2~3 arguments, got:", xs);
+  }
+  let p = read_promise(xs.first(), "promise-then")?;
+  check_callback(&xs[1], "promise-then")?;
+  let on_rejected = match xs.get(2) {
+    None | Some(Calcit::Nil) => None,
+    Some(f) => {
+      check_callback(f, "promise-then")?;
+      Some(f.to_owned())
+    }
+  };
+  let derived = Arc::new(CalcitPromise::default());
+  event_loop::add_reaction(
+    p,
+    PromiseReaction {
+      on_fulfilled: Some(xs[1].to_owned()),
+      on_rejected,
+      derived: derived.to_owned(),
+      call_stack: call_stack.to_owned(),
+    },
+  );
+  Ok(Calcit::Promise(derived))
+}
+
+/// runs the event loop until the promise is settled, other values are returned directly
+pub fn await_value(xs: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Promise(p)] => event_loop::await_promise(p, call_stack),
+    [a] => Ok(a.to_owned()),
+    _ => CalcitErr::err_nodes("await expected 1 argument, got:", xs),
+  }
+}
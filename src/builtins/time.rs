@@ -0,0 +1,372 @@
+//! wall-clock time, represented as milliseconds since Unix epoch in a number.
+//! time zones are passed as `"Z"`, `"utc"`, `"local"`, offsets like `"+08:00"`, or minutes in a number,
+//! and default to UTC so that scripts behave the same on every machine.
+//! patterns are a subset of strftime that also works in the js backend:
+//! `%Y %y %m %d %e %H %I %p %M %S %3f %j %a %A %b %B %z %:z %s %%`
+
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use chrono::{
+  DateTime, Datelike, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Timelike, Utc,
+};
+
+use crate::calcit::{Calcit, CalcitErr};
+use crate::util::number::f64_to_i32;
+
+static MONOTONIC_STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+const FORMAT_SPECIFIERS: &str = "YymdeHIpMSjaAbBzs%";
+
+#[derive(Debug, Clone, Copy)]
+enum TimeZoneArg {
+  Fixed(FixedOffset),
+  Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeUnit {
+  Millisecond,
+  Second,
+  Minute,
+  Hour,
+  Day,
+  Week,
+  Month,
+  Year,
+}
+
+impl TimeUnit {
+  /// milliseconds of units with fixed length, months and years vary by calendar
+  fn fixed_millis(self) -> Option<f64> {
+    match self {
+      TimeUnit::Millisecond => Some(1.0),
+      TimeUnit::Second => Some(1_000.0),
+      TimeUnit::Minute => Some(60_000.0),
+      TimeUnit::Hour => Some(3_600_000.0),
+      TimeUnit::Day => Some(86_400_000.0),
+      TimeUnit::Week => Some(604_800_000.0),
+      TimeUnit::Month | TimeUnit::Year => None,
+    }
+  }
+}
+
+fn parse_unit(x: Option<&Calcit>, proc_name: &str) -> Result<TimeUnit, CalcitErr> {
+  let name = match x {
+    Some(Calcit::Tag(t)) => t.arc_str(),
+    Some(Calcit::Str(s)) => s.to_owned(),
+    Some(a) => return Err(CalcitErr::use_str(format!("{proc_name} expected a unit tag, got: {a}"))),
+    None => return Err(CalcitErr::use_str(format!("{proc_name} expected a unit tag, got nothing"))),
+  };
+  match &*name {
+    "millisecond" => Ok(TimeUnit::Millisecond),
+    "second" => Ok(TimeUnit::Second),
+    "minute" => Ok(TimeUnit::Minute),
+    "hour" => Ok(TimeUnit::Hour),
+    "day" => Ok(TimeUnit::Day),
+    "week" => Ok(TimeUnit::Week),
+    "month" => Ok(TimeUnit::Month),
+    "year" => Ok(TimeUnit::Year),
+    _ => Err(CalcitErr::use_str(format!(
+      "{proc_name} got unknown unit `{name}`, expected :millisecond :second :minute :hour :day :week :month :year"
+    ))),
+  }
+}
+
+fn parse_time_zone(x: Option<&Calcit>, proc_name: &str) -> Result<TimeZoneArg, CalcitErr> {
+  let utc = FixedOffset::east_opt(0).expect("utc offset");
+  match x {
+    None | Some(Calcit::Nil) => Ok(TimeZoneArg::Fixed(utc)),
+    Some(Calcit::Number(n)) => match f64_to_i32(*n).ok().and_then(|m| FixedOffset::east_opt(m * 60)) {
+      Some(offset) => Ok(TimeZoneArg::Fixed(offset)),
+      None => Err(CalcitErr::use_str(format!("{proc_name} got invalid offset in minutes: {n}"))),
+    },
+    Some(Calcit::Str(s)) => parse_time_zone_str(s).ok_or_else(|| CalcitErr::use_str(format!("{proc_name} got invalid time zone: {s}"))),
+    Some(Calcit::Tag(t)) => {
+      parse_time_zone_str(t.ref_str()).ok_or_else(|| CalcitErr::use_str(format!("{proc_name} got invalid time zone: {t}")))
+    }
+    Some(a) => Err(CalcitErr::use_str(format!("{proc_name} expected a time zone, got: {a}"))),
+  }
+}
+
+/// supports `Z`, `utc`, `local`, `+08`, `+0800` and `+08:00`
+fn parse_time_zone_str(s: &str) -> Option<TimeZoneArg> {
+  match s {
+    "Z" | "z" | "utc" | "UTC" => return Some(TimeZoneArg::Fixed(FixedOffset::east_opt(0)?)),
+    "local" => return Some(TimeZoneArg::Local),
+    _ => (),
+  }
+  let sign = match s.chars().next()? {
+    '+' => 1,
+    '-' => -1,
+    _ => return None,
+  };
+  let digits: String = s[1..].chars().filter(|c| *c != ':').collect();
+  if !digits.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  let (hours, minutes) = match digits.len() {
+    2 => (digits.parse::<i32>().ok()?, 0),
+    4 => (digits[0..2].parse::<i32>().ok()?, digits[2..4].parse::<i32>().ok()?),
+    _ => return None,
+  };
+  if minutes >= 60 {
+    return None;
+  }
+  FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(TimeZoneArg::Fixed)
+}
+
+fn read_timestamp(x: Option<&Calcit>, proc_name: &str) -> Result<DateTime<Utc>, CalcitErr> {
+  match x {
+    Some(Calcit::Number(n)) => DateTime::from_timestamp_micros((n * 1000.0).round() as i64)
+      .ok_or_else(|| CalcitErr::use_str(format!("{proc_name} got a timestamp out of range: {n}"))),
+    Some(a) => Err(CalcitErr::use_str(format!(
+      "{proc_name} expected a timestamp in milliseconds, got: {a}"
+    ))),
+    None => Err(CalcitErr::use_str(format!("{proc_name} expected a timestamp, got nothing"))),
+  }
+}
+
+fn to_timestamp(t: &DateTime<FixedOffset>) -> Calcit {
+  Calcit::Number(t.timestamp_micros() as f64 / 1000.0)
+}
+
+fn in_zone(t: &DateTime<Utc>, zone: TimeZoneArg) -> DateTime<FixedOffset> {
+  match zone {
+    TimeZoneArg::Fixed(offset) => t.with_timezone(&offset),
+    TimeZoneArg::Local => t.with_timezone(&Local).fixed_offset(),
+  }
+}
+
+/// local time repeated by DST resolves to the earlier instant, skipped local time is invalid
+fn from_naive(t: &NaiveDateTime, zone: TimeZoneArg) -> Option<DateTime<FixedOffset>> {
+  match zone {
+    TimeZoneArg::Fixed(offset) => offset.from_local_datetime(t).earliest(),
+    TimeZoneArg::Local => Local.from_local_datetime(t).earliest().map(|v| v.fixed_offset()),
+  }
+}
+
+fn check_pattern(pattern: &str, proc_name: &str) -> Result<(), CalcitErr> {
+  let mut chars = pattern.chars();
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      continue;
+    }
+    let spec = match chars.next() {
+      Some(':') => chars.next().filter(|x| *x == 'z').map(|_| ()),
+      Some('3') => chars.next().filter(|x| *x == 'f').map(|_| ()),
+      Some(x) if FORMAT_SPECIFIERS.contains(x) => Some(()),
+      _ => None,
+    };
+    if spec.is_none() {
+      return Err(CalcitErr::use_str(format!(
+        "{proc_name} got unsupported pattern `{pattern}`, specifiers are limited to %Y %y %m %d %e %H %I %p %M %S %3f %j %a %A %b %B %z %:z %s %%"
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// wall-clock time in milliseconds since Unix epoch
+pub fn now(_xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  Ok(Calcit::Number(Utc::now().timestamp_micros() as f64 / 1000.0))
+}
+
+/// milliseconds from a monotonic clock, only useful for measuring durations
+pub fn monotonic_now(_xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  let elapsed = Instant::now().duration_since(*MONOTONIC_STARTED);
+  Ok(Calcit::Number(elapsed.as_micros() as f64 / 1000.0))
+}
+
+/// `(format-time t)` gives ISO 8601 in UTC, `(format-time t pattern zone)` for others
+pub fn format_time(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.is_empty() || xs.len() > 3 {
+    return CalcitErr::err_nodes("format-time expected 1~3 arguments, got:", xs);
+  }
+  let t = read_timestamp(xs.first(), "format-time")?;
+  let zone = parse_time_zone(xs.get(2), "format-time")?;
+  let zoned = in_zone(&t, zone);
+  match xs.get(1) {
+    None | Some(Calcit::Nil) => Ok(Calcit::Str(zoned.to_rfc3339_opts(SecondsFormat::Millis, true).into())),
+    Some(Calcit::Str(pattern)) => {
+      check_pattern(pattern, "format-time")?;
+      let mut s = String::new();
+      match write!(s, "{}", zoned.format(pattern)) {
+        Ok(_) => Ok(Calcit::Str(s.into())),
+        Err(_) => CalcitErr::err_str(format!("format-time failed to format with pattern: {pattern}")),
+      }
+    }
+    Some(a) => CalcitErr::err_str(format!("format-time expected a pattern string, got: {a}")),
+  }
+}
+
+/// `(parse-time s)` reads ISO 8601, `(parse-time s pattern zone)` reads custom patterns,
+/// zone is used when the text itself carries no offset
+pub fn parse_time(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.is_empty() || xs.len() > 3 {
+    return CalcitErr::err_nodes("parse-time expected 1~3 arguments, got:", xs);
+  }
+  let s = match &xs[0] {
+    Calcit::Str(s) => s.to_owned(),
+    a => return CalcitErr::err_str(format!("parse-time expected a string, got: {a}")),
+  };
+  let zone = parse_time_zone(xs.get(2), "parse-time")?;
+  let parsed = match xs.get(1) {
+    None | Some(Calcit::Nil) => parse_iso(&s, zone),
+    Some(Calcit::Str(pattern)) => {
+      check_pattern(pattern, "parse-time")?;
+      parse_with_pattern(&s, pattern, zone)
+    }
+    Some(a) => return CalcitErr::err_str(format!("parse-time expected a pattern string, got: {a}")),
+  };
+  match parsed {
+    Some(t) => Ok(to_timestamp(&t)),
+    None => match xs.get(1) {
+      Some(Calcit::Str(pattern)) => CalcitErr::err_str(format!("parse-time failed to parse `{s}` with pattern `{pattern}`")),
+      _ => CalcitErr::err_str(format!("parse-time failed to parse `{s}` as ISO 8601")),
+    },
+  }
+}
+
+fn parse_iso(s: &str, zone: TimeZoneArg) -> Option<DateTime<FixedOffset>> {
+  if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+    return Some(t);
+  }
+  if let Ok(t) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f") {
+    return from_naive(&t, zone);
+  }
+  if let Ok(t) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M") {
+    return from_naive(&t, zone);
+  }
+  if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+    return from_naive(&d.and_time(NaiveTime::MIN), zone);
+  }
+  None
+}
+
+fn parse_with_pattern(s: &str, pattern: &str, zone: TimeZoneArg) -> Option<DateTime<FixedOffset>> {
+  if let Ok(t) = DateTime::parse_from_str(s, pattern) {
+    return Some(t);
+  }
+  if let Ok(t) = NaiveDateTime::parse_from_str(s, pattern) {
+    return from_naive(&t, zone);
+  }
+  if let Ok(d) = NaiveDate::parse_from_str(s, pattern) {
+    return from_naive(&d.and_time(NaiveTime::MIN), zone);
+  }
+  None
+}
+
+fn add_months(t: &NaiveDateTime, n: i32) -> Option<NaiveDateTime> {
+  if n >= 0 {
+    t.checked_add_months(Months::new(n as u32))
+  } else {
+    t.checked_sub_months(Months::new(n.unsigned_abs()))
+  }
+}
+
+/// `(time-add t n unit zone)`, days and longer units follow the calendar in the zone,
+/// month overflow clamps to the last day, like 01-31 plus 1 month is 02-28
+pub fn time_add(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() < 3 || xs.len() > 4 {
+    return CalcitErr::err_nodes("time-add expected 3~4 arguments, got:", xs);
+  }
+  let t = read_timestamp(xs.first(), "time-add")?;
+  let n = match &xs[1] {
+    Calcit::Number(n) => *n,
+    a => return CalcitErr::err_str(format!("time-add expected an amount in number, got: {a}")),
+  };
+  let unit = parse_unit(xs.get(2), "time-add")?;
+  let zone = parse_time_zone(xs.get(3), "time-add")?;
+
+  let calendar_months = match unit {
+    TimeUnit::Month => Some(n),
+    TimeUnit::Year => Some(n * 12.0),
+    _ => None,
+  };
+  let naive = in_zone(&t, zone).naive_local();
+  let moved = match (unit, calendar_months) {
+    (_, Some(months)) => {
+      let months = f64_to_i32(months).map_err(|e| CalcitErr::use_str(format!("time-add expected integer for months, {e}")))?;
+      add_months(&naive, months).and_then(|v| from_naive(&v, zone))
+    }
+    (TimeUnit::Day | TimeUnit::Week, None) => {
+      let days = if unit == TimeUnit::Week { n * 7.0 } else { n };
+      let days = f64_to_i32(days).map_err(|e| CalcitErr::use_str(format!("time-add expected integer for days, {e}")))?;
+      naive
+        .checked_add_signed(chrono::Duration::days(days as i64))
+        .and_then(|v| from_naive(&v, zone))
+    }
+    (_, None) => {
+      let delta = n * unit.fixed_millis().expect("fixed unit");
+      return match xs[0] {
+        Calcit::Number(base) => Ok(Calcit::Number(base + delta)),
+        _ => unreachable!("checked timestamp"),
+      };
+    }
+  };
+  match moved {
+    Some(v) => Ok(to_timestamp(&v)),
+    None => CalcitErr::err_str(format!("time-add got result out of range: {} {n} {unit:?}", xs[0])),
+  }
+}
+
+/// `(time-diff a b unit zone)` measures `a - b`, months and years are counted in whole calendar months
+pub fn time_diff(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() < 3 || xs.len() > 4 {
+    return CalcitErr::err_nodes("time-diff expected 3~4 arguments, got:", xs);
+  }
+  let a = read_timestamp(xs.first(), "time-diff")?;
+  let b = read_timestamp(xs.get(1), "time-diff")?;
+  let unit = parse_unit(xs.get(2), "time-diff")?;
+  let zone = parse_time_zone(xs.get(3), "time-diff")?;
+
+  if let Some(size) = unit.fixed_millis() {
+    let delta = (a.timestamp_micros() - b.timestamp_micros()) as f64 / 1000.0;
+    return Ok(Calcit::Number(delta / size));
+  }
+
+  let za = in_zone(&a, zone).naive_local();
+  let zb = in_zone(&b, zone).naive_local();
+  let mut months = (za.year() - zb.year()) * 12 + (za.month() as i32 - zb.month() as i32);
+  // step back when the last month is not completed
+  if months > 0 && add_months(&zb, months).is_some_and(|v| v > za) {
+    months -= 1;
+  } else if months < 0 && add_months(&zb, months).is_some_and(|v| v < za) {
+    months += 1;
+  }
+  match unit {
+    TimeUnit::Year => Ok(Calcit::Number((months / 12) as f64)),
+    _ => Ok(Calcit::Number(months as f64)),
+  }
+}
+
+/// `(time-truncate t unit zone)` moves to the start of the unit, weeks start from Monday
+pub fn time_truncate(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  if xs.len() < 2 || xs.len() > 3 {
+    return CalcitErr::err_nodes("time-truncate expected 2~3 arguments, got:", xs);
+  }
+  let t = read_timestamp(xs.first(), "time-truncate")?;
+  let unit = parse_unit(xs.get(1), "time-truncate")?;
+  let zone = parse_time_zone(xs.get(2), "time-truncate")?;
+
+  let naive = in_zone(&t, zone).naive_local();
+  let date = naive.date();
+  let truncated = match unit {
+    TimeUnit::Millisecond => date.and_hms_milli_opt(naive.hour(), naive.minute(), naive.second(), naive.nanosecond() / 1_000_000),
+    TimeUnit::Second => date.and_hms_opt(naive.hour(), naive.minute(), naive.second()),
+    TimeUnit::Minute => date.and_hms_opt(naive.hour(), naive.minute(), 0),
+    TimeUnit::Hour => date.and_hms_opt(naive.hour(), 0, 0),
+    TimeUnit::Day => Some(date.and_time(NaiveTime::MIN)),
+    TimeUnit::Week => date
+      .checked_sub_days(chrono::Days::new(date.weekday().num_days_from_monday() as u64))
+      .map(|d| d.and_time(NaiveTime::MIN)),
+    TimeUnit::Month => date.with_day(1).map(|d| d.and_time(NaiveTime::MIN)),
+    TimeUnit::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).map(|d| d.and_time(NaiveTime::MIN)),
+  };
+  match truncated.and_then(|v| from_naive(&v, zone)) {
+    Some(v) => Ok(to_timestamp(&v)),
+    None => CalcitErr::err_str(format!("time-truncate failed to truncate {} by {unit:?}", xs[0])),
+  }
+}
@@ -0,0 +1,214 @@
+//! procs for transients, collections mutated in place while being built and then frozen with `persistent!`.
+//! mutating procs return the transient itself so they can be chained in `foldl`.
+//! a transient can not be used anymore after `persistent!`, data returned from it is never mutated.
+
+use std::sync::Arc;
+
+use crate::calcit::{Calcit, CalcitErr, CalcitTransient, TransientState};
+use crate::util::number::f64_to_usize;
+
+/// problems found while a transient is locked, values are formatted after the lock is released
+/// since displaying an argument that is the transient itself locks it again
+enum StateErr {
+  Message(String),
+  NotIndex(Calcit),
+}
+
+/// runs `f` on the state of a transient in use
+fn with_state<T>(x: &Calcit, proc_name: &str, f: impl FnOnce(&mut TransientState) -> Result<T, StateErr>) -> Result<T, CalcitErr> {
+  match x {
+    Calcit::Transient(t) => {
+      let result = {
+        let mut state = t.state.lock().expect("write transient");
+        if let TransientState::Frozen = &*state {
+          return Err(CalcitErr::use_str(format!(
+            "{proc_name} expected a transient in use, it is already persistent"
+          )));
+        }
+        f(&mut state)
+      };
+      result.map_err(|e| match e {
+        StateErr::Message(m) => CalcitErr::use_str(format!("{proc_name} {m}")),
+        StateErr::NotIndex(a) => CalcitErr::use_str(format!("{proc_name} expected an index, got: {a}")),
+      })
+    }
+    a => Err(CalcitErr::use_str(format!("{proc_name} expected a transient, got: {a}"))),
+  }
+}
+
+fn read_index(x: &Calcit) -> Result<usize, StateErr> {
+  match x {
+    Calcit::Number(n) => f64_to_usize(*n).map_err(|e| StateErr::Message(format!("expected an index, {e}"))),
+    a => Err(StateErr::NotIndex(a.to_owned())),
+  }
+}
+
+fn unexpected_kind(expected: &str, state: &TransientState) -> StateErr {
+  StateErr::Message(format!("expected a transient {expected}, got a transient {}", state.kind_name()))
+}
+
+pub fn transient(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => match CalcitTransient::from_data(a) {
+      Some(t) => Ok(Calcit::Transient(Arc::new(t))),
+      None => CalcitErr::err_str(format!("transient expected a list, a map or a set, got: {a}")),
+    },
+    _ => CalcitErr::err_nodes("transient expected 1 argument, got:", xs),
+  }
+}
+
+pub fn transient_ques(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [a] => Ok(Calcit::Bool(matches!(a, Calcit::Transient(_)))),
+    _ => CalcitErr::err_nodes("transient? expected 1 argument, got:", xs),
+  }
+}
+
+/// returns the data and leaves the transient frozen
+pub fn persistent(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [Calcit::Transient(t)] => match t.freeze() {
+      Some(v) => Ok(v),
+      None => CalcitErr::err_str("persistent! expected a transient in use, it is already persistent"),
+    },
+    [a] => CalcitErr::err_str(format!("persistent! expected a transient, got: {a}")),
+    _ => CalcitErr::err_nodes("persistent! expected 1 argument, got:", xs),
+  }
+}
+
+pub fn count(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t] => with_state(t, "&transient:count", |state| Ok(Calcit::Number(state.len() as f64))),
+    _ => CalcitErr::err_nodes("&transient:count expected 1 argument, got:", xs),
+  }
+}
+
+/// item at an index of a list or value of a key in a map, `nil` when missing
+pub fn get(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, k] => with_state(t, "&transient:get", |state| match state {
+      TransientState::List(ys) => Ok(ys.get(read_index(k)?).cloned().unwrap_or(Calcit::Nil)),
+      TransientState::Map(ys) => Ok(ys.get(k).cloned().unwrap_or(Calcit::Nil)),
+      TransientState::SortedMap(ys) => Ok(ys.get(k).cloned().unwrap_or(Calcit::Nil)),
+      s => Err(unexpected_kind("list or map", s)),
+    }),
+    _ => CalcitErr::err_nodes("&transient:get expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn append(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, x] => {
+      with_state(t, "&transient:append!", |state| match state {
+        TransientState::List(ys) => {
+          ys.push(x.to_owned());
+          Ok(())
+        }
+        s => Err(unexpected_kind("list", s)),
+      })?;
+      Ok(t.to_owned())
+    }
+    _ => CalcitErr::err_nodes("&transient:append! expected 2 arguments, got:", xs),
+  }
+}
+
+/// sets a key in a map, or replaces an item of a list at an index within its size
+pub fn assoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, k, v] => {
+      with_state(t, "&transient:assoc!", |state| match state {
+        TransientState::List(ys) => {
+          let idx = read_index(k)?;
+          match ys.get_mut(idx) {
+            Some(y) => {
+              *y = v.to_owned();
+              Ok(())
+            }
+            None => Err(StateErr::Message(format!("expected an index within 0~{}, got: {idx}", ys.len()))),
+          }
+        }
+        TransientState::Map(ys) => {
+          ys.insert_mut(k.to_owned(), v.to_owned());
+          Ok(())
+        }
+        TransientState::SortedMap(ys) => {
+          ys.insert_mut(k.to_owned(), v.to_owned());
+          Ok(())
+        }
+        s => Err(unexpected_kind("list or map", s)),
+      })?;
+      Ok(t.to_owned())
+    }
+    _ => CalcitErr::err_nodes("&transient:assoc! expected 3 arguments, got:", xs),
+  }
+}
+
+/// removes a key from a map, or an item at an index from a list
+pub fn dissoc(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, k] => {
+      with_state(t, "&transient:dissoc!", |state| match state {
+        TransientState::List(ys) => {
+          let idx = read_index(k)?;
+          if idx < ys.len() {
+            ys.remove(idx);
+            Ok(())
+          } else {
+            Err(StateErr::Message(format!("expected an index within 0~{}, got: {idx}", ys.len())))
+          }
+        }
+        TransientState::Map(ys) => {
+          ys.remove_mut(k);
+          Ok(())
+        }
+        TransientState::SortedMap(ys) => {
+          ys.remove_mut(k);
+          Ok(())
+        }
+        s => Err(unexpected_kind("list or map", s)),
+      })?;
+      Ok(t.to_owned())
+    }
+    _ => CalcitErr::err_nodes("&transient:dissoc! expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn include(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, x] => {
+      with_state(t, "&transient:include!", |state| match state {
+        TransientState::Set(ys) => {
+          ys.insert_mut(x.to_owned());
+          Ok(())
+        }
+        TransientState::SortedSet(ys) => {
+          ys.insert_mut(x.to_owned());
+          Ok(())
+        }
+        s => Err(unexpected_kind("set", s)),
+      })?;
+      Ok(t.to_owned())
+    }
+    _ => CalcitErr::err_nodes("&transient:include! expected 2 arguments, got:", xs),
+  }
+}
+
+pub fn exclude(xs: &[Calcit]) -> Result<Calcit, CalcitErr> {
+  match xs {
+    [t, x] => {
+      with_state(t, "&transient:exclude!", |state| match state {
+        TransientState::Set(ys) => {
+          ys.remove_mut(x);
+          Ok(())
+        }
+        TransientState::SortedSet(ys) => {
+          ys.remove_mut(x);
+          Ok(())
+        }
+        s => Err(unexpected_kind("set", s)),
+      })?;
+      Ok(t.to_owned())
+    }
+    _ => CalcitErr::err_nodes("&transient:exclude! expected 2 arguments, got:", xs),
+  }
+}
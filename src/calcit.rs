@@ -1,12 +1,17 @@
 mod fns;
+mod inline_cache;
+mod lazy;
 mod list;
 mod local;
 mod proc_name;
+mod promise;
 mod record;
 mod symbol;
 mod syntax_name;
 mod thunk;
+mod transient;
 mod tuple;
+mod type_hint;
 
 use core::cmp::Ord;
 use std::cmp::Eq;
@@ -25,14 +30,19 @@ use cirru_parser::Cirru;
 use im_ternary_tree::TernaryTreeList;
 
 pub use fns::{CalcitArgLabel, CalcitFn, CalcitFnArgs, CalcitMacro, CalcitScope};
+pub use inline_cache::{MethodInlineCache, ReceiverClass};
+pub use lazy::{CalcitLazySeq, LazyNode};
 pub use list::CalcitList;
 pub use local::CalcitLocal;
-pub use proc_name::CalcitProc;
-pub use record::CalcitRecord;
+pub use proc_name::{CalcitProc, ProcTypeSignature};
+pub use promise::{CalcitPromise, PromiseReaction, PromiseState};
+pub use record::{CalcitRecord, RecordFieldSchema, RecordSchema};
 pub use symbol::{CalcitImport, CalcitSymbolInfo, ImportInfo};
 pub use syntax_name::CalcitSyntax;
 pub use thunk::{CalcitThunk, CalcitThunkInfo};
+pub use transient::{CalcitTransient, TransientState};
 pub use tuple::CalcitTuple;
+pub use type_hint::{CalcitTypeHint, RecordShape};
 
 use crate::builtins::ValueAndListeners;
 use crate::call_stack::CallStackList;
@@ -70,6 +80,12 @@ pub enum Calcit {
   Tuple(CalcitTuple),
   /// binary data, to be used by FFIs
   Buffer(Vec<u8>),
+  /// promise-like value for async code, settled by the event loop
+  Promise(Arc<CalcitPromise>),
+  /// collection under construction, mutated in place and turned back with `persistent!`
+  Transient(Arc<CalcitTransient>),
+  /// sequence realized on demand, items are produced when consumed by `foldl` and alike
+  LazySeq(Arc<CalcitLazySeq>),
   /// cirru quoted data, for faster meta programming
   CirruQuote(Cirru),
   /// not for data, but for recursion
@@ -77,6 +93,10 @@ pub enum Calcit {
   List(Arc<CalcitList>),
   Set(rpds::HashTrieSetSync<Calcit>),
   Map(rpds::HashTrieMapSync<Calcit, Calcit>),
+  /// set ordered by `Ord` of items, treated as a set in methods and equality
+  SortedSet(rpds::RedBlackTreeSetSync<Calcit>),
+  /// map ordered by `Ord` of keys, treated as a map in methods and equality
+  SortedMap(rpds::RedBlackTreeMapSync<Calcit, Calcit>),
   /// with only static and limited keys, for performance and checking
   /// size of keys are values should be kept consistent
   Record(CalcitRecord),
@@ -215,6 +235,20 @@ impl fmt::Display for Calcit {
         f.write_str(")")?;
         Ok(())
       }
+      Calcit::SortedSet(xs) => {
+        f.write_str("(sorted-set")?;
+        for x in xs {
+          f.write_str(&format!(" {x}"))?;
+        }
+        f.write_str(")")
+      }
+      Calcit::SortedMap(xs) => {
+        f.write_str("(sorted-map")?;
+        for (k, v) in xs {
+          f.write_str(&format!(" {k} {v}"))?;
+        }
+        f.write_str(")")
+      }
       Calcit::Record(CalcitRecord { name, fields, values, .. }) => {
         f.write_str(&format!("(%{{}} {}", Calcit::Tag(name.to_owned())))?;
         for idx in 0..fields.len() {
@@ -283,6 +317,12 @@ impl fmt::Display for Calcit {
       Calcit::Syntax(name, _ns) => f.write_str(&format!("(&syntax {name})")),
       Calcit::Method(name, method_kind) => f.write_str(&format!("(&{method_kind} {name})")),
       Calcit::RawCode(_, code) => f.write_str(&format!("(&raw-code {code})")),
+      Calcit::Promise(p) => f.write_str(&format!("(&promise {} :{})", p.id, p.status_name())),
+      Calcit::Transient(t) => {
+        let state = t.state.lock().expect("read transient");
+        f.write_str(&format!("(&transient {} :{} {})", t.id, state.kind_name(), state.len()))
+      }
+      Calcit::LazySeq(s) => f.write_str(&format!("(&lazy-seq {} :{})", s.id, s.node.kind_name())),
       Calcit::AnyRef(_r) => f.write_str("(&any-ref ...)"),
     }
   }
@@ -391,6 +431,18 @@ impl Hash for Calcit {
         "cirru-quote:".hash(_state);
         code.hash(_state);
       }
+      Calcit::Promise(p) => {
+        "promise:".hash(_state);
+        p.id.hash(_state);
+      }
+      Calcit::Transient(t) => {
+        "transient:".hash(_state);
+        t.id.hash(_state);
+      }
+      Calcit::LazySeq(s) => {
+        "lazy-seq:".hash(_state);
+        s.id.hash(_state);
+      }
       Calcit::Recur(v) => {
         "list:".hash(_state);
         v.hash(_state);
@@ -417,6 +469,19 @@ impl Hash for Calcit {
           x.hash(_state)
         }
       }
+      // same as hash sets and hash maps, since they are equal when items are equal
+      Calcit::SortedSet(v) => {
+        "set:".hash(_state);
+        for x in v {
+          x.hash(_state)
+        }
+      }
+      Calcit::SortedMap(v) => {
+        "map:".hash(_state);
+        for x in v {
+          x.hash(_state)
+        }
+      }
       Calcit::Record(CalcitRecord { name, fields, values, .. }) => {
         "record:".hash(_state);
         name.hash(_state);
@@ -545,29 +610,38 @@ impl Ord for Calcit {
       (Calcit::List(_), _) => Less,
       (_, Calcit::List(_)) => Greater,
 
-      (Calcit::Set(a), Calcit::Set(b)) => match a.size().cmp(&b.size()) {
-        Equal => {
-          if a == b {
-            Equal
-          } else {
-            unreachable!("TODO sets are not cmp ed")
-          }
-        }
-        a => a,
-      },
-      (Calcit::Set(_), _) => Less,
-      (_, Calcit::Set(_)) => Greater,
+      // sets are compared by sizes, and then by items in order
+      (Calcit::Set(_) | Calcit::SortedSet(_), Calcit::Set(_) | Calcit::SortedSet(_)) => {
+        let (xs, ys) = (self.ordered_set_items(), other.ordered_set_items());
+        xs.len().cmp(&ys.len()).then_with(|| xs.cmp(&ys))
+      }
+      (Calcit::Set(_) | Calcit::SortedSet(_), _) => Less,
+      (_, Calcit::Set(_) | Calcit::SortedSet(_)) => Greater,
 
-      (Calcit::Map(a), Calcit::Map(b)) => {
-        unreachable!("TODO maps are not cmp ed {:?} {:?}", a, b)
+      (Calcit::Map(_) | Calcit::SortedMap(_), Calcit::Map(_) | Calcit::SortedMap(_)) => {
+        let (xs, ys) = (self.ordered_map_entries(), other.ordered_map_entries());
+        xs.len().cmp(&ys.len()).then_with(|| xs.cmp(&ys))
       }
-      (Calcit::Map(_), _) => Less,
-      (_, Calcit::Map(_)) => Greater,
+      (Calcit::Map(_) | Calcit::SortedMap(_), _) => Less,
+      (_, Calcit::Map(_) | Calcit::SortedMap(_)) => Greater,
 
-      (Calcit::Record(CalcitRecord { name: name1, .. }), Calcit::Record(CalcitRecord { name: name2, .. })) => match name1.cmp(name2) {
-        Equal => unreachable!("TODO records are not cmp ed"),
-        ord => ord,
-      },
+      (
+        Calcit::Record(CalcitRecord {
+          name: name1,
+          fields: fields1,
+          values: values1,
+          ..
+        }),
+        Calcit::Record(CalcitRecord {
+          name: name2,
+          fields: fields2,
+          values: values2,
+          ..
+        }),
+      ) => name1
+        .cmp(name2)
+        .then_with(|| fields1.cmp(fields2))
+        .then_with(|| values1.cmp(values2)),
       (Calcit::Record { .. }, _) => Less,
       (_, Calcit::Record { .. }) => Greater,
 
@@ -598,6 +672,18 @@ impl Ord for Calcit {
       (Calcit::RawCode(..), _) => Less,
       (_, Calcit::RawCode(..)) => Greater,
 
+      (Calcit::Promise(a), Calcit::Promise(b)) => a.id.cmp(&b.id),
+      (Calcit::Promise(..), _) => Less,
+      (_, Calcit::Promise(..)) => Greater,
+
+      (Calcit::Transient(a), Calcit::Transient(b)) => a.id.cmp(&b.id),
+      (Calcit::Transient(..), _) => Less,
+      (_, Calcit::Transient(..)) => Greater,
+
+      (Calcit::LazySeq(a), Calcit::LazySeq(b)) => a.id.cmp(&b.id),
+      (Calcit::LazySeq(..), _) => Less,
+      (_, Calcit::LazySeq(..)) => Greater,
+
       (Calcit::AnyRef(_), Calcit::AnyRef(_)) => unreachable!("AnyRef should not be used in cmp"),
     }
   }
@@ -635,9 +721,20 @@ impl PartialEq for Calcit {
       (Calcit::Tuple(a), Calcit::Tuple(b)) => a == b,
       (Calcit::Buffer(b), Calcit::Buffer(d)) => b == d,
       (Calcit::CirruQuote(b), Calcit::CirruQuote(d)) => b == d,
+      (Calcit::Promise(a), Calcit::Promise(b)) => a.id == b.id,
+      (Calcit::Transient(a), Calcit::Transient(b)) => a.id == b.id,
+      (Calcit::LazySeq(a), Calcit::LazySeq(b)) => a.id == b.id,
       (Calcit::List(a), Calcit::List(b)) => a == b,
       (Calcit::Set(a), Calcit::Set(b)) => a == b,
+      (Calcit::SortedSet(a), Calcit::SortedSet(b)) => a == b,
+      (Calcit::Set(a), Calcit::SortedSet(b)) | (Calcit::SortedSet(b), Calcit::Set(a)) => {
+        a.size() == b.size() && a.iter().all(|x| b.contains(x))
+      }
       (Calcit::Map(a), Calcit::Map(b)) => a == b,
+      (Calcit::SortedMap(a), Calcit::SortedMap(b)) => a == b,
+      (Calcit::Map(a), Calcit::SortedMap(b)) | (Calcit::SortedMap(b), Calcit::Map(a)) => {
+        a.size() == b.size() && a.iter().all(|(k, v)| b.get(k) == Some(v))
+      }
       (Calcit::Record(a), Calcit::Record(b)) => a == b,
       (Calcit::Proc(a), Calcit::Proc(b)) => a == b,
       (Calcit::Macro { id: a, .. }, Calcit::Macro { id: b, .. }) => a == b,
@@ -722,6 +819,32 @@ impl Calcit {
       Calcit::Symbol { .. } | Calcit::Local { .. } | Calcit::Import(..) | Calcit::Thunk(..) | Calcit::List(..)
     )
   }
+
+  /// items of a hash set or a sorted set, in order
+  fn ordered_set_items(&self) -> Vec<&Calcit> {
+    match self {
+      Calcit::Set(xs) => {
+        let mut ys: Vec<&Calcit> = xs.iter().collect();
+        ys.sort();
+        ys
+      }
+      Calcit::SortedSet(xs) => xs.iter().collect(),
+      _ => vec![],
+    }
+  }
+
+  /// entries of a hash map or a sorted map, in order of keys
+  fn ordered_map_entries(&self) -> Vec<(&Calcit, &Calcit)> {
+    match self {
+      Calcit::Map(xs) => {
+        let mut ys: Vec<(&Calcit, &Calcit)> = xs.iter().collect();
+        ys.sort_by(|a, b| a.0.cmp(b.0));
+        ys
+      }
+      Calcit::SortedMap(xs) => xs.iter().collect(),
+      _ => vec![],
+    }
+  }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -747,6 +870,8 @@ pub fn gen_core_id() -> Arc<str> {
 pub struct CalcitErr {
   pub msg: String,
   pub warnings: Vec<LocatedWarning>,
+  /// data from `raise`, passed to handlers of `try` as `:payload`
+  pub payload: Option<Arc<Calcit>>,
   pub location: Option<Arc<NodeLocation>>,
   pub stack: CallStackList,
 }
@@ -768,6 +893,7 @@ impl From<String> for CalcitErr {
     CalcitErr {
       msg,
       warnings: vec![],
+      payload: None,
       stack: CallStackList::default(),
       location: None,
     }
@@ -779,6 +905,7 @@ impl CalcitErr {
     CalcitErr {
       msg: msg.into(),
       warnings: vec![],
+      payload: None,
       stack: CallStackList::default(),
       location: None,
     }
@@ -787,6 +914,7 @@ impl CalcitErr {
     Err(CalcitErr {
       msg: msg.into(),
       warnings: vec![],
+      payload: None,
       stack: CallStackList::default(),
       location: None,
     })
@@ -796,6 +924,7 @@ impl CalcitErr {
     Err(CalcitErr {
       msg: format!("{} {}", msg.into(), CalcitList::from(nodes)),
       warnings: vec![],
+      payload: None,
       stack: CallStackList::default(),
       location: None,
     })
@@ -804,6 +933,7 @@ impl CalcitErr {
     Err(CalcitErr {
       msg: msg.into(),
       warnings: vec![],
+      payload: None,
       stack: CallStackList::default(),
       location,
     })
@@ -812,6 +942,7 @@ impl CalcitErr {
     CalcitErr {
       msg: msg.into(),
       warnings: vec![],
+      payload: None,
       stack: stack.to_owned(),
       location: None,
     }
@@ -820,12 +951,100 @@ impl CalcitErr {
     CalcitErr {
       msg: msg.into(),
       warnings: vec![],
+      payload: None,
       stack: stack.to_owned(),
       location: location.map(Arc::new),
     }
   }
 }
 
+/// name of the record passed to handlers of `try`
+pub const ERROR_RECORD_NAME: &str = "CalcitError";
+
+impl CalcitErr {
+  /// record for handlers of `try`, with fields `:location :message :payload :stack`.
+  /// `:stack` only keeps frames inside `outer_stack`, which is the stack of `try` itself
+  pub fn to_error_record(&self, outer_stack: &CallStackList) -> Calcit {
+    let inner_size = self.stack.len().saturating_sub(outer_stack.len());
+    let stack = self
+      .stack
+      .0
+      .iter()
+      .take(inner_size)
+      .map(|s| Calcit::Str(format!("{}/{}", s.ns, s.def).into()))
+      .collect::<Vec<_>>();
+    let location = match &self.location {
+      Some(l) => {
+        let mut m = rpds::HashTrieMap::new_sync();
+        m.insert_mut(Calcit::tag("ns"), Calcit::Str(l.ns.to_owned()));
+        m.insert_mut(Calcit::tag("def"), Calcit::Str(l.def.to_owned()));
+        m.insert_mut(
+          Calcit::tag("coord"),
+          Calcit::from(l.coord.iter().map(|x| Calcit::Number(*x as f64)).collect::<Vec<_>>()),
+        );
+        Calcit::Map(m)
+      }
+      None => Calcit::Nil,
+    };
+    Calcit::Record(CalcitRecord {
+      name: EdnTag::from(ERROR_RECORD_NAME),
+      fields: Arc::new(vec![
+        EdnTag::from("location"),
+        EdnTag::from("message"),
+        EdnTag::from("payload"),
+        EdnTag::from("stack"),
+      ]),
+      values: Arc::new(vec![
+        location,
+        Calcit::Str(self.msg.to_owned().into()),
+        self.payload.as_deref().cloned().unwrap_or(Calcit::Nil),
+        Calcit::from(stack),
+      ]),
+      class: None,
+      schema: None,
+    })
+  }
+
+  /// turns a record from `to_error_record` back into an error, for rethrowing.
+  /// the stack is not recovered, since the record only keeps names
+  pub fn from_error_record(x: &Calcit) -> Option<CalcitErr> {
+    let record = match x {
+      Calcit::Record(r) if r.name.ref_str() == ERROR_RECORD_NAME => r,
+      _ => return None,
+    };
+    let msg = match record.get("message") {
+      Some(Calcit::Str(s)) => (**s).to_owned(),
+      _ => return None,
+    };
+    let location = match record.get("location") {
+      Some(Calcit::Map(m)) => match (m.get(&Calcit::tag("ns")), m.get(&Calcit::tag("def")), m.get(&Calcit::tag("coord"))) {
+        (Some(Calcit::Str(ns)), Some(Calcit::Str(def)), Some(Calcit::List(coord))) => {
+          let coord = coord
+            .iter()
+            .map(|c| match c {
+              Calcit::Number(n) => *n as u8,
+              _ => 0,
+            })
+            .collect::<Vec<_>>();
+          Some(Arc::new(NodeLocation::new(ns.to_owned(), def.to_owned(), Arc::new(coord))))
+        }
+        _ => None,
+      },
+      _ => None,
+    };
+    Some(CalcitErr {
+      msg,
+      warnings: vec![],
+      payload: match record.get("payload") {
+        None | Some(Calcit::Nil) => None,
+        Some(v) => Some(Arc::new(v.to_owned())),
+      },
+      stack: CallStackList::default(),
+      location,
+    })
+  }
+}
+
 /// location of node in Snapshot
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NodeLocation {
@@ -872,19 +1091,84 @@ impl NodeLocation {
   }
 }
 
+/// category of warnings, for tools to handle them without parsing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+  /// calling a function with wrong number of arguments
+  Arity,
+  UnknownSymbol,
+  /// local binding with the name of a core definition, or of an outer binding in `cr check`
+  Shadowing,
+  /// unexpected data during preprocessing
+  Unexpected,
+  /// entry in `:require` of `ns` which is never used, from `cr check`
+  UnusedImport,
+  /// `&let` binding never referred in its body, from `cr check`
+  UnusedLocal,
+  /// definition not referred anywhere in the program, from `cr check`
+  UnusedDef,
+  /// value not matching type hints or signatures of procs, printed without blocking the program
+  Type,
+}
+
+impl WarningKind {
+  /// warnings from type hints are only printed, others block running and codegen
+  pub fn is_blocking(&self) -> bool {
+    !matches!(self, Self::Type)
+  }
+}
+
+impl fmt::Display for WarningKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Arity => write!(f, "arity"),
+      Self::UnknownSymbol => write!(f, "unknown-symbol"),
+      Self::Shadowing => write!(f, "shadowing"),
+      Self::Unexpected => write!(f, "unexpected"),
+      Self::UnusedImport => write!(f, "unused-import"),
+      Self::UnusedLocal => write!(f, "unused-local"),
+      Self::UnusedDef => write!(f, "unused-def"),
+      Self::Type => write!(f, "type"),
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct LocatedWarning(String, NodeLocation);
+pub struct LocatedWarning(WarningKind, String, NodeLocation);
 
 impl Display for LocatedWarning {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{} @{}", self.0, self.1)
+    write!(f, "{} @{}", self.1, self.2)
+  }
+}
+
+/// `{} (:kind :arity) (:message |...) (:location $ {} (:ns |app.main) (:def |f) (:coord $ [] 3 1))`
+impl From<&LocatedWarning> for Edn {
+  fn from(v: &LocatedWarning) -> Self {
+    Edn::map_from_iter([
+      (Edn::tag("kind"), Edn::tag(v.0.to_string())),
+      (Edn::tag("message"), v.1.to_owned().into()),
+      (Edn::tag("location"), (&v.2).into()),
+    ])
   }
 }
 
 /// warning from static checking of macro expanding
 impl LocatedWarning {
-  pub fn new(msg: String, location: NodeLocation) -> Self {
-    LocatedWarning(msg, location)
+  pub fn new(kind: WarningKind, msg: String, location: NodeLocation) -> Self {
+    LocatedWarning(kind, msg, location)
+  }
+
+  pub fn kind(&self) -> WarningKind {
+    self.0
+  }
+
+  pub fn message(&self) -> &str {
+    &self.1
+  }
+
+  pub fn location(&self) -> &NodeLocation {
+    &self.2
   }
 
   /// create an empty list
@@ -897,12 +1181,25 @@ impl LocatedWarning {
       println!("{warn}");
     }
   }
+
+  /// prints warnings that don't block to stderr, returns the others
+  pub fn take_blocking(list: &[Self]) -> Vec<Self> {
+    let mut blocking = vec![];
+    for warn in list {
+      if warn.0.is_blocking() {
+        blocking.push(warn.to_owned());
+      } else {
+        eprintln!("{warn}");
+      }
+    }
+    blocking
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum MethodKind {
-  /// (.call a)
-  Invoke,
+  /// (.call a), with a cache of its call site
+  Invoke(Arc<MethodInlineCache>),
   /// (.!f a)
   InvokeNative,
   /// (.?!f a)
@@ -916,7 +1213,7 @@ pub enum MethodKind {
 impl fmt::Display for MethodKind {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      MethodKind::Invoke => write!(f, "invoke"),
+      MethodKind::Invoke(_) => write!(f, "invoke"),
       MethodKind::InvokeNative => write!(f, "invoke-native"),
       MethodKind::InvokeNativeOptional => write!(f, "invoke-native-optional"),
       MethodKind::Access => write!(f, "access"),
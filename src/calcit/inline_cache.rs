@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+
+use super::{Calcit, CalcitRecord};
+
+/// class of a receiver found at a call site, builtin classes are named by their definitions in `calcit.core`
+#[derive(Debug, Clone, Copy)]
+pub enum ReceiverClass<'a> {
+  Core(&'static str),
+  Record(&'a Arc<CalcitRecord>),
+}
+
+#[derive(Debug, Clone)]
+enum CachedClass {
+  Core(&'static str),
+  /// holding the class so its pointers are not reused by other classes
+  Record(Arc<CalcitRecord>),
+}
+
+#[derive(Debug, Clone)]
+struct CachedMethod {
+  class: CachedClass,
+  /// builtin classes are looked up again when program changed
+  generation: usize,
+  method: Calcit,
+}
+
+/// inline cache at a call site of `.method`, remembers the method found for the last class of receivers.
+/// caches are not part of code, so they are ignored in comparing and hashing
+#[derive(Debug, Default)]
+pub struct MethodInlineCache(RwLock<Option<CachedMethod>>);
+
+impl MethodInlineCache {
+  /// method cached for the class, records are compared by identity of their classes
+  pub fn lookup(&self, class: ReceiverClass, generation: usize) -> Option<Calcit> {
+    let cached = self.0.read().expect("read method cache");
+    let entry = cached.as_ref()?;
+    let matched = match (&entry.class, class) {
+      (CachedClass::Core(a), ReceiverClass::Core(b)) => *a == b && entry.generation == generation,
+      (CachedClass::Record(a), ReceiverClass::Record(b)) => Arc::ptr_eq(&a.fields, &b.fields) && Arc::ptr_eq(&a.values, &b.values),
+      _ => false,
+    };
+    if matched {
+      Some(entry.method.to_owned())
+    } else {
+      None
+    }
+  }
+
+  pub fn store(&self, class: ReceiverClass, generation: usize, method: Calcit) {
+    let class = match class {
+      ReceiverClass::Core(name) => CachedClass::Core(name),
+      ReceiverClass::Record(record) => CachedClass::Record(record.to_owned()),
+    };
+    let mut cached = self.0.write().expect("write method cache");
+    *cached = Some(CachedMethod { class, generation, method });
+  }
+}
+
+impl PartialEq for MethodInlineCache {
+  fn eq(&self, _other: &Self) -> bool {
+    true
+  }
+}
+
+impl Eq for MethodInlineCache {}
+
+impl PartialOrd for MethodInlineCache {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for MethodInlineCache {
+  fn cmp(&self, _other: &Self) -> Ordering {
+    Ordering::Equal
+  }
+}
+
+impl Hash for MethodInlineCache {
+  fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
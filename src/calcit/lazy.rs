@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::{Calcit, CalcitList};
+
+static LAZY_SEQ_ID_GEN: AtomicUsize = AtomicUsize::new(0);
+
+/// how items of a lazy sequence are produced, sources and steps on top of other sequences
+#[derive(Debug, Clone)]
+pub enum LazyNode {
+  List(Arc<CalcitList>),
+  /// `to` is `None` for an infinite range
+  Range {
+    from: f64,
+    to: Option<f64>,
+    step: f64,
+  },
+  /// `x`, `(f x)`, `(f (f x))`...
+  Iterate {
+    init: Calcit,
+    f: Calcit,
+  },
+  Repeat(Calcit),
+  /// `f` returns `nil` to stop, or `[] value next-seed`
+  Unfold {
+    seed: Calcit,
+    f: Calcit,
+  },
+  /// file is opened when the sequence is consumed
+  FileLines(Arc<str>),
+  /// lines are taken from stdin when consumed, so it can only be consumed once
+  StdinLines,
+  Map(Arc<CalcitLazySeq>, Calcit),
+  Filter(Arc<CalcitLazySeq>, Calcit),
+  Take(Arc<CalcitLazySeq>, usize),
+  Drop(Arc<CalcitLazySeq>, usize),
+  TakeWhile(Arc<CalcitLazySeq>, Calcit),
+  Concat(Vec<Arc<CalcitLazySeq>>),
+  Zip(Arc<CalcitLazySeq>, Arc<CalcitLazySeq>),
+}
+
+impl LazyNode {
+  pub fn kind_name(&self) -> &'static str {
+    match self {
+      LazyNode::List(_) => "list",
+      LazyNode::Range { .. } => "range",
+      LazyNode::Iterate { .. } => "iterate",
+      LazyNode::Repeat(_) => "repeat",
+      LazyNode::Unfold { .. } => "unfold",
+      LazyNode::FileLines(_) => "file-lines",
+      LazyNode::StdinLines => "stdin-lines",
+      LazyNode::Map(..) => "map",
+      LazyNode::Filter(..) => "filter",
+      LazyNode::Take(..) => "take",
+      LazyNode::Drop(..) => "drop",
+      LazyNode::TakeWhile(..) => "take-while",
+      LazyNode::Concat(_) => "concat",
+      LazyNode::Zip(..) => "zip",
+    }
+  }
+}
+
+/// a lazy sequence only describes how to produce items, nothing is computed until it is consumed.
+/// every consumer starts over from the source, so functions in steps may run again for each consumer
+#[derive(Debug)]
+pub struct CalcitLazySeq {
+  pub id: usize,
+  pub node: LazyNode,
+}
+
+impl CalcitLazySeq {
+  pub fn new(node: LazyNode) -> Self {
+    CalcitLazySeq {
+      id: LAZY_SEQ_ID_GEN.fetch_add(1, Ordering::SeqCst),
+      node,
+    }
+  }
+}
@@ -1,5 +1,7 @@
 use strum_macros::{AsRefStr, EnumString};
 
+use super::CalcitTypeHint;
+
 /// represent builtin functions for performance reasons.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, EnumString, strum_macros::Display, AsRefStr)]
 pub enum CalcitProc {
@@ -30,6 +32,98 @@ pub enum CalcitProc {
   NativeFormatTernaryTree,
   #[strum(serialize = "&buffer")]
   NativeBuffer,
+  #[strum(serialize = "&buffer:count")]
+  NativeBufferCount,
+  #[strum(serialize = "&buffer:nth")]
+  NativeBufferNth,
+  #[strum(serialize = "&buffer:slice")]
+  NativeBufferSlice,
+  #[strum(serialize = "&buffer:concat")]
+  NativeBufferConcat,
+  #[strum(serialize = "&buffer:assoc")]
+  NativeBufferAssoc,
+  #[strum(serialize = "&buffer:find-index")]
+  NativeBufferFindIndex,
+  #[strum(serialize = "&buffer:read")]
+  NativeBufferRead,
+  #[strum(serialize = "&buffer:write")]
+  NativeBufferWrite,
+  #[strum(serialize = "&buffer:to-hex")]
+  NativeBufferToHex,
+  #[strum(serialize = "&buffer:from-hex")]
+  NativeBufferFromHex,
+  #[strum(serialize = "&buffer:to-base64")]
+  NativeBufferToBase64,
+  #[strum(serialize = "&buffer:from-base64")]
+  NativeBufferFromBase64,
+  #[strum(serialize = "&buffer:to-string")]
+  NativeBufferToString,
+  #[strum(serialize = "&buffer:from-string")]
+  NativeBufferFromString,
+  #[strum(serialize = "&buffer:to-list")]
+  NativeBufferToList,
+  #[strum(serialize = "&buffer:from-list")]
+  NativeBufferFromList,
+  // transients
+  #[strum(serialize = "transient")]
+  Transient,
+  #[strum(serialize = "transient?")]
+  IsTransient,
+  #[strum(serialize = "persistent!")]
+  Persistent,
+  #[strum(serialize = "&transient:count")]
+  NativeTransientCount,
+  #[strum(serialize = "&transient:get")]
+  NativeTransientGet,
+  #[strum(serialize = "&transient:append!")]
+  NativeTransientAppend,
+  #[strum(serialize = "&transient:assoc!")]
+  NativeTransientAssoc,
+  #[strum(serialize = "&transient:dissoc!")]
+  NativeTransientDissoc,
+  #[strum(serialize = "&transient:include!")]
+  NativeTransientInclude,
+  #[strum(serialize = "&transient:exclude!")]
+  NativeTransientExclude,
+  // lazy sequences
+  #[strum(serialize = "to-lazy")]
+  ToLazy,
+  #[strum(serialize = "lazy-seq?")]
+  IsLazySeq,
+  #[strum(serialize = "lazy-range")]
+  LazyRange,
+  #[strum(serialize = "lazy-iterate")]
+  LazyIterate,
+  #[strum(serialize = "lazy-repeat")]
+  LazyRepeat,
+  #[strum(serialize = "lazy-unfold")]
+  LazyUnfold,
+  #[strum(serialize = "read-file-lines")]
+  ReadFileLines,
+  #[strum(serialize = "read-stdin-lines")]
+  ReadStdinLines,
+  #[strum(serialize = "&lazy:map")]
+  NativeLazyMap,
+  #[strum(serialize = "&lazy:filter")]
+  NativeLazyFilter,
+  #[strum(serialize = "&lazy:take")]
+  NativeLazyTake,
+  #[strum(serialize = "&lazy:drop")]
+  NativeLazyDrop,
+  #[strum(serialize = "&lazy:take-while")]
+  NativeLazyTakeWhile,
+  #[strum(serialize = "&lazy:concat")]
+  NativeLazyConcat,
+  #[strum(serialize = "&lazy:zip")]
+  NativeLazyZip,
+  #[strum(serialize = "&lazy:to-list")]
+  NativeLazyToList,
+  #[strum(serialize = "&lazy:first")]
+  NativeLazyFirst,
+  #[strum(serialize = "&lazy:empty?")]
+  NativeLazyEmpty,
+  #[strum(serialize = "&lazy:count")]
+  NativeLazyCount,
   #[strum(serialize = "&hash")]
   NativeHash,
   #[strum(serialize = "&extract-code-into-edn")]
@@ -60,6 +154,8 @@ pub enum CalcitProc {
   NativeDisplayStack,
   #[strum(serialize = "raise")]
   Raise,
+  #[strum(serialize = "raise-data")]
+  RaiseData,
   #[strum(serialize = "quit!")]
   Quit,
   #[strum(serialize = "get-env")]
@@ -70,6 +166,32 @@ pub enum CalcitProc {
   ReadFile,
   #[strum(serialize = "write-file")]
   WriteFile,
+  #[strum(serialize = "read-stdin")]
+  ReadStdin,
+  #[strum(serialize = "read-stdin-line")]
+  ReadStdinLine,
+  #[strum(serialize = "each-stdin-line")]
+  EachStdinLine,
+  #[strum(serialize = "read-file-buffer")]
+  ReadFileBuffer,
+  #[strum(serialize = "write-file-buffer")]
+  WriteFileBuffer,
+  #[strum(serialize = "list-dir")]
+  ListDir,
+  #[strum(serialize = "make-dir")]
+  MakeDir,
+  #[strum(serialize = "remove-path")]
+  RemovePath,
+  #[strum(serialize = "rename-path")]
+  RenamePath,
+  #[strum(serialize = "path-exists?")]
+  PathExists,
+  #[strum(serialize = "path-stat")]
+  PathStat,
+  #[strum(serialize = "glob-paths")]
+  GlobPaths,
+  #[strum(serialize = "run-process")]
+  RunProcess,
   /// to detect syntax `&`
   #[strum(serialize = "is-spreading-mark?")]
   IsSpreadingMark,
@@ -84,11 +206,50 @@ pub enum CalcitProc {
   ParseCirruEdn,
   #[strum(serialize = "format-cirru-edn")]
   FormatCirruEdn,
+  #[strum(serialize = "parse-json")]
+  ParseJson,
+  #[strum(serialize = "format-json")]
+  FormatJson,
+  #[strum(serialize = "parse-binary")]
+  ParseBinary,
+  #[strum(serialize = "format-binary")]
+  FormatBinary,
   #[strum(serialize = "&cirru-quote:to-list")]
   NativeCirruQuoteToList,
   // time
   #[strum(serialize = "cpu-time")]
   CpuTime,
+  #[strum(serialize = "now!")]
+  Now,
+  #[strum(serialize = "monotonic-now!")]
+  MonotonicNow,
+  #[strum(serialize = "format-time")]
+  FormatTime,
+  #[strum(serialize = "parse-time")]
+  ParseTime,
+  #[strum(serialize = "time-add")]
+  TimeAdd,
+  #[strum(serialize = "time-diff")]
+  TimeDiff,
+  #[strum(serialize = "time-truncate")]
+  TimeTruncate,
+  // async
+  #[strum(serialize = "set-timeout")]
+  SetTimeout,
+  #[strum(serialize = "set-interval")]
+  SetInterval,
+  #[strum(serialize = "clear-timer")]
+  ClearTimer,
+  #[strum(serialize = "new-promise")]
+  NewPromise,
+  #[strum(serialize = "resolve-promise!")]
+  ResolvePromise,
+  #[strum(serialize = "reject-promise!")]
+  RejectPromise,
+  #[strum(serialize = "promise-then")]
+  PromiseThen,
+  #[strum(serialize = "await")]
+  Await,
   // logics
   #[strum(serialize = "&=")]
   NativeEquals,
@@ -284,6 +445,16 @@ pub enum CalcitProc {
   NativeMapDiffKeys,
   #[strum(serialize = "&map:common-keys")]
   NativeMapCommonKeys,
+  #[strum(serialize = "sorted-map")]
+  SortedMap,
+  #[strum(serialize = "sorted-map?")]
+  IsSortedMap,
+  #[strum(serialize = "&map:first")]
+  NativeMapFirst,
+  #[strum(serialize = "&map:last")]
+  NativeMapLast,
+  #[strum(serialize = "&map:range")]
+  NativeMapRange,
   // sets
   #[strum(serialize = "#{}")]
   Set,
@@ -307,6 +478,16 @@ pub enum CalcitProc {
   NativeSetIncludes,
   #[strum(serialize = "&set:destruct")]
   NativeSetDestruct,
+  #[strum(serialize = "sorted-set")]
+  SortedSet,
+  #[strum(serialize = "sorted-set?")]
+  IsSortedSet,
+  #[strum(serialize = "&set:first")]
+  NativeSetFirst,
+  #[strum(serialize = "&set:last")]
+  NativeSetLast,
+  #[strum(serialize = "&set:range")]
+  NativeSetRange,
   // refs
   #[strum(serialize = "atom")]
   Atom,
@@ -316,6 +497,16 @@ pub enum CalcitProc {
   AddWatch,
   #[strum(serialize = "remove-watch")]
   RemoveWatch,
+  #[strum(serialize = "compare-and-set!")]
+  CompareAndSet,
+  #[strum(serialize = "&atom:swap!")]
+  NativeAtomSwap,
+  #[strum(serialize = "transact!")]
+  Transact,
+  #[strum(serialize = "&refs:registered")]
+  NativeRefsRegistered,
+  #[strum(serialize = "&refs:reset-all!")]
+  NativeRefsResetAll,
   // records
   #[strum(serialize = "new-record")]
   NewRecord,
@@ -333,6 +524,10 @@ pub enum CalcitProc {
   NativeRecordMatches,
   #[strum(serialize = "&record:from-map")]
   NativeRecordFromMap,
+  #[strum(serialize = "&record:with-schema")]
+  NativeRecordWithSchema,
+  #[strum(serialize = "&record:schema")]
+  NativeRecordSchema,
   #[strum(serialize = "&record:get-name")]
   NativeRecordGetName,
   #[strum(serialize = "&record:to-map")]
@@ -347,4 +542,247 @@ pub enum CalcitProc {
   NativeRecordAssoc,
   #[strum(serialize = "&record:extend-as")]
   NativeRecordExtendAs,
+  // protocols
+  #[strum(serialize = "new-protocol")]
+  NewProtocol,
+  #[strum(serialize = "extend-protocol!")]
+  ExtendProtocol,
+  #[strum(serialize = "satisfies?")]
+  Satisfies,
+}
+
+/// types of arguments and the return value of a proc, arguments out of `args` are not checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcTypeSignature {
+  pub args: Vec<CalcitTypeHint>,
+  pub return_type: CalcitTypeHint,
+}
+
+impl CalcitProc {
+  /// signature for static checking. arguments accepting values of different types are `:any`,
+  /// so are return values that depend on arguments
+  pub fn type_signature(&self) -> ProcTypeSignature {
+    use CalcitProc::*;
+    use CalcitTypeHint as T;
+
+    let opt = |t: T| T::optional(t);
+    let (args, return_type) = match self {
+      // meta
+      TypeOf => (vec![T::Any], T::Tag),
+      Recur => (vec![], T::Any),
+      FormatToLisp | FormatToCirru => (vec![T::Any], T::Str),
+      NativeResetGenSymIndex => (vec![], T::Nil),
+      NativeGetCalcitRunningMode => (vec![], T::Tag),
+      GenerateId => (vec![], T::Str),
+      TurnSymbol => (vec![T::Any], T::Symbol),
+      TurnTag => (vec![T::Any], T::Tag),
+      NativeCompare => (vec![T::Any, T::Any], T::Number),
+      NativeGetOs => (vec![], T::Tag),
+      NativeFormatTernaryTree => (vec![T::List], T::Str),
+      NativeBuffer => (vec![], T::Buffer),
+      NativeBufferCount => (vec![T::Buffer], T::Number),
+      NativeBufferNth => (vec![T::Buffer, T::Number], T::Any),
+      NativeBufferSlice => (vec![T::Buffer, T::Number, opt(T::Number)], T::Buffer),
+      NativeBufferConcat => (vec![], T::Buffer),
+      NativeBufferAssoc => (vec![T::Buffer, T::Number, T::Number], T::Buffer),
+      NativeBufferFindIndex => (vec![T::Buffer, T::Any], T::Number),
+      NativeBufferRead => (vec![T::Buffer, T::Tag, T::Number], T::Number),
+      NativeBufferWrite => (vec![T::Buffer, T::Tag, T::Number, T::Number], T::Buffer),
+      NativeBufferToHex => (vec![T::Buffer], T::Str),
+      NativeBufferFromHex => (vec![T::Str], T::Buffer),
+      NativeBufferToBase64 => (vec![T::Buffer], T::Str),
+      NativeBufferFromBase64 => (vec![T::Str], T::Buffer),
+      NativeBufferToString => (vec![T::Buffer], T::Str),
+      NativeBufferFromString => (vec![T::Str], T::Buffer),
+      NativeBufferToList => (vec![T::Buffer], T::List),
+      NativeBufferFromList => (vec![T::List], T::Buffer),
+      // transients
+      Transient => (vec![T::Any], T::Transient),
+      IsTransient => (vec![T::Any], T::Bool),
+      Persistent => (vec![T::Transient], T::Any),
+      NativeTransientCount => (vec![T::Transient], T::Number),
+      NativeTransientGet => (vec![T::Transient, T::Any], T::Any),
+      NativeTransientAppend => (vec![T::Transient, T::Any], T::Transient),
+      NativeTransientAssoc => (vec![T::Transient, T::Any, T::Any], T::Transient),
+      NativeTransientDissoc | NativeTransientInclude | NativeTransientExclude => (vec![T::Transient, T::Any], T::Transient),
+      // lazy sequences
+      ToLazy => (vec![T::Any], T::LazySeq),
+      IsLazySeq => (vec![T::Any], T::Bool),
+      LazyRange => (vec![T::Number, T::Any, T::Number], T::LazySeq),
+      LazyIterate | LazyUnfold => (vec![T::Any, T::Fn], T::LazySeq),
+      LazyRepeat => (vec![T::Any], T::LazySeq),
+      ReadFileLines => (vec![T::Str], T::LazySeq),
+      ReadStdinLines => (vec![], T::LazySeq),
+      NativeLazyMap | NativeLazyFilter | NativeLazyTakeWhile => (vec![T::Any, T::Fn], T::LazySeq),
+      NativeLazyTake | NativeLazyDrop => (vec![T::Any, T::Number], T::LazySeq),
+      NativeLazyConcat => (vec![], T::LazySeq),
+      NativeLazyZip => (vec![T::Any, T::Any], T::LazySeq),
+      NativeLazyToList => (vec![T::Any], T::List),
+      NativeLazyFirst => (vec![T::Any], T::Any),
+      NativeLazyEmpty => (vec![T::Any], T::Bool),
+      NativeLazyCount => (vec![T::Any], T::Number),
+      NativeHash => (vec![T::Any], T::Number),
+      NativeExtractCodeIntoEdn | NativeDataToCode | NativeCirruNth => (vec![], T::Any),
+      NativeCirruType => (vec![T::Any], T::Tag),
+      NativeTuple => (vec![], T::Tuple(None)),
+      NativeClassTuple => (vec![T::Record(None)], T::Tuple(None)),
+      NativeTupleNth => (vec![T::Tuple(None), T::Number], T::Any),
+      NativeTupleAssoc => (vec![T::Tuple(None), T::Number], T::Tuple(None)),
+      NativeTupleCount => (vec![T::Tuple(None)], T::Number),
+      NativeTupleClass => (vec![T::Tuple(None)], T::Any),
+      NativeTupleParams => (vec![T::Tuple(None)], T::List),
+      NativeTupleWithClass => (vec![T::Tuple(None), T::Record(None)], T::Tuple(None)),
+      NativeDisplayStack => (vec![], T::Nil),
+      Raise | Quit => (vec![], T::Any),
+      RaiseData => (vec![T::Any, T::Any], T::Any),
+      GetEnv => (vec![T::Str], T::Any),
+      NativeGetCalcitBackend => (vec![], T::Tag),
+      ReadFile => (vec![T::Str], T::Str),
+      WriteFile => (vec![T::Str, T::Str], T::Nil),
+      ReadStdin => (vec![], T::Str),
+      ReadStdinLine => (vec![], opt(T::Str)),
+      EachStdinLine => (vec![T::Fn], T::Nil),
+      ReadFileBuffer => (vec![T::Str], T::Buffer),
+      WriteFileBuffer => (vec![T::Str, T::Buffer], T::Nil),
+      ListDir | GlobPaths => (vec![T::Str], T::List),
+      MakeDir | RemovePath => (vec![T::Str], T::Nil),
+      RenamePath => (vec![T::Str, T::Str], T::Nil),
+      PathExists => (vec![T::Str], T::Bool),
+      PathStat => (vec![T::Str], T::Map),
+      RunProcess => (vec![T::Str, opt(T::List), opt(T::Map)], T::Map),
+      IsSpreadingMark => (vec![T::Any], T::Bool),
+      // external format
+      ParseCirru | ParseCirruList => (vec![T::Str], T::List),
+      FormatCirru | FormatCirruEdn | FormatJson => (vec![T::Any], T::Str),
+      ParseCirruEdn | ParseJson => (vec![T::Str], T::Any),
+      FormatBinary => (vec![T::Any], T::Buffer),
+      ParseBinary => (vec![T::Buffer], T::Any),
+      NativeCirruQuoteToList => (vec![T::Any], T::List),
+      // time
+      CpuTime | Now | MonotonicNow => (vec![], T::Number),
+      FormatTime => (vec![T::Number], T::Str),
+      ParseTime => (vec![T::Str], T::Number),
+      TimeAdd => (vec![T::Number, T::Number], T::Number),
+      TimeDiff => (vec![T::Number, T::Number], T::Number),
+      TimeTruncate => (vec![T::Number], T::Number),
+      // async
+      SetTimeout | SetInterval => (vec![T::Number, T::Fn], T::Number),
+      ClearTimer => (vec![T::Number], T::Nil),
+      NewPromise => (vec![], T::Promise),
+      ResolvePromise | RejectPromise => (vec![T::Promise, T::Any], T::Nil),
+      PromiseThen => (vec![T::Promise, T::Fn, opt(T::Fn)], T::Promise),
+      Await => (vec![T::Any], T::Any),
+      // logics
+      NativeEquals | NativeLessThan | NativeGreaterThan | Identical => (vec![T::Any, T::Any], T::Bool),
+      Not => (vec![opt(T::Bool)], T::Bool),
+      // math
+      NativeAdd | NativeMinus | NativeMultiply | NativeDivide | Pow | NativeNumberRem => (vec![T::Number, T::Number], T::Number),
+      Round | Floor | Sin | Cos | Ceil | Sqrt | NativeNumberFract => (vec![T::Number], T::Number),
+      IsRound => (vec![T::Number], T::Bool),
+      NativeNumberFormat | NativeNumberDisplayBy => (vec![T::Number, T::Number], T::Str),
+      BitShl | BitShr | BitAnd | BitOr | BitXor => (vec![T::Number, T::Number], T::Number),
+      BitNot => (vec![T::Number], T::Number),
+      // strings
+      NativeStrConcat => (vec![T::Any, T::Any], T::Str),
+      Trim | NativeStrEscape => (vec![T::Str], T::Str),
+      NativeStr | TurnString | PrStr => (vec![T::Any], T::Str),
+      Split => (vec![T::Str, T::Str], T::List),
+      SplitLines => (vec![T::Str], T::List),
+      StartsWith => (vec![T::Any, T::Any], T::Bool),
+      EndsWith | NativeStrIncludes => (vec![T::Str, T::Str], T::Bool),
+      GetCharCode => (vec![T::Str], T::Number),
+      CharFromCode => (vec![T::Number], T::Str),
+      ParseFloat => (vec![T::Str], T::Any),
+      IsBlank => (vec![T::Any], T::Bool),
+      NativeStrCompare | NativeStrFindIndex => (vec![T::Str, T::Str], T::Number),
+      NativeStrReplace => (vec![T::Str, T::Str, T::Str], T::Str),
+      NativeStrSlice => (vec![T::Str, T::Number], T::Str),
+      NativeStrCount => (vec![T::Str], T::Number),
+      NativeStrEmpty => (vec![T::Str], T::Bool),
+      NativeStrContains => (vec![T::Str, T::Number], T::Bool),
+      NativeStrNth => (vec![opt(T::Str), T::Number], T::Any),
+      NativeStrFirst => (vec![opt(T::Str)], T::Any),
+      NativeStrRest => (vec![T::Str], T::Str),
+      NativeStrPadLeft | NativeStrPadRight => (vec![T::Str, T::Number, T::Str], T::Str),
+      // lists
+      List => (vec![], T::List),
+      Append | Prepend => (vec![T::List, T::Any], T::List),
+      Butlast | NativeListReverse | NativeListRest => (vec![opt(T::List)], T::Any),
+      Range => (vec![T::Number], T::List),
+      Sort => (vec![T::List, T::Fn], T::List),
+      Foldl | FoldlShortcut | FoldrShortcut => (vec![], T::Any),
+      NativeListConcat => (vec![], T::List),
+      NativeListCount => (vec![T::List], T::Number),
+      NativeListEmpty => (vec![T::List], T::Bool),
+      NativeListSlice | NativeListAssocBefore | NativeListAssocAfter | NativeListAssoc | NativeListDissoc => {
+        (vec![T::List, T::Number], T::List)
+      }
+      NativeListContains => (vec![T::List, T::Number], T::Bool),
+      NativeListIncludes => (vec![T::List, T::Any], T::Bool),
+      NativeListNth => (vec![opt(T::List), T::Number], T::Any),
+      NativeListFirst => (vec![opt(T::List)], T::Any),
+      NativeListToSet => (vec![T::List], T::Set),
+      NativeListDistinct => (vec![T::List], T::List),
+      // maps
+      NativeMap => (vec![], T::Map),
+      NativeMerge | NativeMergeNonNil | ToPairs => (vec![], T::Any),
+      NativeMapGet => (vec![opt(T::Map), T::Any], T::Any),
+      NativeMapDissoc | NativeMapAssoc => (vec![T::Map], T::Map),
+      NativeMapToList => (vec![T::Map], T::List),
+      NativeMapCount => (vec![T::Map], T::Number),
+      NativeMapEmpty => (vec![T::Map], T::Bool),
+      NativeMapContains | NativeMapIncludes => (vec![T::Map, T::Any], T::Bool),
+      NativeMapDestruct => (vec![opt(T::Map)], T::Any),
+      NativeMapDiffNew => (vec![T::Map, T::Map], T::Map),
+      NativeMapDiffKeys | NativeMapCommonKeys => (vec![T::Map, T::Map], T::Set),
+      SortedMap => (vec![], T::Map),
+      IsSortedMap => (vec![T::Any], T::Bool),
+      NativeMapFirst | NativeMapLast => (vec![T::Map], T::Any),
+      NativeMapRange => (vec![T::Map, T::Any, T::Any], T::Map),
+      // sets
+      Set => (vec![], T::Set),
+      NativeInclude | NativeExclude => (vec![T::Set], T::Set),
+      NativeDifference | NativeUnion | NativeSetIntersection => (vec![T::Set, T::Set], T::Set),
+      NativeSetToList => (vec![T::Set], T::List),
+      NativeSetCount => (vec![T::Set], T::Number),
+      NativeSetEmpty => (vec![T::Set], T::Bool),
+      NativeSetIncludes => (vec![T::Set, T::Any], T::Bool),
+      NativeSetDestruct => (vec![opt(T::Set)], T::Any),
+      SortedSet => (vec![], T::Set),
+      IsSortedSet => (vec![T::Any], T::Bool),
+      NativeSetFirst | NativeSetLast => (vec![T::Set], T::Any),
+      NativeSetRange => (vec![T::Set, T::Any, T::Any], T::Set),
+      // refs
+      Atom => (vec![T::Any], T::Ref),
+      AtomDeref => (vec![T::Ref], T::Any),
+      AddWatch => (vec![T::Ref, T::Any, T::Fn], T::Nil),
+      RemoveWatch => (vec![T::Ref, T::Any], T::Nil),
+      CompareAndSet => (vec![T::Ref, T::Any, T::Any], T::Bool),
+      NativeAtomSwap => (vec![T::Ref, T::Any], T::Any),
+      Transact => (vec![T::List, T::Any], T::List),
+      NativeRefsRegistered => (vec![], T::List),
+      NativeRefsResetAll => (vec![], T::Nil),
+      // records
+      NewRecord => (vec![T::Any], T::Record(None)),
+      NewClassRecord => (vec![T::Record(None), T::Any], T::Record(None)),
+      NativeRecord | NativeRecordWith | NativeRecordAssoc => (vec![T::Record(None)], T::Record(None)),
+      NativeRecordClass => (vec![T::Record(None)], T::Any),
+      NativeRecordWithClass => (vec![T::Record(None), T::Record(None)], T::Record(None)),
+      NativeRecordMatches => (vec![T::Record(None), T::Record(None)], T::Bool),
+      NativeRecordFromMap => (vec![T::Record(None), T::Map], T::Record(None)),
+      NativeRecordWithSchema => (vec![T::Record(None), T::Map], T::Record(None)),
+      NativeRecordSchema => (vec![T::Record(None)], opt(T::Map)),
+      NativeRecordGetName => (vec![T::Record(None)], T::Tag),
+      NativeRecordToMap => (vec![T::Record(None)], T::Map),
+      NativeRecordCount => (vec![T::Record(None)], T::Number),
+      NativeRecordContains => (vec![T::Record(None), T::Any], T::Bool),
+      NativeRecordGet => (vec![opt(T::Record(None)), T::Any], T::Any),
+      NativeRecordExtendAs => (vec![T::Record(None)], T::Record(None)),
+      // protocols
+      NewProtocol => (vec![T::Tag], T::Record(None)),
+      ExtendProtocol => (vec![T::Record(None), T::Any, T::Map], T::Nil),
+      Satisfies => (vec![T::Record(None), T::Any], T::Bool),
+    };
+    ProcTypeSignature { args, return_type }
+  }
 }
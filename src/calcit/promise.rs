@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::call_stack::CallStackList;
+
+use super::Calcit;
+
+static PROMISE_ID_GEN: AtomicUsize = AtomicUsize::new(0);
+
+/// handlers attached by `promise-then`, the derived promise takes result of the handler
+#[derive(Debug, Clone)]
+pub struct PromiseReaction {
+  pub on_fulfilled: Option<Calcit>,
+  pub on_rejected: Option<Calcit>,
+  pub derived: Arc<CalcitPromise>,
+  pub call_stack: CallStackList,
+}
+
+#[derive(Debug, Clone)]
+pub enum PromiseState {
+  Pending(Vec<PromiseReaction>),
+  Fulfilled(Calcit),
+  Rejected(Calcit),
+}
+
+/// promise-like value settled by the event loop, like a `Promise` in JavaScript
+#[derive(Debug)]
+pub struct CalcitPromise {
+  pub id: usize,
+  pub state: Mutex<PromiseState>,
+}
+
+impl Default for CalcitPromise {
+  fn default() -> Self {
+    CalcitPromise {
+      id: PROMISE_ID_GEN.fetch_add(1, Ordering::SeqCst),
+      state: Mutex::new(PromiseState::Pending(vec![])),
+    }
+  }
+}
+
+impl CalcitPromise {
+  /// tag for displaying, `:pending`, `:fulfilled` or `:rejected`
+  pub fn status_name(&self) -> &'static str {
+    match &*self.state.lock().expect("read promise") {
+      PromiseState::Pending(_) => "pending",
+      PromiseState::Fulfilled(_) => "fulfilled",
+      PromiseState::Rejected(_) => "rejected",
+    }
+  }
+
+  /// settled value, `Err` for rejected ones, `None` when still pending
+  pub fn settled(&self) -> Option<Result<Calcit, Calcit>> {
+    match &*self.state.lock().expect("read promise") {
+      PromiseState::Pending(_) => None,
+      PromiseState::Fulfilled(v) => Some(Ok(v.to_owned())),
+      PromiseState::Rejected(e) => Some(Err(e.to_owned())),
+    }
+  }
+}
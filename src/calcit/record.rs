@@ -10,8 +10,25 @@ pub struct CalcitRecord {
   pub fields: Arc<Vec<EdnTag>>,
   pub values: Arc<Vec<Calcit>>,
   pub class: Option<Arc<CalcitRecord>>,
+  /// how fields are filled and checked when constructing, records built from a definition share it
+  pub schema: Option<Arc<RecordSchema>>,
 }
 
+/// rules of a field when constructing a record
+#[derive(Debug, Clone, Default)]
+pub struct RecordFieldSchema {
+  /// filled in when the field is missing
+  pub default: Option<Calcit>,
+  /// missing field is filled with `nil`
+  pub optional: bool,
+  /// called with the value, a falsy result marks the value as invalid
+  pub validator: Option<Calcit>,
+}
+
+/// schema of a record definition, holding rules in the same order as `fields`
+#[derive(Debug, Clone, Default)]
+pub struct RecordSchema(pub Vec<RecordFieldSchema>);
+
 impl PartialEq for CalcitRecord {
   fn eq(&self, other: &Self) -> bool {
     self.name == other.name && self.fields == other.fields && self.values == other.values
@@ -27,6 +44,7 @@ impl Default for CalcitRecord {
       fields: Arc::new(vec![]),
       values: Arc::new(vec![]),
       class: None,
+      schema: None,
     }
   }
 }
@@ -110,6 +128,8 @@ impl CalcitRecord {
       fields: Arc::new(next_fields),
       values: Arc::new(next_values),
       class: self.class.to_owned(),
+      // rules are kept in order of fields, a record with a new field does not reuse them
+      schema: None,
     })
   }
 }
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::Calcit;
+
+static TRANSIENT_ID_GEN: AtomicUsize = AtomicUsize::new(0);
+
+/// data inside a transient, collections are mutated in place until `persistent!` is called
+#[derive(Debug, Clone)]
+pub enum TransientState {
+  List(Vec<Calcit>),
+  Map(rpds::HashTrieMapSync<Calcit, Calcit>),
+  Set(rpds::HashTrieSetSync<Calcit>),
+  SortedMap(rpds::RedBlackTreeMapSync<Calcit, Calcit>),
+  SortedSet(rpds::RedBlackTreeSetSync<Calcit>),
+  /// already turned back into persistent data, not usable anymore
+  Frozen,
+}
+
+impl TransientState {
+  /// `:list`, `:map` or `:set`, sorted collections are still maps and sets
+  pub fn kind_name(&self) -> &'static str {
+    match self {
+      TransientState::List(_) => "list",
+      TransientState::Map(_) | TransientState::SortedMap(_) => "map",
+      TransientState::Set(_) | TransientState::SortedSet(_) => "set",
+      TransientState::Frozen => "frozen",
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      TransientState::List(xs) => xs.len(),
+      TransientState::Map(xs) => xs.size(),
+      TransientState::Set(xs) => xs.size(),
+      TransientState::SortedMap(xs) => xs.size(),
+      TransientState::SortedSet(xs) => xs.size(),
+      TransientState::Frozen => 0,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// mutable builder for a list, a map or a set, created with `transient` and frozen with `persistent!`.
+/// rpds nodes are copied on the first write and then updated in place since they are not shared anymore
+#[derive(Debug)]
+pub struct CalcitTransient {
+  pub id: usize,
+  pub state: Mutex<TransientState>,
+}
+
+impl CalcitTransient {
+  /// `None` for data that has no transient form
+  pub fn from_data(x: &Calcit) -> Option<Self> {
+    let state = match x {
+      Calcit::List(xs) => TransientState::List(xs.to_vec()),
+      Calcit::Map(xs) => TransientState::Map(xs.to_owned()),
+      Calcit::Set(xs) => TransientState::Set(xs.to_owned()),
+      Calcit::SortedMap(xs) => TransientState::SortedMap(xs.to_owned()),
+      Calcit::SortedSet(xs) => TransientState::SortedSet(xs.to_owned()),
+      _ => return None,
+    };
+    Some(CalcitTransient {
+      id: TRANSIENT_ID_GEN.fetch_add(1, Ordering::SeqCst),
+      state: Mutex::new(state),
+    })
+  }
+
+  /// takes data out and leaves the transient frozen, `None` if it was already frozen
+  pub fn freeze(&self) -> Option<Calcit> {
+    let mut state = self.state.lock().expect("write transient");
+    match std::mem::replace(&mut *state, TransientState::Frozen) {
+      TransientState::List(xs) => Some(Calcit::from(xs)),
+      TransientState::Map(xs) => Some(Calcit::Map(xs)),
+      TransientState::Set(xs) => Some(Calcit::Set(xs)),
+      TransientState::SortedMap(xs) => Some(Calcit::SortedMap(xs)),
+      TransientState::SortedSet(xs) => Some(Calcit::SortedSet(xs)),
+      TransientState::Frozen => None,
+    }
+  }
+}
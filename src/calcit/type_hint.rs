@@ -0,0 +1,138 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// fields of a record known from code without evaluating it.
+/// classes are records too, their fields are names of methods
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordShape {
+  pub name: Arc<str>,
+  pub fields: Vec<Arc<str>>,
+  /// class of the record, `None` when not known
+  pub class: Option<Arc<RecordShape>>,
+}
+
+impl RecordShape {
+  pub fn has_field(&self, name: &str) -> bool {
+    self.fields.iter().any(|f| &**f == name)
+  }
+}
+
+/// type from hints in `hint-fn`, or inferred during static checking.
+/// types are never checked at runtime, unknown types are `Any` and accepted everywhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalcitTypeHint {
+  Any,
+  Nil,
+  Bool,
+  Number,
+  Str,
+  Tag,
+  Symbol,
+  List,
+  Map,
+  Set,
+  Fn,
+  Buffer,
+  Ref,
+  Promise,
+  Transient,
+  LazySeq,
+  /// with shape of its class when known
+  Tuple(Option<Arc<RecordShape>>),
+  Record(Option<Arc<RecordShape>>),
+  /// `nil` or a value of the type
+  Optional(Box<CalcitTypeHint>),
+}
+
+impl CalcitTypeHint {
+  /// from tags like `:number`, types with parameters are not included
+  pub fn from_name(name: &str) -> Option<Self> {
+    let t = match name {
+      "any" => Self::Any,
+      "nil" => Self::Nil,
+      "bool" => Self::Bool,
+      "number" => Self::Number,
+      "string" => Self::Str,
+      "tag" => Self::Tag,
+      "symbol" => Self::Symbol,
+      "list" => Self::List,
+      "map" => Self::Map,
+      "set" => Self::Set,
+      "fn" => Self::Fn,
+      "buffer" => Self::Buffer,
+      "ref" => Self::Ref,
+      "promise" => Self::Promise,
+      "transient" => Self::Transient,
+      "lazy-seq" => Self::LazySeq,
+      "tuple" => Self::Tuple(None),
+      "record" => Self::Record(None),
+      _ => return None,
+    };
+    Some(t)
+  }
+
+  pub fn optional(t: Self) -> Self {
+    match t {
+      Self::Any | Self::Nil | Self::Optional(_) => t,
+      _ => Self::Optional(Box::new(t)),
+    }
+  }
+
+  /// whether a value of type `actual` can be passed where `self` is expected.
+  /// optional values are accepted for non-optional types since checking for `nil` is not tracked
+  pub fn accepts(&self, actual: &Self) -> bool {
+    match (self, actual) {
+      (Self::Any, _) | (_, Self::Any) => true,
+      (Self::Optional(_), Self::Nil) => true,
+      (Self::Optional(t), a) => t.accepts(a),
+      (t, Self::Optional(a)) => t.accepts(a),
+      (Self::Record(Some(a)), Self::Record(Some(b))) => a.name == b.name,
+      (Self::Record(_), Self::Record(_)) => true,
+      (Self::Tuple(_), Self::Tuple(_)) => true,
+      (a, b) => a == b,
+    }
+  }
+
+  /// type of a value that comes from either branch
+  pub fn union(self, other: Self) -> Self {
+    if self == other {
+      self
+    } else {
+      match (self, other) {
+        (Self::Nil, t) | (t, Self::Nil) => Self::optional(t),
+        (Self::Optional(a), b) | (b, Self::Optional(a)) if *a == b => Self::Optional(a),
+        (Self::Record(_), Self::Record(_)) => Self::Record(None),
+        (Self::Tuple(_), Self::Tuple(_)) => Self::Tuple(None),
+        _ => Self::Any,
+      }
+    }
+  }
+}
+
+impl fmt::Display for CalcitTypeHint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Any => f.write_str(":any"),
+      Self::Nil => f.write_str(":nil"),
+      Self::Bool => f.write_str(":bool"),
+      Self::Number => f.write_str(":number"),
+      Self::Str => f.write_str(":string"),
+      Self::Tag => f.write_str(":tag"),
+      Self::Symbol => f.write_str(":symbol"),
+      Self::List => f.write_str(":list"),
+      Self::Map => f.write_str(":map"),
+      Self::Set => f.write_str(":set"),
+      Self::Fn => f.write_str(":fn"),
+      Self::Buffer => f.write_str(":buffer"),
+      Self::Ref => f.write_str(":ref"),
+      Self::Promise => f.write_str(":promise"),
+      Self::Transient => f.write_str(":transient"),
+      Self::LazySeq => f.write_str(":lazy-seq"),
+      Self::Tuple(None) => f.write_str(":tuple"),
+      Self::Tuple(Some(class)) => write!(f, "(:tuple {})", class.name),
+      Self::Record(None) => f.write_str(":record"),
+      Self::Record(Some(shape)) => write!(f, "(:record {})", shape.name),
+      Self::Optional(t) => write!(f, "(:optional {t})"),
+    }
+  }
+}
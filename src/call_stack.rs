@@ -1,11 +1,5 @@
 use crate::calcit::Calcit;
-use crate::calcit::NodeLocation;
-use crate::data::cirru;
-use crate::data::edn;
-use cirru_edn::Edn;
-use cirru_edn::EdnListView;
 use std::fmt;
-use std::fs;
 use std::hash::Hash;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
@@ -103,49 +97,3 @@ pub fn show_stack(stack: &CallStackList) {
     println!("  {}/{}{}", s.ns, s.def, if is_macro { "\t ~macro" } else { "" });
   }
 }
-
-pub fn display_stack(failure: &str, stack: &CallStackList, location: Option<&Arc<NodeLocation>>) -> Result<(), String> {
-  eprintln!("\nFailure: {failure}");
-  eprintln!("\ncall stack:");
-
-  for s in &stack.0 {
-    let is_macro = s.kind == StackKind::Macro;
-    eprintln!("  {}/{}{}", s.ns, s.def, if is_macro { "\t ~macro" } else { "" });
-  }
-
-  let mut stack_list = EdnListView::default();
-  for s in &stack.0 {
-    let mut args = EdnListView::default();
-    for v in s.args.iter() {
-      args.push(edn::calcit_to_edn(v)?);
-    }
-    let info = Edn::map_from_iter([
-      (Edn::tag("def"), format!("{}/{}", s.ns, s.def).into()),
-      (Edn::tag("code"), cirru::calcit_to_cirru(&s.code)?.into()),
-      (Edn::tag("args"), args.into()),
-      (Edn::tag("kind"), Edn::tag(s.kind.to_string())),
-    ]);
-
-    stack_list.push(info);
-  }
-
-  let content = cirru_edn::format(
-    &Edn::map_from_iter([
-      (Edn::tag("message"), failure.into()),
-      (Edn::tag("stack"), stack_list.into()),
-      (
-        Edn::tag("location"),
-        match location {
-          Some(l) => (&**l).into(),
-          None => Edn::Nil,
-        },
-      ),
-    ]),
-    true,
-  )?;
-  let _ = fs::write(ERROR_SNAPSHOT, content);
-  eprintln!("\nrun `cat {ERROR_SNAPSHOT}` to read stack details.");
-  Ok(())
-}
-
-const ERROR_SNAPSHOT: &str = ".calcit-error.cirru";
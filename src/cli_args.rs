@@ -34,6 +34,15 @@ pub struct ToplevelCalcit {
   #[argh(option)]
   /// specify a path to watch assets changes
   pub watch_dir: Option<String>,
+  /// format of error reports, `cirru` or `json`, defaults to `cirru`
+  #[argh(option, default = "String::from(\"cirru\")")]
+  pub error_format: String,
+  /// path of error reports, defaults to `.calcit-error.cirru` or `.calcit-error.json`
+  #[argh(option)]
+  pub error_path: Option<String>,
+  /// path of a Unix socket to listen on in watch mode, for reading and resetting refs, evaluating code and reloading
+  #[argh(option)]
+  pub inspect: Option<String>,
   /// input source file, defaults to "compact.cirru"
   #[argh(positional, default = "String::from(\"compact.cirru\")")]
   pub input: String,
@@ -48,6 +57,12 @@ pub enum CalcitCommand {
   EmitIr(EmitIrCommand),
   /// evaluate snippet
   Eval(EvalCommand),
+  /// check all definitions of the program without running it
+  Check(CheckCommand),
+  /// export dependency graph of namespaces or defs
+  Deps(DepsCommand),
+  /// show code of a def after macros expanded and symbols resolved
+  Expand(ExpandCommand),
 }
 
 /// emit JavaScript rather than interpreting
@@ -79,3 +94,64 @@ pub struct EvalCommand {
   #[argh(option)]
   pub dep: Vec<String>,
 }
+
+/// check all definitions of the program without running it
+#[derive(FromArgs, PartialEq, Debug, Clone)]
+#[argh(subcommand, name = "check")]
+pub struct CheckCommand {
+  /// report format, `human` or `json`, defaults to `human`
+  #[argh(option, default = "String::from(\"human\")")]
+  pub format: String,
+}
+
+/// export dependency graph of namespaces or defs
+#[derive(FromArgs, PartialEq, Debug, Clone)]
+#[argh(subcommand, name = "deps")]
+pub struct DepsCommand {
+  /// output format, `human`, `dot` or `json`, defaults to `human`
+  #[argh(option, default = "String::from(\"human\")")]
+  pub format: String,
+  /// nodes of the graph, `ns` or `def`, defaults to `ns`
+  #[argh(option, default = "String::from(\"ns\")")]
+  pub level: String,
+  /// only namespaces in the entry file, without modules
+  #[argh(switch)]
+  pub app_only: bool,
+}
+
+/// show code of a def after macros expanded and symbols resolved
+#[derive(FromArgs, PartialEq, Debug, Clone)]
+#[argh(subcommand, name = "expand")]
+pub struct ExpandCommand {
+  /// def to expand, in `ns/def`
+  #[argh(positional)]
+  pub target: String,
+  /// also show each application of macros, with the macro and the code it returns
+  #[argh(switch)]
+  pub steps: bool,
+}
+
+/// parse arguments of `cr` from env. arguments after `--` are not parsed,
+/// they are returned to be passed to the init function, `None` if there's no `--`
+pub fn parse_cli_args() -> (ToplevelCalcit, Option<Vec<String>>) {
+  let strings: Vec<String> = std::env::args().collect();
+  let (own, trailing) = match strings.iter().position(|s| s == "--") {
+    Some(idx) => (&strings[..idx], Some(strings[idx + 1..].to_vec())),
+    None => (&strings[..], None),
+  };
+  let cmd = own.first().map(|s| s.as_str()).unwrap_or("cr");
+  let strs: Vec<&str> = own.iter().skip(1).map(|s| s.as_str()).collect();
+  match ToplevelCalcit::from_args(&[cmd], &strs) {
+    Ok(args) => (args, trailing),
+    Err(early_exit) => std::process::exit(match early_exit.status {
+      Ok(()) => {
+        println!("{}", early_exit.output);
+        0
+      }
+      Err(()) => {
+        eprintln!("{}\nRun {cmd} --help for more information.", early_exit.output);
+        1
+      }
+    }),
+  }
+}
@@ -140,6 +140,7 @@ fn escape_cirru_str(s: &str) -> String {
       '\\' => result.push_str("\\\\"),
       '\"' => result.push_str("\\\""),
       '\n' => result.push_str("\\n"),
+      '\r' => result.push_str("\\r"),
       '\t' => result.push_str("\\t"),
       _ => result.push(c),
     }
@@ -178,7 +179,7 @@ fn quote_to_js(xs: &Calcit, var_prefix: &str, tags: &RefCell<HashSet<EdnTag>>) -
       let code = match kind {
         MethodKind::Access => ".-",
         MethodKind::InvokeNative => ".!",
-        MethodKind::Invoke => ".",
+        MethodKind::Invoke(_) => ".",
         MethodKind::AccessOptional => ".?-",
         MethodKind::InvokeNativeOptional => ".?!",
       };
@@ -266,7 +267,7 @@ fn to_js_code(
       }
       Calcit::Method(name, kind) => {
         let proc_prefix = get_proc_prefix(ns);
-        if *kind == MethodKind::Invoke {
+        if matches!(kind, MethodKind::Invoke(_)) {
           Ok(format!("{proc_prefix}invoke_method_closure({})", escape_cirru_str(name)))
         } else {
           Err(format!("Does not expect native method as closure: {kind}"))
@@ -400,7 +401,7 @@ fn gen_call_code(
             let handler = to_js_code(handler, ns, local_defs, file_imports, tags, None)?;
 
             gen_stack::pop_call_stack();
-            let code = snippets::tmpl_try(err_var, try_code, handler, next_return_label);
+            let code = snippets::tmpl_try(err_var, try_code, handler, next_return_label, get_proc_prefix(ns));
             match return_label {
               Some(_) => Ok(code),
               None => Ok(snippets::tmpl_fn_wrapper(code)),
@@ -423,28 +424,25 @@ fn gen_call_code(
     }
     Calcit::Proc(CalcitProc::Raise) => {
       // not core syntax, but treat as macro for better debugging experience
-      match body.first() {
-        Some(m) => {
-          let message: String = to_js_code(m, ns, local_defs, file_imports, tags, None)?;
-          let has_await = detect_await(&body);
-          let data_code = match body.get(1) {
-            Some(d) => to_js_code(d, ns, local_defs, file_imports, tags, None)?,
-            None => String::from("null"),
-          };
-          let err_var = js_gensym("err");
-          let ret = format!(
-            "let {} = new Error({});\n {}.data = {};\n throw {};",
-            err_var, message, err_var, data_code, err_var
-          );
-          // println!("inside raise: {:?} {}", return_label, xs);
-          match return_label {
-            Some(_) => Ok(ret),
-            _ => Ok(make_fn_wrapper(&ret, has_await)),
-          }
-        }
-        None => Err(format!("raise expected 1~2 arguments, got: {}", body)),
+      let has_await = detect_await(&body);
+      let args_code = gen_args_code(&body, ns, local_defs, file_imports, tags)?;
+      // message, payload and rethrowing are handled in `make_error`, same as `raise` in Rust
+      let ret = format!("throw {}make_error({args_code});", get_proc_prefix(ns));
+      // println!("inside raise: {:?} {}", return_label, xs);
+      match return_label {
+        Some(_) => Ok(ret),
+        _ => Ok(make_fn_wrapper(&ret, has_await)),
       }
     }
+    Calcit::Proc(CalcitProc::Await) => match body.first() {
+      // `await` is a keyword in JavaScript, the function is expected to be hinted with `async`
+      Some(v) if body.len() == 1 => Ok(format!(
+        "{}(await {})",
+        return_code,
+        to_js_code(v, ns, local_defs, file_imports, tags, None)?
+      )),
+      _ => Err(format!("await expected 1 argument, got: {}", body)),
+    },
     Calcit::Proc(_) => {
       let args_code = gen_args_code(&body, ns, local_defs, file_imports, tags)?;
       Ok(format!(
@@ -587,7 +585,7 @@ fn gen_call_code(
           Err(format!("invoke-native-optional expected at least 1 object, got: {xs}"))
         }
       }
-      MethodKind::Invoke => {
+      MethodKind::Invoke(_) => {
         let proc_prefix = get_proc_prefix(ns);
         if !body.is_empty() {
           let obj = to_js_code(&body[0], ns, local_defs, file_imports, tags, None)?;
@@ -668,10 +666,11 @@ fn detect_await(xs: &CalcitList) -> bool {
           return true;
         }
       }
-      Calcit::Symbol { sym, .. } => {
-        if &**sym == "js-await" {
-          return true;
-        }
+      Calcit::Symbol { sym, .. } if &**sym == "js-await" => {
+        return true;
+      }
+      Calcit::Proc(CalcitProc::Await) => {
+        return true;
       }
       _ => {}
     }
@@ -1253,9 +1252,6 @@ pub fn emit_js(entry_ns: &str, emit_path: &str) -> Result<(), String> {
     // remember defs of each ns for comparing
     internal_states::write_as_ns_cache(&ns, defs_in_current);
 
-    // reset index each file
-    reset_js_gensym_index();
-
     let core_lib = to_js_import_name("calcit.core", true);
 
     let mut defs_code = String::from(""); // code generated by functions
@@ -1280,6 +1276,9 @@ pub fn emit_js(entry_ns: &str, emit_path: &str) -> Result<(), String> {
     // println!("deps order: {:?}", deps_in_order);
 
     for def in deps_in_order {
+      // reset index each def
+      reset_js_gensym_index();
+
       if &*ns == calcit::CORE_NS {
         // some defs from core can be replaced by calcit.procs
         if is_js_unavailable_procs(&def) {
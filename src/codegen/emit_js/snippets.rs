@@ -2,14 +2,15 @@ use crate::{builtins::meta::js_gensym, codegen::emit_js::get_proc_prefix};
 
 pub const CALCIT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub fn tmpl_try(err_var: String, body: String, handler: String, return_code: &str) -> String {
+/// handler receives an error record converted by `to_error_record`
+pub fn tmpl_try(err_var: String, body: String, handler: String, return_code: &str, proc_prefix: &str) -> String {
   format!(
     "try {{
   {}
 }} catch ({}) {{
-  {} ({})({})
+  {} ({})({}to_error_record({}))
 }}",
-    body, err_var, return_code, handler, err_var,
+    body, err_var, return_code, handler, proc_prefix, err_var,
   )
 }
 
@@ -127,6 +128,9 @@ $calcit_procs.register_calcit_builtin_classes({{
   string: _$n_core_string_class,
   nil: _$n_core_nil_class,
   fn: _$n_core_fn_class,
+  buffer: _$n_core_buffer_class,
+  transient: _$n_core_transient_class,
+  lazy_seq: _$n_core_lazy_seq_class,
 }});
 
 let runtimeVersion = $calcit_procs.calcit_version;
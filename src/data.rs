@@ -5,8 +5,10 @@ use crate::{
   Calcit,
 };
 
+pub mod binary;
 pub mod cirru;
 pub mod edn;
+pub mod json;
 
 pub fn data_to_calcit(x: &Calcit, ns: &str, at_def: &str) -> Result<Calcit, String> {
   match x {
@@ -64,6 +66,21 @@ pub fn data_to_calcit(x: &Calcit, ns: &str, at_def: &str) -> Result<Calcit, Stri
       }
       Ok(Calcit::from(ys))
     }
+    Calcit::SortedSet(xs) => {
+      let mut ys = vec![Calcit::Proc(CalcitProc::SortedSet)];
+      for x in xs {
+        ys.push(data_to_calcit(x, ns, at_def)?);
+      }
+      Ok(Calcit::from(ys))
+    }
+    Calcit::SortedMap(xs) => {
+      let mut ys = vec![Calcit::Proc(CalcitProc::SortedMap)];
+      for (k, v) in xs {
+        ys.push(data_to_calcit(k, ns, at_def)?);
+        ys.push(data_to_calcit(v, ns, at_def)?);
+      }
+      Ok(Calcit::from(ys))
+    }
     Calcit::Record(CalcitRecord {
       name: tag, fields, values, ..
     }) => {
@@ -88,6 +105,9 @@ pub fn data_to_calcit(x: &Calcit, ns: &str, at_def: &str) -> Result<Calcit, Stri
     Calcit::Ref(_, _) => Err(format!("data_to_calcit not implemented for ref: {}", x)),
     Calcit::Thunk(thunk) => Ok(thunk.get_code().to_owned()),
     Calcit::Buffer(_) => Err(format!("data_to_calcit not implemented for buffer: {}", x)),
+    Calcit::Promise(_) => Err(format!("data_to_calcit not implemented for promise: {}", x)),
+    Calcit::Transient(_) => Err(format!("data_to_calcit not implemented for transient: {}", x)),
+    Calcit::LazySeq(_) => Err(format!("data_to_calcit not implemented for lazy-seq: {}", x)),
     Calcit::Recur(_xs) => Err(format!("data_to_calcit not implemented for recur: {}", x)),
     Calcit::Macro { .. } => Err(format!("data_to_calcit not implemented for macro: {}", x)),
     Calcit::Fn { .. } => Err(format!("data_to_calcit not implemented for fn: {}", x)),
@@ -0,0 +1,451 @@
+//! binary encoding of Calcit data in CBOR(RFC 8949), kept byte-identical with `ts-src/js-binary.mts`.
+//! - integers within the safe range(`2^53 - 1`) are written as CBOR integers, other numbers as 64-bit floats
+//! - strings, buffers, lists and maps use the CBOR major types, entries of maps are sorted by encoded keys
+//! - tags, sets, symbols, records, tuples and Cirru quotes are wrapped in CBOR tags, see constants below
+//! - functions, refs and other values that are not data are rejected
+//! - classes attached to records and tuples are not kept, decoded values have no classes
+//! - sorted maps and sorted sets are decoded as plain maps and sets
+//! - half floats are decoded, items nested deeper than 512 levels are rejected
+
+use std::sync::Arc;
+
+use cirru_edn::EdnTag;
+use cirru_parser::Cirru;
+
+use crate::calcit::{self, Calcit, CalcitImport, CalcitLocal, CalcitRecord, CalcitSymbolInfo, CalcitTuple};
+
+/// text, registered as "identifier"
+pub const CBOR_TAG_TAG: u64 = 39;
+/// array of unique items, registered as "finite set"
+pub const CBOR_TAG_SET: u64 = 258;
+/// text
+pub const CBOR_TAG_SYMBOL: u64 = 40001;
+/// array of record name, array of field names and array of values
+pub const CBOR_TAG_RECORD: u64 = 40002;
+/// array of tag and extra values
+pub const CBOR_TAG_TUPLE: u64 = 40003;
+/// Cirru nodes, leaves in text and expressions in arrays
+pub const CBOR_TAG_QUOTE: u64 = 40004;
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// items nested deeper are rejected before the stack overflows, same limit as `parse-json`
+pub const MAX_BINARY_DEPTH: usize = 512;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+pub fn format_binary(x: &Calcit) -> Result<Vec<u8>, String> {
+  let mut buffer: Vec<u8> = vec![];
+  write_value(x, &mut buffer)?;
+  Ok(buffer)
+}
+
+/// the head of an item, with the argument in shortest form
+fn write_head(major: u8, n: u64, buffer: &mut Vec<u8>) {
+  let m = major << 5;
+  if n < 24 {
+    buffer.push(m | n as u8);
+  } else if n <= u8::MAX as u64 {
+    buffer.push(m | 24);
+    buffer.push(n as u8);
+  } else if n <= u16::MAX as u64 {
+    buffer.push(m | 25);
+    buffer.extend_from_slice(&(n as u16).to_be_bytes());
+  } else if n <= u32::MAX as u64 {
+    buffer.push(m | 26);
+    buffer.extend_from_slice(&(n as u32).to_be_bytes());
+  } else {
+    buffer.push(m | 27);
+    buffer.extend_from_slice(&n.to_be_bytes());
+  }
+}
+
+fn write_text(s: &str, buffer: &mut Vec<u8>) {
+  write_head(MAJOR_TEXT, s.len() as u64, buffer);
+  buffer.extend_from_slice(s.as_bytes());
+}
+
+fn write_number(n: f64, buffer: &mut Vec<u8>) {
+  let is_negative_zero = n == 0.0 && n.is_sign_negative();
+  if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER && !is_negative_zero {
+    if n >= 0.0 {
+      write_head(MAJOR_UNSIGNED, n as u64, buffer);
+    } else {
+      write_head(MAJOR_NEGATIVE, (-1.0 - n) as u64, buffer);
+    }
+  } else {
+    buffer.push((MAJOR_SIMPLE << 5) | 27);
+    buffer.extend_from_slice(&n.to_be_bytes());
+  }
+}
+
+fn write_symbol(sym: &str, buffer: &mut Vec<u8>) {
+  write_head(MAJOR_TAG, CBOR_TAG_SYMBOL, buffer);
+  write_text(sym, buffer);
+}
+
+fn write_cirru(x: &Cirru, buffer: &mut Vec<u8>) {
+  match x {
+    Cirru::Leaf(s) => write_text(s, buffer),
+    Cirru::List(xs) => {
+      write_head(MAJOR_ARRAY, xs.len() as u64, buffer);
+      for y in xs {
+        write_cirru(y, buffer);
+      }
+    }
+  }
+}
+
+/// items encoded separately and sorted by bytes, for deterministic output
+fn write_sorted(mut items: Vec<Vec<u8>>, buffer: &mut Vec<u8>) {
+  items.sort();
+  for item in items {
+    buffer.extend(item);
+  }
+}
+
+fn write_value(x: &Calcit, buffer: &mut Vec<u8>) -> Result<(), String> {
+  match x {
+    Calcit::Nil => buffer.push((MAJOR_SIMPLE << 5) | 22),
+    Calcit::Bool(b) => buffer.push((MAJOR_SIMPLE << 5) | if *b { 21 } else { 20 }),
+    Calcit::Number(n) => write_number(*n, buffer),
+    Calcit::Str(s) => write_text(s, buffer),
+    Calcit::Buffer(buf) => {
+      write_head(MAJOR_BYTES, buf.len() as u64, buffer);
+      buffer.extend_from_slice(buf);
+    }
+    Calcit::Tag(t) => {
+      write_head(MAJOR_TAG, CBOR_TAG_TAG, buffer);
+      write_text(t.ref_str(), buffer);
+    }
+    Calcit::Symbol { sym, .. } => write_symbol(sym, buffer),
+    Calcit::Local(CalcitLocal { sym, .. }) => write_symbol(sym, buffer),
+    Calcit::Import(CalcitImport { def, .. }) => write_symbol(def, buffer),
+    Calcit::List(xs) => {
+      write_head(MAJOR_ARRAY, xs.len() as u64, buffer);
+      for y in xs.iter() {
+        write_value(y, buffer)?;
+      }
+    }
+    // sorted sets and sorted maps are encoded as sets and maps, bytes are identical when they are equal,
+    // sortedness is lost and they are decoded as plain sets and maps
+    Calcit::Set(_) | Calcit::SortedSet(_) => {
+      let ys: Vec<&Calcit> = match x {
+        Calcit::Set(xs) => xs.iter().collect(),
+        Calcit::SortedSet(xs) => xs.iter().collect(),
+        _ => unreachable!(),
+      };
+      write_head(MAJOR_TAG, CBOR_TAG_SET, buffer);
+      write_head(MAJOR_ARRAY, ys.len() as u64, buffer);
+      let mut items: Vec<Vec<u8>> = Vec::with_capacity(ys.len());
+      for y in ys {
+        items.push(format_binary(y)?);
+      }
+      write_sorted(items, buffer);
+    }
+    Calcit::Map(_) | Calcit::SortedMap(_) => {
+      let pairs: Vec<(&Calcit, &Calcit)> = match x {
+        Calcit::Map(xs) => xs.iter().collect(),
+        Calcit::SortedMap(xs) => xs.iter().collect(),
+        _ => unreachable!(),
+      };
+      write_head(MAJOR_MAP, pairs.len() as u64, buffer);
+      let mut entries: Vec<Vec<u8>> = Vec::with_capacity(pairs.len());
+      for (k, v) in pairs {
+        let mut entry = format_binary(k)?;
+        write_value(v, &mut entry)?;
+        entries.push(entry);
+      }
+      // keys are unique, so entries are sorted by keys
+      write_sorted(entries, buffer);
+    }
+    Calcit::Record(CalcitRecord { name, fields, values, .. }) => {
+      write_head(MAJOR_TAG, CBOR_TAG_RECORD, buffer);
+      write_head(MAJOR_ARRAY, 3, buffer);
+      write_text(name.ref_str(), buffer);
+      // sorted by names, since js sorts fields in another order
+      let mut pairs: Vec<(&str, &Calcit)> = fields.iter().map(|f| f.ref_str()).zip(values.iter()).collect();
+      pairs.sort_by(|a, b| a.0.cmp(b.0));
+      write_head(MAJOR_ARRAY, pairs.len() as u64, buffer);
+      for (field, _) in &pairs {
+        write_text(field, buffer);
+      }
+      write_head(MAJOR_ARRAY, pairs.len() as u64, buffer);
+      for (_, v) in pairs {
+        write_value(v, buffer)?;
+      }
+    }
+    Calcit::Tuple(CalcitTuple { tag, extra, .. }) => {
+      write_head(MAJOR_TAG, CBOR_TAG_TUPLE, buffer);
+      write_head(MAJOR_ARRAY, extra.len() as u64 + 1, buffer);
+      // enum tuples are tagged by records, which are encoded with their variants
+      write_value(tag, buffer)?;
+      for y in extra {
+        write_value(y, buffer)?;
+      }
+    }
+    Calcit::CirruQuote(code) => {
+      write_head(MAJOR_TAG, CBOR_TAG_QUOTE, buffer);
+      write_cirru(code, buffer);
+    }
+    a => return Err(format!("cannot encode value: {a}")),
+  }
+  Ok(())
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Calcit, String> {
+  let mut parser = BinaryParser { bytes, pos: 0 };
+  let v = parser.read_value(0)?;
+  if parser.pos < bytes.len() {
+    return Err(parser.fail("unexpected trailing bytes"));
+  }
+  Ok(v)
+}
+
+struct BinaryParser<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl BinaryParser<'_> {
+  fn fail(&self, reason: &str) -> String {
+    format!("failed at byte {}: {reason}", self.pos)
+  }
+
+  fn take(&mut self, size: usize) -> Result<&[u8], String> {
+    if self.bytes.len() - self.pos < size {
+      return Err(self.fail("unexpected end of input"));
+    }
+    let chunk = &self.bytes[self.pos..self.pos + size];
+    self.pos += size;
+    Ok(chunk)
+  }
+
+  /// reads major type and argument of the next item, indefinite lengths are not supported
+  fn read_head(&mut self) -> Result<(u8, u64), String> {
+    let start = self.pos;
+    let initial = self.take(1)?[0];
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    let n = match info {
+      0..=23 => info as u64,
+      24 => self.take(1)?[0] as u64,
+      25 => u16::from_be_bytes(self.take(2)?.try_into().expect("2 bytes")) as u64,
+      26 => u32::from_be_bytes(self.take(4)?.try_into().expect("4 bytes")) as u64,
+      27 => u64::from_be_bytes(self.take(8)?.try_into().expect("8 bytes")),
+      _ => {
+        self.pos = start;
+        return Err(self.fail(&format!("unsupported initial byte 0x{initial:02x}")));
+      }
+    };
+    Ok((major, n))
+  }
+
+  /// length of a string or a collection, checked against remaining bytes
+  fn read_size(&mut self, n: u64) -> Result<usize, String> {
+    match usize::try_from(n) {
+      Ok(size) if size <= self.bytes.len() - self.pos => Ok(size),
+      _ => Err(self.fail(&format!("length {n} exceeds remaining bytes"))),
+    }
+  }
+
+  fn read_text_body(&mut self, n: u64) -> Result<Arc<str>, String> {
+    let size = self.read_size(n)?;
+    let start = self.pos;
+    let chunk = self.take(size)?;
+    match std::str::from_utf8(chunk) {
+      Ok(s) => Ok(s.into()),
+      Err(_) => {
+        self.pos = start;
+        Err(self.fail("invalid UTF-8 in text"))
+      }
+    }
+  }
+
+  fn read_text(&mut self) -> Result<Arc<str>, String> {
+    match self.read_head()? {
+      (MAJOR_TEXT, n) => self.read_text_body(n),
+      _ => Err(self.fail("expected text")),
+    }
+  }
+
+  fn read_array_size(&mut self) -> Result<usize, String> {
+    match self.read_head()? {
+      (MAJOR_ARRAY, n) => self.read_size(n),
+      _ => Err(self.fail("expected array")),
+    }
+  }
+
+  fn read_items(&mut self, size: usize, depth: usize) -> Result<Vec<Calcit>, String> {
+    let mut ys: Vec<Calcit> = Vec::with_capacity(size);
+    for _ in 0..size {
+      ys.push(self.read_value(depth)?);
+    }
+    Ok(ys)
+  }
+
+  fn check_depth(&self, depth: usize) -> Result<(), String> {
+    if depth > MAX_BINARY_DEPTH {
+      Err(self.fail(&format!("nested deeper than {MAX_BINARY_DEPTH} levels")))
+    } else {
+      Ok(())
+    }
+  }
+
+  fn read_cirru(&mut self, depth: usize) -> Result<Cirru, String> {
+    self.check_depth(depth)?;
+    match self.read_head()? {
+      (MAJOR_TEXT, n) => Ok(Cirru::Leaf(self.read_text_body(n)?)),
+      (MAJOR_ARRAY, n) => {
+        let size = self.read_size(n)?;
+        let mut ys: Vec<Cirru> = Vec::with_capacity(size);
+        for _ in 0..size {
+          ys.push(self.read_cirru(depth + 1)?);
+        }
+        Ok(Cirru::List(ys))
+      }
+      _ => Err(self.fail("expected text or array in Cirru quote")),
+    }
+  }
+
+  /// `depth` counts collections and tags around the item
+  fn read_value(&mut self, depth: usize) -> Result<Calcit, String> {
+    self.check_depth(depth)?;
+    let start = self.pos;
+    let (major, n) = self.read_head()?;
+    match major {
+      MAJOR_UNSIGNED => Ok(Calcit::Number(n as f64)),
+      MAJOR_NEGATIVE => Ok(Calcit::Number(-1.0 - n as f64)),
+      MAJOR_BYTES => {
+        let size = self.read_size(n)?;
+        Ok(Calcit::Buffer(self.take(size)?.to_vec()))
+      }
+      MAJOR_TEXT => Ok(Calcit::Str(self.read_text_body(n)?)),
+      MAJOR_ARRAY => {
+        let size = self.read_size(n)?;
+        Ok(Calcit::from(self.read_items(size, depth + 1)?))
+      }
+      MAJOR_MAP => {
+        let size = self.read_size(n)?;
+        let mut ys = rpds::HashTrieMap::new_sync();
+        for _ in 0..size {
+          let k = self.read_value(depth + 1)?;
+          if ys.contains_key(&k) {
+            return Err(self.fail(&format!("duplicated key {k} in map")));
+          }
+          let v = self.read_value(depth + 1)?;
+          ys.insert_mut(k, v);
+        }
+        Ok(Calcit::Map(ys))
+      }
+      MAJOR_TAG => self.read_tagged(n, start, depth + 1),
+      MAJOR_SIMPLE => {
+        let initial = self.bytes[start] & 0x1f;
+        match initial {
+          20 => Ok(Calcit::Bool(false)),
+          21 => Ok(Calcit::Bool(true)),
+          22 => Ok(Calcit::Nil),
+          25 => Ok(Calcit::Number(f16_to_f64(n as u16))),
+          26 => Ok(Calcit::Number(f32::from_bits(n as u32) as f64)),
+          27 => Ok(Calcit::Number(f64::from_bits(n))),
+          _ => {
+            self.pos = start;
+            Err(self.fail(&format!("unsupported simple value 0x{:02x}", self.bytes[start])))
+          }
+        }
+      }
+      _ => unreachable!("major type is within 3 bits"),
+    }
+  }
+
+  fn read_tagged(&mut self, tag: u64, start: usize, depth: usize) -> Result<Calcit, String> {
+    match tag {
+      CBOR_TAG_TAG => Ok(Calcit::Tag(EdnTag::from(&*self.read_text()?))),
+      CBOR_TAG_SYMBOL => Ok(Calcit::Symbol {
+        sym: self.read_text()?,
+        info: Arc::new(CalcitSymbolInfo {
+          at_ns: calcit::GEN_NS.into(),
+          at_def: calcit::GENERATED_DEF.into(),
+        }),
+        location: None,
+      }),
+      CBOR_TAG_SET => {
+        let size = self.read_array_size()?;
+        let mut ys = rpds::HashTrieSet::new_sync();
+        for y in self.read_items(size, depth)? {
+          if ys.contains(&y) {
+            return Err(self.fail(&format!("duplicated item {y} in set")));
+          }
+          ys.insert_mut(y);
+        }
+        Ok(Calcit::Set(ys))
+      }
+      CBOR_TAG_RECORD => {
+        if self.read_array_size()? != 3 {
+          return Err(self.fail("expected name, fields and values in record"));
+        }
+        let name = self.read_text()?;
+        let size = self.read_array_size()?;
+        let mut fields: Vec<Arc<str>> = Vec::with_capacity(size);
+        for _ in 0..size {
+          fields.push(self.read_text()?);
+        }
+        if self.read_array_size()? != size {
+          return Err(self.fail("expected values as many as fields in record"));
+        }
+        let values = self.read_items(size, depth)?;
+        let mut pairs: Vec<(Arc<str>, Calcit)> = fields.into_iter().zip(values).collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some(w) = pairs.windows(2).find(|w| w[0].0 == w[1].0) {
+          return Err(self.fail(&format!("duplicated field `{}` in record", w[0].0)));
+        }
+        let (fields, values): (Vec<EdnTag>, Vec<Calcit>) = pairs.into_iter().map(|(k, v)| (EdnTag::from(&*k), v)).unzip();
+        Ok(Calcit::Record(CalcitRecord {
+          name: EdnTag::from(&*name),
+          fields: Arc::new(fields),
+          values: Arc::new(values),
+          class: None,
+          schema: None,
+        }))
+      }
+      CBOR_TAG_TUPLE => {
+        let size = self.read_array_size()?;
+        if size == 0 {
+          return Err(self.fail("expected a tag in tuple"));
+        }
+        let mut items = self.read_items(size, depth)?;
+        let extra = items.split_off(1);
+        Ok(Calcit::Tuple(CalcitTuple {
+          tag: Arc::new(items.remove(0)),
+          extra,
+          class: None,
+        }))
+      }
+      CBOR_TAG_QUOTE => Ok(Calcit::CirruQuote(self.read_cirru(depth)?)),
+      _ => {
+        self.pos = start;
+        Err(self.fail(&format!("unknown tag {tag}")))
+      }
+    }
+  }
+}
+
+/// half precision float, only decoded since numbers are encoded in 64 bits
+fn f16_to_f64(bits: u16) -> f64 {
+  let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+  let exponent = ((bits >> 10) & 0x1f) as i32;
+  let fraction = (bits & 0x3ff) as f64;
+  match exponent {
+    0 => sign * fraction * 2f64.powi(-24),
+    0x1f if fraction == 0.0 => sign * f64::INFINITY,
+    0x1f => f64::NAN,
+    e => sign * (1.0 + fraction / 1024.0) * 2f64.powi(e - 15),
+  }
+}
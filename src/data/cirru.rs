@@ -40,7 +40,7 @@ pub fn code_to_calcit(xs: &Cirru, ns: &str, def: &str, coord: Vec<u8>) -> Result
           } else if let Some(stripped) = s.strip_prefix(".?!") {
             Ok(Calcit::Method(stripped.into(), MethodKind::InvokeNativeOptional))
           } else {
-            Ok(Calcit::Method(s[1..].to_owned().into(), MethodKind::Invoke))
+            Ok(Calcit::Method(s[1..].to_owned().into(), MethodKind::Invoke(Arc::default())))
           }
         }
         '"' | '|' => Ok(Calcit::new_str(&s[1..])),
@@ -195,7 +195,7 @@ pub fn calcit_to_cirru(x: &Calcit) -> Result<Cirru, String> {
     Calcit::Method(name, kind) => match kind {
       MethodKind::Access => Ok(Cirru::leaf(format!(".-{name}"))),
       MethodKind::InvokeNative => Ok(Cirru::leaf(format!(".!{name}"))),
-      MethodKind::Invoke => Ok(Cirru::leaf(format!(".{name}"))),
+      MethodKind::Invoke(_) => Ok(Cirru::leaf(format!(".{name}"))),
       MethodKind::AccessOptional => Ok(Cirru::leaf(format!(".?-{name}"))),
       MethodKind::InvokeNativeOptional => Ok(Cirru::leaf(format!(".?!{name}"))),
     },
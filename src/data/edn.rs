@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
+use crate::builtins::records;
 use crate::calcit::{self, CalcitImport, CalcitList, CalcitLocal, CalcitTuple};
-use crate::calcit::{Calcit, CalcitRecord};
+use crate::calcit::{Calcit, CalcitErr, CalcitRecord};
+use crate::call_stack::CallStackList;
 use crate::{calcit::MethodKind, data::cirru};
 
 use cirru_edn::{Edn, EdnListView, EdnMapView, EdnRecordView, EdnSetView, EdnTag, EdnTupleView};
@@ -40,6 +42,22 @@ pub fn calcit_to_edn(x: &Calcit) -> Result<Edn, String> {
       }
       Ok(ys.into())
     }
+    // EDN has no sorted collections, entries are sorted when EDN is formatted,
+    // but sortedness is lost, parsed values are plain maps and sets
+    Calcit::SortedSet(xs) => {
+      let mut ys = EdnSetView::default();
+      for x in xs {
+        ys.insert(calcit_to_edn(x)?);
+      }
+      Ok(ys.into())
+    }
+    Calcit::SortedMap(xs) => {
+      let mut ys = EdnMapView::default();
+      for (k, x) in xs {
+        ys.insert(calcit_to_edn(k)?, calcit_to_edn(x)?);
+      }
+      Ok(ys.into())
+    }
     Calcit::Record(CalcitRecord { name, fields, values, .. }) => {
       let mut entries = EdnRecordView::new(name.to_owned());
       for idx in 0..fields.len() {
@@ -94,7 +112,7 @@ pub fn calcit_to_edn(x: &Calcit) -> Result<Edn, String> {
     Calcit::Method(name, kind) => match kind {
       MethodKind::Access => Ok(Edn::Symbol(format!(".-{name}").into())),
       MethodKind::InvokeNative => Ok(Edn::Symbol(format!(".!{name}").into())),
-      MethodKind::Invoke => Ok(Edn::Symbol(format!(".{name}").into())),
+      MethodKind::Invoke(_) => Ok(Edn::Symbol(format!(".{name}").into())),
       MethodKind::AccessOptional => Ok(Edn::Symbol(format!(".?-{name}").into())),
       MethodKind::InvokeNativeOptional => Ok(Edn::Symbol(format!(".?!{name}").into())),
     },
@@ -107,8 +125,9 @@ pub fn calcit_to_edn(x: &Calcit) -> Result<Edn, String> {
   }
 }
 
-pub fn edn_to_calcit(x: &Edn, options: &Calcit) -> Calcit {
-  match x {
+/// records in `options` are used as definitions of records with same names, filled and checked by their schemas
+pub fn edn_to_calcit(x: &Edn, options: &Calcit, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  Ok(match x {
     Edn::Nil => Calcit::Nil,
     Edn::Bool(b) => Calcit::Bool(*b),
     Edn::Number(n) => Calcit::Number(*n),
@@ -124,71 +143,75 @@ pub fn edn_to_calcit(x: &Edn, options: &Calcit) -> Calcit {
     Edn::Str(s) => Calcit::Str((**s).into()),
     Edn::Quote(nodes) => Calcit::CirruQuote(nodes.to_owned()),
     Edn::Tuple(EdnTupleView { tag, extra }) => Calcit::Tuple(CalcitTuple {
-      tag: Arc::new(edn_to_calcit(tag, options)),
-      extra: extra.iter().map(|x| edn_to_calcit(x, options)).collect(),
+      tag: Arc::new(edn_to_calcit(tag, options, call_stack)?),
+      extra: extra
+        .iter()
+        .map(|x| edn_to_calcit(x, options, call_stack))
+        .collect::<Result<Vec<_>, _>>()?,
       class: None,
     }),
     Edn::List(EdnListView(xs)) => {
       let mut ys: Vec<Calcit> = vec![];
       for x in xs {
-        ys.push(edn_to_calcit(x, options))
+        ys.push(edn_to_calcit(x, options, call_stack)?)
       }
       Calcit::from(CalcitList::Vector(ys))
     }
     Edn::Set(EdnSetView(xs)) => {
       let mut ys: rpds::HashTrieSetSync<Calcit> = rpds::HashTrieSet::new_sync();
       for x in xs {
-        ys.insert_mut(edn_to_calcit(x, options));
+        ys.insert_mut(edn_to_calcit(x, options, call_stack)?);
       }
       Calcit::Set(ys)
     }
     Edn::Map(EdnMapView(xs)) => {
       let mut ys: rpds::HashTrieMapSync<Calcit, Calcit> = rpds::HashTrieMap::new_sync();
       for (k, v) in xs {
-        ys.insert_mut(edn_to_calcit(k, options), edn_to_calcit(v, options));
+        ys.insert_mut(edn_to_calcit(k, options, call_stack)?, edn_to_calcit(v, options, call_stack)?);
       }
       Calcit::Map(ys)
     }
-    Edn::Record(EdnRecordView { tag: name, pairs: entries }) => {
-      let mut fields: Vec<EdnTag> = Vec::with_capacity(entries.len());
-      let mut values: Vec<Calcit> = Vec::with_capacity(entries.len());
-      let mut sorted = entries.to_owned();
-      sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
-      for v in sorted {
-        fields.push(v.0.to_owned());
-        values.push(edn_to_calcit(&v.1, options));
-      }
-
-      match find_record_in_options(&name.arc_str(), options) {
-        Some(Calcit::Record(CalcitRecord {
-          name: pre_name,
-          fields: pre_fields,
-          values: pre_values,
-          class: pre_class,
-        })) => {
-          if fields == **pre_fields {
-            Calcit::Record(CalcitRecord {
-              name: pre_name.to_owned(),
-              fields: pre_fields.to_owned(),
-              values: pre_values.to_owned(),
-              class: pre_class.to_owned(),
-            })
-          } else {
-            unreachable!("record fields mismatch: {:?} vs {:?}", fields, pre_fields)
+    Edn::Record(EdnRecordView { tag: name, pairs: entries }) => match find_record_in_options(&name.arc_str(), options) {
+      Some(Calcit::Record(record)) => {
+        let mut values: Vec<Option<Calcit>> = vec![None; record.fields.len()];
+        for (k, v) in entries {
+          match record.index_of(k.ref_str()) {
+            Some(pos) => values[pos] = Some(edn_to_calcit(v, options, call_stack)?),
+            None => {
+              return Err(CalcitErr::use_msg_stack(
+                format!("unexpected field :{k} for record :{name} in EDN, expected: {:?}", record.fields),
+                call_stack,
+              ))
+            }
           }
         }
-        _ => Calcit::Record(CalcitRecord {
+        Calcit::Record(CalcitRecord {
+          values: Arc::new(records::fill_record_values(record, values, "parse-cirru-edn", call_stack)?),
+          ..record.to_owned()
+        })
+      }
+      _ => {
+        let mut fields: Vec<EdnTag> = Vec::with_capacity(entries.len());
+        let mut values: Vec<Calcit> = Vec::with_capacity(entries.len());
+        let mut sorted = entries.to_owned();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for v in sorted {
+          fields.push(v.0.to_owned());
+          values.push(edn_to_calcit(&v.1, options, call_stack)?);
+        }
+        Calcit::Record(CalcitRecord {
           name: name.to_owned(),
           fields: Arc::new(fields),
           values: Arc::new(values),
           class: None,
-        }),
+          schema: None,
+        })
       }
-    }
+    },
     Edn::Buffer(buf) => Calcit::Buffer(buf.to_owned()),
     Edn::AnyRef(r) => Calcit::AnyRef(r.to_owned()),
-    Edn::Atom(a) => crate::builtins::quick_build_atom(edn_to_calcit(a, options)),
-  }
+    Edn::Atom(a) => crate::builtins::quick_build_atom(edn_to_calcit(a, options, call_stack)?),
+  })
 }
 /// find a record field in options
 fn find_record_in_options<'a>(name: &str, options: &'a Calcit) -> Option<&'a Calcit> {
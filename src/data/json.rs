@@ -0,0 +1,500 @@
+//! JSON encoding of Calcit data, kept byte-identical with `ts-src/js-json.mts`.
+//! - maps and records turn into objects, keys are sorted
+//! - lists turn into arrays, sets turn into arrays sorted by encoded items
+//! - tuples turn into arrays like `["tag", ...extra]`
+//! - numbers are printed like `Number.prototype.toString` in JavaScript
+
+use crate::calcit::{Calcit, CalcitTuple};
+
+/// how integers out of the safe range(`2^53 - 1`) are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonBigNumber {
+  /// read as float, precision might be lost
+  Float,
+  Error,
+  /// keep literal in a string
+  Str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JsonParseOptions {
+  pub keys_as_tags: bool,
+  pub big_number: JsonBigNumber,
+}
+
+impl Default for JsonParseOptions {
+  fn default() -> Self {
+    JsonParseOptions {
+      keys_as_tags: true,
+      big_number: JsonBigNumber::Float,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatOptions {
+  /// spaces for indentation, `None` for compact output
+  pub indent: Option<usize>,
+  /// write `NaN` and infinities as `null` instead of failing
+  pub non_finite_as_null: bool,
+}
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// arrays and objects nested deeper are rejected before the stack overflows, same limit as serde_json
+pub const MAX_JSON_DEPTH: usize = 512;
+
+pub fn parse_json(s: &str, options: JsonParseOptions) -> Result<Calcit, String> {
+  let mut parser = JsonParser {
+    bytes: s.as_bytes(),
+    text: s,
+    pos: 0,
+    depth: 0,
+    options,
+  };
+  parser.skip_whitespace();
+  let v = parser.read_value()?;
+  parser.skip_whitespace();
+  if parser.pos < parser.bytes.len() {
+    return Err(parser.fail("unexpected trailing characters"));
+  }
+  Ok(v)
+}
+
+struct JsonParser<'a> {
+  bytes: &'a [u8],
+  text: &'a str,
+  pos: usize,
+  /// arrays and objects being read
+  depth: usize,
+  options: JsonParseOptions,
+}
+
+impl JsonParser<'_> {
+  fn fail(&self, reason: &str) -> String {
+    format!("failed at byte {}: {reason}", self.pos)
+  }
+
+  fn unexpected(&self) -> String {
+    match self.text[self.pos..].chars().next() {
+      Some(c) => self.fail(&format!("unexpected character `{c}`")),
+      None => self.fail("unexpected end of input"),
+    }
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+      self.pos += 1;
+    }
+  }
+
+  fn expect_literal(&mut self, literal: &str, value: Calcit) -> Result<Calcit, String> {
+    if self.text[self.pos..].starts_with(literal) {
+      self.pos += literal.len();
+      Ok(value)
+    } else {
+      Err(self.unexpected())
+    }
+  }
+
+  fn read_value(&mut self) -> Result<Calcit, String> {
+    match self.bytes.get(self.pos) {
+      Some(b'{' | b'[') => {
+        if self.depth >= MAX_JSON_DEPTH {
+          return Err(self.fail(&format!("nested deeper than {MAX_JSON_DEPTH} levels")));
+        }
+        self.depth += 1;
+        let v = if self.bytes[self.pos] == b'{' {
+          self.read_object()
+        } else {
+          self.read_array()
+        };
+        self.depth -= 1;
+        v
+      }
+      Some(b'"') => Ok(Calcit::Str(self.read_string()?.into())),
+      Some(b't') => self.expect_literal("true", Calcit::Bool(true)),
+      Some(b'f') => self.expect_literal("false", Calcit::Bool(false)),
+      Some(b'n') => self.expect_literal("null", Calcit::Nil),
+      Some(b'-' | b'0'..=b'9') => self.read_number(),
+      _ => Err(self.unexpected()),
+    }
+  }
+
+  fn read_digits(&mut self) -> usize {
+    let start = self.pos;
+    while let Some(b'0'..=b'9') = self.bytes.get(self.pos) {
+      self.pos += 1;
+    }
+    self.pos - start
+  }
+
+  fn read_number(&mut self) -> Result<Calcit, String> {
+    let start = self.pos;
+    if self.bytes.get(self.pos) == Some(&b'-') {
+      self.pos += 1;
+    }
+    match self.bytes.get(self.pos) {
+      Some(b'0') => self.pos += 1,
+      Some(b'1'..=b'9') => {
+        self.read_digits();
+      }
+      _ => return Err(self.unexpected()),
+    }
+    let mut is_integer = true;
+    if self.bytes.get(self.pos) == Some(&b'.') {
+      is_integer = false;
+      self.pos += 1;
+      if self.read_digits() == 0 {
+        return Err(self.unexpected());
+      }
+    }
+    if let Some(b'e' | b'E') = self.bytes.get(self.pos) {
+      is_integer = false;
+      self.pos += 1;
+      if let Some(b'+' | b'-') = self.bytes.get(self.pos) {
+        self.pos += 1;
+      }
+      if self.read_digits() == 0 {
+        return Err(self.unexpected());
+      }
+    }
+    let literal = &self.text[start..self.pos];
+    let n: f64 = literal.parse().map_err(|_| self.fail(&format!("invalid number `{literal}`")))?;
+    if is_integer && n.abs() > MAX_SAFE_INTEGER {
+      match self.options.big_number {
+        JsonBigNumber::Float => (),
+        JsonBigNumber::Str => return Ok(Calcit::Str(literal.into())),
+        JsonBigNumber::Error => {
+          self.pos = start;
+          return Err(self.fail(&format!("integer `{literal}` is out of safe range")));
+        }
+      }
+    }
+    Ok(Calcit::Number(n))
+  }
+
+  fn read_hex4(&mut self) -> Result<u32, String> {
+    let digits = self
+      .text
+      .get(self.pos..self.pos + 4)
+      .ok_or_else(|| self.fail("invalid unicode escape"))?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+      return Err(self.fail("invalid unicode escape"));
+    }
+    let code = u32::from_str_radix(digits, 16).map_err(|_| self.fail("invalid unicode escape"))?;
+    self.pos += 4;
+    Ok(code)
+  }
+
+  fn read_string(&mut self) -> Result<String, String> {
+    // skip opening quote
+    self.pos += 1;
+    let mut buffer = String::new();
+    loop {
+      let c = match self.text[self.pos..].chars().next() {
+        Some(c) => c,
+        None => return Err(self.fail("unterminated string")),
+      };
+      match c {
+        '"' => {
+          self.pos += 1;
+          return Ok(buffer);
+        }
+        '\\' => {
+          self.pos += 1;
+          let escaped = match self.bytes.get(self.pos) {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+              let escape_start = self.pos - 1;
+              self.pos += 1;
+              let high = self.read_hex4()?;
+              let code = if (0xD800..0xDC00).contains(&high) {
+                if !self.text[self.pos..].starts_with("\\u") {
+                  self.pos = escape_start;
+                  return Err(self.fail("invalid unicode escape"));
+                }
+                self.pos += 2;
+                let low = self.read_hex4()?;
+                if !(0xDC00..0xE000).contains(&low) {
+                  self.pos = escape_start;
+                  return Err(self.fail("invalid unicode escape"));
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+              } else {
+                high
+              };
+              match char::from_u32(code) {
+                Some(c) => {
+                  buffer.push(c);
+                  continue;
+                }
+                None => {
+                  self.pos = escape_start;
+                  return Err(self.fail("invalid unicode escape"));
+                }
+              }
+            }
+            _ => {
+              self.pos -= 1;
+              return Err(self.fail("invalid escape"));
+            }
+          };
+          self.pos += 1;
+          buffer.push(escaped);
+        }
+        c if (c as u32) < 0x20 => return Err(self.fail("control character in string")),
+        c => {
+          self.pos += c.len_utf8();
+          buffer.push(c);
+        }
+      }
+    }
+  }
+
+  fn read_array(&mut self) -> Result<Calcit, String> {
+    self.pos += 1;
+    let mut ys: Vec<Calcit> = vec![];
+    self.skip_whitespace();
+    if self.bytes.get(self.pos) == Some(&b']') {
+      self.pos += 1;
+      return Ok(Calcit::from(ys));
+    }
+    loop {
+      self.skip_whitespace();
+      ys.push(self.read_value()?);
+      self.skip_whitespace();
+      match self.bytes.get(self.pos) {
+        Some(b',') => self.pos += 1,
+        Some(b']') => {
+          self.pos += 1;
+          return Ok(Calcit::from(ys));
+        }
+        _ => return Err(self.unexpected()),
+      }
+    }
+  }
+
+  fn read_object(&mut self) -> Result<Calcit, String> {
+    self.pos += 1;
+    let mut ys = rpds::HashTrieMap::new_sync();
+    self.skip_whitespace();
+    if self.bytes.get(self.pos) == Some(&b'}') {
+      self.pos += 1;
+      return Ok(Calcit::Map(ys));
+    }
+    loop {
+      self.skip_whitespace();
+      if self.bytes.get(self.pos) != Some(&b'"') {
+        return Err(self.unexpected());
+      }
+      let key = self.read_string()?;
+      self.skip_whitespace();
+      if self.bytes.get(self.pos) != Some(&b':') {
+        return Err(self.unexpected());
+      }
+      self.pos += 1;
+      self.skip_whitespace();
+      let value = self.read_value()?;
+      let key = if self.options.keys_as_tags {
+        Calcit::tag(&key)
+      } else {
+        Calcit::Str(key.into())
+      };
+      ys.insert_mut(key, value);
+      self.skip_whitespace();
+      match self.bytes.get(self.pos) {
+        Some(b',') => self.pos += 1,
+        Some(b'}') => {
+          self.pos += 1;
+          return Ok(Calcit::Map(ys));
+        }
+        _ => return Err(self.unexpected()),
+      }
+    }
+  }
+}
+
+pub fn format_json(x: &Calcit, options: JsonFormatOptions) -> Result<String, String> {
+  let mut buffer = String::new();
+  write_json(x, &options, 0, &mut buffer)?;
+  Ok(buffer)
+}
+
+fn write_json(x: &Calcit, options: &JsonFormatOptions, depth: usize, buffer: &mut String) -> Result<(), String> {
+  match x {
+    Calcit::Nil => buffer.push_str("null"),
+    Calcit::Bool(b) => buffer.push_str(if *b { "true" } else { "false" }),
+    Calcit::Number(n) => {
+      if n.is_finite() {
+        buffer.push_str(&js_number_string(*n))
+      } else if options.non_finite_as_null {
+        buffer.push_str("null")
+      } else {
+        return Err(format!("cannot encode non-finite number: {n}"));
+      }
+    }
+    Calcit::Str(s) => write_json_string(s, buffer),
+    Calcit::Tag(t) => write_json_string(t.ref_str(), buffer),
+    Calcit::List(xs) => {
+      let mut items: Vec<String> = Vec::with_capacity(xs.len());
+      for y in xs.iter() {
+        items.push(format_item(y, options, depth + 1)?);
+      }
+      write_collection(items, ('[', ']'), options, depth, buffer);
+    }
+    Calcit::Set(_) | Calcit::SortedSet(_) => {
+      let ys: Vec<&Calcit> = match x {
+        Calcit::Set(xs) => xs.iter().collect(),
+        Calcit::SortedSet(xs) => xs.iter().collect(),
+        _ => unreachable!(),
+      };
+      let mut items: Vec<String> = Vec::with_capacity(ys.len());
+      for y in ys {
+        items.push(format_item(y, options, depth + 1)?);
+      }
+      items.sort();
+      write_collection(items, ('[', ']'), options, depth, buffer);
+    }
+    Calcit::Tuple(CalcitTuple { tag, extra, .. }) => {
+      let mut items: Vec<String> = Vec::with_capacity(extra.len() + 1);
+      items.push(format_item(tag, options, depth + 1)?);
+      for y in extra {
+        items.push(format_item(y, options, depth + 1)?);
+      }
+      write_collection(items, ('[', ']'), options, depth, buffer);
+    }
+    Calcit::Map(_) | Calcit::SortedMap(_) => {
+      let pairs: Vec<(&Calcit, &Calcit)> = match x {
+        Calcit::Map(xs) => xs.iter().collect(),
+        Calcit::SortedMap(xs) => xs.iter().collect(),
+        _ => unreachable!(),
+      };
+      let mut entries: Vec<(&str, &Calcit)> = Vec::with_capacity(pairs.len());
+      for (k, v) in pairs {
+        let key = match k {
+          Calcit::Str(s) => &**s,
+          Calcit::Tag(t) => t.ref_str(),
+          _ => return Err(format!("expected string or tag for key, got: {k}")),
+        };
+        entries.push((key, v));
+      }
+      entries.sort_by(|a, b| a.0.cmp(b.0));
+      if let Some(w) = entries.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(format!("duplicated key `{}` in map", w[0].0));
+      }
+      write_object(entries, options, depth, buffer)?;
+    }
+    Calcit::Record(record) => {
+      let mut entries: Vec<(&str, &Calcit)> = record.fields.iter().map(|f| f.ref_str()).zip(record.values.iter()).collect();
+      entries.sort_by(|a, b| a.0.cmp(b.0));
+      write_object(entries, options, depth, buffer)?;
+    }
+    a => return Err(format!("cannot encode value: {a}")),
+  }
+  Ok(())
+}
+
+fn format_item(x: &Calcit, options: &JsonFormatOptions, depth: usize) -> Result<String, String> {
+  let mut buffer = String::new();
+  write_json(x, options, depth, &mut buffer)?;
+  Ok(buffer)
+}
+
+fn write_object(entries: Vec<(&str, &Calcit)>, options: &JsonFormatOptions, depth: usize, buffer: &mut String) -> Result<(), String> {
+  let mut items: Vec<String> = Vec::with_capacity(entries.len());
+  for (k, v) in entries {
+    let mut item = String::new();
+    write_json_string(k, &mut item);
+    item.push_str(if options.indent.is_some() { ": " } else { ":" });
+    write_json(v, options, depth + 1, &mut item)?;
+    items.push(item);
+  }
+  write_collection(items, ('{', '}'), options, depth, buffer);
+  Ok(())
+}
+
+fn write_collection(items: Vec<String>, brackets: (char, char), options: &JsonFormatOptions, depth: usize, buffer: &mut String) {
+  buffer.push(brackets.0);
+  if items.is_empty() {
+    buffer.push(brackets.1);
+    return;
+  }
+  match options.indent {
+    Some(size) => {
+      let inner = " ".repeat(size * (depth + 1));
+      for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+          buffer.push(',');
+        }
+        buffer.push('\n');
+        buffer.push_str(&inner);
+        buffer.push_str(item);
+      }
+      buffer.push('\n');
+      buffer.push_str(&" ".repeat(size * depth));
+    }
+    None => buffer.push_str(&items.join(",")),
+  }
+  buffer.push(brackets.1);
+}
+
+/// escapes like `JSON.stringify`
+fn write_json_string(s: &str, buffer: &mut String) {
+  buffer.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => buffer.push_str("\\\""),
+      '\\' => buffer.push_str("\\\\"),
+      '\u{8}' => buffer.push_str("\\b"),
+      '\u{c}' => buffer.push_str("\\f"),
+      '\n' => buffer.push_str("\\n"),
+      '\r' => buffer.push_str("\\r"),
+      '\t' => buffer.push_str("\\t"),
+      c if (c as u32) < 0x20 => buffer.push_str(&format!("\\u{:04x}", c as u32)),
+      c => buffer.push(c),
+    }
+  }
+  buffer.push('"');
+}
+
+/// same output as `Number.prototype.toString` for finite numbers
+pub fn js_number_string(f: f64) -> String {
+  if f == 0.0 {
+    return String::from("0");
+  }
+  // shortest digits that round trip, like `1.2345e-7`
+  let sci = format!("{:e}", f.abs());
+  let (mantissa, exp) = sci.split_once('e').expect("exponent");
+  let exp: i32 = exp.parse().expect("exponent number");
+  let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+  let k = digits.len() as i32;
+  let n = exp + 1;
+  let body = if k <= n && n <= 21 {
+    format!("{digits}{}", "0".repeat((n - k) as usize))
+  } else if 0 < n && n <= 21 {
+    format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+  } else if -6 < n && n <= 0 {
+    format!("0.{}{digits}", "0".repeat((-n) as usize))
+  } else {
+    let e = n - 1;
+    let sign = if e < 0 { '-' } else { '+' };
+    if k == 1 {
+      format!("{digits}e{sign}{}", e.abs())
+    } else {
+      format!("{}.{}e{sign}{}", &digits[..1], &digits[1..], e.abs())
+    }
+  };
+  if f < 0.0 {
+    format!("-{body}")
+  } else {
+    body
+  }
+}
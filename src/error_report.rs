@@ -0,0 +1,437 @@
+//! reports of failures, printed to stderr with excerpts of the failing code, and written to a file for editors and CI to parse.
+//! the file is `.calcit-error.cirru` in Cirru EDN by default, or `.calcit-error.json` with `--error-format json`,
+//! and could be moved with `--error-path`.
+//!
+//! fields of a report, same in both formats:
+//!
+//! - `version`, `1` for this schema
+//! - `kind`, one of `preprocess` `runtime` `codegen` `warnings`
+//! - `message`
+//! - `location`, `{ns, def, coord}` of the failure, or nil
+//! - `stack`, frames from the innermost call, each with `def` in `ns/def`, `kind`, `code` and `args`
+//! - `warnings`, each with `kind`, `message` and `location`
+//!
+//! in Cirru EDN, `code` is quoted Cirru and `args` are data. in JSON, they are strings of Cirru and of values.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+
+use cirru_edn::{Edn, EdnListView};
+use cirru_parser::{Cirru, CirruWriterOptions};
+use colored::Colorize;
+
+use crate::calcit::{Calcit, CalcitErr, CalcitLocal, LocatedWarning, NodeLocation};
+use crate::call_stack::{CalcitStack, CallStackList, StackKind};
+use crate::data::cirru;
+use crate::data::edn;
+use crate::data::json::{format_json, JsonFormatOptions};
+use crate::program;
+use crate::runner;
+
+/// bumped when fields are renamed or removed
+pub const SCHEMA_VERSION: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+  Cirru,
+  Json,
+}
+
+impl ErrorFormat {
+  pub fn parse(s: &str) -> Result<Self, String> {
+    match s {
+      "cirru" => Ok(Self::Cirru),
+      "json" => Ok(Self::Json),
+      _ => Err(format!("unknown error format `{s}`, expected `cirru` or `json`")),
+    }
+  }
+
+  fn default_path(&self) -> &'static str {
+    match self {
+      Self::Cirru => ".calcit-error.cirru",
+      Self::Json => ".calcit-error.json",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct ReportOptions {
+  format: ErrorFormat,
+  /// `None` for the default path of the format
+  path: Option<PathBuf>,
+}
+
+static REPORT_OPTIONS: LazyLock<RwLock<ReportOptions>> = LazyLock::new(|| {
+  RwLock::new(ReportOptions {
+    format: ErrorFormat::Cirru,
+    path: None,
+  })
+});
+
+/// format and path of report files, path defaults to a file in current directory
+pub fn configure(format: ErrorFormat, path: Option<String>) {
+  let mut options = REPORT_OPTIONS.write().expect("write report options");
+  options.format = format;
+  options.path = path.map(PathBuf::from);
+}
+
+/// path of the report file
+pub fn report_path() -> PathBuf {
+  let options = REPORT_OPTIONS.read().expect("read report options");
+  match &options.path {
+    Some(p) => p.to_owned(),
+    None => PathBuf::from(options.format.default_path()),
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+  /// failed in preprocessing, like unknown symbols or macros throwing errors
+  Preprocess,
+  Runtime,
+  /// failed in generating js or IR
+  Codegen,
+  /// preprocessing found warnings, so the program is not run
+  Warnings,
+}
+
+impl FailureKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Preprocess => "preprocess",
+      Self::Runtime => "runtime",
+      Self::Codegen => "codegen",
+      Self::Warnings => "warnings",
+    }
+  }
+}
+
+pub struct ErrorReport<'a> {
+  pub kind: FailureKind,
+  pub message: &'a str,
+  pub stack: &'a CallStackList,
+  pub location: Option<&'a NodeLocation>,
+  pub warnings: &'a [LocatedWarning],
+}
+
+impl<'a> ErrorReport<'a> {
+  pub fn from_err(kind: FailureKind, e: &'a CalcitErr) -> Self {
+    ErrorReport {
+      kind,
+      message: &e.msg,
+      stack: &e.stack,
+      location: e.location.as_deref(),
+      warnings: &e.warnings,
+    }
+  }
+
+  pub fn to_edn(&self) -> Result<Edn, String> {
+    let mut stack_list = EdnListView::default();
+    for s in &self.stack.0 {
+      let mut args = EdnListView::default();
+      for v in s.args.iter() {
+        // values like refs are not data
+        args.push(edn::calcit_to_edn(v).unwrap_or_else(|_| Edn::Str(v.to_string().into())));
+      }
+      stack_list.push(Edn::map_from_iter([
+        (Edn::tag("def"), format!("{}/{}", s.ns, s.def).into()),
+        (Edn::tag("kind"), Edn::tag(s.kind.to_string())),
+        (Edn::tag("code"), cirru::calcit_to_cirru(&s.code)?.into()),
+        (Edn::tag("args"), args.into()),
+      ]));
+    }
+    let mut warnings = EdnListView::default();
+    for w in self.warnings {
+      warnings.push(Edn::map_from_iter([
+        (Edn::tag("kind"), Edn::tag(w.kind().to_string())),
+        (Edn::tag("message"), w.message().into()),
+        (Edn::tag("location"), w.location().into()),
+      ]));
+    }
+    Ok(Edn::map_from_iter([
+      (Edn::tag("version"), Edn::Number(SCHEMA_VERSION as f64)),
+      (Edn::tag("kind"), Edn::tag(self.kind.as_str())),
+      (Edn::tag("message"), self.message.into()),
+      (
+        Edn::tag("location"),
+        match self.location {
+          Some(l) => l.into(),
+          None => Edn::Nil,
+        },
+      ),
+      (Edn::tag("stack"), stack_list.into()),
+      (Edn::tag("warnings"), warnings.into()),
+    ]))
+  }
+
+  pub fn to_json(&self) -> Result<String, String> {
+    let location_data = |l: &NodeLocation| {
+      let coord: Vec<Calcit> = l.coord.iter().map(|x| Calcit::Number(*x as f64)).collect();
+      map_of(vec![
+        ("ns", Calcit::Str(l.ns.to_owned())),
+        ("def", Calcit::Str(l.def.to_owned())),
+        ("coord", Calcit::from(coord)),
+      ])
+    };
+    let mut frames: Vec<Calcit> = vec![];
+    for s in &self.stack.0 {
+      let code = match cirru::calcit_to_cirru(&s.code) {
+        Ok(list @ Cirru::List(_)) => cirru_parser::format(&[list], CirruWriterOptions { use_inline: true })
+          .map(|c| c.trim().to_owned())
+          .unwrap_or_else(|_| s.code.to_string()),
+        Ok(leaf) => leaf.to_string(),
+        Err(_) => s.code.to_string(),
+      };
+      let args: Vec<Calcit> = s.args.iter().map(|v| Calcit::new_str(v.to_string())).collect();
+      frames.push(map_of(vec![
+        ("def", Calcit::new_str(format!("{}/{}", s.ns, s.def))),
+        ("kind", Calcit::new_str(s.kind.to_string())),
+        ("code", Calcit::new_str(code)),
+        ("args", Calcit::from(args)),
+      ]));
+    }
+    let warnings: Vec<Calcit> = self
+      .warnings
+      .iter()
+      .map(|w| {
+        map_of(vec![
+          ("kind", Calcit::new_str(w.kind().to_string())),
+          ("message", Calcit::new_str(w.message())),
+          ("location", location_data(w.location())),
+        ])
+      })
+      .collect();
+    let data = map_of(vec![
+      ("version", Calcit::Number(SCHEMA_VERSION as f64)),
+      ("kind", Calcit::new_str(self.kind.as_str())),
+      ("message", Calcit::new_str(self.message)),
+      (
+        "location",
+        match self.location {
+          Some(l) => location_data(l),
+          None => Calcit::Nil,
+        },
+      ),
+      ("stack", Calcit::from(frames)),
+      ("warnings", Calcit::from(warnings)),
+    ]);
+    format_json(
+      &data,
+      JsonFormatOptions {
+        indent: Some(2),
+        non_finite_as_null: true,
+      },
+    )
+  }
+}
+
+fn map_of(pairs: Vec<(&str, Calcit)>) -> Calcit {
+  let mut m = rpds::HashTrieMap::new_sync();
+  for (k, v) in pairs {
+    m.insert_mut(Calcit::tag(k), v);
+  }
+  Calcit::Map(m)
+}
+
+/// width of excerpts and arguments in stderr, longer lines are truncated
+const DISPLAY_WIDTH: usize = 100;
+
+/// prints the failure with an excerpt of the failing code and the stack to stderr, and writes the report file.
+/// failures of reporting are only logged, so callers still return the original error
+pub fn report(r: &ErrorReport) {
+  if r.kind != FailureKind::Warnings {
+    if !io::stderr().is_terminal() {
+      colored::control::set_override(false);
+    }
+    eprintln!("\n{} {}", "Failure:".red().bold(), r.message);
+    let location = r.location.cloned().or_else(|| r.stack.0.iter().find_map(frame_location));
+    if let Some(excerpt) = location.as_ref().and_then(render_excerpt) {
+      eprintln!("\n{excerpt}");
+    }
+    eprintln!("\ncall stack:");
+    for s in &r.stack.0 {
+      let is_macro = s.kind == StackKind::Macro;
+      eprintln!(
+        "  {}/{}{}",
+        s.ns,
+        s.def.bold(),
+        if is_macro { "\t ~macro".dimmed() } else { "".normal() }
+      );
+      if !s.args.is_empty() {
+        let args = s
+          .args
+          .iter()
+          .map(|x| match s.kind {
+            // arguments of macros and syntax are code
+            StackKind::Macro | StackKind::Syntax => {
+              let mut code = String::new();
+              write_inline(x, &mut code);
+              code
+            }
+            _ => x.to_string(),
+          })
+          .collect::<Vec<_>>()
+          .join(" ");
+        eprintln!("    {}", truncate(&args, DISPLAY_WIDTH).dimmed());
+      }
+    }
+  }
+
+  let format = REPORT_OPTIONS.read().expect("read report options").format;
+  let content = match format {
+    ErrorFormat::Cirru => r.to_edn().and_then(|data| cirru_edn::format(&data, true)),
+    ErrorFormat::Json => r.to_json(),
+  };
+  let path = report_path();
+  match content {
+    Ok(content) => {
+      if let Err(e) = fs::write(&path, content) {
+        eprintln!("failed to write error report to {}: {e}", path.display());
+      } else {
+        eprintln!("\nrun `cat {}` to read stack details.", path.display());
+      }
+    }
+    Err(e) => eprintln!("failed to format error report: {e}"),
+  }
+}
+
+/// location of the call in a frame, when it is from code of the program
+fn frame_location(s: &CalcitStack) -> Option<NodeLocation> {
+  match &s.code {
+    Calcit::List(xs) => runner::call_location(xs).filter(|l| program::lookup_def_code(&l.ns, &l.def).is_some()),
+    _ => None,
+  }
+}
+
+/// the expression around the node at the location, in one line with the node highlighted and marked below
+fn render_excerpt(location: &NodeLocation) -> Option<String> {
+  let code = program::lookup_def_code(&location.ns, &location.def)?;
+  // shows the parent for context, unless the node is the whole def
+  let (context_coord, target) = match location.coord.split_last() {
+    Some((idx, parent)) => (parent, Some(*idx as usize)),
+    None => (&location.coord[..], None),
+  };
+  let mut context = &code;
+  for idx in context_coord {
+    match context {
+      Calcit::List(xs) => context = xs.get(*idx as usize)?,
+      _ => return None,
+    }
+  }
+
+  let mut text = String::new();
+  let mut span = (0, 0);
+  match (context, target) {
+    (Calcit::List(xs), Some(target)) if target < xs.len() => {
+      text.push('(');
+      for (idx, x) in xs.iter().enumerate() {
+        if idx > 0 {
+          text.push(' ');
+        }
+        let start = text.chars().count();
+        write_inline(x, &mut text);
+        if idx == target {
+          span = (start, text.chars().count());
+        }
+      }
+      text.push(')');
+    }
+    _ => {
+      write_inline(context, &mut text);
+      span = (0, text.chars().count());
+    }
+  }
+
+  // a window of the line around the node
+  let chars: Vec<char> = text.chars().collect();
+  let (from, to) = if chars.len() <= DISPLAY_WIDTH {
+    (0, chars.len())
+  } else if span.1 - span.0 >= DISPLAY_WIDTH {
+    (span.0, span.0 + DISPLAY_WIDTH)
+  } else {
+    let from = span
+      .0
+      .saturating_sub((DISPLAY_WIDTH - (span.1 - span.0)) / 2)
+      .min(chars.len() - DISPLAY_WIDTH);
+    (from, from + DISPLAY_WIDTH)
+  };
+  let slice = |a: usize, b: usize| chars[a.clamp(from, to)..b.clamp(from, to)].iter().collect::<String>();
+  let prefix = if from > 0 { "…" } else { "" };
+  let suffix = if to < chars.len() { "…" } else { "" };
+  let line = format!(
+    "{prefix}{}{}{}{suffix}",
+    slice(from, span.0),
+    slice(span.0, span.1).red().bold(),
+    slice(span.1, to)
+  );
+  let marker_offset = prefix.chars().count() + span.0.clamp(from, to) - from;
+  let marker_width = (span.1.clamp(from, to) - span.0.clamp(from, to)).max(1);
+  Some(format!(
+    "{} {location}\n  | {line}\n  | {}{}",
+    "at".dimmed(),
+    " ".repeat(marker_offset),
+    "^".repeat(marker_width).red()
+  ))
+}
+
+/// code in one line with parentheses, like `(f a (g b))`
+fn write_inline(x: &Calcit, out: &mut String) {
+  match x {
+    Calcit::List(xs) => {
+      out.push('(');
+      for (idx, y) in xs.iter().enumerate() {
+        if idx > 0 {
+          out.push(' ');
+        }
+        write_inline(y, out);
+      }
+      out.push(')');
+    }
+    // strings with spaces are quoted as in Cirru
+    Calcit::Str(s) if s.contains(char::is_whitespace) => out.push_str(&format!("{s:?}")),
+    Calcit::Str(s) => out.push_str(&format!("|{s}")),
+    _ => {
+      let leaf = match x {
+        Calcit::Symbol { sym, .. } => sym.to_string(),
+        Calcit::Local(CalcitLocal { sym, .. }) => sym.to_string(),
+        Calcit::Registered(alias) => alias.to_string(),
+        Calcit::Syntax(s, _ns) => s.as_ref().to_owned(),
+        Calcit::Proc(p) => p.as_ref().to_owned(),
+        a => a.to_string(),
+      };
+      if leaf.is_empty() || leaf.contains(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"') {
+        out.push_str(&format!("{leaf:?}"));
+      } else {
+        out.push_str(&leaf);
+      }
+    }
+  }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+  if s.chars().count() <= width {
+    s.to_owned()
+  } else {
+    let mut short: String = s.chars().take(width - 1).collect();
+    short.push('…');
+    short
+  }
+}
+
+/// reports an error with its stack, location and warnings
+pub fn report_err(kind: FailureKind, e: &CalcitErr) {
+  report(&ErrorReport::from_err(kind, e))
+}
+
+/// reports a failure that is not a `CalcitErr`, like from codegen
+pub fn report_failure(kind: FailureKind, message: &str, stack: &CallStackList) {
+  report(&ErrorReport {
+    kind,
+    message,
+    stack,
+    location: None,
+    warnings: &[],
+  })
+}
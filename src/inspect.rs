@@ -0,0 +1,220 @@
+//! control channel of `cr --inspect <socket>` in watch mode, for editors to look into global states of a running program,
+//! like nREPL. a client connects to the Unix socket and sends requests in Cirru EDN, each ending with an empty line,
+//! and responses are sent back in the same way.
+//!
+//! requests are maps with an `:op`:
+//!
+//! - `{} (:op :refs)`, lists refs defined with `defatom`, with paths and tags of watchers
+//! - `{} (:op :read) (:path |app.main/*store)`, reads value of a ref
+//! - `{} (:op :reset) (:path |app.main/*store) (:value ...)`, resets a ref, watchers are triggered
+//! - `{} (:op :eval) (:ns |app.main) (:code "|println 1")`, evaluates code in a namespace, returns value of the last expression
+//! - `{} (:op :reload) (:changes ...)`, applies changes in the format of `.compact-inc.cirru` and calls the reload function
+//!
+//! responses are `{} (:ok true) (:value ...)` or `{} (:ok false) (:error |message)`.
+//! connections are served one at a time, and each request holds `event_loop::lock_runtime()`,
+//! which is also taken by reloads from watching files, so requests never run along with reloads or tasks of the event loop.
+//! the socket file is removed when the process exits by `quit!`, Ctrl-C, or after tasks are cleared.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use cirru_edn::{Edn, EdnMapView};
+
+use crate::builtins::refs::{self, ValueAndListeners};
+use crate::calcit::{Calcit, CalcitScope, LocatedWarning};
+use crate::call_stack::CallStackList;
+use crate::data::{cirru, edn};
+use crate::program;
+use crate::runner::{self, event_loop, preprocess};
+use crate::snapshot::ChangesDict;
+
+/// def name for code from `:eval`, shown in stacks and warnings
+const EVAL_DEF: &str = "&inspect";
+
+/// path of the socket being listened on, removed on exit
+static SOCKET_PATH: Mutex<Option<String>> = Mutex::new(None);
+
+/// removes the socket file if it's listened on, called before the process exits
+pub fn remove_socket() {
+  if let Some(path) = SOCKET_PATH.lock().expect("lock socket path").take() {
+    if let Err(e) = std::fs::remove_file(&path) {
+      eprintln!("failed to remove socket {path}: {e}");
+    }
+  }
+}
+
+/// handles a request, `reload` is called with changes for `:reload`
+pub fn handle_request(request: &Edn, reload: &dyn Fn(ChangesDict) -> Result<(), String>) -> Edn {
+  match dispatch(request, reload) {
+    Ok(value) => {
+      let mut response = EdnMapView::default();
+      response.insert_key("ok", Edn::Bool(true));
+      response.insert_key("value", value);
+      response.into()
+    }
+    Err(e) => failure(e),
+  }
+}
+
+fn failure(message: String) -> Edn {
+  let mut response = EdnMapView::default();
+  response.insert_key("ok", Edn::Bool(false));
+  response.insert_key("error", Edn::str(message));
+  response.into()
+}
+
+fn dispatch(request: &Edn, reload: &dyn Fn(ChangesDict) -> Result<(), String>) -> Result<Edn, String> {
+  let data = request.view_map()?;
+  let op = match data.get_or_nil("op") {
+    Edn::Tag(t) => t,
+    a => return Err(format!("expected `:op` in tag, got: {a}")),
+  };
+  let _runtime = event_loop::lock_runtime();
+  match op.ref_str() {
+    "refs" => {
+      let xs = refs::registered_refs(&[]).map_err(|e| e.msg)?;
+      edn::calcit_to_edn(&xs)
+    }
+    "read" => {
+      let pair = read_ref(&data)?;
+      let value = pair.lock().expect("read ref").0.to_owned();
+      edn::calcit_to_edn(&value)
+    }
+    "reset" => {
+      let pair = read_ref(&data)?;
+      let call_stack = CallStackList::default();
+      let value = edn::edn_to_calcit(&data.get_or_nil("value"), &Calcit::Nil, &call_stack).map_err(|e| e.msg)?;
+      refs::modify_ref(pair, value, &call_stack).map_err(|e| e.msg)?;
+      Ok(Edn::Nil)
+    }
+    "eval" => {
+      let ns = data.get_or_nil("ns").read_str()?;
+      let code = data.get_or_nil("code").read_str()?;
+      let value = eval_in_ns(&ns, &code)?;
+      edn::calcit_to_edn(&value)
+    }
+    "reload" => {
+      let changes: ChangesDict = data.get_or_nil("changes").try_into()?;
+      reload(changes)?;
+      Ok(Edn::Nil)
+    }
+    _ => Err(format!("unknown op :{op}")),
+  }
+}
+
+fn read_ref(data: &EdnMapView) -> Result<Arc<Mutex<ValueAndListeners>>, String> {
+  let path = data.get_or_nil("path").read_str()?;
+  refs::find_registered(&path).ok_or_else(|| format!("unknown ref: {path}"))
+}
+
+/// code is preprocessed in the namespace, so definitions and imports of it are available
+fn eval_in_ns(ns: &str, code: &str) -> Result<Calcit, String> {
+  if !program::PROGRAM_CODE_DATA.read().expect("read program code").contains_key(ns) {
+    return Err(format!("unknown ns: {ns}"));
+  }
+  let exprs = cirru_parser::parse(code)?;
+  let call_stack = CallStackList::default();
+  let check_warnings: &RefCell<Vec<LocatedWarning>> = &RefCell::new(vec![]);
+  let mut value = Calcit::Nil;
+  for expr in &exprs {
+    let form = cirru::code_to_calcit(expr, ns, EVAL_DEF, vec![])?;
+    let resolved = preprocess::preprocess_expr(&form, &HashSet::new(), ns, check_warnings, &call_stack).map_err(|e| e.msg)?;
+    let warnings = LocatedWarning::take_blocking(&check_warnings.take());
+    if !warnings.is_empty() {
+      return Err(warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>().join("\n"));
+    }
+    value = runner::evaluate_expr(&resolved, &CalcitScope::default(), ns, &call_stack).map_err(|e| e.msg)?;
+  }
+  Ok(value)
+}
+
+/// sockets left by previous runs are replaced, other files at the path are not touched
+#[cfg(unix)]
+pub fn check_socket_path(path: &str) -> Result<(), String> {
+  use std::os::unix::fs::FileTypeExt;
+
+  match std::fs::symlink_metadata(path) {
+    Ok(meta) if meta.file_type().is_socket() => Ok(()),
+    Ok(_) => Err(format!("--inspect expected a path for the socket, {path} is not a socket")),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+    Err(e) => Err(format!("failed to check socket {path}: {e}")),
+  }
+}
+
+#[cfg(not(unix))]
+pub fn check_socket_path(_path: &str) -> Result<(), String> {
+  Ok(())
+}
+
+/// reads requests from the socket, one connection at a time
+#[cfg(unix)]
+pub fn serve(path: &str, reload: &dyn Fn(ChangesDict) -> Result<(), String>) -> Result<(), String> {
+  use std::io::{BufRead, BufReader, Write};
+  use std::os::unix::net::UnixListener;
+
+  // socket file from previous runs
+  check_socket_path(path)?;
+  if std::path::Path::new(path).exists() {
+    std::fs::remove_file(path).map_err(|e| format!("failed to remove socket {path}: {e}"))?;
+  }
+  let listener = UnixListener::bind(path).map_err(|e| format!("failed to listen on {path}: {e}"))?;
+  *SOCKET_PATH.lock().expect("lock socket path") = Some(path.to_owned());
+  eprintln!("inspecting on socket: {path}");
+
+  for stream in listener.incoming() {
+    let stream = match stream {
+      Ok(s) => s,
+      Err(e) => {
+        eprintln!("inspect connection error: {e}");
+        continue;
+      }
+    };
+    let mut writer = match stream.try_clone() {
+      Ok(s) => s,
+      Err(e) => {
+        eprintln!("inspect connection error: {e}");
+        continue;
+      }
+    };
+    let mut message = String::new();
+    for line in BufReader::new(stream).lines() {
+      let line = match line {
+        Ok(l) => l,
+        Err(e) => {
+          eprintln!("inspect connection error: {e}");
+          break;
+        }
+      };
+      if !line.trim().is_empty() {
+        message.push_str(&line);
+        message.push('\n');
+        continue;
+      }
+      if message.is_empty() {
+        continue;
+      }
+      let response = match cirru_edn::parse(&message) {
+        Ok(request) => handle_request(&request, reload),
+        Err(e) => failure(format!("failed to parse request: {e}")),
+      };
+      message.clear();
+      let text = cirru_edn::format(&response, true)
+        .or_else(|e| cirru_edn::format(&failure(format!("failed to format response: {e}")), true))
+        .unwrap_or_else(|e| {
+          eprintln!("failed to format response: {e}");
+          String::from("{} (:ok false) (:error \"|failed to format response\")")
+        });
+      if let Err(e) = writer.write_all(format!("{}\n\n", text.trim_end()).as_bytes()) {
+        eprintln!("inspect connection error: {e}");
+        break;
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_path: &str, _reload: &dyn Fn(ChangesDict) -> Result<(), String>) -> Result<(), String> {
+  Err(String::from("inspecting over socket is only supported on Unix"))
+}
@@ -5,13 +5,16 @@ pub mod calcit;
 pub mod call_stack;
 pub mod cli_args;
 pub mod codegen;
+pub mod error_report;
+pub mod inspect;
 pub mod program;
 pub mod runner;
 pub mod snapshot;
 pub mod util;
 
 use calcit::LocatedWarning;
-use call_stack::CallStackList;
+use call_stack::{CallStackList, StackKind};
+use error_report::FailureKind;
 use std::cell::RefCell;
 use std::fs;
 use std::path::Path;
@@ -47,29 +50,34 @@ pub fn run_program(init_ns: Arc<str>, init_def: Arc<str>, params: &[Calcit]) ->
     Ok(_) => (),
     Err(failure) => {
       eprintln!("\nfailed preprocessing, {failure}");
-      call_stack::display_stack(&failure.msg, &failure.stack, failure.location.as_ref())?;
+      error_report::report_err(FailureKind::Preprocess, &failure);
       return CalcitErr::err_str(failure.msg);
     }
   }
 
-  let warnings = check_warnings.borrow();
+  let warnings = LocatedWarning::take_blocking(&check_warnings.borrow());
   if !warnings.is_empty() {
-    return Err(CalcitErr {
+    let failure = CalcitErr {
       msg: format!("Found {} warnings, runner blocked", warnings.len()),
-      warnings: warnings.to_owned(),
+      warnings,
+      payload: None,
       stack: CallStackList::default(),
       location: None,
-    });
+    };
+    error_report::report_err(FailureKind::Warnings, &failure);
+    return Err(failure);
   }
   match program::lookup_evaled_def(&init_ns, &init_def) {
     None => CalcitErr::err_str(format!("entry not initialized: {init_ns}/{init_def}")),
     Some(entry) => match entry {
       Calcit::Fn { info, .. } => {
-        let result = runner::run_fn(params, &info, &CallStackList::default());
+        // the entry is tracked like other calls, so its definition is known to procs it calls
+        let call_stack = CallStackList::default().extend(&init_ns, &init_def, StackKind::Fn, &Calcit::Nil, params);
+        let result = runner::run_fn(params, &info, &call_stack);
         match result {
           Ok(v) => Ok(v),
           Err(failure) => {
-            call_stack::display_stack(&failure.msg, &failure.stack, failure.location.as_ref())?;
+            error_report::report_err(FailureKind::Runtime, &failure);
             Err(failure)
           }
         }
@@ -93,7 +101,7 @@ pub fn load_module(path: &str, base_dir: &Path, module_folder: &Path) -> Result<
     module_folder.join(&file_path).as_path().to_owned()
   };
 
-  println!("loading: {}", file_path.as_str());
+  eprintln!("loading: {}", file_path.as_str());
 
   let mut content = fs::read_to_string(&fullpath).unwrap_or_else(|_| panic!("expected Cirru snapshot {fullpath:?}"));
   strip_shebang(&mut content);
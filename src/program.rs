@@ -1,12 +1,14 @@
 mod entry_book;
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::LazyLock;
 use std::sync::RwLock;
 
 use cirru_parser::Cirru;
 
+use crate::builtins::refs;
 use crate::calcit::Calcit;
 use crate::data::cirru::code_to_calcit;
 use crate::snapshot;
@@ -43,6 +45,16 @@ pub type ProgramCodeData = HashMap<Arc<str>, ProgramFileData>;
 static PROGRAM_EVALED_DATA_STATE: LazyLock<RwLock<ProgramEvaledData>> = LazyLock::new(|| RwLock::new(EntryBook::default()));
 /// raw code information before program running
 pub static PROGRAM_CODE_DATA: LazyLock<RwLock<ProgramCodeData>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+/// bumped when code changes or evaled data is cleared, caches of values from program are dropped by comparing it
+static PROGRAM_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+pub fn program_generation() -> usize {
+  PROGRAM_GENERATION.load(Ordering::Acquire)
+}
+
+fn bump_program_generation() {
+  PROGRAM_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
 
 fn extract_import_rule(nodes: &Cirru) -> Result<Vec<ImportMapPair>, String> {
   match nodes {
@@ -264,7 +276,9 @@ pub fn apply_code_changes(changes: &snapshot::ChangesDict) -> Result<(), String>
       file.defs.insert(def.to_owned(), code_to_calcit(code, ns, def, coord0.to_owned())?);
     }
   }
+  refs::drop_orphaned_refs(&program_code);
 
+  bump_program_generation();
   Ok(())
 }
 
@@ -290,5 +304,6 @@ pub fn clear_all_program_evaled_defs(init_ns: Arc<str>, reload_ns: Arc<str>, rel
       (*program).remove(&k);
     }
   }
+  bump_program_generation();
   Ok(())
 }
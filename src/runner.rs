@@ -1,5 +1,10 @@
+pub mod dep_graph;
+pub mod event_loop;
+pub mod expand;
+pub mod lint;
 pub mod preprocess;
 pub mod track;
+pub mod type_check;
 
 use std::sync::Arc;
 use std::vec;
@@ -26,6 +31,9 @@ pub fn evaluate_expr(expr: &Calcit, scope: &CalcitScope, file_ns: &str, call_sta
     | Calcit::Ref(..)
     | Calcit::Tuple { .. }
     | Calcit::Buffer(..)
+    | Calcit::Promise(..)
+    | Calcit::Transient(..)
+    | Calcit::LazySeq(..)
     | Calcit::CirruQuote(..)
     | Calcit::Proc(_)
     | Calcit::Macro { .. }
@@ -57,8 +65,8 @@ pub fn evaluate_expr(expr: &Calcit, scope: &CalcitScope, file_ns: &str, call_sta
     },
     Calcit::Recur(_) => unreachable!("recur not expected to be from symbol"),
     Calcit::RawCode(_, code) => unreachable!("raw code `{}` cannot be called", code),
-    Calcit::Set(_) => Err(CalcitErr::use_msg_stack("unexpected set for expr", call_stack)),
-    Calcit::Map(_) => Err(CalcitErr::use_msg_stack("unexpected map for expr", call_stack)),
+    Calcit::Set(_) | Calcit::SortedSet(_) => Err(CalcitErr::use_msg_stack("unexpected set for expr", call_stack)),
+    Calcit::Map(_) | Calcit::SortedMap(_) => Err(CalcitErr::use_msg_stack("unexpected map for expr", call_stack)),
     Calcit::Record { .. } => Err(CalcitErr::use_msg_stack("unexpected record for expr", call_stack)),
   }
 }
@@ -80,7 +88,14 @@ pub fn call_expr(
       } else {
         evaluate_args(rest_nodes, scope, file_ns, call_stack)?
       };
-      builtins::handle_proc(*p, &values, call_stack)
+      builtins::handle_proc(*p, &values, call_stack).map_err(|e| match e.location {
+        Some(_) => e,
+        // procs do not know where they are called
+        None => CalcitErr {
+          location: call_location(xs).map(Arc::new),
+          ..e
+        },
+      })
     }
     Calcit::Syntax(s, def_ns) => {
       if using_stack() {
@@ -99,7 +114,7 @@ pub fn call_expr(
       }
     }
     Calcit::Method(name, kind) => {
-      if *kind == MethodKind::Invoke {
+      if let MethodKind::Invoke(cache) = kind {
         let values = if spreading {
           evaluate_spreaded_args(rest_nodes, scope, file_ns, call_stack)?
         } else {
@@ -107,9 +122,9 @@ pub fn call_expr(
         };
         if using_stack() {
           let next_stack = call_stack.extend(file_ns, name, StackKind::Method, &Calcit::Nil, &values);
-          builtins::meta::invoke_method(name, &values, &next_stack)
+          builtins::meta::invoke_method(name, cache, &values, &next_stack)
         } else {
-          builtins::meta::invoke_method(name, &values, call_stack)
+          builtins::meta::invoke_method(name, cache, &values, call_stack)
         }
       } else {
         CalcitErr::err_str(format!("unknown method for rust runtime: {kind}"))
@@ -312,6 +327,32 @@ pub fn evaluate_symbol_from_program(
   }
 }
 
+/// location of a call expression, from the first symbol inside it that carries a coord
+pub fn call_location(xs: &CalcitList) -> Option<NodeLocation> {
+  fn find_leaf(xs: &CalcitList, depth: usize) -> Option<(NodeLocation, usize)> {
+    xs.iter().find_map(|x| match x {
+      Calcit::Symbol {
+        info,
+        location: Some(coord),
+        ..
+      }
+      | Calcit::Local(CalcitLocal {
+        info,
+        location: Some(coord),
+        ..
+      }) => Some((
+        NodeLocation::new(info.at_ns.to_owned(), info.at_def.to_owned(), coord.to_owned()),
+        depth,
+      )),
+      Calcit::List(ys) => find_leaf(ys, depth + 1),
+      _ => None,
+    })
+  }
+  let (leaf, depth) = find_leaf(xs, 0)?;
+  let size = leaf.coord.len().checked_sub(depth + 1)?;
+  Some(NodeLocation::new(leaf.ns, leaf.def, Arc::new(leaf.coord[..size].to_vec())))
+}
+
 pub fn parse_ns_def(s: &str) -> Option<(Arc<str>, Arc<str>)> {
   if !has_ns_part(s) {
     return None;
@@ -381,6 +422,18 @@ pub fn run_fn(values: &[Calcit], info: &CalcitFn, call_stack: &CallStackList) ->
   Ok(v)
 }
 
+/// calls a function or a proc passed in as a callback
+pub fn call_callback(f: &Calcit, args: &[Calcit], call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  match f {
+    Calcit::Fn { info, .. } => run_fn(args, info, call_stack),
+    Calcit::Proc(p) => builtins::handle_proc(*p, args, call_stack),
+    a => Err(CalcitErr::use_msg_stack(
+      format!("expected a function for callback, got: {a}"),
+      call_stack,
+    )),
+  }
+}
+
 /// quick path for `run_fn` which takes ownership of values
 pub fn run_fn_owned(values: Vec<Calcit>, info: &CalcitFn, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
   let mut body_scope = (*info.scope).to_owned();
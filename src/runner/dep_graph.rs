@@ -0,0 +1,297 @@
+//! dependency graphs of the program for `cr deps`, built from raw code without preprocessing.
+//! namespaces depend on what they `:require`, defs depend on defs referred by symbols in their code.
+
+use std::collections::{btree_set, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use crate::calcit::{self, Calcit};
+use crate::data::json::{format_json, JsonFormatOptions};
+use crate::program::{ImportRule, ProgramCodeData};
+use crate::runner::lint::{collect_symbols, resolve_raw_symbol};
+
+/// a directed graph with nodes in sorted order, so outputs are stable
+#[derive(Debug, Default)]
+pub struct DepGraph {
+  /// every node has an entry, even without dependencies
+  pub edges: BTreeMap<Arc<str>, BTreeSet<Arc<str>>>,
+}
+
+impl DepGraph {
+  fn add_node(&mut self, node: Arc<str>) {
+    self.edges.entry(node).or_default();
+  }
+
+  fn add_edge(&mut self, from: Arc<str>, to: Arc<str>) {
+    self.add_node(to.to_owned());
+    self.edges.entry(from).or_default().insert(to);
+  }
+
+  pub fn fan_out(&self, node: &str) -> usize {
+    self.edges.get(node).map_or(0, |xs| xs.len())
+  }
+
+  /// fan-in of all nodes, counted in one pass over edges
+  pub fn fan_ins(&self) -> HashMap<&str, usize> {
+    let mut counts: HashMap<&str, usize> = self.edges.keys().map(|x| (&**x, 0)).collect();
+    for target in self.edges.values().flatten() {
+      *counts.entry(target).or_default() += 1;
+    }
+    counts
+  }
+
+  /// one cycle for each strongly connected component, as a path that starts and ends with the same node.
+  /// a node depending on itself is a cycle too
+  pub fn find_cycles(&self) -> Vec<Vec<Arc<str>>> {
+    let mut cycles = vec![];
+    for component in self.strongly_connected() {
+      let start = &component[0];
+      if component.len() == 1 && !self.edges[start].contains(start) {
+        continue;
+      }
+      if let Some(path) = self.shortest_cycle(start, &component) {
+        cycles.push(path);
+      }
+    }
+    cycles.sort();
+    cycles
+  }
+
+  /// Tarjan's algorithm, nodes in each component are sorted
+  fn strongly_connected(&self) -> Vec<Vec<Arc<str>>> {
+    struct Tarjan<'a> {
+      graph: &'a DepGraph,
+      counter: usize,
+      index: HashMap<&'a str, usize>,
+      low: HashMap<&'a str, usize>,
+      stack: Vec<&'a Arc<str>>,
+      on_stack: BTreeSet<&'a str>,
+      components: Vec<Vec<Arc<str>>>,
+    }
+
+    impl<'a> Tarjan<'a> {
+      fn enter(&mut self, node: &'a Arc<str>) {
+        self.index.insert(node, self.counter);
+        self.low.insert(node, self.counter);
+        self.counter += 1;
+        self.stack.push(node);
+        self.on_stack.insert(node);
+      }
+
+      /// depth first search with frames kept in a vector, so long chains of dependencies do not overflow the stack
+      fn visit(&mut self, root: &'a Arc<str>) {
+        self.enter(root);
+        let mut frames: Vec<(&'a Arc<str>, btree_set::Iter<'a, Arc<str>>)> = vec![(root, self.graph.edges[root].iter())];
+        while let Some((node, nexts)) = frames.last_mut() {
+          let node: &'a Arc<str> = node;
+          if let Some(next) = nexts.next() {
+            if !self.index.contains_key(&**next) {
+              self.enter(next);
+              frames.push((next, self.graph.edges[next].iter()));
+            } else if self.on_stack.contains(&**next) {
+              let low = self.low[&**node].min(self.index[&**next]);
+              self.low.insert(node, low);
+            }
+            continue;
+          }
+          frames.pop();
+          if let Some((parent, _)) = frames.last() {
+            let low = self.low[&***parent].min(self.low[&**node]);
+            self.low.insert(parent, low);
+          }
+          if self.low[&**node] == self.index[&**node] {
+            let mut component = vec![];
+            while let Some(x) = self.stack.pop() {
+              self.on_stack.remove(&**x);
+              component.push(x.to_owned());
+              if x == node {
+                break;
+              }
+            }
+            component.sort();
+            self.components.push(component);
+          }
+        }
+      }
+    }
+
+    let mut tarjan = Tarjan {
+      graph: self,
+      counter: 0,
+      index: HashMap::new(),
+      low: HashMap::new(),
+      stack: vec![],
+      on_stack: BTreeSet::new(),
+      components: vec![],
+    };
+    for node in self.edges.keys() {
+      if !tarjan.index.contains_key(&**node) {
+        tarjan.visit(node);
+      }
+    }
+    tarjan.components
+  }
+
+  /// breadth first search inside a component, from `start` back to itself
+  fn shortest_cycle(&self, start: &Arc<str>, component: &[Arc<str>]) -> Option<Vec<Arc<str>>> {
+    let component: HashSet<&Arc<str>> = component.iter().collect();
+    let mut parents: HashMap<&Arc<str>, &Arc<str>> = HashMap::new();
+    let mut queue: VecDeque<&Arc<str>> = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+      for next in &self.edges[node] {
+        if next == start {
+          let mut path = vec![start.to_owned()];
+          let mut x = node;
+          while x != start {
+            path.push(x.to_owned());
+            x = parents[x];
+          }
+          path.push(start.to_owned());
+          path.reverse();
+          return Some(path);
+        }
+        if component.contains(next) && !parents.contains_key(next) {
+          parents.insert(next, node);
+          queue.push_back(next);
+        }
+      }
+    }
+    None
+  }
+
+  /// nodes with fan-in and fan-out, followed by cycles
+  pub fn format_human(&self) -> String {
+    let width = self.edges.keys().map(|x| x.len()).max().unwrap_or(0);
+    let fan_ins = self.fan_ins();
+    let mut lines: Vec<String> = vec![];
+    for node in self.edges.keys() {
+      lines.push(format!("{node:<width$}  in: {:<3} out: {}", fan_ins[&**node], self.fan_out(node)));
+    }
+    let cycles = self.find_cycles();
+    if cycles.is_empty() {
+      lines.push(String::from("no cycles found"));
+    } else {
+      lines.push(format!("found {} cycles:", cycles.len()));
+      for cycle in cycles {
+        lines.push(format!("  {}", cycle.join(" -> ")));
+      }
+    }
+    lines.join("\n")
+  }
+
+  /// Graphviz, edges in cycles are red
+  pub fn format_dot(&self) -> String {
+    let mut cycle_edges: BTreeSet<(&str, &str)> = BTreeSet::new();
+    let cycles = self.find_cycles();
+    for cycle in &cycles {
+      for pair in cycle.windows(2) {
+        cycle_edges.insert((&pair[0], &pair[1]));
+      }
+    }
+    let mut lines: Vec<String> = vec![String::from("digraph deps {"), String::from("  node [shape=box];")];
+    for (node, targets) in &self.edges {
+      lines.push(format!("  {node:?};"));
+      for target in targets {
+        if cycle_edges.contains(&(&**node, &**target)) {
+          lines.push(format!("  {node:?} -> {target:?} [color=red];"));
+        } else {
+          lines.push(format!("  {node:?} -> {target:?};"));
+        }
+      }
+    }
+    lines.push(String::from("}"));
+    lines.join("\n")
+  }
+
+  /// `{"nodes": [{"name", "fan-in", "fan-out"}], "edges": [{"from", "to"}], "cycles": [[...]]}`
+  pub fn format_json(&self) -> Result<String, String> {
+    let fan_ins = self.fan_ins();
+    let mut nodes: Vec<Calcit> = vec![];
+    let mut edges: Vec<Calcit> = vec![];
+    for (node, targets) in &self.edges {
+      let mut m = rpds::HashTrieMap::new_sync();
+      m.insert_mut(Calcit::tag("name"), Calcit::Str(node.to_owned()));
+      m.insert_mut(Calcit::tag("fan-in"), Calcit::Number(fan_ins[&**node] as f64));
+      m.insert_mut(Calcit::tag("fan-out"), Calcit::Number(targets.len() as f64));
+      nodes.push(Calcit::Map(m));
+      for target in targets {
+        let mut m = rpds::HashTrieMap::new_sync();
+        m.insert_mut(Calcit::tag("from"), Calcit::Str(node.to_owned()));
+        m.insert_mut(Calcit::tag("to"), Calcit::Str(target.to_owned()));
+        edges.push(Calcit::Map(m));
+      }
+    }
+    let cycles: Vec<Calcit> = self
+      .find_cycles()
+      .into_iter()
+      .map(|cycle| Calcit::from(cycle.into_iter().map(Calcit::Str).collect::<Vec<_>>()))
+      .collect();
+    let mut data = rpds::HashTrieMap::new_sync();
+    data.insert_mut(Calcit::tag("nodes"), Calcit::from(nodes));
+    data.insert_mut(Calcit::tag("edges"), Calcit::from(edges));
+    data.insert_mut(Calcit::tag("cycles"), Calcit::from(cycles));
+    format_json(
+      &Calcit::Map(data),
+      JsonFormatOptions {
+        indent: Some(2),
+        non_finite_as_null: false,
+      },
+    )
+  }
+}
+
+/// namespaces in the graph, core and generated `.$meta` namespaces are skipped
+fn is_graph_ns(ns: &str, only: Option<&[Arc<str>]>) -> bool {
+  if ns == calcit::CORE_NS || ns.ends_with(".$meta") {
+    return false;
+  }
+  match only {
+    Some(namespaces) => namespaces.iter().any(|x| &**x == ns),
+    None => true,
+  }
+}
+
+/// namespaces and the namespaces they `:require`, npm packages are not included
+pub fn ns_graph(code: &ProgramCodeData, only: Option<&[Arc<str>]>) -> DepGraph {
+  let mut graph = DepGraph::default();
+  for (ns, file) in code {
+    if !is_graph_ns(ns, only) {
+      continue;
+    }
+    graph.add_node(ns.to_owned());
+    for rule in file.import_map.values() {
+      let target = match &**rule {
+        ImportRule::NsAs(target) | ImportRule::NsReferDef(target, _) => target,
+        ImportRule::NsDefault(_) => continue,
+      };
+      if code.contains_key(target) && is_graph_ns(target, only) {
+        graph.add_edge(ns.to_owned(), target.to_owned());
+      }
+    }
+  }
+  graph
+}
+
+/// defs as `ns/def`, and defs they refer to. references generated by macros and recursive calls are not included
+pub fn def_graph(code: &ProgramCodeData, only: Option<&[Arc<str>]>) -> DepGraph {
+  let mut graph = DepGraph::default();
+  for (ns, file) in code {
+    if !is_graph_ns(ns, only) {
+      continue;
+    }
+    for (def, def_code) in &file.defs {
+      let path: Arc<str> = format!("{ns}/{def}").into();
+      graph.add_node(path.to_owned());
+      let mut symbols: Vec<Arc<str>> = vec![];
+      collect_symbols(def_code, &mut symbols);
+      for sym in symbols {
+        if let (Some((target_ns, target_def)), _) = resolve_raw_symbol(code, ns, &sym) {
+          // name of the def is in its own code, recursions are not distinguished from it
+          if is_graph_ns(&target_ns, only) && !(target_ns == *ns && target_def == *def) {
+            graph.add_edge(path.to_owned(), format!("{target_ns}/{target_def}").into());
+          }
+        }
+      }
+    }
+  }
+  graph
+}
@@ -0,0 +1,324 @@
+//! single-threaded event loop for timers and promises.
+//! tasks can be queued from any thread, but callbacks only run on the thread driving the loop,
+//! which is the main thread inside `track::exit_when_cleared`, or the caller of `await`.
+//!
+//! program code from other threads, like reloading and inspecting, runs with `lock_runtime`,
+//! which is also held during each callback so they never run together.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use crate::calcit::{Calcit, CalcitErr, CalcitPromise, PromiseReaction, PromiseState};
+use crate::call_stack::CallStackList;
+use crate::error_report::{self, ErrorReport, FailureKind};
+use crate::runner::{self, track};
+
+struct Timer {
+  due: Instant,
+  /// `Some` for timers created by `set-interval`
+  interval: Option<Duration>,
+  /// `None` for timers only keeping the process alive, like `async-sleep`
+  callback: Option<Calcit>,
+  call_stack: CallStackList,
+}
+
+enum Job {
+  Call(Calcit, Vec<Calcit>, CallStackList),
+  React(PromiseReaction, Result<Calcit, Calcit>),
+}
+
+#[derive(Default)]
+struct LoopState {
+  timers: BTreeMap<usize, Timer>,
+  jobs: VecDeque<Job>,
+  next_timer_id: usize,
+  /// rejected promises without handlers, reported when the loop gets idle
+  unhandled: Vec<Arc<CalcitPromise>>,
+}
+
+static LOOP_STATE: LazyLock<Mutex<LoopState>> = LazyLock::new(|| Mutex::new(LoopState::default()));
+
+/// wakes up the loop when tasks are added, or when tracked tasks are released
+static LOOP_SIGNAL: Condvar = Condvar::new();
+
+fn lock_state() -> MutexGuard<'static, LoopState> {
+  LOOP_STATE.lock().expect("lock event loop")
+}
+
+/// thread running program code, and how many times it locked the runtime
+#[derive(Default)]
+struct RuntimeOwner {
+  thread: Option<ThreadId>,
+  depth: usize,
+}
+
+static RUNTIME_OWNER: LazyLock<Mutex<RuntimeOwner>> = LazyLock::new(|| Mutex::new(RuntimeOwner::default()));
+
+static RUNTIME_RELEASED: Condvar = Condvar::new();
+
+/// releases the runtime when dropped
+pub struct RuntimeGuard(());
+
+impl Drop for RuntimeGuard {
+  fn drop(&mut self) {
+    let mut owner = RUNTIME_OWNER.lock().expect("lock runtime owner");
+    owner.depth -= 1;
+    if owner.depth == 0 {
+      owner.thread = None;
+      RUNTIME_RELEASED.notify_all();
+    }
+  }
+}
+
+/// waits until no other threads are running program code. it's reentrant,
+/// since callbacks may `await` inside a reload, which runs more callbacks on the same thread
+pub fn lock_runtime() -> RuntimeGuard {
+  let current = thread::current().id();
+  let mut owner = RUNTIME_OWNER.lock().expect("lock runtime owner");
+  loop {
+    match owner.thread {
+      None => {
+        owner.thread = Some(current);
+        break;
+      }
+      Some(t) if t == current => break,
+      Some(_) => owner = RUNTIME_RELEASED.wait(owner).expect("wait for runtime"),
+    }
+  }
+  owner.depth += 1;
+  RuntimeGuard(())
+}
+
+/// notify the thread waiting on the loop, used by `track` when pending tasks change
+pub fn wake_up() {
+  let _state = lock_state();
+  LOOP_SIGNAL.notify_all();
+}
+
+/// returns id of the timer, which can be used in `clear_timer`
+pub fn add_timer(delay: Duration, repeat: bool, callback: Option<Calcit>, call_stack: &CallStackList) -> usize {
+  let mut state = lock_state();
+  state.next_timer_id += 1;
+  let id = state.next_timer_id;
+  state.timers.insert(
+    id,
+    Timer {
+      due: Instant::now() + delay,
+      interval: if repeat { Some(delay) } else { None },
+      callback,
+      call_stack: call_stack.to_owned(),
+    },
+  );
+  LOOP_SIGNAL.notify_all();
+  id
+}
+
+/// returns `false` if the timer was already finished or cleared
+pub fn clear_timer(id: usize) -> bool {
+  let mut state = lock_state();
+  let existed = state.timers.remove(&id).is_some();
+  LOOP_SIGNAL.notify_all();
+  existed
+}
+
+fn queue_job(job: Job) {
+  let mut state = lock_state();
+  state.jobs.push_back(job);
+  LOOP_SIGNAL.notify_all();
+}
+
+/// settle a pending promise, nothing happens if it's already settled.
+/// fulfilling with another promise makes it follow that promise
+pub fn settle_promise(promise: &Arc<CalcitPromise>, outcome: Result<Calcit, Calcit>) {
+  if let Ok(Calcit::Promise(inner)) = &outcome {
+    if !Arc::ptr_eq(inner, promise) {
+      add_reaction(
+        inner,
+        PromiseReaction {
+          on_fulfilled: None,
+          on_rejected: None,
+          derived: promise.to_owned(),
+          call_stack: CallStackList::default(),
+        },
+      );
+      return;
+    }
+  }
+  let reactions = {
+    let mut state = promise.state.lock().expect("write promise");
+    if !matches!(&*state, PromiseState::Pending(_)) {
+      return;
+    }
+    let next = match &outcome {
+      Ok(v) => PromiseState::Fulfilled(v.to_owned()),
+      Err(e) => PromiseState::Rejected(e.to_owned()),
+    };
+    match std::mem::replace(&mut *state, next) {
+      PromiseState::Pending(reactions) => reactions,
+      _ => unreachable!("checked pending state"),
+    }
+  };
+  if outcome.is_err() && reactions.is_empty() {
+    lock_state().unhandled.push(promise.to_owned());
+  }
+  for reaction in reactions {
+    queue_job(Job::React(reaction, outcome.to_owned()));
+  }
+  wake_up();
+}
+
+/// handlers run in later turns of the loop, even if the promise is already settled
+pub fn add_reaction(promise: &Arc<CalcitPromise>, reaction: PromiseReaction) {
+  let settled = {
+    let mut state = promise.state.lock().expect("write promise");
+    match &mut *state {
+      PromiseState::Pending(reactions) => {
+        reactions.push(reaction);
+        return;
+      }
+      PromiseState::Fulfilled(v) => Ok(v.to_owned()),
+      PromiseState::Rejected(e) => Err(e.to_owned()),
+    }
+  };
+  if settled.is_err() {
+    mark_handled(promise);
+  }
+  queue_job(Job::React(reaction, settled));
+}
+
+fn mark_handled(promise: &Arc<CalcitPromise>) {
+  lock_state().unhandled.retain(|p| !Arc::ptr_eq(p, promise));
+}
+
+fn report_unhandled(state: &mut LoopState) {
+  for promise in state.unhandled.drain(..) {
+    if let Some(Err(e)) = promise.settled() {
+      eprintln!("[Error] unhandled promise rejection: {e}");
+    }
+  }
+}
+
+fn report_failure(e: &CalcitErr) {
+  let message = format!("[Error] event loop callback failed: {}", e.msg);
+  error_report::report(&ErrorReport {
+    message: &message,
+    ..ErrorReport::from_err(FailureKind::Runtime, e)
+  });
+}
+
+fn run_job(job: Job) {
+  match job {
+    Job::Call(f, args, call_stack) => {
+      if let Err(e) = runner::call_callback(&f, &args, &call_stack) {
+        report_failure(&e);
+      }
+    }
+    Job::React(reaction, outcome) => {
+      let handler = match &outcome {
+        Ok(_) => &reaction.on_fulfilled,
+        Err(_) => &reaction.on_rejected,
+      };
+      let next = match (handler, outcome) {
+        (Some(f), Ok(v) | Err(v)) => {
+          runner::call_callback(f, &[v], &reaction.call_stack).map_err(|e| e.to_error_record(&reaction.call_stack))
+        }
+        (None, passed) => passed,
+      };
+      settle_promise(&reaction.derived, next);
+    }
+  }
+}
+
+/// runs tasks until `finished` returns true.
+/// returns `false` when there's nothing left to wait for, which also means `finished` may never return true
+fn drive(finished: impl Fn() -> bool) -> bool {
+  loop {
+    if finished() {
+      return true;
+    }
+    // locked before the loop state, same order as callbacks adding timers
+    let runtime = lock_runtime();
+    let mut state = lock_state();
+    if let Some(job) = state.jobs.pop_front() {
+      drop(state);
+      run_job(job);
+      continue;
+    }
+    report_unhandled(&mut state);
+    let now = Instant::now();
+    let next_timer = state.timers.iter().min_by_key(|(id, t)| (t.due, **id)).map(|(id, t)| (*id, t.due));
+    match next_timer {
+      Some((id, due)) if due <= now => {
+        let timer = state.timers.remove(&id).expect("timer exists");
+        if let Some(interval) = timer.interval {
+          // schedule next tick before running, callback may clear it
+          state.timers.insert(
+            id,
+            Timer {
+              due: now + interval,
+              interval: timer.interval,
+              callback: timer.callback.to_owned(),
+              call_stack: timer.call_stack.to_owned(),
+            },
+          );
+        }
+        drop(state);
+        if let Some(f) = timer.callback {
+          run_job(Job::Call(f, vec![], timer.call_stack));
+        }
+      }
+      Some((_, due)) => {
+        drop(runtime);
+        drop(LOOP_SIGNAL.wait_timeout(state, due - now).expect("wait for timer"));
+      }
+      None => {
+        if track::count_pending_tasks() == 0 {
+          return false;
+        }
+        drop(runtime);
+        drop(LOOP_SIGNAL.wait(state).expect("wait for tasks"));
+      }
+    }
+  }
+}
+
+/// keep running until no timers, jobs or tracked tasks remain
+pub fn run_until_cleared() {
+  drive(|| false);
+}
+
+/// block current thread, running the loop, until the promise gets settled
+pub fn await_promise(promise: &Arc<CalcitPromise>, call_stack: &CallStackList) -> Result<Calcit, CalcitErr> {
+  drive(|| promise.settled().is_some());
+  match promise.settled() {
+    Some(Ok(v)) => Ok(v),
+    Some(Err(e)) => {
+      mark_handled(promise);
+      Err(rejection_to_err(e, call_stack))
+    }
+    None => Err(CalcitErr::use_msg_stack(
+      format!("await on a promise that never settles: {}", Calcit::Promise(promise.to_owned())),
+      call_stack,
+    )),
+  }
+}
+
+/// errors from callbacks are rejected as error records, and are thrown again as they were.
+/// other values become payloads, so handlers of `try` still get them
+fn rejection_to_err(e: Calcit, call_stack: &CallStackList) -> CalcitErr {
+  if let Some(err) = CalcitErr::from_error_record(&e) {
+    return CalcitErr {
+      stack: call_stack.to_owned(),
+      ..err
+    };
+  }
+  match e {
+    Calcit::Str(s) => CalcitErr::use_msg_stack(&*s, call_stack),
+    e => CalcitErr {
+      payload: Some(Arc::new(e.to_owned())),
+      ..CalcitErr::use_msg_stack(e.to_string(), call_stack)
+    },
+  }
+}
@@ -0,0 +1,91 @@
+//! `cr expand`, shows code of a def after preprocessing, and optionally each macro applied to it.
+//! in the output, imports are written as `ns/def` and methods as `&invoke:name`, other leaves are plain.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use cirru_parser::{Cirru, CirruWriterOptions};
+
+use crate::calcit::{Calcit, CalcitImport, CalcitLocal, LocatedWarning};
+use crate::call_stack::CallStackList;
+use crate::program;
+use crate::runner::preprocess::{self, MacroStep};
+
+/// preprocessed code of a def, with macro steps when `with_steps` is set
+pub struct Expansion {
+  pub code: Calcit,
+  pub steps: Vec<MacroStep>,
+  pub warnings: Vec<LocatedWarning>,
+}
+
+/// defs the code depends on are preprocessed first, so steps are only from code of this def
+pub fn expand_def(ns: &str, def: &str, with_steps: bool) -> Result<Expansion, String> {
+  let code = program::lookup_def_code(ns, def).ok_or_else(|| format!("unknown def: {ns}/{def}"))?;
+  let check_warnings: &RefCell<Vec<LocatedWarning>> = &RefCell::new(vec![]);
+  let call_stack = CallStackList::default();
+  preprocess::preprocess_ns_def(ns, def, &RefCell::new(vec![]), &call_stack).map_err(|e| e.msg)?;
+
+  if with_steps {
+    preprocess::record_macro_steps();
+  }
+  let resolved = preprocess::preprocess_expr(&code, &HashSet::new(), ns, check_warnings, &call_stack);
+  let steps = preprocess::take_macro_steps();
+  Ok(Expansion {
+    code: resolved.map_err(|e| e.msg)?,
+    steps,
+    warnings: check_warnings.take(),
+  })
+}
+
+fn code_to_cirru(x: &Calcit) -> Cirru {
+  match x {
+    Calcit::List(ys) => Cirru::List(ys.iter().map(code_to_cirru).collect()),
+    Calcit::Symbol { sym, .. } => Cirru::Leaf(sym.to_owned()),
+    Calcit::Local(CalcitLocal { sym, .. }) => Cirru::Leaf(sym.to_owned()),
+    Calcit::Import(CalcitImport { ns, def, .. }) => Cirru::leaf(format!("{ns}/{def}")),
+    Calcit::Registered(alias) => Cirru::Leaf(alias.to_owned()),
+    Calcit::Syntax(s, _ns) => Cirru::leaf(s.as_ref()),
+    Calcit::Proc(p) => Cirru::leaf(p.as_ref()),
+    Calcit::Method(name, kind) => Cirru::leaf(format!("&{kind}:{name}")),
+    Calcit::Str(s) => Cirru::leaf(format!("|{s}")),
+    a => Cirru::leaf(a.to_string()),
+  }
+}
+
+/// code in Cirru syntax, indented with `indent` spaces
+pub fn format_code(x: &Calcit, indent: usize) -> String {
+  let text = match code_to_cirru(x) {
+    leaf @ Cirru::Leaf(_) => leaf.to_string(),
+    list => match cirru_parser::format(&[list], CirruWriterOptions { use_inline: false }) {
+      Ok(s) => s,
+      Err(e) => format!("failed to format code: {e}"),
+    },
+  };
+  let prefix = " ".repeat(indent);
+  text
+    .trim()
+    .lines()
+    .map(|line| format!("{prefix}{line}"))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+impl Expansion {
+  /// steps in order of applications, then the preprocessed code
+  pub fn format(&self) -> String {
+    let mut chunks: Vec<String> = vec![];
+    for (idx, step) in self.steps.iter().enumerate() {
+      chunks.push(format!(
+        "step {}: {}/{} @{}\n{}\n  =>\n{}",
+        idx + 1,
+        step.macro_ns,
+        step.macro_name,
+        step.location,
+        format_code(&step.from, 2),
+        format_code(&step.to, 2)
+      ));
+    }
+    chunks.push(format_code(&self.code, 0));
+    chunks.join("\n\n")
+  }
+}
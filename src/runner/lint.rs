@@ -0,0 +1,355 @@
+//! static checks for `cr check`. every def in app namespaces gets preprocessed, reachable or not,
+//! and the program is never run.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::builtins::is_proc_name;
+use crate::calcit::{self, Calcit, CalcitLocal, CalcitSyntax, LocatedWarning, NodeLocation, WarningKind};
+use crate::call_stack::CallStackList;
+use crate::data::json::{format_json, JsonFormatOptions};
+use crate::program::{self, ImportRule, ProgramCodeData};
+use crate::runner::{self, preprocess};
+
+/// results of checking, both lists are sorted by location
+#[derive(Debug, Default)]
+pub struct CheckReport {
+  /// failures of preprocessing, like unknown namespaces or broken macros
+  pub errors: Vec<(NodeLocation, String)>,
+  pub warnings: Vec<LocatedWarning>,
+}
+
+impl CheckReport {
+  pub fn is_empty(&self) -> bool {
+    self.errors.is_empty() && self.warnings.is_empty()
+  }
+
+  /// warnings and errors in lines, ends with a summary
+  pub fn format_human(&self) -> String {
+    let mut lines: Vec<String> = vec![];
+    for (location, msg) in &self.errors {
+      lines.push(format!("[Error] {} @{location}", msg.trim()));
+    }
+    for warn in &self.warnings {
+      lines.push(warn.to_string());
+    }
+    lines.push(format!("found {} errors and {} warnings", self.errors.len(), self.warnings.len()));
+    lines.join("\n")
+  }
+
+  /// a list of `{"kind", "message", "ns", "def", "coord"}`, kind of errors is `"error"`
+  pub fn format_json(&self) -> Result<String, String> {
+    let item = |kind: &str, msg: &str, location: &NodeLocation| {
+      let mut m = rpds::HashTrieMap::new_sync();
+      m.insert_mut(Calcit::tag("kind"), Calcit::new_str(kind));
+      m.insert_mut(Calcit::tag("message"), Calcit::new_str(msg));
+      m.insert_mut(Calcit::tag("ns"), Calcit::Str(location.ns.to_owned()));
+      m.insert_mut(Calcit::tag("def"), Calcit::Str(location.def.to_owned()));
+      let coord: Vec<Calcit> = location.coord.iter().map(|x| Calcit::Number(*x as f64)).collect();
+      m.insert_mut(Calcit::tag("coord"), Calcit::from(coord));
+      Calcit::Map(m)
+    };
+    let mut xs: Vec<Calcit> = vec![];
+    for (location, msg) in &self.errors {
+      xs.push(item("error", msg.trim(), location));
+    }
+    for warn in &self.warnings {
+      xs.push(item(&warn.kind().to_string(), warn.message(), warn.location()));
+    }
+    format_json(
+      &Calcit::from(xs),
+      JsonFormatOptions {
+        indent: Some(2),
+        non_finite_as_null: false,
+      },
+    )
+  }
+}
+
+/// preprocess all defs in `app_ns` and run lint rules on them.
+/// `entries` are `ns/def` strings of entry functions, which are not reported as unused
+pub fn check_program(app_ns: &[Arc<str>], entries: &[Arc<str>]) -> CheckReport {
+  let code: ProgramCodeData = { program::PROGRAM_CODE_DATA.read().expect("read program code").to_owned() };
+  let app_set: HashSet<&str> = app_ns.iter().map(|ns| &**ns).collect();
+  let mut report = CheckReport::default();
+  let mut warnings: Vec<LocatedWarning> = vec![];
+
+  let mut namespaces: Vec<&Arc<str>> = app_ns.iter().filter(|ns| code.contains_key(*ns)).collect();
+  namespaces.sort();
+  namespaces.dedup();
+
+  // code of defs is recorded in the same pass that reports warnings of preprocessing
+  preprocess::record_resolved_defs();
+  for ns in &namespaces {
+    let mut defs: Vec<&Arc<str>> = code[*ns].defs.keys().collect();
+    defs.sort();
+    for def in defs {
+      let check_warnings: &RefCell<Vec<LocatedWarning>> = &RefCell::new(vec![]);
+      if let Err(e) = preprocess::preprocess_ns_def(ns, def, check_warnings, &CallStackList::default()) {
+        let location = match &e.location {
+          Some(l) => (**l).to_owned(),
+          None => NodeLocation::new((*ns).to_owned(), def.to_owned(), Arc::new(vec![])),
+        };
+        report.errors.push((location, e.msg));
+        warnings.extend(e.warnings);
+      }
+      warnings.extend(check_warnings.take());
+    }
+  }
+  let resolved_defs = preprocess::take_resolved_defs();
+
+  for ns in namespaces {
+    let file = &code[ns];
+    let mut defs: Vec<&Arc<str>> = file.defs.keys().collect();
+    defs.sort();
+
+    // names a local binding may hide in this file
+    let mut file_names: HashSet<Arc<str>> = file.defs.keys().cloned().collect();
+    for (alias, rule) in &file.import_map {
+      if let ImportRule::NsReferDef(..) = &**rule {
+        file_names.insert(alias.to_owned());
+      }
+    }
+
+    for def in defs {
+      if let Some(resolved) = resolved_defs.get(&(ns.to_owned(), def.to_owned())) {
+        let mut locals = LocalsLint {
+          ns,
+          file_names: &file_names,
+          scope: vec![],
+          warnings: &mut warnings,
+        };
+        locals.walk(resolved);
+      }
+    }
+  }
+
+  warnings.extend(lint_references(&code, &app_set, entries));
+
+  // only warnings of app code, even if preprocessing reached into modules
+  warnings.retain(|w| app_set.contains(&*w.location().ns));
+  let mut seen: HashSet<String> = HashSet::new();
+  warnings.retain(|w| seen.insert(format!("{} {w}", w.kind())));
+  warnings.sort_by_key(|w| location_key(w.location()));
+  report.errors.sort_by_key(|e| location_key(&e.0));
+  report.warnings = warnings;
+  report
+}
+
+fn location_key(location: &NodeLocation) -> (Arc<str>, Arc<str>, Arc<Vec<u8>>) {
+  (location.ns.to_owned(), location.def.to_owned(), location.coord.to_owned())
+}
+
+struct LocalBinding {
+  local: CalcitLocal,
+  /// written in code of the checked file, not generated by macros
+  from_user: bool,
+  /// bound by `&let`, arguments of functions are not reported when unused
+  from_let: bool,
+  used: bool,
+}
+
+/// walks preprocessed code of a def, for unused `&let` bindings and shadowed bindings
+struct LocalsLint<'a> {
+  ns: &'a str,
+  file_names: &'a HashSet<Arc<str>>,
+  scope: Vec<LocalBinding>,
+  warnings: &'a mut Vec<LocatedWarning>,
+}
+
+impl LocalsLint<'_> {
+  fn location_of(local: &CalcitLocal) -> NodeLocation {
+    NodeLocation::new(
+      local.info.at_ns.to_owned(),
+      local.info.at_def.to_owned(),
+      local.location.to_owned().unwrap_or_default(),
+    )
+  }
+
+  fn bind(&mut self, local: &CalcitLocal, from_let: bool) {
+    let from_user = local.location.is_some() && &*local.info.at_ns == self.ns;
+    if from_user && !local.sym.starts_with('_') {
+      let sym = &local.sym;
+      let shadowed = self.scope.iter().any(|b| b.from_user && b.local.sym == *sym);
+      let msg = if shadowed {
+        Some(format!("[Warn] local `{sym}` shadows an outer binding in {}", local.info.at_def))
+      } else if self.file_names.contains(sym) {
+        Some(format!("[Warn] local `{sym}` shadows `{sym}` of {}", self.ns))
+      } else {
+        None
+      };
+      if let Some(msg) = msg {
+        self
+          .warnings
+          .push(LocatedWarning::new(WarningKind::Shadowing, msg, Self::location_of(local)));
+      }
+    }
+    self.scope.push(LocalBinding {
+      local: local.to_owned(),
+      from_user,
+      from_let,
+      used: false,
+    });
+  }
+
+  fn unbind(&mut self, size: usize) {
+    while self.scope.len() > size {
+      let b = self.scope.pop().expect("binding in scope");
+      if b.from_let && b.from_user && !b.used && !b.local.sym.starts_with('_') {
+        self.warnings.push(LocatedWarning::new(
+          WarningKind::UnusedLocal,
+          format!("[Warn] unused local `{}` in {}", b.local.sym, b.local.info.at_def),
+          Self::location_of(&b.local),
+        ));
+      }
+    }
+  }
+
+  fn walk(&mut self, code: &Calcit) {
+    match code {
+      Calcit::Local(CalcitLocal { sym, .. }) => {
+        if let Some(b) = self.scope.iter_mut().rev().find(|b| b.local.sym == *sym) {
+          b.used = true;
+        }
+      }
+      Calcit::List(xs) => match xs.first() {
+        Some(Calcit::Syntax(CalcitSyntax::Quote, _)) => (),
+        Some(Calcit::Syntax(CalcitSyntax::Defn | CalcitSyntax::Defmacro, _)) => {
+          let size = self.scope.len();
+          if let Some(Calcit::List(args)) = xs.get(2) {
+            for arg in args.iter() {
+              if let Calcit::Local(local) = arg {
+                self.bind(local, false);
+              }
+            }
+          }
+          for x in xs.iter().skip(3) {
+            self.walk(x);
+          }
+          self.unbind(size);
+        }
+        Some(Calcit::Syntax(CalcitSyntax::CoreLet, _)) => {
+          let size = self.scope.len();
+          if let Some(Calcit::List(pair)) = xs.get(1) {
+            if let (Some(Calcit::Local(local)), Some(value)) = (pair.first(), pair.get(1)) {
+              // value is evaluated before the binding exists
+              self.walk(value);
+              self.bind(local, true);
+            }
+          }
+          for x in xs.iter().skip(2) {
+            self.walk(x);
+          }
+          self.unbind(size);
+        }
+        _ => {
+          for x in xs.iter() {
+            self.walk(x);
+          }
+        }
+      },
+      _ => (),
+    }
+  }
+}
+
+/// `(ns, def)` of a definition
+type DefPath = (Arc<str>, Arc<str>);
+
+/// resolves a symbol in raw code like preprocessing does, without locals.
+/// returns target `(ns, def)` and the import entry it goes through
+pub(crate) fn resolve_raw_symbol(code: &ProgramCodeData, ns: &str, sym: &str) -> (Option<DefPath>, Option<Arc<str>>) {
+  let file = match code.get(ns) {
+    Some(f) => f,
+    None => return (None, None),
+  };
+  if let Some((alias, def)) = runner::parse_ns_def(sym) {
+    return match file.import_map.get(&alias).map(|r| &**r) {
+      Some(ImportRule::NsAs(target)) => (Some((target.to_owned(), def)), Some(alias)),
+      Some(_) => (None, Some(alias)),
+      None if code.get(&alias).is_some_and(|f| f.defs.contains_key(&def)) => (Some((alias, def)), None),
+      None => (None, None),
+    };
+  }
+  if CalcitSyntax::is_valid(sym) || is_proc_name(sym) {
+    (None, None)
+  } else if ns != calcit::CORE_NS && code.get(calcit::CORE_NS).is_some_and(|f| f.defs.contains_key(sym)) {
+    (Some((calcit::CORE_NS.into(), sym.into())), None)
+  } else if file.defs.contains_key(sym) {
+    (Some((ns.into(), sym.into())), None)
+  } else {
+    match file.import_map.get(sym).map(|r| &**r) {
+      Some(ImportRule::NsReferDef(target, def)) => (Some((target.to_owned(), def.to_owned())), Some(sym.into())),
+      Some(_) => (None, Some(sym.into())),
+      None => (None, None),
+    }
+  }
+}
+
+pub(crate) fn collect_symbols(code: &Calcit, collected: &mut Vec<Arc<str>>) {
+  match code {
+    Calcit::Symbol { sym, .. } => collected.push(sym.to_owned()),
+    Calcit::List(xs) => {
+      for x in xs.iter() {
+        collect_symbols(x, collected);
+      }
+    }
+    _ => (),
+  }
+}
+
+/// unused imports of app namespaces, and defs of app namespaces not referred by any other def.
+/// symbols are collected from raw code, so references generated by macros are missed
+fn lint_references(code: &ProgramCodeData, app_set: &HashSet<&str>, entries: &[Arc<str>]) -> Vec<LocatedWarning> {
+  let mut used_imports: HashSet<(Arc<str>, Arc<str>)> = HashSet::new();
+  let mut used_defs: HashSet<DefPath> = HashSet::new();
+  for (ns, file) in code {
+    for (def, def_code) in &file.defs {
+      let mut symbols: Vec<Arc<str>> = vec![];
+      collect_symbols(def_code, &mut symbols);
+      for sym in symbols {
+        let (target, import) = resolve_raw_symbol(code, ns, &sym);
+        if let Some(alias) = import {
+          used_imports.insert((ns.to_owned(), alias));
+        }
+        if let Some(target) = target {
+          // recursive calls do not count
+          if !(target.0 == *ns && target.1 == *def) {
+            used_defs.insert(target);
+          }
+        }
+      }
+    }
+  }
+
+  let entry_set: HashSet<&str> = entries.iter().map(|e| &**e).collect();
+  let mut warnings: Vec<LocatedWarning> = vec![];
+  for (ns, file) in code {
+    if !app_set.contains(&**ns) {
+      continue;
+    }
+    for (alias, rule) in &file.import_map {
+      if !used_imports.contains(&(ns.to_owned(), alias.to_owned())) {
+        let target = match &**rule {
+          ImportRule::NsAs(target) | ImportRule::NsReferDef(target, _) | ImportRule::NsDefault(target) => target,
+        };
+        warnings.push(LocatedWarning::new(
+          WarningKind::UnusedImport,
+          format!("[Warn] unused import `{alias}` from {target} in {ns}"),
+          NodeLocation::new(ns.to_owned(), "ns".into(), Arc::new(vec![])),
+        ));
+      }
+    }
+    for def in file.defs.keys() {
+      if !used_defs.contains(&(ns.to_owned(), def.to_owned())) && !entry_set.contains(format!("{ns}/{def}").as_str()) {
+        warnings.push(LocatedWarning::new(
+          WarningKind::UnusedDef,
+          format!("[Warn] unused def `{ns}/{def}`"),
+          NodeLocation::new(ns.to_owned(), def.to_owned(), Arc::new(vec![])),
+        ));
+      }
+    }
+  }
+  warnings
+}
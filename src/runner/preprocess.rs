@@ -1,20 +1,63 @@
 use crate::{
-  builtins::{is_js_syntax_procs, is_proc_name, is_registered_proc},
+  builtins::{self, is_js_syntax_procs, is_proc_name, is_registered_proc},
   calcit::{
     self, Calcit, CalcitArgLabel, CalcitErr, CalcitFnArgs, CalcitImport, CalcitList, CalcitLocal, CalcitProc, CalcitScope,
-    CalcitSymbolInfo, CalcitSyntax, CalcitThunk, CalcitThunkInfo, ImportInfo, LocatedWarning, NodeLocation, RawCodeType, GENERATED_DEF,
+    CalcitSymbolInfo, CalcitSyntax, CalcitThunk, CalcitThunkInfo, ImportInfo, LocatedWarning, MethodKind, NodeLocation, RawCodeType,
+    WarningKind, GENERATED_DEF,
   },
   call_stack::{CallStackList, StackKind},
-  codegen, program, runner,
+  codegen, program,
+  runner::{self, type_check},
 };
 
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::{cell::RefCell, vec};
 
 use im_ternary_tree::TernaryTreeList;
 use strum::ParseError;
 
+/// an application of a macro during preprocessing, recorded for `cr expand --steps`
+#[derive(Debug, Clone)]
+pub struct MacroStep {
+  pub macro_ns: Arc<str>,
+  pub macro_name: Arc<str>,
+  pub location: NodeLocation,
+  /// the call, with arguments not preprocessed yet
+  pub from: Calcit,
+  /// code returned by the macro
+  pub to: Calcit,
+}
+
+/// `None` when not recording
+static MACRO_STEPS: LazyLock<Mutex<Option<Vec<MacroStep>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// start recording applications of macros, previous records are dropped
+pub fn record_macro_steps() {
+  *MACRO_STEPS.lock().expect("lock macro steps") = Some(vec![]);
+}
+
+/// stop recording and returns steps in order of applications
+pub fn take_macro_steps() -> Vec<MacroStep> {
+  MACRO_STEPS.lock().expect("lock macro steps").take().unwrap_or_default()
+}
+
+/// preprocessed code of defs by `(ns, def)`
+pub type ResolvedDefs = HashMap<(Arc<str>, Arc<str>), Calcit>;
+
+/// `None` when not recording
+static RESOLVED_DEFS: LazyLock<Mutex<Option<ResolvedDefs>>> = LazyLock::new(|| Mutex::new(None));
+
+/// start recording preprocessed code of defs, used by `cr check` to lint code without preprocessing it again
+pub fn record_resolved_defs() {
+  *RESOLVED_DEFS.lock().expect("lock resolved defs") = Some(HashMap::new());
+}
+
+/// stop recording and returns code of defs preprocessed since then
+pub fn take_resolved_defs() -> ResolvedDefs {
+  RESOLVED_DEFS.lock().expect("lock resolved defs").take().unwrap_or_default()
+}
+
 /// returns the resolved symbol(only functions and macros are used),
 /// if code related is not preprocessed, do it internally.
 pub fn preprocess_ns_def(
@@ -41,12 +84,15 @@ pub fn preprocess_ns_def(
           let next_stack = call_stack.extend(ns, def, StackKind::Fn, &code, &[]);
 
           let resolved_code = preprocess_expr(&code, &HashSet::new(), ns, check_warnings, &next_stack)?;
+          type_check::check_def(&code, &resolved_code, ns, def, check_warnings);
+          builtins::protocols::declare_protocol(ns, def, &resolved_code);
+          builtins::refs::drop_replaced_ref(ns, def, &resolved_code);
+          if let Some(defs) = RESOLVED_DEFS.lock().expect("lock resolved defs").as_mut() {
+            defs.insert((ns.into(), def.into()), resolved_code.to_owned());
+          }
           // println!("\n resolve code to run: {:?}", resolved_code);
           let v = if is_fn_or_macro(&resolved_code) {
-            match runner::evaluate_expr(&resolved_code, &CalcitScope::default(), ns, &next_stack) {
-              Ok(ret) => ret,
-              Err(e) => return Err(e),
-            }
+            runner::evaluate_expr(&resolved_code, &CalcitScope::default(), ns, &next_stack)?
           } else {
             Calcit::Thunk(CalcitThunk::Code {
               code: Arc::new(resolved_code),
@@ -253,6 +299,7 @@ pub fn preprocess_expr(
                 }
                 let mut warnings = check_warnings.borrow_mut();
                 warnings.push(LocatedWarning::new(
+                  WarningKind::UnknownSymbol,
                   format!("[Warn] unknown `{def}` in {def_ns}/{at_def}, locals {{{}}}", names.join(" ")),
                   NodeLocation::new(def_ns.to_owned(), at_def.to_owned(), location.to_owned().unwrap_or_default()),
                 ));
@@ -288,6 +335,7 @@ pub fn preprocess_expr(
         coord: Arc::from(vec![]),
       };
       warnings.push(LocatedWarning::new(
+        WarningKind::Unexpected,
         format!("[Warn] unexpected data during preprocess: {expr:?}"),
         loc,
       ));
@@ -306,7 +354,7 @@ fn preprocess_list_call(
   let head = &xs[0];
   let head_form = preprocess_expr(head, scope_defs, file_ns, check_warnings, call_stack)?;
   let args = xs.drop_left();
-  let def_name = grab_def_name(head);
+  let call_location = grab_call_location(head, file_ns);
 
   let head_value = match &head_form {
     Calcit::Import(CalcitImport { ns, def, .. }) => preprocess_ns_def(ns, def, check_warnings, call_stack)?,
@@ -342,31 +390,43 @@ fn preprocess_list_call(
       let next_stack = call_stack.extend(&info.def_ns, &info.name, StackKind::Macro, &code, &args.to_vec());
 
       let mut body_scope = CalcitScope::default();
-
-      loop {
-        // need to handle recursion
-        // println!("evaling line: {:?}", body);
-        runner::bind_marked_args(&mut body_scope, &info.args, &current_values, &next_stack)?;
-        let code = runner::evaluate_lines(&info.body.to_vec(), &body_scope, file_ns, &next_stack)?;
-        match code {
-          Calcit::Recur(ys) => {
-            current_values = ys;
-          }
-          _ => {
-            // println!("gen code: {} {}", code, &code.lisp_str());
-            return preprocess_expr(&code, scope_defs, file_ns, check_warnings, &next_stack);
+      let code = {
+        // also used by functions called from the macro, and left before expanding the code it returns
+        let _seed = builtins::meta::enter_gensym_seed(&info.def_ns, &info.name, &call_location, &code);
+        loop {
+          // need to handle recursion
+          // println!("evaling line: {:?}", body);
+          runner::bind_marked_args(&mut body_scope, &info.args, &current_values, &next_stack)?;
+          let code = runner::evaluate_lines(&info.body.to_vec(), &body_scope, file_ns, &next_stack)?;
+          match code {
+            Calcit::Recur(ys) => {
+              current_values = ys;
+            }
+            _ => break code,
           }
         }
+      };
+
+      // println!("gen code: {} {}", code, &code.lisp_str());
+      if let Some(steps) = MACRO_STEPS.lock().expect("lock macro steps").as_mut() {
+        steps.push(MacroStep {
+          macro_ns: info.def_ns.to_owned(),
+          macro_name: info.name.to_owned(),
+          location: call_location.to_owned(),
+          from: Calcit::List(Arc::new(xs.to_owned())),
+          to: code.to_owned(),
+        });
       }
+      preprocess_expr(&code, scope_defs, file_ns, check_warnings, &next_stack)
     }
 
     Some(Calcit::Fn { info, .. }) => {
       match &*info.args {
         CalcitFnArgs::MarkedArgs(xs) => {
-          check_fn_marked_args(xs, &args, file_ns, &info.name, &def_name, check_warnings);
+          check_fn_marked_args(xs, &args, &info.name, &call_location, check_warnings);
         }
         CalcitFnArgs::Args(xs) => {
-          check_fn_args(xs, &args, file_ns, &info.name, &def_name, check_warnings);
+          check_fn_args(xs, &args, &info.name, &call_location, check_warnings);
         }
       }
       let mut ys = CalcitList::new_inner_from(&[head_form.to_owned()]);
@@ -482,8 +542,13 @@ fn preprocess_list_call(
         call_stack,
       )),
 
-      Calcit::Method(_, _) => {
-        let mut ys = CalcitList::new_inner_from(&[head.to_owned()]);
+      Calcit::Method(name, kind) => {
+        // every call site gets its own cache, code from macros may share nodes
+        let head_form = match kind {
+          MethodKind::Invoke(_) => Calcit::Method(name.to_owned(), MethodKind::Invoke(Arc::default())),
+          _ => head.to_owned(),
+        };
+        let mut ys = CalcitList::new_inner_from(&[head_form]);
         let mut has_spread = false;
 
         args.traverse_result::<CalcitErr>(&mut |a| {
@@ -539,9 +604,8 @@ fn preprocess_list_call(
 fn check_fn_marked_args(
   defined_args: &[CalcitArgLabel],
   params: &CalcitList,
-  file_ns: &str,
   f_name: &str,
-  def_name: &str,
+  location: &NodeLocation,
   check_warnings: &RefCell<Vec<LocatedWarning>>,
 ) {
   let mut i = 0;
@@ -575,26 +639,26 @@ fn check_fn_marked_args(
           continue;
         } else {
           let mut warnings = check_warnings.borrow_mut();
-          let loc = NodeLocation::new(Arc::from(file_ns), Arc::from(GENERATED_DEF), Arc::from(vec![]));
           warnings.push(LocatedWarning::new(
+            WarningKind::Arity,
             format!(
               "[Warn] lack of args in {} `{:?}` with `{}`, at {}/{}",
-              f_name, defined_args, params, file_ns, def_name
+              f_name, defined_args, params, location.ns, location.def
             ),
-            loc,
+            location.to_owned(),
           ));
           return;
         }
       }
       (None, Some(_)) => {
         let mut warnings = check_warnings.borrow_mut();
-        let loc = NodeLocation::new(Arc::from(file_ns), Arc::from(GENERATED_DEF), Arc::from(vec![]));
         warnings.push(LocatedWarning::new(
+          WarningKind::Arity,
           format!(
             "[Warn] too many args for {} `{:?}` with `{}`, at {}/{}",
-            f_name, defined_args, params, file_ns, def_name
+            f_name, defined_args, params, location.ns, location.def
           ),
-          loc,
+          location.to_owned(),
         ));
         return;
       }
@@ -611,9 +675,8 @@ fn check_fn_marked_args(
 fn check_fn_args(
   defined_args: &[u16],
   params: &CalcitList,
-  file_ns: &str,
   f_name: &str,
-  def_name: &str,
+  location: &NodeLocation,
   check_warnings: &RefCell<Vec<LocatedWarning>>,
 ) {
   let expected_size = defined_args.len();
@@ -623,14 +686,14 @@ fn check_fn_args(
     if let Calcit::Syntax(CalcitSyntax::ArgSpread, _) = item {
       if expected_size < (idx + 1) {
         let mut warnings = check_warnings.borrow_mut();
-        let loc = NodeLocation::new(Arc::from(file_ns), Arc::from(GENERATED_DEF), Arc::from(vec![]));
         let args = CalcitLocal::display_args(defined_args);
         warnings.push(LocatedWarning::new(
+          WarningKind::Arity,
           format!(
             "[Warn] expected {} args in {} `{}`, got spreading form `{}`, at {}/{}",
-            expected_size, f_name, args, params, file_ns, def_name
+            expected_size, f_name, args, params, location.ns, location.def
           ),
-          loc,
+          location.to_owned(),
         ));
       }
       return; // no need to check
@@ -639,22 +702,36 @@ fn check_fn_args(
 
   if expected_size != actual_size {
     let mut warnings = check_warnings.borrow_mut();
-    let loc = NodeLocation::new(Arc::from(file_ns), Arc::from(GENERATED_DEF), Arc::from(vec![]));
+    let args = CalcitLocal::display_args(defined_args);
     warnings.push(LocatedWarning::new(
+      WarningKind::Arity,
       format!(
-        "[Warn] expected {} args in {} `{:?}` with `{}`, at {}/{}",
-        expected_size, f_name, defined_args, params, file_ns, def_name
+        "[Warn] expected {} args in {} `{}` with `{}`, at {}/{}",
+        expected_size, f_name, args, params, location.ns, location.def
       ),
-      loc,
+      location.to_owned(),
     ));
   }
 }
 
-// TODO this native implementation only handles symbols
-fn grab_def_name(x: &Calcit) -> Arc<str> {
-  match x {
-    Calcit::Symbol { info, .. } => info.at_def.to_owned(),
-    _ => String::from("??").into(),
+/// location of a call expression, derived from the symbol at its head.
+/// falls back to `GENERATED_DEF` for code without symbols, like code from macros
+pub fn grab_call_location(head: &Calcit, file_ns: &str) -> NodeLocation {
+  match head {
+    Calcit::Symbol {
+      info,
+      location: Some(coord),
+      ..
+    } => {
+      // head is the first item of the expression
+      let call_coord = match coord.split_last() {
+        Some((0, parent)) => parent.to_vec(),
+        _ => (**coord).to_owned(),
+      };
+      NodeLocation::new(info.at_ns.to_owned(), info.at_def.to_owned(), Arc::new(call_coord))
+    }
+    Calcit::Symbol { info, location: None, .. } => NodeLocation::new(info.at_ns.to_owned(), info.at_def.to_owned(), Arc::new(vec![])),
+    _ => NodeLocation::new(Arc::from(file_ns), Arc::from(GENERATED_DEF), Arc::new(vec![])),
   }
 }
 
@@ -785,6 +862,7 @@ fn check_symbol(sym: &str, args: &CalcitList, location: NodeLocation, check_warn
   if is_proc_name(sym) || CalcitSyntax::is_valid(sym) || program::has_def_code(calcit::CORE_NS, sym) {
     let mut warnings = check_warnings.borrow_mut();
     warnings.push(LocatedWarning::new(
+      WarningKind::Shadowing,
       format!("[Warn] local binding `{}` shadowed `calcit.core/{}`, with {}", sym, sym, args),
       location,
     ));
@@ -808,11 +886,11 @@ pub fn preprocess_core_let(
     Some(Calcit::List(ys)) if ys.is_empty() => Calcit::from(CalcitList::default()),
     Some(Calcit::List(ys)) if ys.len() == 2 => match (&ys[0], &ys[1]) {
       (Calcit::Symbol { sym, info, location }, a) => {
-        let loc = NodeLocation {
-          ns: Arc::from(head_ns),
-          def: GENERATED_DEF.into(),
-          coord: Arc::from(vec![]),
-        };
+        let loc = NodeLocation::new(
+          info.at_ns.to_owned(),
+          info.at_def.to_owned(),
+          location.to_owned().unwrap_or_default(),
+        );
         check_symbol(sym, args, loc, check_warnings);
         body_defs.insert(sym.to_owned());
         let form = preprocess_expr(a, &body_defs, file_ns, check_warnings, call_stack)?;
@@ -1,20 +1,13 @@
 use std::sync::atomic;
 use std::sync::atomic::AtomicUsize;
-use std::{thread, time};
+
+use super::event_loop;
 
 static TASK_COUNT: AtomicUsize = AtomicUsize::new(0);
 
+/// runs the event loop until no timers, jobs or tracked tasks remain
 pub fn exit_when_cleared() {
-  let delay = time::Duration::from_millis(40);
-
-  // keep looping until remaining task size 0
-  loop {
-    if TASK_COUNT.load(atomic::Ordering::Relaxed) == 0 {
-      break;
-    } else {
-      thread::sleep(delay);
-    }
-  }
+  event_loop::run_until_cleared();
 }
 
 /// by default, watcher adds 1 task
@@ -24,8 +17,10 @@ pub fn count_pending_tasks() -> usize {
 
 pub fn track_task_add() {
   TASK_COUNT.fetch_add(1, atomic::Ordering::SeqCst);
+  event_loop::wake_up();
 }
 
 pub fn track_task_release() {
   TASK_COUNT.fetch_sub(1, atomic::Ordering::SeqCst);
+  event_loop::wake_up();
 }
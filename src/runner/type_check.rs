@@ -0,0 +1,631 @@
+//! gradual type checking during preprocessing. types come from hints in `hint-fn`, signatures of procs,
+//! literals, and records defined in code. values of unknown types are never reported, so code without hints
+//! only gets checked against procs.
+//!
+//! hints are written in the body of a function, and ignored at runtime and in js codegen:
+//!
+//! ```cirru
+//! defn greet (name n)
+//!   hint-fn (:args :string :number) (:return :string)
+//!   str name n
+//! ```
+//!
+//! types are tags like `:number` `:string` `:bool` `:list` `:map` `:fn`, or `(:optional :string)`,
+//! `(:record Person)` and `(:tuple SomeClass)` with records defined in code.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::builtins::protocols;
+use crate::calcit::{
+  self, Calcit, CalcitArgLabel, CalcitFnArgs, CalcitImport, CalcitList, CalcitLocal, CalcitProc, CalcitSyntax, CalcitTypeHint,
+  LocatedWarning, MethodKind, NodeLocation, RecordShape, WarningKind,
+};
+use crate::program;
+use crate::runner::{self, preprocess::grab_call_location};
+
+/// classes are usually records of records, deeper references are not followed
+const MAX_SHAPE_DEPTH: usize = 4;
+
+/// hints from `(hint-fn (:args ...) (:return ...))` lines in a function body
+#[derive(Debug, Clone)]
+pub struct FnTypeHints {
+  /// types of arguments in order, `None` if not hinted
+  pub args: Option<Vec<CalcitTypeHint>>,
+  pub return_type: CalcitTypeHint,
+}
+
+impl Default for FnTypeHints {
+  fn default() -> Self {
+    FnTypeHints {
+      args: None,
+      return_type: CalcitTypeHint::Any,
+    }
+  }
+}
+
+fn is_hint_line(x: &Calcit) -> bool {
+  match x {
+    Calcit::List(xs) => matches!(xs.first(), Some(Calcit::Syntax(CalcitSyntax::HintFn, _))),
+    _ => false,
+  }
+}
+
+/// reads hints from lines of a function body, problems in hints are returned as messages
+pub fn read_fn_hints<'a>(body: impl Iterator<Item = &'a Calcit>) -> (FnTypeHints, Vec<String>) {
+  let mut hints = FnTypeHints::default();
+  let mut problems: Vec<String> = vec![];
+  for line in body {
+    let xs = match line {
+      Calcit::List(xs) if is_hint_line(line) => xs,
+      _ => continue,
+    };
+    for item in xs.iter().skip(1) {
+      match item {
+        // used by js codegen
+        Calcit::Symbol { sym, .. } if &**sym == "async" => (),
+        Calcit::List(ys) => match ys.first() {
+          Some(Calcit::Tag(t)) if t.ref_str() == "args" => {
+            let mut types = vec![];
+            for y in ys.iter().skip(1) {
+              types.push(parse_type(y).unwrap_or_else(|e| {
+                problems.push(e);
+                CalcitTypeHint::Any
+              }));
+            }
+            hints.args = Some(types);
+          }
+          Some(Calcit::Tag(t)) if t.ref_str() == "return" && ys.len() == 2 => {
+            hints.return_type = parse_type(&ys[1]).unwrap_or_else(|e| {
+              problems.push(e);
+              CalcitTypeHint::Any
+            });
+          }
+          _ => problems.push(format!("unknown hint: {item}")),
+        },
+        _ => problems.push(format!("unknown hint: {item}")),
+      }
+    }
+  }
+  (hints, problems)
+}
+
+fn parse_type(x: &Calcit) -> Result<CalcitTypeHint, String> {
+  match x {
+    Calcit::Tag(t) => CalcitTypeHint::from_name(t.ref_str()).ok_or_else(|| format!("unknown type: {x}")),
+    Calcit::List(xs) if xs.len() == 2 => match (&xs[0], &xs[1]) {
+      (Calcit::Tag(t), inner) if t.ref_str() == "optional" => Ok(CalcitTypeHint::optional(parse_type(inner)?)),
+      (Calcit::Tag(t), name) if t.ref_str() == "record" => {
+        match resolve_def(name).and_then(|(ns, def)| record_shape_of(&ns, &def, 0)) {
+          Some(shape) => Ok(CalcitTypeHint::Record(Some(shape))),
+          None => Err(format!("unknown record: {name}")),
+        }
+      }
+      (Calcit::Tag(t), name) if t.ref_str() == "tuple" => match resolve_def(name).and_then(|(ns, def)| record_shape_of(&ns, &def, 0)) {
+        Some(shape) => Ok(CalcitTypeHint::Tuple(Some(shape))),
+        None => Err(format!("unknown class: {name}")),
+      },
+      _ => Err(format!("unknown type: {x}")),
+    },
+    _ => Err(format!("unknown type: {x}")),
+  }
+}
+
+/// finds `(ns, def)` a symbol in raw code refers to, locals are not considered
+fn resolve_def(x: &Calcit) -> Option<(Arc<str>, Arc<str>)> {
+  match x {
+    Calcit::Import(CalcitImport { ns, def, .. }) => Some((ns.to_owned(), def.to_owned())),
+    Calcit::Symbol { sym, info, .. } => {
+      if let Some((alias, def)) = runner::parse_ns_def(sym) {
+        match program::lookup_ns_target_in_import(&info.at_ns, &alias) {
+          Some(ns) => Some((ns, def)),
+          None if program::has_def_code(&alias, &def) => Some((alias, def)),
+          None => None,
+        }
+      } else if program::has_def_code(&info.at_ns, sym) {
+        Some((info.at_ns.to_owned(), sym.to_owned()))
+      } else if let Some(ns) = program::lookup_def_target_in_import(&info.at_ns, sym) {
+        Some((ns, sym.to_owned()))
+      } else if program::has_def_code(calcit::CORE_NS, sym) {
+        Some((calcit::CORE_NS.into(), sym.to_owned()))
+      } else {
+        None
+      }
+    }
+    _ => None,
+  }
+}
+
+/// shape of a record from its definition, without evaluating it. recognizes `defrecord`, `defrecord!`,
+/// `new-record`, `new-class-record`, and `%{}` which creates a record of the same shape
+pub fn record_shape_of(ns: &str, def: &str, depth: usize) -> Option<Arc<RecordShape>> {
+  if depth > MAX_SHAPE_DEPTH {
+    return None;
+  }
+  shape_from_code(&program::lookup_def_code(ns, def)?, depth)
+}
+
+fn shape_from_code(code: &Calcit, depth: usize) -> Option<Arc<RecordShape>> {
+  let xs = match code {
+    Calcit::List(xs) => xs,
+    _ => return None,
+  };
+  let head = match xs.first() {
+    Some(Calcit::Symbol { sym, .. }) => sym.to_owned(),
+    _ => return None,
+  };
+  let name_of = |x: Option<&Calcit>| match x {
+    Some(Calcit::Symbol { sym, .. }) => Some(sym.to_owned()),
+    Some(Calcit::Tag(t)) => Some(t.arc_str()),
+    _ => None,
+  };
+  let tags_of = |ys: &CalcitList, from: usize| -> Option<Vec<Arc<str>>> {
+    let mut fields = vec![];
+    for y in ys.iter().skip(from) {
+      match y {
+        Calcit::Tag(t) => fields.push(t.arc_str()),
+        _ => return None,
+      }
+    }
+    Some(fields)
+  };
+  match &*head {
+    "def" => shape_from_code(xs.get(2)?, depth),
+    "defrecord" | "new-record" => Some(Arc::new(RecordShape {
+      name: name_of(xs.get(1))?,
+      fields: tags_of(xs, 2)?,
+      class: None,
+    })),
+    "defrecord!" => {
+      let mut fields = vec![];
+      for pair in xs.iter().skip(2) {
+        match pair {
+          Calcit::List(ys) => match ys.first() {
+            Some(Calcit::Tag(t)) => fields.push(t.arc_str()),
+            _ => return None,
+          },
+          _ => return None,
+        }
+      }
+      Some(Arc::new(RecordShape {
+        name: name_of(xs.get(1))?,
+        fields,
+        class: None,
+      }))
+    }
+    "new-class-record" => {
+      let (class_ns, class_def) = resolve_def(xs.get(1)?)?;
+      Some(Arc::new(RecordShape {
+        name: name_of(xs.get(2))?,
+        fields: tags_of(xs, 3)?,
+        class: Some(record_shape_of(&class_ns, &class_def, depth + 1)?),
+      }))
+    }
+    "%{}" => {
+      let (base_ns, base_def) = resolve_def(xs.get(1)?)?;
+      record_shape_of(&base_ns, &base_def, depth + 1)
+    }
+    _ => None,
+  }
+}
+
+/// builtin classes for method calls on values that are not records or tuples
+fn core_class_of(t: &CalcitTypeHint) -> Option<&'static str> {
+  match t {
+    CalcitTypeHint::Str => Some("&core-string-class"),
+    CalcitTypeHint::Number => Some("&core-number-class"),
+    CalcitTypeHint::List => Some("&core-list-class"),
+    CalcitTypeHint::Map => Some("&core-map-class"),
+    CalcitTypeHint::Set => Some("&core-set-class"),
+    CalcitTypeHint::Nil => Some("&core-nil-class"),
+    CalcitTypeHint::Fn => Some("&core-fn-class"),
+    CalcitTypeHint::Buffer => Some("&core-buffer-class"),
+    CalcitTypeHint::Transient => Some("&core-transient-class"),
+    CalcitTypeHint::LazySeq => Some("&core-lazy-seq-class"),
+    _ => None,
+  }
+}
+
+/// expected types of arguments at each position of a call, and type of items of the rest argument
+fn expected_arg_types(args: &CalcitFnArgs, types: &[CalcitTypeHint]) -> (Vec<CalcitTypeHint>, Option<CalcitTypeHint>) {
+  let type_at = |i: usize| types.get(i).cloned().unwrap_or(CalcitTypeHint::Any);
+  match args {
+    CalcitFnArgs::Args(xs) => ((0..xs.len()).map(type_at).collect(), None),
+    CalcitFnArgs::MarkedArgs(labels) => {
+      let mut positions = vec![];
+      let mut rest = None;
+      let (mut optional, mut spreading) = (false, false);
+      let mut idx = 0;
+      for label in labels {
+        match label {
+          CalcitArgLabel::OptionalMark => optional = true,
+          CalcitArgLabel::RestMark => spreading = true,
+          CalcitArgLabel::Idx(_) => {
+            let t = type_at(idx);
+            idx += 1;
+            if spreading {
+              rest = Some(t);
+            } else if optional {
+              positions.push(CalcitTypeHint::optional(t));
+            } else {
+              positions.push(t);
+            }
+          }
+        }
+      }
+      (positions, rest)
+    }
+  }
+}
+
+/// check preprocessed code of a def, `raw` is the code before preprocessing, used for locations
+pub fn check_def(raw: &Calcit, resolved: &Calcit, ns: &str, def: &str, check_warnings: &RefCell<Vec<LocatedWarning>>) {
+  let mut checker = TypeChecker {
+    scope: vec![],
+    near: NodeLocation::new(ns.into(), def.into(), Arc::new(vec![])),
+    check_warnings,
+  };
+  checker.infer(resolved, Some(raw));
+}
+
+struct TypeChecker<'a> {
+  scope: Vec<(Arc<str>, CalcitTypeHint)>,
+  /// location of the closest expression with a known location
+  near: NodeLocation,
+  check_warnings: &'a RefCell<Vec<LocatedWarning>>,
+}
+
+impl TypeChecker<'_> {
+  fn warn(&self, msg: String) {
+    let mut warnings = self.check_warnings.borrow_mut();
+    warnings.push(LocatedWarning::new(WarningKind::Type, msg, self.near.to_owned()));
+  }
+
+  fn lookup(&self, sym: &str) -> CalcitTypeHint {
+    match self.scope.iter().rev().find(|(s, _)| &**s == sym) {
+      Some((_, t)) => t.to_owned(),
+      None => CalcitTypeHint::Any,
+    }
+  }
+
+  fn infer(&mut self, code: &Calcit, raw: Option<&Calcit>) -> CalcitTypeHint {
+    match code {
+      Calcit::Nil => CalcitTypeHint::Nil,
+      Calcit::Bool(_) => CalcitTypeHint::Bool,
+      Calcit::Number(_) => CalcitTypeHint::Number,
+      Calcit::Str(_) => CalcitTypeHint::Str,
+      Calcit::Tag(_) => CalcitTypeHint::Tag,
+      Calcit::Buffer(_) => CalcitTypeHint::Buffer,
+      Calcit::Proc(_) | Calcit::Fn { .. } => CalcitTypeHint::Fn,
+      Calcit::Local(local) => self.lookup(&local.sym),
+      Calcit::List(xs) if !xs.is_empty() => {
+        let raw_xs = match raw {
+          Some(Calcit::List(ys)) if ys.len() == xs.len() && same_head(&xs[0], &ys[0]) => Some(&**ys),
+          _ => None,
+        };
+        let outer = self.near.to_owned();
+        let location = match raw_xs {
+          Some(ys) => raw_call_location(ys, &outer.ns),
+          // code from macros, locals written by users still have locations
+          None => locals_call_location(xs, &outer.ns),
+        };
+        if let Some(location) = location {
+          self.near = location;
+        }
+        let t = self.infer_list(xs, raw_xs);
+        self.near = outer;
+        t
+      }
+      _ => CalcitTypeHint::Any,
+    }
+  }
+
+  fn infer_items(&mut self, xs: &CalcitList, raw_xs: Option<&CalcitList>, from: usize) -> Vec<CalcitTypeHint> {
+    let mut types = vec![];
+    for (idx, x) in xs.iter().enumerate().skip(from) {
+      types.push(self.infer(x, raw_xs.and_then(|ys| ys.get(idx))));
+    }
+    types
+  }
+
+  fn infer_list(&mut self, xs: &CalcitList, raw_xs: Option<&CalcitList>) -> CalcitTypeHint {
+    let has_spread = xs.iter().any(|x| matches!(x, Calcit::Syntax(CalcitSyntax::ArgSpread, _)));
+    match &xs[0] {
+      Calcit::Syntax(syntax, _) => match syntax {
+        CalcitSyntax::Defn => {
+          self.check_fn(xs, raw_xs);
+          CalcitTypeHint::Fn
+        }
+        CalcitSyntax::CoreLet => {
+          let size = self.scope.len();
+          if let Some(Calcit::List(pair)) = xs.get(1) {
+            if let (Some(Calcit::Local(local)), Some(value)) = (pair.first(), pair.get(1)) {
+              let raw_value = match raw_xs.and_then(|ys| ys.get(1)) {
+                Some(Calcit::List(raw_pair)) if raw_pair.len() == 2 => raw_pair.get(1),
+                _ => None,
+              };
+              let t = self.infer(value, raw_value);
+              self.scope.push((local.sym.to_owned(), t));
+            }
+          }
+          let t = self.infer_items(xs, raw_xs, 2).pop().unwrap_or(CalcitTypeHint::Nil);
+          self.scope.truncate(size);
+          t
+        }
+        CalcitSyntax::If => {
+          let mut types = self.infer_items(xs, raw_xs, 1);
+          match types.len() {
+            2 => types.pop().expect("then branch").union(CalcitTypeHint::Nil),
+            3 => {
+              let else_type = types.pop().expect("else branch");
+              types.pop().expect("then branch").union(else_type)
+            }
+            _ => CalcitTypeHint::Any,
+          }
+        }
+        CalcitSyntax::Defatom => {
+          self.infer_items(xs, raw_xs, 2);
+          CalcitTypeHint::Ref
+        }
+        CalcitSyntax::Try | CalcitSyntax::Reset | CalcitSyntax::CallSpread => {
+          self.infer_items(xs, raw_xs, 1);
+          CalcitTypeHint::Any
+        }
+        // macros work on code, not checked
+        _ => CalcitTypeHint::Any,
+      },
+      Calcit::Proc(p) => {
+        let types = self.infer_items(xs, raw_xs, 1);
+        if has_spread {
+          CalcitTypeHint::Any
+        } else {
+          self.check_proc(*p, xs, &types)
+        }
+      }
+      Calcit::Import(import) => {
+        let types = self.infer_items(xs, raw_xs, 1);
+        if has_spread {
+          CalcitTypeHint::Any
+        } else {
+          self.check_import_call(import, xs, &types)
+        }
+      }
+      Calcit::Method(name, MethodKind::Invoke(_)) => {
+        let types = self.infer_items(xs, raw_xs, 1);
+        if let Some(t) = types.first() {
+          self.check_method(name, t);
+        }
+        CalcitTypeHint::Any
+      }
+      _ => {
+        self.infer_items(xs, raw_xs, 0);
+        CalcitTypeHint::Any
+      }
+    }
+  }
+
+  /// `(defn name (args) body)`, arguments get types from hints
+  fn check_fn(&mut self, xs: &CalcitList, raw_xs: Option<&CalcitList>) {
+    let name = match xs.get(1) {
+      Some(Calcit::Symbol { sym, .. }) => sym.to_owned(),
+      _ => Arc::from("fn"),
+    };
+    let (hints, problems) = read_fn_hints(xs.iter().skip(3));
+    for problem in problems {
+      self.warn(format!("[Warn] invalid hint in `{name}`, {problem}"));
+    }
+    let size = self.scope.len();
+    if let Some(Calcit::List(args)) = xs.get(2) {
+      let locals_count = args.iter().filter(|a| matches!(a, Calcit::Local(_))).count();
+      if let Some(types) = &hints.args {
+        if types.len() != locals_count {
+          self.warn(format!(
+            "[Warn] `{name}` has {locals_count} arguments, but hinted with {} types",
+            types.len()
+          ));
+        }
+      }
+      let (mut optional, mut spreading) = (false, false);
+      let mut idx = 0;
+      for arg in args.iter() {
+        match arg {
+          Calcit::Syntax(CalcitSyntax::ArgOptional, _) => optional = true,
+          Calcit::Syntax(CalcitSyntax::ArgSpread, _) => spreading = true,
+          Calcit::Local(local) => {
+            let t = hints
+              .args
+              .as_ref()
+              .and_then(|ts| ts.get(idx))
+              .cloned()
+              .unwrap_or(CalcitTypeHint::Any);
+            idx += 1;
+            let t = if spreading {
+              CalcitTypeHint::List
+            } else if optional {
+              CalcitTypeHint::optional(t)
+            } else {
+              t
+            };
+            self.scope.push((local.sym.to_owned(), t));
+          }
+          _ => (),
+        }
+      }
+    }
+    let mut last = CalcitTypeHint::Nil;
+    for (idx, line) in xs.iter().enumerate().skip(3) {
+      if is_hint_line(line) {
+        continue;
+      }
+      last = self.infer(line, raw_xs.and_then(|ys| ys.get(idx)));
+    }
+    if !hints.return_type.accepts(&last) {
+      self.warn(format!("[Warn] `{name}` is hinted to return {}, got {last}", hints.return_type));
+    }
+    self.scope.truncate(size);
+  }
+
+  fn check_args(&self, f_name: &str, expected: &[CalcitTypeHint], rest: Option<&CalcitTypeHint>, types: &[CalcitTypeHint]) {
+    for (idx, actual) in types.iter().enumerate() {
+      let t = match expected.get(idx).or(rest) {
+        Some(t) => t,
+        None => break,
+      };
+      if !t.accepts(actual) {
+        self.warn(format!("[Warn] `{f_name}` expected {t} for argument {}, got {actual}", idx + 1));
+      }
+    }
+  }
+
+  fn record_shape_in(t: Option<&CalcitTypeHint>) -> Option<&Arc<RecordShape>> {
+    match t {
+      Some(CalcitTypeHint::Record(Some(shape))) => Some(shape),
+      _ => None,
+    }
+  }
+
+  /// checks tags at positions of record fields
+  fn check_fields<'b>(&self, shape: &RecordShape, keys: impl Iterator<Item = Option<&'b Calcit>>) {
+    for key in keys.flatten() {
+      if let Calcit::Tag(t) = key {
+        if !shape.has_field(t.ref_str()) {
+          let fields = shape.fields.iter().map(|f| format!(":{f}")).collect::<Vec<_>>().join(" ");
+          self.warn(format!("[Warn] unknown field :{t} in record {}, fields: {fields}", shape.name));
+        }
+      }
+    }
+  }
+
+  fn check_proc(&self, p: CalcitProc, xs: &CalcitList, types: &[CalcitTypeHint]) -> CalcitTypeHint {
+    let signature = p.type_signature();
+    self.check_args(p.as_ref(), &signature.args, None, types);
+    let arg_shape = |idx: usize| {
+      xs.get(idx)
+        .and_then(resolve_def)
+        .and_then(|(ns, def)| record_shape_of(&ns, &def, 0))
+    };
+    match p {
+      CalcitProc::NativeRecord => match arg_shape(1) {
+        Some(shape) => {
+          self.check_fields(&shape, (2..xs.len()).step_by(2).map(|i| xs.get(i)));
+          CalcitTypeHint::Record(Some(shape))
+        }
+        None => signature.return_type,
+      },
+      CalcitProc::NativeClassTuple => CalcitTypeHint::Tuple(arg_shape(1)),
+      CalcitProc::NativeRecordGet => {
+        if let Some(shape) = Self::record_shape_in(types.first()) {
+          self.check_fields(shape, [xs.get(2)].into_iter());
+        }
+        signature.return_type
+      }
+      CalcitProc::NativeRecordAssoc | CalcitProc::NativeRecordWith => match Self::record_shape_in(types.first()) {
+        Some(shape) => {
+          self.check_fields(shape, (2..xs.len()).step_by(2).map(|i| xs.get(i)));
+          types[0].to_owned()
+        }
+        None => signature.return_type,
+      },
+      _ => signature.return_type,
+    }
+  }
+
+  fn check_import_call(&self, import: &CalcitImport, xs: &CalcitList, types: &[CalcitTypeHint]) -> CalcitTypeHint {
+    if &*import.ns == calcit::CORE_NS && (&*import.def == "get" || &*import.def == "assoc") {
+      if let Some(shape) = Self::record_shape_in(types.first()) {
+        self.check_fields(shape, [xs.get(2)].into_iter());
+        if &*import.def == "assoc" {
+          return types[0].to_owned();
+        }
+      }
+      return CalcitTypeHint::Any;
+    }
+    match program::lookup_evaled_def(&import.ns, &import.def) {
+      Some(Calcit::Fn { info, .. }) => {
+        let (hints, _problems) = read_fn_hints(info.body.iter());
+        if let Some(arg_types) = &hints.args {
+          let (expected, rest) = expected_arg_types(&info.args, arg_types);
+          self.check_args(&info.name, &expected, rest.as_ref(), types);
+        }
+        hints.return_type
+      }
+      _ => CalcitTypeHint::Any,
+    }
+  }
+
+  fn check_method(&self, name: &str, receiver: &CalcitTypeHint) {
+    let class = match receiver {
+      CalcitTypeHint::Record(Some(shape)) => shape.class.to_owned(),
+      CalcitTypeHint::Tuple(class) => class.to_owned(),
+      t => core_class_of(t).and_then(|class| record_shape_of(calcit::CORE_NS, class, 0)),
+    };
+    if let Some(class) = class {
+      // methods of protocols are implemented at runtime
+      if !class.has_field(name) && !protocols::is_protocol_method(name) {
+        let methods = class.fields.iter().map(|f| format!(".{f}")).collect::<Vec<_>>().join(" ");
+        self.warn(format!(
+          "[Warn] unknown method `.{name}` for {receiver}, available methods: {methods}"
+        ));
+      }
+    }
+  }
+}
+
+/// whether the raw code has the same head as the preprocessed code, so items can be matched by positions
+fn same_head(resolved: &Calcit, raw: &Calcit) -> bool {
+  match (resolved, raw) {
+    (Calcit::Method(a, kind_a), Calcit::Method(b, kind_b)) => a == b && kind_a == kind_b,
+    // procs and syntax could be parsed directly
+    (Calcit::Proc(a), Calcit::Proc(b)) => a == b,
+    (Calcit::Syntax(a, _), Calcit::Syntax(b, _)) => a == b,
+    (_, Calcit::Symbol { sym, .. }) => match resolved {
+      Calcit::Syntax(s, _) => s.as_ref() == &**sym,
+      Calcit::Proc(p) => p.as_ref() == &**sym,
+      Calcit::Import(CalcitImport { def, .. }) => def == sym || sym.ends_with(&format!("/{def}")),
+      Calcit::Local(local) => local.sym == *sym,
+      Calcit::Registered(name) => name == sym,
+      _ => false,
+    },
+    _ => false,
+  }
+}
+
+/// location of a call in raw code. when the head is not a symbol, it's derived from the first symbol in arguments
+fn raw_call_location(ys: &CalcitList, file_ns: &str) -> Option<NodeLocation> {
+  if let head @ Calcit::Symbol { .. } = &ys[0] {
+    return Some(grab_call_location(head, file_ns));
+  }
+  ys.iter().skip(1).find_map(|y| match y {
+    Calcit::Symbol {
+      info,
+      location: Some(coord),
+      ..
+    } => {
+      let (_, parent) = coord.split_last()?;
+      Some(NodeLocation::new(
+        info.at_ns.to_owned(),
+        info.at_def.to_owned(),
+        Arc::new(parent.to_vec()),
+      ))
+    }
+    _ => None,
+  })
+}
+
+fn locals_call_location(xs: &CalcitList, file_ns: &str) -> Option<NodeLocation> {
+  xs.iter().skip(1).find_map(|x| match x {
+    Calcit::Local(CalcitLocal {
+      info,
+      location: Some(coord),
+      ..
+    }) if &*info.at_ns == file_ns => {
+      let (_, parent) = coord.split_last()?;
+      Some(NodeLocation::new(
+        info.at_ns.to_owned(),
+        info.at_def.to_owned(),
+        Arc::new(parent.to_vec()),
+      ))
+    }
+    _ => None,
+  })
+}
@@ -179,7 +179,8 @@ pub fn create_file_from_snippet(raw: &str) -> Result<FileInSnapShot, String> {
   match cirru_parser::parse(raw) {
     Ok(lines) => {
       let mut def_dict: HashMap<Arc<str>, CodeEntry> = HashMap::with_capacity(2);
-      let mut func_code = vec![Cirru::leaf("defn"), "main!".into(), Cirru::List(vec![])];
+      // `args` holds arguments after `--` in command line, or `nil`
+      let mut func_code = vec![Cirru::leaf("defn"), "main!".into(), vec!["?", "args"].into()];
       for line in lines {
         func_code.push(line.to_owned());
       }
@@ -213,6 +214,7 @@ impl From<&FileChangeInfo> for Edn {
     }
 
     if !data.added_defs.is_empty() {
+      #[allow(clippy::mutable_key_type)]
       let defs: HashMap<Edn, Edn> = data
         .added_defs
         .iter()
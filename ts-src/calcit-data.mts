@@ -13,6 +13,8 @@ import { CalcitTuple } from "./js-tuple.mjs";
 import { CalcitCirruQuote, cirru_deep_equal } from "./js-cirru.mjs";
 import { CirruWriterNode } from "@cirru/writer.ts";
 import { CalcitRef } from "./js-ref.mjs";
+import { CalcitTransient } from "./js-transient.mjs";
+import { CalcitLazySeq } from "./js-lazy.mjs";
 
 // we have to inject cache in a dirty way in some cases
 const calcit_dirty_hash_key = "_calcit_cached_hash";
@@ -176,6 +178,9 @@ let defaultHash_symbol = valueHash("symbol:");
 let defaultHash_fn = valueHash("fn:");
 let defaultHash_ref = valueHash("ref:");
 let defaultHash_tuple = valueHash("tuple:");
+let defaultHash_buffer = valueHash("buffer:");
+let defaultHash_transient = valueHash("transient:");
+let defaultHash_lazy_seq = valueHash("lazy-seq:");
 let defaultHash_set = valueHash("set:");
 let defaultHash_list = valueHash("list:");
 let defaultHash_map = valueHash("map:");
@@ -232,6 +237,20 @@ export let hashFunction = (x: CalcitValue): Hash => {
     x.cachedHash = h;
     return h;
   }
+  if (x instanceof Uint8Array) {
+    let base = defaultHash_buffer;
+    for (let idx = 0; idx < x.length; idx++) {
+      base = mergeValueHash(base, x[idx]);
+    }
+    (x as any)[calcit_dirty_hash_key] = base;
+    return base;
+  }
+  if (x instanceof CalcitTransient) {
+    return mergeValueHash(defaultHash_transient, x.id);
+  }
+  if (x instanceof CalcitLazySeq) {
+    return mergeValueHash(defaultHash_lazy_seq, x.id);
+  }
   if (x instanceof CalcitTuple) {
     let base = defaultHash_tuple;
     base = mergeValueHash(base, hashFunction(x.tag));
@@ -390,6 +409,19 @@ export let toString = (x: CalcitValue, escaped: boolean, disableJsDataWarning: b
   if (x instanceof CalcitCirruQuote) {
     return x.toString();
   }
+  if (x instanceof Uint8Array) {
+    let bytes = Array.from(x.subarray(0, 8), (b) => b.toString(16).padStart(2, "0"));
+    if (x.length > 8) {
+      bytes.push(`..+${x.length - 8}`);
+    }
+    return `(&buffer${bytes.map((b) => " " + b).join("")})`;
+  }
+  if (x instanceof CalcitTransient) {
+    return x.toString();
+  }
+  if (x instanceof CalcitLazySeq) {
+    return x.toString();
+  }
 
   if (!disableJsDataWarning) {
     console.warn("Non Calcit data in stringify", x);
@@ -579,6 +611,20 @@ export let _$n__$e_ = (x: CalcitValue, y: CalcitValue): boolean => {
     }
     return false;
   }
+  if (x instanceof Uint8Array) {
+    if (y instanceof Uint8Array) {
+      if (x.length !== y.length) {
+        return false;
+      }
+      for (let idx = 0; idx < x.length; idx++) {
+        if (x[idx] !== y[idx]) {
+          return false;
+        }
+      }
+      return true;
+    }
+    return false;
+  }
   if (x instanceof CalcitTuple) {
     if (y instanceof CalcitTuple) {
       return x.eq(y);
@@ -6,11 +6,11 @@ export const calcit_package_json = pkg;
 import { parse, ICirruNode } from "@cirru/parser.ts";
 import { writeCirruCode } from "@cirru/writer.ts";
 
-import { CalcitValue } from "./js-primes.mjs";
+import { CalcitValue, _$n_compare } from "./js-primes.mjs";
 import { CalcitSymbol, CalcitTag, CalcitFn, CalcitRecur, newTag, refsRegistry, toString, getStringName, _$n__$e_, hashFunction } from "./calcit-data.mjs";
 
 import { CalcitRef } from "./js-ref.mjs";
-import { fieldsEqual, CalcitRecord } from "./js-record.mjs";
+import { fieldsEqual, CalcitRecord, new_record } from "./js-record.mjs";
 
 export * from "./calcit-data.mjs";
 export * from "./js-record.mjs";
@@ -21,12 +21,21 @@ export * from "./js-primes.mjs";
 export * from "./js-tuple.mjs";
 export * from "./custom-formatter.mjs";
 export * from "./js-cirru.mjs";
+export * from "./js-time.mjs";
+export * from "./js-json.mjs";
+export * from "./js-binary.mjs";
+export * from "./js-buffer.mjs";
+export * from "./js-transient.mjs";
+export * from "./js-lazy.mjs";
+export * from "./js-files.mjs";
 export { _$n_compare } from "./js-primes.mjs";
 
 import { CalcitList, CalcitSliceList, foldl } from "./js-list.mjs";
-import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
-import { CalcitSet } from "./js-set.mjs";
+import { CalcitMap, CalcitSliceMap, CalcitSortedMap } from "./js-map.mjs";
+import { CalcitSet, CalcitSortedSet } from "./js-set.mjs";
 import { CalcitTuple } from "./js-tuple.mjs";
+import { CalcitTransient } from "./js-transient.mjs";
+import { CalcitLazySeq } from "./js-lazy.mjs";
 import { to_calcit_data, extract_cirru_edn, CalcitCirruQuote } from "./js-cirru.mjs";
 
 let inNodeJs = typeof process !== "undefined" && process?.release?.name === "node";
@@ -68,6 +77,15 @@ export let type_of = (x: any): CalcitTag => {
   if (x instanceof CalcitCirruQuote) {
     return newTag("cirru-quote");
   }
+  if (x instanceof Uint8Array) {
+    return newTag("buffer");
+  }
+  if (x instanceof CalcitTransient) {
+    return newTag("transient");
+  }
+  if (x instanceof CalcitLazySeq) {
+    return newTag("lazy-seq");
+  }
   if (x === true || x === false) {
     return newTag("bool");
   }
@@ -78,6 +96,9 @@ export let type_of = (x: any): CalcitTag => {
     }
     return newTag("fn");
   }
+  if (x instanceof Promise) {
+    return newTag("promise");
+  }
   if (typeof x === "object") {
     return newTag("js-object");
   }
@@ -130,12 +151,45 @@ export let _$n__$M_ = (...xs: CalcitValue[]): CalcitSliceMap => {
   return new CalcitSliceMap(xs);
 };
 
+export let sorted_map = (...xs: CalcitValue[]): CalcitSortedMap => {
+  if (xs.length % 2 !== 0) {
+    throw new Error(`sorted-map expected even number of arguments, got: ${new CalcitSliceList(xs)}`);
+  }
+  return CalcitSortedMap.from(xs);
+};
+
+export let sorted_set = (...xs: CalcitValue[]): CalcitSortedSet => {
+  return CalcitSortedSet.from(xs);
+};
+
+/** initial values of global refs, for resetting */
+let refsInitialValues = new Map<string, CalcitValue>();
+
 export let defatom = (path: string, x: CalcitValue): CalcitValue => {
   let v = new CalcitRef(x, path);
   refsRegistry.set(path, v);
+  refsInitialValues.set(path, x);
   return v;
 };
 
+export let _$n_refs_$o_registered = (): CalcitSliceList => {
+  let paths = Array.from(refsRegistry.keys()).sort();
+  return new CalcitSliceList(
+    paths.map((path) => {
+      let watchers = new CalcitSet(Array.from(refsRegistry.get(path).listeners.keys()));
+      return new CalcitSliceMap([newTag("path"), path, newTag("watchers"), watchers]);
+    })
+  );
+};
+
+export let _$n_refs_$o_reset_all_$x_ = (): null => {
+  refsRegistry.forEach((r, path) => {
+    r.value = refsInitialValues.get(path);
+    r.listeners.clear();
+  });
+  return null;
+};
+
 export { atom } from "./js-ref.mjs";
 
 export let peekDefatom = (path: string): CalcitRef => {
@@ -444,6 +498,85 @@ export let remove_watch = (a: CalcitRef, k: CalcitTag): null => {
   return null;
 };
 
+export let compare_and_set_$x_ = (a: CalcitRef, old: CalcitValue, v: CalcitValue): boolean => {
+  if (!(a instanceof CalcitRef)) {
+    throw new Error(`compare-and-set! expected a ref, got: ${a}`);
+  }
+  if (!_$n__$e_(a.value, old)) {
+    return false;
+  }
+  if (!_$n__$e_(a.value, v)) {
+    let prev = a.value;
+    a.value = v;
+    a.listeners.forEach((f) => {
+      f(v, prev);
+    });
+  }
+  return true;
+};
+
+/** a conflicting update only happens when the function itself modifies the ref */
+const maxRetries = 10000;
+
+export let _$n_atom_$o_swap_$x_ = (a: CalcitRef, f: CalcitFn, ...args: CalcitValue[]): CalcitValue => {
+  if (!(a instanceof CalcitRef)) {
+    throw new Error(`swap! expected a ref, got: ${a}`);
+  }
+  for (let i = 0; i < maxRetries; i++) {
+    let prev = a.value;
+    let v = f(prev, ...args);
+    if (!_$n__$e_(a.value, prev)) {
+      continue;
+    }
+    if (!_$n__$e_(v, prev)) {
+      a.value = v;
+      a.listeners.forEach((g) => {
+        g(v, prev);
+      });
+    }
+    return v;
+  }
+  throw new Error(`swap! gave up after ${maxRetries} conflicting updates`);
+};
+
+export let transact_$x_ = (refs: CalcitList | CalcitSliceList, f: CalcitFn): CalcitValue => {
+  if (!(refs instanceof CalcitList || refs instanceof CalcitSliceList)) {
+    throw new Error(`transact! expected a list of refs, got: ${refs}`);
+  }
+  let items = refs.toArray() as CalcitRef[];
+  items.forEach((r, idx) => {
+    if (!(r instanceof CalcitRef)) {
+      throw new Error(`transact! expected a ref, got: ${r}`);
+    }
+    if (items.indexOf(r) !== idx) {
+      throw new Error(`transact! got duplicated ref: ${r}`);
+    }
+  });
+  for (let i = 0; i < maxRetries; i++) {
+    let prevs = items.map((r) => r.value);
+    let ret = f(new CalcitSliceList(prevs));
+    if (!((ret instanceof CalcitList || ret instanceof CalcitSliceList) && ret.len() === items.length)) {
+      throw new Error(`transact! expected a list of ${items.length} values from the function, got: ${toString(ret, true)}`);
+    }
+    if (items.some((r, idx) => !_$n__$e_(r.value, prevs[idx]))) {
+      continue;
+    }
+    let values = ret.toArray();
+    let changed = items.filter((r, idx) => !_$n__$e_(values[idx], prevs[idx]));
+    items.forEach((r, idx) => {
+      r.value = values[idx];
+    });
+    changed.forEach((r) => {
+      let idx = items.indexOf(r);
+      r.listeners.forEach((g) => {
+        g(values[idx], prevs[idx]);
+      });
+    });
+    return ret;
+  }
+  throw new Error(`transact! gave up after ${maxRetries} conflicting updates`);
+};
+
 export let range = (n: number, m: number, step: number = 1): CalcitSliceList | CalcitList => {
   var result: CalcitList | CalcitSliceList = new CalcitSliceList([]);
   if (m != null) {
@@ -787,6 +920,10 @@ export let to_pairs = (xs: CalcitValue): CalcitValue | CalcitSliceList => {
     for (let idx = 0; idx < pairs.length; idx++) {
       result.push(new CalcitSliceList(pairs[idx]));
     }
+    // pairs are ordered by keys since keys are unique
+    if (xs instanceof CalcitSortedMap) {
+      return CalcitSortedSet.fromSorted(result);
+    }
     return new CalcitSet(result);
   } else if (xs instanceof CalcitRecord) {
     let arr_result: Array<CalcitSliceList> = [];
@@ -1037,6 +1174,58 @@ export let cpu_time = (): number => {
   return performance.now();
 };
 
+export let set_timeout = (ms: number, f: () => void): any => {
+  return setTimeout(f, ms);
+};
+
+export let set_interval = (ms: number, f: () => void): any => {
+  if (!(ms > 0)) {
+    throw new Error("set-interval expected a positive interval");
+  }
+  return setInterval(f, ms);
+};
+
+/** clears timers from both `set-timeout` and `set-interval` */
+export let clear_timer = (id: any): void => {
+  clearTimeout(id);
+  clearInterval(id);
+};
+
+/** resolvers of promises created by `new-promise` */
+let promiseSettlers: WeakMap<Promise<CalcitValue>, { resolve: (v: CalcitValue) => void; reject: (e: CalcitValue) => void }> = new WeakMap();
+
+export let new_promise = (): Promise<CalcitValue> => {
+  let settlers: { resolve: (v: CalcitValue) => void; reject: (e: CalcitValue) => void };
+  let p = new Promise<CalcitValue>((resolve, reject) => {
+    settlers = { resolve, reject };
+  });
+  promiseSettlers.set(p, settlers);
+  return p;
+};
+
+export let resolve_promise_$x_ = (p: Promise<CalcitValue>, v: CalcitValue): void => {
+  let settlers = promiseSettlers.get(p);
+  if (settlers == null) {
+    throw new Error(`resolve-promise! expected a promise from new-promise, got: ${p}`);
+  }
+  settlers.resolve(v);
+};
+
+export let reject_promise_$x_ = (p: Promise<CalcitValue>, e: CalcitValue): void => {
+  let settlers = promiseSettlers.get(p);
+  if (settlers == null) {
+    throw new Error(`reject-promise! expected a promise from new-promise, got: ${p}`);
+  }
+  settlers.reject(e);
+};
+
+export let promise_then = (p: Promise<CalcitValue>, f: (v: CalcitValue) => CalcitValue, g?: (e: CalcitValue) => CalcitValue): Promise<CalcitValue> => {
+  if (!(p instanceof Promise)) {
+    throw new Error(`promise-then expected a promise, got: ${p}`);
+  }
+  return p.then(f, g ?? undefined);
+};
+
 export let quit_$x_ = (): void => {
   if (inNodeJs) {
     process.exit(1);
@@ -1148,6 +1337,12 @@ export let list_$q_ = (x: CalcitValue): boolean => {
 export let set_$q_ = (x: CalcitValue): boolean => {
   return x instanceof CalcitSet;
 };
+export let sorted_map_$q_ = (x: CalcitValue): boolean => {
+  return x instanceof CalcitSortedMap;
+};
+export let sorted_set_$q_ = (x: CalcitValue): boolean => {
+  return x instanceof CalcitSortedSet;
+};
 export let fn_$q_ = (x: CalcitValue): boolean => {
   return typeof x === "function";
 };
@@ -1161,8 +1356,7 @@ export let tuple_$q_ = (x: CalcitValue): boolean => {
   return x instanceof CalcitTuple;
 };
 export let buffer_$q_ = (x: CalcitValue): boolean => {
-  console.warn("TODO, detecting buffer");
-  return false;
+  return x instanceof Uint8Array;
 };
 
 export let _$n_str_$o_escape = (x: string) => JSON.stringify(x);
@@ -1292,6 +1486,9 @@ let calcit_builtin_classes = {
   map: null as CalcitRecord,
   nil: null as CalcitRecord,
   fn: null as CalcitRecord,
+  buffer: null as CalcitRecord,
+  transient: null as CalcitRecord,
+  lazy_seq: null as CalcitRecord,
 };
 
 // need to register code from outside
@@ -1316,19 +1513,12 @@ function lookup_class(obj: CalcitValue): [CalcitRecord, string] {
     tag = "&core-map-class";
     klass = calcit_builtin_classes.map;
   } else if (obj instanceof CalcitRecord) {
-    if (obj.klass instanceof CalcitRecord) {
-      tag = obj.name.toString();
-      klass = obj.klass;
-    } else {
-      throw new Error("Method invoking expected a record as class");
-    }
+    // records and tuples without classes may still have methods from protocols
+    tag = obj.name.toString();
+    klass = obj.klass instanceof CalcitRecord ? obj.klass : null;
   } else if (obj instanceof CalcitTuple) {
-    if (obj.klass instanceof CalcitRecord) {
-      tag = obj.tag.toString();
-      klass = obj.klass;
-    } else {
-      throw new Error("Method invoking expected a record as class");
-    }
+    tag = obj.tag.toString();
+    klass = obj.klass instanceof CalcitRecord ? obj.klass : null;
   } else if (obj instanceof CalcitSet) {
     tag = "&core-set-class";
     klass = calcit_builtin_classes.set;
@@ -1344,6 +1534,15 @@ function lookup_class(obj: CalcitValue): [CalcitRecord, string] {
   } else if (typeof obj === "function") {
     tag = "&core-fn-class";
     klass = calcit_builtin_classes.fn;
+  } else if (obj instanceof Uint8Array) {
+    tag = "&core-buffer-class";
+    klass = calcit_builtin_classes.buffer;
+  } else if (obj instanceof CalcitTransient) {
+    tag = "&core-transient-class";
+    klass = calcit_builtin_classes.transient;
+  } else if (obj instanceof CalcitLazySeq) {
+    tag = "&core-lazy-seq-class";
+    klass = calcit_builtin_classes.lazy_seq;
   } else {
     return null;
   }
@@ -1357,7 +1556,13 @@ export function invoke_method(p: string, obj: CalcitValue, ...args: CalcitValue[
   }
   let klass = pair[0];
   let tag = pair[1];
-  let method = klass.getOrNil(p);
+  let method = klass?.getOrNil(p);
+  if (method == null) {
+    method = lookupProtocolMethod(protocolTargetOf(obj), p);
+  }
+  if (method == null && klass == null) {
+    throw new Error("Method invoking expected a record as class");
+  }
   if (method == null) {
     throw new Error(`No method '.${p}' for '${tag}' object '${obj}'.\navailable fields are: ${klass.fields.map((fd: CalcitTag) => fd.value).join(" ")}`);
   }
@@ -1368,6 +1573,138 @@ export function invoke_method(p: string, obj: CalcitValue, ...args: CalcitValue[
   }
 }
 
+// protocols, kept in sync with `src/builtins/protocols.rs`.
+// implementations are registered by types, builtin types are named by their classes,
+// records are identified by names and fields, tuples by those of their classes
+
+/** implementations by protocol names, a method name is implemented by one protocol at most */
+let protocolRegistry: Map<string, Map<CalcitTag, Map<string, Function>>> = new Map();
+
+function recordProtocolKey(r: CalcitRecord): string {
+  return [r.name, ...r.fields].map((x) => x.toString()).join(" ");
+}
+
+function lookupProtocolMethod(key: string, name: string): Function {
+  for (let methods of protocolRegistry.get(key)?.values() ?? []) {
+    let f = methods.get(name);
+    if (f != null) {
+      return f;
+    }
+  }
+  return undefined;
+}
+
+let protocolTypeClasses: Record<string, string> = {
+  list: "&core-list-class",
+  map: "&core-map-class",
+  number: "&core-number-class",
+  string: "&core-string-class",
+  set: "&core-set-class",
+  nil: "&core-nil-class",
+  fn: "&core-fn-class",
+  buffer: "&core-buffer-class",
+  transient: "&core-transient-class",
+  "lazy-seq": "&core-lazy-seq-class",
+};
+
+function protocolTargetOf(obj: CalcitValue): string {
+  if (obj instanceof CalcitRecord) {
+    return recordProtocolKey(obj);
+  }
+  if (obj instanceof CalcitTuple) {
+    return obj.klass instanceof CalcitRecord ? recordProtocolKey(obj.klass) : null;
+  }
+  return lookup_class(obj)?.[1];
+}
+
+/** creates a protocol, problems of methods are reported here rather than when it's extended */
+export let new_protocol = (name: CalcitValue, ...methods: CalcitValue[]): CalcitValue => {
+  if (!(name instanceof CalcitTag)) {
+    throw new Error(`new-protocol expected a name in tag, got: ${toString(name, true)}`);
+  }
+  if (methods.length === 0) {
+    throw new Error(`new-protocol expected methods for ${name}`);
+  }
+  let found: Set<CalcitTag> = new Set();
+  for (let m of methods) {
+    if (!(m instanceof CalcitTag)) {
+      throw new Error(`new-protocol expected methods in tags for ${name}, got: ${toString(m, true)}`);
+    }
+    if (found.has(m)) {
+      throw new Error(`new-protocol duplicated method .${m.value} for ${name}`);
+    }
+    found.add(m);
+  }
+  return new_record(name, ...methods);
+};
+
+/** registers implementations of all methods of a protocol for a type, missing and unknown methods are reported together */
+export let extend_protocol_$x_ = (protocol: CalcitValue, target: CalcitValue, impls: CalcitValue): CalcitValue => {
+  if (!(protocol instanceof CalcitRecord)) {
+    throw new Error(`extend-protocol! expected a protocol, got: ${toString(protocol, true)}`);
+  }
+  let key: string;
+  if (target instanceof CalcitTag) {
+    key = protocolTypeClasses[target.value];
+    if (key == null) {
+      throw new Error(`extend-protocol! unknown type ${target}`);
+    }
+  } else if (target instanceof CalcitRecord) {
+    key = recordProtocolKey(target);
+  } else {
+    throw new Error(`extend-protocol! expected a type tag or a record, got: ${toString(target, true)}`);
+  }
+  if (!(impls instanceof CalcitMap || impls instanceof CalcitSliceMap)) {
+    throw new Error(`extend-protocol! expected a map of methods, got: ${toString(impls, true)}`);
+  }
+  let methods: Map<string, Function> = new Map();
+  let problems: string[] = [];
+  for (let [k, f] of impls.pairs()) {
+    let name = getStringName(k);
+    if (!protocol.contains(name)) {
+      problems.push(`unknown method .${name}`);
+    } else if (typeof f !== "function") {
+      problems.push(`expected a function for .${name}, got: ${toString(f, true)}`);
+    }
+    methods.set(name, f as Function);
+  }
+  for (let field of protocol.fields) {
+    if (!methods.has(field.value)) {
+      problems.push(`missing method .${field.value}`);
+    }
+  }
+  let entry = protocolRegistry.get(key);
+  if (entry == null) {
+    entry = new Map();
+    protocolRegistry.set(key, entry);
+  }
+  if (problems.length === 0) {
+    for (let [other, otherMethods] of entry) {
+      if (other !== protocol.name) {
+        for (let name of methods.keys()) {
+          if (otherMethods.has(name)) {
+            problems.push(`method .${name} is already implemented by ${other}`);
+          }
+        }
+      }
+    }
+  }
+  if (problems.length > 0) {
+    problems.sort();
+    throw new Error(`extend-protocol! failed for ${protocol.name} on ${toString(target, true)}\n  ${problems.join("\n  ")}`);
+  }
+  entry.set(protocol.name, methods);
+  return null;
+};
+
+/** whether implementations of the protocol are registered for the type of a value */
+export let satisfies_$q_ = (protocol: CalcitValue, x: CalcitValue): boolean => {
+  if (!(protocol instanceof CalcitRecord)) {
+    throw new Error(`satisfies? expected a protocol, got: ${toString(protocol, true)}`);
+  }
+  return protocolRegistry.get(protocolTargetOf(x))?.has(protocol.name) === true;
+};
+
 export let _$n_map_$o_to_list = (m: CalcitValue): CalcitSliceList => {
   if (m instanceof CalcitMap || m instanceof CalcitSliceMap) {
     let ys = [];
@@ -1406,6 +1743,55 @@ export let _$n_map_$o_common_keys = (a: CalcitValue, b: CalcitValue): CalcitSet
   }
 };
 
+/** checks bounds of ranges in sorted maps and sorted sets, `null` for no upper bound */
+let checkRangeBounds = (from: CalcitValue, to: CalcitValue, procName: string): void => {
+  if (to != null && _$n_compare(from, to) > 0) {
+    throw new Error(`${procName} expected range from a smaller value, got: ${toString(from, true)} ${toString(to, true)}`);
+  }
+};
+
+export let _$n_map_$o_first = (m: CalcitValue): CalcitValue => {
+  if (m instanceof CalcitSortedMap) {
+    let pair = m.first();
+    return pair == null ? null : new CalcitSliceList(pair);
+  }
+  throw new Error(`&map:first expected a sorted map, got: ${toString(m, true)}`);
+};
+
+export let _$n_map_$o_last = (m: CalcitValue): CalcitValue => {
+  if (m instanceof CalcitSortedMap) {
+    let pair = m.last();
+    return pair == null ? null : new CalcitSliceList(pair);
+  }
+  throw new Error(`&map:last expected a sorted map, got: ${toString(m, true)}`);
+};
+
+export let _$n_map_$o_range = (m: CalcitValue, from: CalcitValue, to: CalcitValue): CalcitSortedMap => {
+  if (m instanceof CalcitSortedMap) {
+    checkRangeBounds(from, to, "&map:range");
+    return m.range(from, to);
+  }
+  throw new Error(`&map:range expected a sorted map, got: ${toString(m, true)}`);
+};
+
+export let _$n_set_$o_first = (xs: CalcitValue): CalcitValue => {
+  if (xs instanceof CalcitSortedSet) return xs.first();
+  throw new Error(`&set:first expected a sorted set, got: ${toString(xs, true)}`);
+};
+
+export let _$n_set_$o_last = (xs: CalcitValue): CalcitValue => {
+  if (xs instanceof CalcitSortedSet) return xs.last();
+  throw new Error(`&set:last expected a sorted set, got: ${toString(xs, true)}`);
+};
+
+export let _$n_set_$o_range = (xs: CalcitValue, from: CalcitValue, to: CalcitValue): CalcitSortedSet => {
+  if (xs instanceof CalcitSortedSet) {
+    checkRangeBounds(from, to, "&set:range");
+    return xs.range(from, to);
+  }
+  throw new Error(`&set:range expected a sorted set, got: ${toString(xs, true)}`);
+};
+
 export let bit_shr = (base: number, step: number): number => {
   return base >> step;
 };
@@ -1521,3 +1907,50 @@ export let _calcit_args_mismatch = (name: string, expected: number, got: number)
 
 // already handled in code emitter
 export let raise = unavailableProc;
+
+const errorRecordName = "CalcitError";
+const errorRecordFields = ["location", "message", "payload", "stack"].map((x) => newTag(x));
+
+/** builds the error thrown by `raise`, values are joined with spaces into the message, like `raise` in Rust.
+ * a single value that's not a string is kept as payload in `data`, and an error record from `try` is rethrown */
+export let make_error = (...xs: CalcitValue[]): Error => {
+  let x = xs[0];
+  if (xs.length === 1 && x instanceof CalcitRecord && x.name.value === errorRecordName) {
+    let err = new Error(x.get("message") as string);
+    (err as any).data = x.get("payload");
+    (err as any).calcitLocation = x.get("location");
+    return err;
+  }
+  let err = new Error(xs.map((y) => toString(y, false)).join(" "));
+  (err as any).data = xs.length === 1 && typeof x !== "string" ? x : null;
+  return err;
+};
+
+export let raise_data = function (message: CalcitValue, payload: CalcitValue): never {
+  if (arguments.length !== 2) throw new Error("raise-data takes 2 arguments");
+  let err = new Error(toString(message, false));
+  (err as any).data = payload;
+  throw err;
+};
+
+/** record received by handlers of `try`, same fields as in Rust. `:stack` holds lines from JavaScript stack */
+export let to_error_record = (e: any): CalcitRecord => {
+  if (!(e instanceof Error)) {
+    return new CalcitRecord(newTag(errorRecordName), errorRecordFields, [null, toString(e, false), e, new CalcitSliceList([])]);
+  }
+  let stack: string[] = [];
+  if (typeof e.stack === "string") {
+    for (let line of e.stack.split("\n").slice(1)) {
+      let trimmed = line.trim();
+      if (trimmed.startsWith("at ")) {
+        stack.push(trimmed.slice(3));
+      }
+    }
+  }
+  return new CalcitRecord(newTag(errorRecordName), errorRecordFields, [
+    (e as any).calcitLocation ?? null,
+    e.message,
+    (e as any).data ?? null,
+    new CalcitSliceList(stack),
+  ]);
+};
@@ -0,0 +1,469 @@
+import { CirruWriterNode } from "@cirru/writer.ts";
+
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitRecord } from "./js-record.mjs";
+import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
+import { CalcitSet } from "./js-set.mjs";
+import { CalcitTag, CalcitSymbol, newTag, toString } from "./calcit-data.mjs";
+import { CalcitTuple } from "./js-tuple.mjs";
+import { CalcitCirruQuote } from "./js-cirru.mjs";
+
+// CBOR encoding of Calcit data, kept byte-identical with `src/data/binary.rs`
+
+const CBOR_TAG_TAG = 39;
+const CBOR_TAG_SET = 258;
+const CBOR_TAG_SYMBOL = 40001;
+const CBOR_TAG_RECORD = 40002;
+const CBOR_TAG_TUPLE = 40003;
+const CBOR_TAG_QUOTE = 40004;
+
+/** items nested deeper are rejected before the stack overflows, same as `src/data/binary.rs` */
+const MAX_BINARY_DEPTH = 512;
+
+const MAX_SAFE_INTEGER = 9007199254740991;
+const POW_32 = 4294967296;
+
+const MAJOR_UNSIGNED = 0;
+const MAJOR_NEGATIVE = 1;
+const MAJOR_BYTES = 2;
+const MAJOR_TEXT = 3;
+const MAJOR_ARRAY = 4;
+const MAJOR_MAP = 5;
+const MAJOR_TAG = 6;
+const MAJOR_SIMPLE = 7;
+
+let textEncoder = new TextEncoder();
+let textDecoder = new TextDecoder("utf-8", { fatal: true });
+
+/** compares bytes, shorter one goes first when it is a prefix, same as `Vec<u8>` in Rust */
+let compareBytes = (a: Uint8Array, b: Uint8Array): number => {
+  let size = Math.min(a.length, b.length);
+  for (let i = 0; i < size; i++) {
+    if (a[i] !== b[i]) {
+      return a[i] - b[i];
+    }
+  }
+  return a.length - b.length;
+};
+
+class BinaryWriter {
+  chunks: number[] = [];
+  parts: Uint8Array[] = [];
+
+  flushChunks() {
+    if (this.chunks.length > 0) {
+      this.parts.push(Uint8Array.from(this.chunks));
+      this.chunks = [];
+    }
+  }
+
+  pushBytes(bytes: Uint8Array) {
+    this.flushChunks();
+    this.parts.push(bytes);
+  }
+
+  toBytes(): Uint8Array {
+    this.flushChunks();
+    let size = this.parts.reduce((acc, p) => acc + p.length, 0);
+    let result = new Uint8Array(size);
+    let offset = 0;
+    for (let p of this.parts) {
+      result.set(p, offset);
+      offset += p.length;
+    }
+    return result;
+  }
+
+  /** the head of an item, with the argument in shortest form */
+  writeHead(major: number, n: number) {
+    let m = major << 5;
+    if (n < 24) {
+      this.chunks.push(m | n);
+    } else if (n <= 0xff) {
+      this.chunks.push(m | 24, n);
+    } else if (n <= 0xffff) {
+      this.chunks.push(m | 25, n >>> 8, n & 0xff);
+    } else if (n < POW_32) {
+      this.chunks.push(m | 26, (n >>> 24) & 0xff, (n >>> 16) & 0xff, (n >>> 8) & 0xff, n & 0xff);
+    } else {
+      let hi = Math.floor(n / POW_32);
+      let lo = n % POW_32;
+      this.chunks.push(m | 27);
+      for (let word of [hi, lo]) {
+        this.chunks.push((word >>> 24) & 0xff, (word >>> 16) & 0xff, (word >>> 8) & 0xff, word & 0xff);
+      }
+    }
+  }
+
+  writeText(s: string) {
+    let bytes = textEncoder.encode(s);
+    this.writeHead(MAJOR_TEXT, bytes.length);
+    this.pushBytes(bytes);
+  }
+
+  writeNumber(n: number) {
+    if (Number.isInteger(n) && Math.abs(n) <= MAX_SAFE_INTEGER && !Object.is(n, -0)) {
+      if (n >= 0) {
+        this.writeHead(MAJOR_UNSIGNED, n);
+      } else {
+        this.writeHead(MAJOR_NEGATIVE, -1 - n);
+      }
+    } else {
+      let bytes = new Uint8Array(9);
+      bytes[0] = (MAJOR_SIMPLE << 5) | 27;
+      new DataView(bytes.buffer).setFloat64(1, n);
+      this.pushBytes(bytes);
+    }
+  }
+
+  writeCirru(x: CirruWriterNode) {
+    if (typeof x === "string") {
+      this.writeText(x);
+    } else {
+      this.writeHead(MAJOR_ARRAY, x.length);
+      for (let y of x) {
+        this.writeCirru(y);
+      }
+    }
+  }
+
+  /** items encoded separately and sorted by bytes, for deterministic output */
+  writeSorted(items: Uint8Array[]) {
+    items.sort(compareBytes);
+    for (let item of items) {
+      this.pushBytes(item);
+    }
+  }
+
+  writeValue(x: CalcitValue) {
+    if (x == null) {
+      this.chunks.push((MAJOR_SIMPLE << 5) | 22);
+    } else if (typeof x === "boolean") {
+      this.chunks.push((MAJOR_SIMPLE << 5) | (x ? 21 : 20));
+    } else if (typeof x === "number") {
+      this.writeNumber(x);
+    } else if (typeof x === "string") {
+      this.writeText(x);
+    } else if (x instanceof Uint8Array) {
+      this.writeHead(MAJOR_BYTES, x.length);
+      this.pushBytes(x.slice());
+    } else if (x instanceof CalcitTag) {
+      this.writeHead(MAJOR_TAG, CBOR_TAG_TAG);
+      this.writeText(x.value);
+    } else if (x instanceof CalcitSymbol) {
+      this.writeHead(MAJOR_TAG, CBOR_TAG_SYMBOL);
+      this.writeText(x.value);
+    } else if (x instanceof CalcitList || x instanceof CalcitSliceList) {
+      this.writeHead(MAJOR_ARRAY, x.len());
+      for (let y of x.items()) {
+        this.writeValue(y);
+      }
+    } else if (x instanceof CalcitSet) {
+      let values = x.values();
+      this.writeHead(MAJOR_TAG, CBOR_TAG_SET);
+      this.writeHead(MAJOR_ARRAY, values.length);
+      this.writeSorted(values.map(encodeValue));
+    } else if (x instanceof CalcitMap || x instanceof CalcitSliceMap) {
+      let pairs = x.pairs();
+      this.writeHead(MAJOR_MAP, pairs.length);
+      // keys are unique, so entries are sorted by keys
+      this.writeSorted(
+        pairs.map(([k, v]) => {
+          let entry = new BinaryWriter();
+          entry.writeValue(k);
+          entry.writeValue(v);
+          return entry.toBytes();
+        })
+      );
+    } else if (x instanceof CalcitRecord) {
+      this.writeHead(MAJOR_TAG, CBOR_TAG_RECORD);
+      this.writeHead(MAJOR_ARRAY, 3);
+      this.writeText(x.name.value);
+      // sorted by names, same order as in Rust
+      let pairs: [Uint8Array, CalcitValue][] = x.fields.map((f, idx) => [textEncoder.encode(f.value), x.values[idx]]);
+      pairs.sort((a, b) => compareBytes(a[0], b[0]));
+      this.writeHead(MAJOR_ARRAY, pairs.length);
+      for (let [field] of pairs) {
+        this.writeHead(MAJOR_TEXT, field.length);
+        this.pushBytes(field);
+      }
+      this.writeHead(MAJOR_ARRAY, pairs.length);
+      for (let [, v] of pairs) {
+        this.writeValue(v);
+      }
+    } else if (x instanceof CalcitTuple) {
+      this.writeHead(MAJOR_TAG, CBOR_TAG_TUPLE);
+      this.writeHead(MAJOR_ARRAY, x.extra.length + 1);
+      // enum tuples are tagged by records, which are encoded with their variants
+      this.writeValue(x.tag);
+      for (let y of x.extra) {
+        this.writeValue(y);
+      }
+    } else if (x instanceof CalcitCirruQuote) {
+      this.writeHead(MAJOR_TAG, CBOR_TAG_QUOTE);
+      this.writeCirru(x.value);
+    } else {
+      throw new Error(`format-binary cannot encode value: ${x}`);
+    }
+  }
+}
+
+let encodeValue = (x: CalcitValue): Uint8Array => {
+  let writer = new BinaryWriter();
+  writer.writeValue(x);
+  return writer.toBytes();
+};
+
+class BinaryParser {
+  bytes: Uint8Array;
+  view: DataView;
+  pos: number;
+
+  constructor(bytes: Uint8Array) {
+    this.bytes = bytes;
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    this.pos = 0;
+  }
+
+  fail(reason: string): Error {
+    return new Error(`parse-binary failed at byte ${this.pos}: ${reason}`);
+  }
+
+  take(size: number): number {
+    if (this.bytes.length - this.pos < size) {
+      throw this.fail("unexpected end of input");
+    }
+    let start = this.pos;
+    this.pos += size;
+    return start;
+  }
+
+  /** reads major type, additional info and argument of the next item, indefinite lengths are not supported */
+  readHead(): [number, number, number] {
+    let start = this.pos;
+    let initial = this.bytes[this.take(1)];
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    if (info < 24) {
+      return [major, info, info];
+    } else if (info === 24) {
+      return [major, info, this.bytes[this.take(1)]];
+    } else if (info === 25) {
+      return [major, info, this.view.getUint16(this.take(2))];
+    } else if (info === 26) {
+      return [major, info, this.view.getUint32(this.take(4))];
+    } else if (info === 27) {
+      let offset = this.take(8);
+      return [major, info, this.view.getUint32(offset) * POW_32 + this.view.getUint32(offset + 4)];
+    }
+    this.pos = start;
+    throw this.fail(`unsupported initial byte 0x${initial.toString(16).padStart(2, "0")}`);
+  }
+
+  /** length of a string or a collection, checked against remaining bytes */
+  readSize(n: number): number {
+    if (n > this.bytes.length - this.pos) {
+      throw this.fail(`length ${n} exceeds remaining bytes`);
+    }
+    return n;
+  }
+
+  readTextBody(n: number): string {
+    let size = this.readSize(n);
+    let start = this.take(size);
+    try {
+      return textDecoder.decode(this.bytes.subarray(start, start + size));
+    } catch (e) {
+      this.pos = start;
+      throw this.fail("invalid UTF-8 in text");
+    }
+  }
+
+  readText(): string {
+    let [major, , n] = this.readHead();
+    if (major !== MAJOR_TEXT) {
+      throw this.fail("expected text");
+    }
+    return this.readTextBody(n);
+  }
+
+  readArraySize(): number {
+    let [major, , n] = this.readHead();
+    if (major !== MAJOR_ARRAY) {
+      throw this.fail("expected array");
+    }
+    return this.readSize(n);
+  }
+
+  readItems(size: number, depth: number): CalcitValue[] {
+    let ys: CalcitValue[] = [];
+    for (let i = 0; i < size; i++) {
+      ys.push(this.readValue(depth));
+    }
+    return ys;
+  }
+
+  checkDepth(depth: number) {
+    if (depth > MAX_BINARY_DEPTH) {
+      throw this.fail(`nested deeper than ${MAX_BINARY_DEPTH} levels`);
+    }
+  }
+
+  readCirru(depth: number): CirruWriterNode {
+    this.checkDepth(depth);
+    let [major, , n] = this.readHead();
+    if (major === MAJOR_TEXT) {
+      return this.readTextBody(n);
+    } else if (major === MAJOR_ARRAY) {
+      let size = this.readSize(n);
+      let ys: CirruWriterNode[] = [];
+      for (let i = 0; i < size; i++) {
+        ys.push(this.readCirru(depth + 1));
+      }
+      return ys;
+    }
+    throw this.fail("expected text or array in Cirru quote");
+  }
+
+  /** `depth` counts collections and tags around the item */
+  readValue(depth: number): CalcitValue {
+    this.checkDepth(depth);
+    let start = this.pos;
+    let [major, info, n] = this.readHead();
+    switch (major) {
+      case MAJOR_UNSIGNED:
+        return n;
+      case MAJOR_NEGATIVE:
+        return -1 - n;
+      case MAJOR_BYTES: {
+        let size = this.readSize(n);
+        let offset = this.take(size);
+        return this.bytes.slice(offset, offset + size);
+      }
+      case MAJOR_TEXT:
+        return this.readTextBody(n);
+      case MAJOR_ARRAY:
+        return new CalcitSliceList(this.readItems(this.readSize(n), depth + 1));
+      case MAJOR_MAP: {
+        let size = this.readSize(n);
+        let result = new CalcitSliceMap([]) as CalcitMap | CalcitSliceMap;
+        for (let i = 0; i < size; i++) {
+          let k = this.readValue(depth + 1);
+          if (result.contains(k)) {
+            throw this.fail(`duplicated key ${toString(k, true)} in map`);
+          }
+          let v = this.readValue(depth + 1);
+          result = result.assoc(k, v);
+        }
+        return result;
+      }
+      case MAJOR_TAG:
+        return this.readTagged(n, start, depth + 1);
+      default:
+        if (info === 20) {
+          return false;
+        } else if (info === 21) {
+          return true;
+        } else if (info === 22) {
+          return null;
+        } else if (info === 25) {
+          return halfToNumber(n);
+        } else if (info === 26) {
+          return this.view.getFloat32(this.pos - 4);
+        } else if (info === 27) {
+          return this.view.getFloat64(this.pos - 8);
+        }
+        this.pos = start;
+        throw this.fail(`unsupported simple value 0x${this.bytes[start].toString(16).padStart(2, "0")}`);
+    }
+  }
+
+  readTagged(tag: number, start: number, depth: number): CalcitValue {
+    switch (tag) {
+      case CBOR_TAG_TAG:
+        return newTag(this.readText());
+      case CBOR_TAG_SYMBOL:
+        return new CalcitSymbol(this.readText());
+      case CBOR_TAG_SET: {
+        let result = new CalcitSet([]);
+        for (let y of this.readItems(this.readArraySize(), depth)) {
+          if (result.contains(y)) {
+            throw this.fail(`duplicated item ${toString(y, true)} in set`);
+          }
+          result = result.include(y);
+        }
+        return result;
+      }
+      case CBOR_TAG_RECORD: {
+        if (this.readArraySize() !== 3) {
+          throw this.fail("expected name, fields and values in record");
+        }
+        let name = this.readText();
+        let size = this.readArraySize();
+        let fields: CalcitTag[] = [];
+        for (let i = 0; i < size; i++) {
+          fields.push(newTag(this.readText()));
+        }
+        if (this.readArraySize() !== size) {
+          throw this.fail("expected values as many as fields in record");
+        }
+        let values = this.readItems(size, depth);
+        let pairs: [CalcitTag, CalcitValue][] = fields.map((f, idx) => [f, values[idx]]);
+        pairs.sort((a, b) => a[0].cmp(b[0]));
+        for (let idx = 1; idx < pairs.length; idx++) {
+          if (pairs[idx - 1][0] === pairs[idx][0]) {
+            throw this.fail(`duplicated field \`${pairs[idx][0].value}\` in record`);
+          }
+        }
+        return new CalcitRecord(
+          newTag(name),
+          pairs.map((p) => p[0]),
+          pairs.map((p) => p[1])
+        );
+      }
+      case CBOR_TAG_TUPLE: {
+        let size = this.readArraySize();
+        if (size === 0) {
+          throw this.fail("expected a tag in tuple");
+        }
+        let items = this.readItems(size, depth);
+        return new CalcitTuple(items[0], items.slice(1), undefined);
+      }
+      case CBOR_TAG_QUOTE:
+        return new CalcitCirruQuote(this.readCirru(depth));
+      default:
+        this.pos = start;
+        throw this.fail(`unknown tag ${tag}`);
+    }
+  }
+}
+
+/** half precision float, only decoded since numbers are encoded in 64 bits */
+let halfToNumber = (bits: number): number => {
+  let sign = bits & 0x8000 ? -1 : 1;
+  let exponent = (bits >> 10) & 0x1f;
+  let fraction = bits & 0x3ff;
+  if (exponent === 0) {
+    return sign * fraction * Math.pow(2, -24);
+  } else if (exponent === 0x1f) {
+    return fraction === 0 ? sign * Infinity : NaN;
+  }
+  return sign * (1 + fraction / 1024) * Math.pow(2, exponent - 15);
+};
+
+export let format_binary = (x: CalcitValue): Uint8Array => {
+  return encodeValue(x);
+};
+
+export let parse_binary = (buf: CalcitValue): CalcitValue => {
+  if (!(buf instanceof Uint8Array)) {
+    throw new Error(`parse-binary expected a buffer, got: ${buf}`);
+  }
+  let parser = new BinaryParser(buf);
+  let v = parser.readValue(0);
+  if (parser.pos < buf.length) {
+    throw parser.fail("unexpected trailing bytes");
+  }
+  return v;
+};
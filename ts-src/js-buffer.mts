@@ -0,0 +1,355 @@
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitTag, toString } from "./calcit-data.mjs";
+
+// procs for buffers, kept in sync with `src/builtins/buffers.rs`.
+// buffers are treated as immutable, procs like `&buffer:assoc` return new buffers.
+
+const MAX_SAFE_INTEGER = 9007199254740991;
+
+const BASE64_CHARS = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+let textEncoder = new TextEncoder();
+
+interface ByteNumber {
+  kind: "unsigned" | "signed" | "float";
+  size: number;
+  littleEndian: boolean;
+}
+
+let parseByteNumber = (x: CalcitValue, procName: string): ByteNumber => {
+  if (!(x instanceof CalcitTag)) {
+    throw new Error(`${procName} expected a tag of number kind, got: ${toString(x, true)}`);
+  }
+  let name = x.value;
+  let fail = () => new Error(`${procName} unknown number kind: :${name}`);
+  let [base, suffix] = name.split("-", 2);
+  let littleEndian = false;
+  if (suffix === "le") {
+    littleEndian = true;
+  } else if (suffix != null && suffix !== "be") {
+    throw fail();
+  }
+  let kinds: Record<string, [ByteNumber["kind"], number]> = {
+    u8: ["unsigned", 1],
+    i8: ["signed", 1],
+    u16: ["unsigned", 2],
+    i16: ["signed", 2],
+    u32: ["unsigned", 4],
+    i32: ["signed", 4],
+    u64: ["unsigned", 8],
+    i64: ["signed", 8],
+    f32: ["float", 4],
+    f64: ["float", 8],
+  };
+  let found = kinds.hasOwnProperty(base) ? kinds[base] : null;
+  // endianness is required for numbers with more than 1 byte
+  if (found == null || (found[1] === 1) === (suffix != null) || name.split("-").length > 2) {
+    throw fail();
+  }
+  return { kind: found[0], size: found[1], littleEndian };
+};
+
+let readNumber = (kind: ByteNumber, view: DataView, offset: number): number => {
+  let le = kind.littleEndian;
+  switch (kind.kind) {
+    case "float":
+      return kind.size === 4 ? view.getFloat32(offset, le) : view.getFloat64(offset, le);
+    case "unsigned":
+      switch (kind.size) {
+        case 1:
+          return view.getUint8(offset);
+        case 2:
+          return view.getUint16(offset, le);
+        case 4:
+          return view.getUint32(offset, le);
+        default:
+          return Number(view.getBigUint64(offset, le));
+      }
+    default:
+      switch (kind.size) {
+        case 1:
+          return view.getInt8(offset);
+        case 2:
+          return view.getInt16(offset, le);
+        case 4:
+          return view.getInt32(offset, le);
+        default:
+          return Number(view.getBigInt64(offset, le));
+      }
+  }
+};
+
+let writeNumber = (kind: ByteNumber, view: DataView, offset: number, n: number, procName: string) => {
+  let le = kind.littleEndian;
+  if (kind.kind === "float") {
+    if (kind.size === 4) {
+      view.setFloat32(offset, n, le);
+    } else {
+      view.setFloat64(offset, n, le);
+    }
+    return;
+  }
+  let bits = kind.size * 8;
+  let min = kind.kind === "unsigned" ? 0 : -Math.pow(2, bits - 1);
+  let max = kind.kind === "unsigned" ? Math.pow(2, bits) - 1 : Math.pow(2, bits - 1) - 1;
+  min = Math.max(min, -MAX_SAFE_INTEGER);
+  max = Math.min(max, MAX_SAFE_INTEGER);
+  if (!Number.isInteger(n) || n < min || n > max) {
+    throw new Error(`${procName} expected an integer within ${min}~${max}, got: ${n}`);
+  }
+  switch (kind.size) {
+    case 1:
+      view.setUint8(offset, n & 0xff);
+      break;
+    case 2:
+      view.setUint16(offset, n & 0xffff, le);
+      break;
+    case 4:
+      view.setUint32(offset, n >>> 0, le);
+      break;
+    default:
+      view.setBigInt64(offset, BigInt(n), le);
+  }
+};
+
+let readIndex = (x: CalcitValue, procName: string): number => {
+  if (typeof x === "number" && Number.isInteger(x) && x >= 0) {
+    return x;
+  }
+  throw new Error(`${procName} expected an index, got: ${toString(x, true)}`);
+};
+
+let readByte = (x: CalcitValue, procName: string): number => {
+  if (typeof x === "number" && Number.isInteger(x) && x >= 0 && x <= 255) {
+    return x;
+  }
+  throw new Error(`${procName} expected a byte within 0~255, got: ${toString(x, true)}`);
+};
+
+let expectBuffer = (x: CalcitValue, procName: string): Uint8Array => {
+  if (x instanceof Uint8Array) {
+    return x;
+  }
+  throw new Error(`${procName} expected a buffer, got: ${toString(x, true)}`);
+};
+
+let viewOf = (buf: Uint8Array): DataView => new DataView(buf.buffer, buf.byteOffset, buf.byteLength);
+
+export let _$n_buffer_$o_count = (buf: CalcitValue): number => {
+  return expectBuffer(buf, "&buffer:count").length;
+};
+
+/** `nil` when index is out of range */
+export let _$n_buffer_$o_nth = (buf: CalcitValue, idx: CalcitValue): number => {
+  let xs = expectBuffer(buf, "&buffer:nth");
+  let i = readIndex(idx, "&buffer:nth");
+  return i < xs.length ? xs[i] : null;
+};
+
+export let _$n_buffer_$o_slice = (buf: CalcitValue, from: CalcitValue, to: CalcitValue = null): Uint8Array => {
+  let xs = expectBuffer(buf, "&buffer:slice");
+  let start = readIndex(from, "&buffer:slice");
+  let end = to == null ? xs.length : readIndex(to, "&buffer:slice");
+  if (start > end || end > xs.length) {
+    throw new Error(`&buffer:slice expected range within 0~${xs.length}, got: ${start}~${end}`);
+  }
+  return xs.slice(start, end);
+};
+
+export let _$n_buffer_$o_concat = (...bufs: CalcitValue[]): Uint8Array => {
+  let size = 0;
+  for (let x of bufs) {
+    if (!(x instanceof Uint8Array)) {
+      throw new Error(`&buffer:concat expected buffers, got: ${toString(x, true)}`);
+    }
+    size += x.length;
+  }
+  let ys = new Uint8Array(size);
+  let offset = 0;
+  for (let x of bufs as Uint8Array[]) {
+    ys.set(x, offset);
+    offset += x.length;
+  }
+  return ys;
+};
+
+export let _$n_buffer_$o_assoc = (buf: CalcitValue, idx: CalcitValue, v: CalcitValue): Uint8Array => {
+  let xs = expectBuffer(buf, "&buffer:assoc");
+  let i = readIndex(idx, "&buffer:assoc");
+  let byte = readByte(v, "&buffer:assoc");
+  if (i >= xs.length) {
+    throw new Error(`&buffer:assoc index ${i} out of range 0~${xs.length}`);
+  }
+  let ys = xs.slice();
+  ys[i] = byte;
+  return ys;
+};
+
+/** finds a byte or a sequence of bytes, `-1` when not found */
+export let _$n_buffer_$o_find_index = (buf: CalcitValue, target: CalcitValue): number => {
+  let xs = expectBuffer(buf, "&buffer:find-index");
+  if (target instanceof Uint8Array) {
+    for (let i = 0; i + target.length <= xs.length; i++) {
+      let matched = true;
+      for (let j = 0; j < target.length; j++) {
+        if (xs[i + j] !== target[j]) {
+          matched = false;
+          break;
+        }
+      }
+      if (matched) {
+        return i;
+      }
+    }
+    return -1;
+  }
+  return xs.indexOf(readByte(target, "&buffer:find-index"));
+};
+
+export let _$n_buffer_$o_read = (buf: CalcitValue, kind: CalcitValue, offset: CalcitValue): number => {
+  let xs = expectBuffer(buf, "&buffer:read");
+  let k = parseByteNumber(kind, "&buffer:read");
+  let at = readIndex(offset, "&buffer:read");
+  if (at + k.size > xs.length) {
+    throw new Error(`&buffer:read ${k.size} bytes at ${at} out of buffer size ${xs.length}`);
+  }
+  return readNumber(k, viewOf(xs), at);
+};
+
+export let _$n_buffer_$o_write = (buf: CalcitValue, kind: CalcitValue, offset: CalcitValue, n: CalcitValue): Uint8Array => {
+  let xs = expectBuffer(buf, "&buffer:write");
+  let k = parseByteNumber(kind, "&buffer:write");
+  let at = readIndex(offset, "&buffer:write");
+  if (at + k.size > xs.length) {
+    throw new Error(`&buffer:write ${k.size} bytes at ${at} out of buffer size ${xs.length}`);
+  }
+  if (typeof n !== "number") {
+    throw new Error(`&buffer:write expected a number, got: ${toString(n, true)}`);
+  }
+  let ys = xs.slice();
+  writeNumber(k, viewOf(ys), at, n, "&buffer:write");
+  return ys;
+};
+
+export let _$n_buffer_$o_to_hex = (buf: CalcitValue): string => {
+  let xs = expectBuffer(buf, "&buffer:to-hex");
+  let s = "";
+  for (let i = 0; i < xs.length; i++) {
+    s += xs[i].toString(16).padStart(2, "0");
+  }
+  return s;
+};
+
+export let _$n_buffer_$o_from_hex = (s: CalcitValue): Uint8Array => {
+  if (typeof s !== "string") {
+    throw new Error(`&buffer:from-hex expected a string, got: ${toString(s, true)}`);
+  }
+  if (s.length % 2 !== 0) {
+    throw new Error("&buffer:from-hex failed, Odd number of digits");
+  }
+  let ys = new Uint8Array(s.length / 2);
+  for (let i = 0; i < s.length; i++) {
+    if (!/[0-9a-fA-F]/.test(s[i])) {
+      throw new Error(`&buffer:from-hex failed, Invalid character '${s[i]}' at position ${i}`);
+    }
+  }
+  for (let i = 0; i < ys.length; i++) {
+    ys[i] = parseInt(s.slice(i * 2, i * 2 + 2), 16);
+  }
+  return ys;
+};
+
+/** standard alphabet with padding */
+export let _$n_buffer_$o_to_base64 = (buf: CalcitValue): string => {
+  let xs = expectBuffer(buf, "&buffer:to-base64");
+  let s = "";
+  for (let i = 0; i < xs.length; i += 3) {
+    let size = Math.min(3, xs.length - i);
+    let n = (xs[i] << 16) | ((size > 1 ? xs[i + 1] : 0) << 8) | (size > 2 ? xs[i + 2] : 0);
+    for (let j = 0; j < 4; j++) {
+      s += j <= size ? BASE64_CHARS[(n >> (18 - j * 6)) & 0x3f] : "=";
+    }
+  }
+  return s;
+};
+
+/** standard alphabet, padding is optional */
+export let _$n_buffer_$o_from_base64 = (s: CalcitValue): Uint8Array => {
+  if (typeof s !== "string") {
+    throw new Error(`&buffer:from-base64 expected a string, got: ${toString(s, true)}`);
+  }
+  let body = s.replace(/=+$/, "");
+  if (s.length - body.length > 2 || body.length % 4 === 1 || (s.length !== body.length && s.length % 4 !== 0)) {
+    throw new Error(`&buffer:from-base64 invalid length of base64: ${s.length}`);
+  }
+  let ys: number[] = [];
+  for (let i = 0; i < body.length; i += 4) {
+    let chunk = body.slice(i, i + 4);
+    let n = 0;
+    for (let j = 0; j < chunk.length; j++) {
+      let v = BASE64_CHARS.indexOf(chunk[j]);
+      if (v < 0) {
+        throw new Error(`&buffer:from-base64 invalid character \`${chunk[j]}\``);
+      }
+      n |= v << (18 - j * 6);
+    }
+    for (let j = 0; j < chunk.length - 1; j++) {
+      ys.push((n >> (16 - j * 8)) & 0xff);
+    }
+  }
+  return new Uint8Array(ys);
+};
+
+/** UTF-8 decoding is self-synchronizing, so checking code points one by one finds the first invalid byte */
+let utf8ValidUpTo = (xs: Uint8Array): number => {
+  let decoder = new TextDecoder("utf-8", { fatal: true });
+  let i = 0;
+  outer: while (i < xs.length) {
+    for (let size = 1; size <= 4 && i + size <= xs.length; size++) {
+      try {
+        decoder.decode(xs.subarray(i, i + size));
+        i += size;
+        continue outer;
+      } catch (e) {
+        // try a longer sequence
+      }
+    }
+    return i;
+  }
+  return i;
+};
+
+export let _$n_buffer_$o_to_string = (buf: CalcitValue): string => {
+  let xs = expectBuffer(buf, "&buffer:to-string");
+  try {
+    return new TextDecoder("utf-8", { fatal: true }).decode(xs);
+  } catch (e) {
+    throw new Error(`&buffer:to-string invalid UTF-8 at byte ${utf8ValidUpTo(xs)}`);
+  }
+};
+
+export let _$n_buffer_$o_from_string = (s: CalcitValue): Uint8Array => {
+  if (typeof s !== "string") {
+    throw new Error(`&buffer:from-string expected a string, got: ${toString(s, true)}`);
+  }
+  return textEncoder.encode(s);
+};
+
+export let _$n_buffer_$o_to_list = (buf: CalcitValue): CalcitList => {
+  let xs = expectBuffer(buf, "&buffer:to-list");
+  return new CalcitSliceList(Array.from(xs));
+};
+
+export let _$n_buffer_$o_from_list = (xs: CalcitValue): Uint8Array => {
+  if (!(xs instanceof CalcitList || xs instanceof CalcitSliceList)) {
+    throw new Error(`&buffer:from-list expected a list, got: ${toString(xs, true)}`);
+  }
+  let ys = new Uint8Array(xs.len());
+  let idx = 0;
+  for (let x of xs.items()) {
+    ys[idx] = readByte(x, "&buffer:from-list");
+    idx++;
+  }
+  return ys;
+};
@@ -3,13 +3,12 @@ import { CirruWriterNode, writeCirruCode } from "@cirru/writer.ts";
 
 import { CalcitValue, isLiteral, _$n_compare } from "./js-primes.mjs";
 import { CalcitList, CalcitSliceList } from "./js-list.mjs";
-import { CalcitRecord } from "./js-record.mjs";
+import { CalcitRecord, fillRecordValues } from "./js-record.mjs";
 import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
 import { CalcitSet } from "./js-set.mjs";
-import { CalcitTag, CalcitSymbol, CalcitRecur, newTag } from "./calcit-data.mjs";
+import { CalcitTag, CalcitSymbol, CalcitRecur, newTag, findInFields } from "./calcit-data.mjs";
 import { CalcitTuple } from "./js-tuple.mjs";
 import { CalcitRef } from "./js-ref.mjs";
-import { deepEqual } from "@calcit/ternary-tree/lib/utils.mjs";
 import { atom } from "./js-ref.mjs";
 
 type CirruEdnFormat = string | CirruEdnFormat[];
@@ -287,10 +286,17 @@ export let extract_cirru_edn = (x: CirruEdnFormat, options: CalcitValue): Calcit
       if (options instanceof CalcitMap || options instanceof CalcitSliceMap) {
         let v = options.get(extractFieldTag(name));
         if (v != null && v instanceof CalcitRecord) {
-          if (!deepEqual(v.fields, fields)) {
-            throw new Error(`Fields mismatch for ${name}, expected ${fields}, got ${v.fields}`);
+          let filled: Array<CalcitValue> = new Array(v.fields.length);
+          let given: Array<boolean> = new Array(v.fields.length).fill(false);
+          for (let idx = 0; idx < fields.length; idx++) {
+            let position = findInFields(v.fields, fields[idx]);
+            if (position < 0) {
+              throw new Error(`unexpected field ${fields[idx]} for record ${v.name} in EDN, expected: ${v.fields}`);
+            }
+            filled[position] = values[idx];
+            given[position] = true;
           }
-          return new CalcitRecord(extractFieldTag(name), fields, values, v.klass);
+          return new CalcitRecord(v.name, v.fields, fillRecordValues(v, filled, given, "parse-cirru-edn"), v.klass, v.schema);
         }
       }
 
@@ -0,0 +1,339 @@
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
+import { CalcitTag, newTag } from "./calcit-data.mjs";
+import { CalcitLazySeq } from "./js-lazy.mjs";
+
+// filesystem and subprocess procs, only available in Node.js, same shapes of results as `src/builtins/files.rs`
+
+let loadModule = (name: string, procName: string): any => {
+  let getBuiltinModule = typeof process !== "undefined" ? (process as any).getBuiltinModule : null;
+  if (typeof getBuiltinModule !== "function") {
+    throw new Error(`${procName} is only available in Node.js 20.16+`);
+  }
+  return getBuiltinModule.call(process, name);
+};
+
+let readPath = (x: CalcitValue, procName: string): string => {
+  if (typeof x === "string") {
+    return x;
+  }
+  throw new Error(`${procName} expected a path string, got: ${x}`);
+};
+
+let withPath = <T>(procName: string, path: string, f: () => T): T => {
+  try {
+    return f();
+  } catch (e) {
+    throw new Error(`${procName} failed at ${path}: ${(e as Error).message}`);
+  }
+};
+
+let compareStrings = (a: string, b: string): number => (a < b ? -1 : a > b ? 1 : 0);
+
+export let read_file_buffer = function (path: CalcitValue): Uint8Array {
+  if (arguments.length !== 1) throw new Error("read-file-buffer takes 1 argument");
+  let p = readPath(path, "read-file-buffer");
+  let fs = loadModule("fs", "read-file-buffer");
+  return withPath("read-file-buffer", p, () => new Uint8Array(fs.readFileSync(p)));
+};
+
+export let write_file_buffer = function (path: CalcitValue, content: CalcitValue): void {
+  if (arguments.length !== 2) throw new Error("write-file-buffer takes 2 arguments");
+  let p = readPath(path, "write-file-buffer");
+  if (!(content instanceof Uint8Array)) {
+    throw new Error(`write-file-buffer expected a buffer, got: ${content}`);
+  }
+  let fs = loadModule("fs", "write-file-buffer");
+  withPath("write-file-buffer", p, () => fs.writeFileSync(p, content));
+};
+
+export let list_dir = function (path: CalcitValue): CalcitSliceList {
+  if (arguments.length !== 1) throw new Error("list-dir takes 1 argument");
+  let p = readPath(path, "list-dir");
+  let fs = loadModule("fs", "list-dir");
+  let names: string[] = withPath("list-dir", p, () => fs.readdirSync(p));
+  return new CalcitSliceList(names.slice().sort(compareStrings));
+};
+
+export let make_dir = function (path: CalcitValue): void {
+  if (arguments.length !== 1) throw new Error("make-dir takes 1 argument");
+  let p = readPath(path, "make-dir");
+  let fs = loadModule("fs", "make-dir");
+  withPath("make-dir", p, () => fs.mkdirSync(p, { recursive: true }));
+};
+
+export let remove_path = function (path: CalcitValue): void {
+  if (arguments.length !== 1) throw new Error("remove-path takes 1 argument");
+  let p = readPath(path, "remove-path");
+  let fs = loadModule("fs", "remove-path");
+  withPath("remove-path", p, () => {
+    fs.lstatSync(p);
+    fs.rmSync(p, { recursive: true });
+  });
+};
+
+export let rename_path = function (from: CalcitValue, to: CalcitValue): void {
+  if (arguments.length !== 2) throw new Error("rename-path takes 2 arguments");
+  let a = readPath(from, "rename-path");
+  let b = readPath(to, "rename-path");
+  let fs = loadModule("fs", "rename-path");
+  try {
+    fs.renameSync(a, b);
+  } catch (e) {
+    throw new Error(`rename-path failed from ${a} to ${b}: ${(e as Error).message}`);
+  }
+};
+
+export let path_exists_$q_ = function (path: CalcitValue): boolean {
+  if (arguments.length !== 1) throw new Error("path-exists? takes 1 argument");
+  let p = readPath(path, "path-exists?");
+  return loadModule("fs", "path-exists?").existsSync(p);
+};
+
+export let path_stat = function (path: CalcitValue): CalcitSliceMap {
+  if (arguments.length !== 1) throw new Error("path-stat takes 1 argument");
+  let p = readPath(path, "path-stat");
+  let fs = loadModule("fs", "path-stat");
+  let stat = withPath("path-stat", p, () => fs.lstatSync(p));
+  let kind = stat.isSymbolicLink() ? "symlink" : stat.isDirectory() ? "dir" : "file";
+  // same as `Permissions::readonly` in Rust, no write bit for anyone
+  let readonly = (stat.mode & 0o222) === 0;
+  return new CalcitSliceMap([
+    newTag("kind"),
+    newTag(kind),
+    newTag("size"),
+    stat.size,
+    newTag("modified"),
+    stat.mtimeMs,
+    newTag("readonly"),
+    readonly,
+  ]);
+};
+
+export let glob_paths = function (pattern: CalcitValue): CalcitSliceList {
+  if (arguments.length !== 1) throw new Error("glob-paths takes 1 argument");
+  if (typeof pattern !== "string") {
+    throw new Error(`glob-paths expected a pattern string, got: ${pattern}`);
+  }
+  let fs = loadModule("fs", "glob-paths");
+  if (typeof fs.globSync !== "function") {
+    throw new Error("glob-paths is only available in Node.js 22+");
+  }
+  let paths: string[] = fs.globSync(pattern);
+  return new CalcitSliceList(paths.slice().sort(compareStrings));
+};
+
+let readOption = (options: CalcitValue, name: string): CalcitValue => {
+  if (options == null) {
+    return null;
+  }
+  if (options instanceof CalcitMap || options instanceof CalcitSliceMap) {
+    return options.get(newTag(name));
+  }
+  throw new Error(`run-process expected a map of options, got: ${options}`);
+};
+
+/** blocking, like in Rust. since output is collected after the process exits,
+ * `:on-line` receives all lines of stdout before lines of stderr */
+export let run_process = (cmd: CalcitValue, args?: CalcitValue, options?: CalcitValue): CalcitSliceMap => {
+  if (typeof cmd !== "string") {
+    throw new Error(`run-process expected a command string, got: ${cmd}`);
+  }
+  let argv: string[] = [];
+  if (args instanceof CalcitList || args instanceof CalcitSliceList) {
+    for (let x of args.items()) {
+      if (typeof x !== "string") {
+        throw new Error(`run-process expected string arguments, got: ${x}`);
+      }
+      argv.push(x);
+    }
+  } else if (args != null) {
+    throw new Error(`run-process expected a list of arguments, got: ${args}`);
+  }
+
+  let cwd = readOption(options, "cwd");
+  if (cwd != null && typeof cwd !== "string") {
+    throw new Error(`run-process expected a string for :cwd, got: ${cwd}`);
+  }
+  let env: Record<string, string> = Object.assign({}, process.env);
+  let envOption = readOption(options, "env");
+  if (envOption instanceof CalcitMap || envOption instanceof CalcitSliceMap) {
+    for (let [k, v] of envOption.pairs()) {
+      let key = k instanceof CalcitTag ? k.value : k;
+      if (typeof key !== "string" || typeof v !== "string") {
+        throw new Error(`run-process expected strings in :env, got: ${k} ${v}`);
+      }
+      env[key] = v;
+    }
+  } else if (envOption != null) {
+    throw new Error(`run-process expected a map for :env, got: ${envOption}`);
+  }
+  let stdin = readOption(options, "stdin");
+  if (stdin != null && typeof stdin !== "string") {
+    throw new Error(`run-process expected a string for :stdin, got: ${stdin}`);
+  }
+  let onLine = readOption(options, "on-line");
+  if (onLine != null && typeof onLine !== "function") {
+    throw new Error(`run-process expected a function for :on-line, got: ${onLine}`);
+  }
+
+  let child_process = loadModule("child_process", "run-process");
+  let result = child_process.spawnSync(cmd, argv, {
+    cwd: cwd ?? undefined,
+    env,
+    input: stdin ?? undefined,
+    stdio: [stdin != null ? "pipe" : "ignore", "pipe", "pipe"],
+    encoding: "utf8",
+    maxBuffer: Infinity,
+  });
+  if (result.error != null) {
+    throw new Error(`run-process failed to spawn \`${cmd}\`: ${result.error.message}`);
+  }
+  let stdout: string = result.stdout ?? "";
+  let stderr: string = result.stderr ?? "";
+  if (onLine != null) {
+    for (let [text, stream] of [
+      [stdout, "stdout"],
+      [stderr, "stderr"],
+    ]) {
+      let lines = text.split("\n");
+      if (lines[lines.length - 1] === "") {
+        lines.pop();
+      }
+      for (let line of lines) {
+        (onLine as Function)(line.endsWith("\r") ? line.slice(0, -1) : line, newTag(stream));
+      }
+    }
+  }
+  return new CalcitSliceMap([newTag("code"), result.status, newTag("stdout"), stdout, newTag("stderr"), stderr]);
+};
+
+/** bytes read from stdin but not consumed by `read-stdin-line` yet */
+let stdinPending: Uint8Array = new Uint8Array(0);
+let stdinEnded = false;
+
+/** reads a chunk from stdin synchronously, returns false at EOF */
+let readStdinChunk = (procName: string): boolean => {
+  if (stdinEnded) {
+    return false;
+  }
+  let fs = loadModule("fs", procName);
+  let chunk = new Uint8Array(65536);
+  let size: number;
+  for (;;) {
+    try {
+      size = fs.readSync(0, chunk, 0, chunk.length, null);
+      break;
+    } catch (e) {
+      // stdin could be non-blocking in Node.js, keep trying
+      if ((e as any).code === "EAGAIN") {
+        continue;
+      }
+      if ((e as any).code === "EOF") {
+        size = 0;
+        break;
+      }
+      throw new Error(`${procName} failed, ${(e as Error).message}`);
+    }
+  }
+  if (size === 0) {
+    stdinEnded = true;
+    return false;
+  }
+  let next = new Uint8Array(stdinPending.length + size);
+  next.set(stdinPending, 0);
+  next.set(chunk.subarray(0, size), stdinPending.length);
+  stdinPending = next;
+  return true;
+};
+
+export let read_stdin = (): string => {
+  while (readStdinChunk("read-stdin")) {}
+  let content = new TextDecoder().decode(stdinPending);
+  stdinPending = new Uint8Array(0);
+  return content;
+};
+
+/** returns `null` at EOF, line endings are removed */
+export let read_stdin_line = (): string => {
+  let searched = 0;
+  for (;;) {
+    let idx = stdinPending.indexOf(10, searched);
+    if (idx >= 0) {
+      let line = new TextDecoder().decode(stdinPending.subarray(0, idx));
+      stdinPending = stdinPending.slice(idx + 1);
+      return line.endsWith("\r") ? line.slice(0, -1) : line;
+    }
+    searched = stdinPending.length;
+    if (!readStdinChunk("read-stdin-line")) {
+      if (stdinPending.length === 0) {
+        return null;
+      }
+      let line = new TextDecoder().decode(stdinPending);
+      stdinPending = new Uint8Array(0);
+      return line;
+    }
+  }
+};
+
+export let each_stdin_line = (f: CalcitValue): void => {
+  if (typeof f !== "function") {
+    throw new Error(`each-stdin-line expected a function, got: ${f}`);
+  }
+  for (;;) {
+    let line = read_stdin_line();
+    if (line == null) {
+      return;
+    }
+    f(line);
+  }
+};
+
+/** lines without line endings, the file is opened again every time the sequence is consumed */
+export let read_file_lines = (path: CalcitValue): CalcitLazySeq => {
+  let p = readPath(path, "read-file-lines");
+  return new CalcitLazySeq("file-lines", function* () {
+    let fs = loadModule("fs", "read-file-lines");
+    let fd: number = withPath("read-file-lines", p, () => fs.openSync(p, "r"));
+    try {
+      let pending = new Uint8Array(0);
+      let chunk = new Uint8Array(65536);
+      for (;;) {
+        let size: number = withPath("read-file-lines", p, () => fs.readSync(fd, chunk, 0, chunk.length, null));
+        if (size === 0) {
+          break;
+        }
+        let next = new Uint8Array(pending.length + size);
+        next.set(pending, 0);
+        next.set(chunk.subarray(0, size), pending.length);
+        pending = next;
+        let start = 0;
+        for (let idx = pending.indexOf(10); idx >= 0; idx = pending.indexOf(10, start)) {
+          let line = new TextDecoder().decode(pending.subarray(start, idx));
+          start = idx + 1;
+          yield line.endsWith("\r") ? line.slice(0, -1) : line;
+        }
+        pending = pending.slice(start);
+      }
+      if (pending.length > 0) {
+        yield new TextDecoder().decode(pending);
+      }
+    } finally {
+      fs.closeSync(fd);
+    }
+  });
+};
+
+/** lines of stdin, taken away from stdin while consumed */
+export let read_stdin_lines = (): CalcitLazySeq => {
+  return new CalcitLazySeq("stdin-lines", function* () {
+    for (;;) {
+      let line = read_stdin_line();
+      if (line == null) {
+        return;
+      }
+      yield line;
+    }
+  });
+};
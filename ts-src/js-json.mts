@@ -0,0 +1,476 @@
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitRecord } from "./js-record.mjs";
+import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
+import { CalcitSet } from "./js-set.mjs";
+import { CalcitTag, newTag } from "./calcit-data.mjs";
+import { CalcitTuple } from "./js-tuple.mjs";
+
+// JSON encoding of Calcit data, kept byte-identical with `src/data/json.rs`
+
+const MAX_SAFE_INTEGER = 9007199254740991;
+
+type BigNumberPolicy = "float" | "error" | "string";
+
+let readOption = (options: CalcitValue, name: string, procName: string): CalcitValue => {
+  if (options == null) {
+    return null;
+  }
+  if (options instanceof CalcitMap || options instanceof CalcitSliceMap) {
+    return options.get(newTag(name));
+  }
+  throw new Error(`${procName} expected a map of options, got: ${options}`);
+};
+
+let isTagOf = (x: CalcitValue, name: string): boolean => {
+  return x instanceof CalcitTag && x.value === name;
+};
+
+/** compares by code points, same as byte order of UTF-8 in Rust */
+let compareCodePoints = (a: string, b: string): number => {
+  let i = 0;
+  let j = 0;
+  while (i < a.length && j < b.length) {
+    let ca = a.codePointAt(i);
+    let cb = b.codePointAt(j);
+    if (ca !== cb) {
+      return ca < cb ? -1 : 1;
+    }
+    i += ca > 0xffff ? 2 : 1;
+    j += cb > 0xffff ? 2 : 1;
+  }
+  return i < a.length ? 1 : j < b.length ? -1 : 0;
+};
+
+/** arrays and objects nested deeper are rejected before the stack overflows, same as `src/data/json.rs` */
+const MAX_JSON_DEPTH = 512;
+
+class JsonParser {
+  text: string;
+  pos: number;
+  depth: number;
+  keysAsTags: boolean;
+  bigNumber: BigNumberPolicy;
+
+  constructor(text: string, keysAsTags: boolean, bigNumber: BigNumberPolicy) {
+    this.text = text;
+    this.pos = 0;
+    this.depth = 0;
+    this.keysAsTags = keysAsTags;
+    this.bigNumber = bigNumber;
+  }
+
+  fail(reason: string): Error {
+    let byteOffset = new TextEncoder().encode(this.text.slice(0, this.pos)).length;
+    return new Error(`parse-json failed at byte ${byteOffset}: ${reason}`);
+  }
+
+  unexpected(): Error {
+    if (this.pos >= this.text.length) {
+      return this.fail("unexpected end of input");
+    }
+    let c = String.fromCodePoint(this.text.codePointAt(this.pos));
+    return this.fail(`unexpected character \`${c}\``);
+  }
+
+  skipWhitespace() {
+    while (this.pos < this.text.length) {
+      let c = this.text[this.pos];
+      if (c === " " || c === "\t" || c === "\n" || c === "\r") {
+        this.pos += 1;
+      } else {
+        break;
+      }
+    }
+  }
+
+  expectLiteral(literal: string, value: CalcitValue): CalcitValue {
+    if (this.text.startsWith(literal, this.pos)) {
+      this.pos += literal.length;
+      return value;
+    }
+    throw this.unexpected();
+  }
+
+  readValue(): CalcitValue {
+    let c = this.text[this.pos];
+    switch (c) {
+      case "{":
+      case "[": {
+        if (this.depth >= MAX_JSON_DEPTH) {
+          throw this.fail(`nested deeper than ${MAX_JSON_DEPTH} levels`);
+        }
+        this.depth += 1;
+        let v = c === "{" ? this.readObject() : this.readArray();
+        this.depth -= 1;
+        return v;
+      }
+      case '"':
+        return this.readString();
+      case "t":
+        return this.expectLiteral("true", true);
+      case "f":
+        return this.expectLiteral("false", false);
+      case "n":
+        return this.expectLiteral("null", null);
+      default:
+        if (c === "-" || (c >= "0" && c <= "9")) {
+          return this.readNumber();
+        }
+        throw this.unexpected();
+    }
+  }
+
+  readDigits(): number {
+    let start = this.pos;
+    while (this.pos < this.text.length && this.text[this.pos] >= "0" && this.text[this.pos] <= "9") {
+      this.pos += 1;
+    }
+    return this.pos - start;
+  }
+
+  readNumber(): CalcitValue {
+    let start = this.pos;
+    if (this.text[this.pos] === "-") {
+      this.pos += 1;
+    }
+    let c = this.text[this.pos];
+    if (c === "0") {
+      this.pos += 1;
+    } else if (c >= "1" && c <= "9") {
+      this.readDigits();
+    } else {
+      throw this.unexpected();
+    }
+    let isInteger = true;
+    if (this.text[this.pos] === ".") {
+      isInteger = false;
+      this.pos += 1;
+      if (this.readDigits() === 0) {
+        throw this.unexpected();
+      }
+    }
+    if (this.text[this.pos] === "e" || this.text[this.pos] === "E") {
+      isInteger = false;
+      this.pos += 1;
+      if (this.text[this.pos] === "+" || this.text[this.pos] === "-") {
+        this.pos += 1;
+      }
+      if (this.readDigits() === 0) {
+        throw this.unexpected();
+      }
+    }
+    let literal = this.text.slice(start, this.pos);
+    let n = Number(literal);
+    if (isInteger && Math.abs(n) > MAX_SAFE_INTEGER) {
+      if (this.bigNumber === "string") {
+        return literal;
+      } else if (this.bigNumber === "error") {
+        this.pos = start;
+        throw this.fail(`integer \`${literal}\` is out of safe range`);
+      }
+    }
+    return n;
+  }
+
+  readHex4(): number {
+    let digits = this.text.slice(this.pos, this.pos + 4);
+    if (!/^[0-9a-fA-F]{4}$/.test(digits)) {
+      throw this.fail("invalid unicode escape");
+    }
+    this.pos += 4;
+    return parseInt(digits, 16);
+  }
+
+  readString(): string {
+    // skip opening quote
+    this.pos += 1;
+    let buffer = "";
+    while (true) {
+      if (this.pos >= this.text.length) {
+        throw this.fail("unterminated string");
+      }
+      let c = this.text[this.pos];
+      if (c === '"') {
+        this.pos += 1;
+        return buffer;
+      }
+      if (c === "\\") {
+        this.pos += 1;
+        let e = this.text[this.pos];
+        let escaped: string;
+        switch (e) {
+          case '"':
+          case "\\":
+          case "/":
+            escaped = e;
+            break;
+          case "b":
+            escaped = "\b";
+            break;
+          case "f":
+            escaped = "\f";
+            break;
+          case "n":
+            escaped = "\n";
+            break;
+          case "r":
+            escaped = "\r";
+            break;
+          case "t":
+            escaped = "\t";
+            break;
+          case "u": {
+            let escapeStart = this.pos - 1;
+            this.pos += 1;
+            let high = this.readHex4();
+            let code = high;
+            if (high >= 0xd800 && high < 0xdc00) {
+              if (!this.text.startsWith("\\u", this.pos)) {
+                this.pos = escapeStart;
+                throw this.fail("invalid unicode escape");
+              }
+              this.pos += 2;
+              let low = this.readHex4();
+              if (low < 0xdc00 || low >= 0xe000) {
+                this.pos = escapeStart;
+                throw this.fail("invalid unicode escape");
+              }
+              code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+            } else if (high >= 0xdc00 && high < 0xe000) {
+              this.pos = escapeStart;
+              throw this.fail("invalid unicode escape");
+            }
+            buffer += String.fromCodePoint(code);
+            continue;
+          }
+          default:
+            this.pos -= 1;
+            throw this.fail("invalid escape");
+        }
+        this.pos += 1;
+        buffer += escaped;
+        continue;
+      }
+      if (c.charCodeAt(0) < 0x20) {
+        throw this.fail("control character in string");
+      }
+      buffer += c;
+      this.pos += 1;
+    }
+  }
+
+  readArray(): CalcitValue {
+    this.pos += 1;
+    let ys: CalcitValue[] = [];
+    this.skipWhitespace();
+    if (this.text[this.pos] === "]") {
+      this.pos += 1;
+      return new CalcitSliceList(ys);
+    }
+    while (true) {
+      this.skipWhitespace();
+      ys.push(this.readValue());
+      this.skipWhitespace();
+      let c = this.text[this.pos];
+      if (c === ",") {
+        this.pos += 1;
+      } else if (c === "]") {
+        this.pos += 1;
+        return new CalcitSliceList(ys);
+      } else {
+        throw this.unexpected();
+      }
+    }
+  }
+
+  readObject(): CalcitValue {
+    this.pos += 1;
+    // later values override earlier ones with same key
+    let entries: Map<string, CalcitValue> = new Map();
+    let toMap = () => {
+      let pairs: CalcitValue[] = [];
+      for (let [k, v] of entries) {
+        pairs.push(this.keysAsTags ? newTag(k) : k, v);
+      }
+      return new CalcitSliceMap(pairs);
+    };
+    this.skipWhitespace();
+    if (this.text[this.pos] === "}") {
+      this.pos += 1;
+      return toMap();
+    }
+    while (true) {
+      this.skipWhitespace();
+      if (this.text[this.pos] !== '"') {
+        throw this.unexpected();
+      }
+      let key = this.readString();
+      this.skipWhitespace();
+      if (this.text[this.pos] !== ":") {
+        throw this.unexpected();
+      }
+      this.pos += 1;
+      this.skipWhitespace();
+      entries.set(key, this.readValue());
+      this.skipWhitespace();
+      let c = this.text[this.pos];
+      if (c === ",") {
+        this.pos += 1;
+      } else if (c === "}") {
+        this.pos += 1;
+        return toMap();
+      } else {
+        throw this.unexpected();
+      }
+    }
+  }
+}
+
+export let parse_json = (s: CalcitValue, options: CalcitValue = null): CalcitValue => {
+  let keys = readOption(options, "keys", "parse-json");
+  let keysAsTags = true;
+  if (isTagOf(keys, "string")) {
+    keysAsTags = false;
+  } else if (keys != null && !isTagOf(keys, "tag")) {
+    throw new Error(`parse-json expected :tag or :string for :keys, got: ${keys}`);
+  }
+  let bigNumber = readOption(options, "big-number", "parse-json");
+  let policy: BigNumberPolicy = "float";
+  if (isTagOf(bigNumber, "error") || isTagOf(bigNumber, "string")) {
+    policy = (bigNumber as CalcitTag).value as BigNumberPolicy;
+  } else if (bigNumber != null && !isTagOf(bigNumber, "float")) {
+    throw new Error(`parse-json expected :float, :error or :string for :big-number, got: ${bigNumber}`);
+  }
+  if (typeof s !== "string") {
+    throw new Error(`parse-json expected a string, got: ${s}`);
+  }
+  let parser = new JsonParser(s, keysAsTags, policy);
+  parser.skipWhitespace();
+  let v = parser.readValue();
+  parser.skipWhitespace();
+  if (parser.pos < s.length) {
+    throw parser.fail("unexpected trailing characters");
+  }
+  return v;
+};
+
+interface FormatOptions {
+  indent: number;
+  nonFiniteAsNull: boolean;
+}
+
+/** escapes like `JSON.stringify` */
+let jsonString = (s: string): string => {
+  return JSON.stringify(s);
+};
+
+let writeCollection = (items: string[], brackets: [string, string], options: FormatOptions, depth: number): string => {
+  if (items.length === 0) {
+    return brackets[0] + brackets[1];
+  }
+  if (options.indent === 0) {
+    return brackets[0] + items.join(",") + brackets[1];
+  }
+  let inner = " ".repeat(options.indent * (depth + 1));
+  let chunk = brackets[0];
+  items.forEach((item, idx) => {
+    if (idx > 0) {
+      chunk += ",";
+    }
+    chunk += "\n" + inner + item;
+  });
+  return chunk + "\n" + " ".repeat(options.indent * depth) + brackets[1];
+};
+
+let writeObject = (entries: [string, CalcitValue][], options: FormatOptions, depth: number): string => {
+  let items = entries.map(([k, v]) => {
+    return jsonString(k) + (options.indent > 0 ? ": " : ":") + writeJson(v, options, depth + 1);
+  });
+  return writeCollection(items, ["{", "}"], options, depth);
+};
+
+let writeJson = (x: CalcitValue, options: FormatOptions, depth: number): string => {
+  if (x == null) {
+    return "null";
+  }
+  if (typeof x === "boolean") {
+    return x ? "true" : "false";
+  }
+  if (typeof x === "number") {
+    if (Number.isFinite(x)) {
+      return String(x);
+    }
+    if (options.nonFiniteAsNull) {
+      return "null";
+    }
+    throw new Error(`format-json cannot encode non-finite number: ${x}`);
+  }
+  if (typeof x === "string") {
+    return jsonString(x);
+  }
+  if (x instanceof CalcitTag) {
+    return jsonString(x.value);
+  }
+  if (x instanceof CalcitList || x instanceof CalcitSliceList) {
+    let items: string[] = [];
+    for (let y of x.items()) {
+      items.push(writeJson(y, options, depth + 1));
+    }
+    return writeCollection(items, ["[", "]"], options, depth);
+  }
+  if (x instanceof CalcitSet) {
+    let items = x.values().map((y) => writeJson(y, options, depth + 1));
+    items.sort(compareCodePoints);
+    return writeCollection(items, ["[", "]"], options, depth);
+  }
+  if (x instanceof CalcitTuple) {
+    let items = [x.tag, ...x.extra].map((y) => writeJson(y, options, depth + 1));
+    return writeCollection(items, ["[", "]"], options, depth);
+  }
+  if (x instanceof CalcitMap || x instanceof CalcitSliceMap) {
+    let entries: [string, CalcitValue][] = x.pairs().map(([k, v]) => {
+      if (typeof k === "string") {
+        return [k, v];
+      } else if (k instanceof CalcitTag) {
+        return [k.value, v];
+      }
+      throw new Error(`format-json expected string or tag for key, got: ${k}`);
+    });
+    entries.sort((a, b) => compareCodePoints(a[0], b[0]));
+    for (let idx = 1; idx < entries.length; idx++) {
+      if (entries[idx - 1][0] === entries[idx][0]) {
+        throw new Error(`format-json duplicated key \`${entries[idx][0]}\` in map`);
+      }
+    }
+    return writeObject(entries, options, depth);
+  }
+  if (x instanceof CalcitRecord) {
+    let entries: [string, CalcitValue][] = x.fields.map((f, idx) => [f.value, x.values[idx]]);
+    entries.sort((a, b) => compareCodePoints(a[0], b[0]));
+    return writeObject(entries, options, depth);
+  }
+  throw new Error(`format-json cannot encode value: ${x}`);
+};
+
+export let format_json = (x: CalcitValue, options: CalcitValue = null): string => {
+  let formatOptions: FormatOptions = { indent: 0, nonFiniteAsNull: false };
+  let pretty = readOption(options, "pretty", "format-json");
+  if (pretty === true) {
+    formatOptions.indent = 2;
+  } else if (typeof pretty === "number") {
+    if (!Number.isInteger(pretty) || pretty < 0 || pretty > 10) {
+      throw new Error(`format-json expected indentation within 0~10, got: ${pretty}`);
+    }
+    formatOptions.indent = pretty;
+  } else if (pretty != null && pretty !== false) {
+    throw new Error(`format-json expected a bool or a number for :pretty, got: ${pretty}`);
+  }
+  let nonFinite = readOption(options, "non-finite", "format-json");
+  if (isTagOf(nonFinite, "nil")) {
+    formatOptions.nonFiniteAsNull = true;
+  } else if (nonFinite != null && !isTagOf(nonFinite, "error")) {
+    throw new Error(`format-json expected :error or :nil for :non-finite, got: ${nonFinite}`);
+  }
+  return writeJson(x, formatOptions, 0);
+};
@@ -0,0 +1,274 @@
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
+import { CalcitSet } from "./js-set.mjs";
+import { toString } from "./calcit-data.mjs";
+
+// lazy sequences, kept in sync with `src/builtins/lazy.rs`.
+// a sequence only holds a function creating iterators, every consumer starts over from the source.
+
+let lazySeqIdGen = 0;
+
+export class CalcitLazySeq {
+  id: number;
+  kind: string;
+  produce: () => Iterator<CalcitValue>;
+
+  constructor(kind: string, produce: () => Iterator<CalcitValue>) {
+    this.id = lazySeqIdGen++;
+    this.kind = kind;
+    this.produce = produce;
+  }
+
+  [Symbol.iterator](): Iterator<CalcitValue> {
+    return this.produce();
+  }
+
+  toString(): string {
+    return `(&lazy-seq ${this.id} :${this.kind})`;
+  }
+}
+
+let truthy = (x: CalcitValue): boolean => x != null && x !== false;
+
+/** lazy sequences are used directly, other collections are wrapped with their items */
+let readSeq = (x: CalcitValue, procName: string): CalcitLazySeq => {
+  if (x instanceof CalcitLazySeq) {
+    return x;
+  }
+  if (x instanceof CalcitList || x instanceof CalcitSliceList) {
+    let xs = x;
+    return new CalcitLazySeq("list", function* () {
+      let size = xs.len();
+      for (let idx = 0; idx < size; idx++) {
+        yield xs.get(idx);
+      }
+    });
+  }
+  let items: CalcitValue[];
+  if (x == null) {
+    items = [];
+  } else if (x instanceof CalcitSet) {
+    items = x.values();
+  } else if (x instanceof CalcitMap || x instanceof CalcitSliceMap) {
+    items = x.pairs().map((pair) => new CalcitSliceList(pair));
+  } else {
+    throw new Error(`${procName} expected a lazy-seq or a collection, got: ${toString(x, true)}`);
+  }
+  return new CalcitLazySeq("list", () => items[Symbol.iterator]());
+};
+
+let readFn = (f: CalcitValue, procName: string): Function => {
+  if (typeof f !== "function") {
+    throw new Error(`${procName} expected a function, got: ${toString(f, true)}`);
+  }
+  return f;
+};
+
+let readCount = (n: CalcitValue, procName: string): number => {
+  if (typeof n !== "number") {
+    throw new Error(`${procName} expected a count, got: ${toString(n, true)}`);
+  }
+  if (!Number.isInteger(n) || n < 0) {
+    throw new Error(`${procName} expected a count, got: ${n}`);
+  }
+  return n;
+};
+
+export let to_lazy = (x: CalcitValue): CalcitLazySeq => {
+  return readSeq(x, "to-lazy");
+};
+
+export let lazy_seq_$q_ = (x: CalcitValue): boolean => {
+  return x instanceof CalcitLazySeq;
+};
+
+/** `(lazy-range)` counts from 0 infinitely, otherwise takes arguments like `range`, and a `nil` bound for an infinite range */
+export let lazy_range = (...xs: CalcitValue[]): CalcitLazySeq => {
+  if (xs.length > 3) {
+    throw new Error(`lazy-range expected 0~3 arguments, got: ${xs.length}`);
+  }
+  let from = 0;
+  let to: number = null;
+  if (xs.length === 1) {
+    to = xs[0] as number;
+  } else if (xs.length >= 2) {
+    from = xs[0] as number;
+    to = xs[1] as number;
+  }
+  if (typeof from !== "number" || (to != null && typeof to !== "number")) {
+    throw new Error(`lazy-range expected numbers for base and bound, got: ${xs.map((x) => toString(x, true)).join(" ")}`);
+  }
+  let step = xs.length === 3 ? xs[2] : 1;
+  if (typeof step !== "number") {
+    throw new Error(`lazy-range expected a number for step, got: ${toString(step, true)}`);
+  }
+  if (step === 0 || (to != null && ((to > from && step < 0) || (to < from && step > 0)))) {
+    throw new Error(`lazy-range cannot reach the bound with step ${step}`);
+  }
+  let s = step;
+  return new CalcitLazySeq("range", function* () {
+    for (let x = from; to == null || (s > 0 ? x < to : x > to); x += s) {
+      yield x;
+    }
+  });
+};
+
+export let lazy_iterate = (init: CalcitValue, f: CalcitValue): CalcitLazySeq => {
+  let g = readFn(f, "lazy-iterate");
+  return new CalcitLazySeq("iterate", function* () {
+    let x = init;
+    for (;;) {
+      yield x;
+      x = g(x);
+    }
+  });
+};
+
+export let lazy_repeat = (x: CalcitValue): CalcitLazySeq => {
+  return new CalcitLazySeq("repeat", function* () {
+    for (;;) {
+      yield x;
+    }
+  });
+};
+
+/** `f` returns `nil` to stop or `[] value next-seed` */
+export let lazy_unfold = (seed: CalcitValue, f: CalcitValue): CalcitLazySeq => {
+  let g = readFn(f, "lazy-unfold");
+  return new CalcitLazySeq("unfold", function* () {
+    let s = seed;
+    for (;;) {
+      let ret = g(s);
+      if (ret == null) {
+        return;
+      }
+      if (!((ret instanceof CalcitList || ret instanceof CalcitSliceList) && ret.len() === 2)) {
+        throw new Error(`lazy-unfold expected nil or \`[] value next-seed\` from function, got: ${toString(ret, true)}`);
+      }
+      s = ret.get(1);
+      yield ret.get(0);
+    }
+  });
+};
+
+export let _$n_lazy_$o_map = (xs: CalcitValue, f: CalcitValue): CalcitLazySeq => {
+  let ys = readSeq(xs, "&lazy:map");
+  let g = readFn(f, "&lazy:map");
+  return new CalcitLazySeq("map", function* () {
+    for (let y of ys) {
+      yield g(y);
+    }
+  });
+};
+
+export let _$n_lazy_$o_filter = (xs: CalcitValue, f: CalcitValue): CalcitLazySeq => {
+  let ys = readSeq(xs, "&lazy:filter");
+  let g = readFn(f, "&lazy:filter");
+  return new CalcitLazySeq("filter", function* () {
+    for (let y of ys) {
+      if (truthy(g(y))) {
+        yield y;
+      }
+    }
+  });
+};
+
+export let _$n_lazy_$o_take = (xs: CalcitValue, n: CalcitValue): CalcitLazySeq => {
+  let ys = readSeq(xs, "&lazy:take");
+  let size = readCount(n, "&lazy:take");
+  return new CalcitLazySeq("take", function* () {
+    // not pulling from sources like stdin after enough items are taken
+    if (size === 0) {
+      return;
+    }
+    let taken = 0;
+    for (let y of ys) {
+      yield y;
+      taken++;
+      if (taken >= size) {
+        return;
+      }
+    }
+  });
+};
+
+export let _$n_lazy_$o_drop = (xs: CalcitValue, n: CalcitValue): CalcitLazySeq => {
+  let ys = readSeq(xs, "&lazy:drop");
+  let size = readCount(n, "&lazy:drop");
+  return new CalcitLazySeq("drop", function* () {
+    let skipped = 0;
+    for (let y of ys) {
+      if (skipped < size) {
+        skipped++;
+      } else {
+        yield y;
+      }
+    }
+  });
+};
+
+export let _$n_lazy_$o_take_while = (xs: CalcitValue, f: CalcitValue): CalcitLazySeq => {
+  let ys = readSeq(xs, "&lazy:take-while");
+  let g = readFn(f, "&lazy:take-while");
+  return new CalcitLazySeq("take-while", function* () {
+    for (let y of ys) {
+      if (!truthy(g(y))) {
+        return;
+      }
+      yield y;
+    }
+  });
+};
+
+export let _$n_lazy_$o_concat = (...xs: CalcitValue[]): CalcitLazySeq => {
+  let seqs = xs.map((x) => readSeq(x, "&lazy:concat"));
+  return new CalcitLazySeq("concat", function* () {
+    for (let ys of seqs) {
+      yield* ys;
+    }
+  });
+};
+
+/** pairs items at same positions into lists, ends with the shorter sequence */
+export let _$n_lazy_$o_zip = (xs: CalcitValue, ys: CalcitValue): CalcitLazySeq => {
+  let as = readSeq(xs, "&lazy:zip");
+  let bs = readSeq(ys, "&lazy:zip");
+  return new CalcitLazySeq("zip", function* () {
+    let ai = as.produce();
+    let bi = bs.produce();
+    for (;;) {
+      let a = ai.next();
+      if (a.done) {
+        return;
+      }
+      let b = bi.next();
+      if (b.done) {
+        return;
+      }
+      yield new CalcitSliceList([a.value, b.value]);
+    }
+  });
+};
+
+/** realizes all items, never returns for infinite sequences */
+export let _$n_lazy_$o_to_list = (xs: CalcitValue): CalcitSliceList => {
+  return new CalcitSliceList(Array.from(readSeq(xs, "&lazy:to-list")));
+};
+
+export let _$n_lazy_$o_first = (xs: CalcitValue): CalcitValue => {
+  let item = readSeq(xs, "&lazy:first").produce().next();
+  return item.done ? null : item.value;
+};
+
+export let _$n_lazy_$o_empty_$q_ = (xs: CalcitValue): boolean => {
+  return readSeq(xs, "&lazy:empty?").produce().next().done === true;
+};
+
+export let _$n_lazy_$o_count = (xs: CalcitValue): number => {
+  let size = 0;
+  for (let _y of readSeq(xs, "&lazy:count")) {
+    size++;
+  }
+  return size;
+};
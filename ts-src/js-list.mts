@@ -18,6 +18,7 @@ import {
 
 import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
 import { CalcitSet } from "./js-set.mjs";
+import { CalcitLazySeq } from "./js-lazy.mjs";
 import { CalcitTuple } from "./js-tuple.mjs";
 
 import { isNestedCalcitData, tipNestedCalcitData, toString, CalcitFn } from "./calcit-data.mjs";
@@ -332,6 +333,14 @@ export let foldl = function (xs: CalcitValue, acc: CalcitValue, f: CalcitFn): Ca
     });
     return result;
   }
+  if (xs instanceof CalcitLazySeq) {
+    // items are pulled one by one, so sequences larger than memory can be folded
+    let result = acc;
+    for (let item of xs) {
+      result = f(result, item);
+    }
+    return result;
+  }
   throw new Error("Unknow data for foldl");
 };
 
@@ -424,6 +433,25 @@ export let foldl_shortcut = function (xs: CalcitValue, acc: CalcitValue, v0: Cal
     }
     return v0;
   }
+  if (xs instanceof CalcitLazySeq) {
+    // stops pulling items from the sequence after a shortcut
+    let state = acc;
+    for (let item of xs) {
+      let pair = f(state, item);
+      if (pair instanceof CalcitTuple) {
+        if (typeof pair.tag === "boolean") {
+          if (pair.tag) {
+            return pair.get(1);
+          } else {
+            state = pair.get(1);
+          }
+        }
+      } else {
+        throw new Error("Expected return value in `:: bool acc` structure");
+      }
+    }
+    return v0;
+  }
   throw new Error("Unknow data for foldl-shortcut");
 };
 export let foldr_shortcut = function (xs: CalcitValue, acc: CalcitValue, v0: CalcitValue, f: CalcitFn): CalcitValue {
@@ -1,7 +1,7 @@
 import * as ternaryTree from "@calcit/ternary-tree";
 
-import { CalcitValue, isLiteral } from "./js-primes.mjs";
-import { CalcitSet } from "./js-set.mjs";
+import { CalcitValue, isLiteral, bisectSorted } from "./js-primes.mjs";
+import { CalcitSet, CalcitSortedSet } from "./js-set.mjs";
 
 import {
   TernaryTreeMap,
@@ -404,3 +404,120 @@ export class CalcitSliceMap {
     }
   }
 }
+
+/** map with keys kept in the order of `&compare`, tree is still used for lookups and hashing */
+export class CalcitSortedMap extends CalcitMap {
+  sortedKeys: CalcitValue[];
+  constructor(value: TernaryTreeMap<CalcitValue, CalcitValue>, sortedKeys: CalcitValue[]) {
+    super(value);
+    this.sortedKeys = sortedKeys;
+  }
+  static from(args: CalcitValue[]): CalcitSortedMap {
+    return new CalcitSortedMap(null, []).assoc(...args);
+  }
+  assoc(...args: CalcitValue[]): CalcitSortedMap {
+    if (args.length % 2 !== 0) throw new Error("expected even arguments");
+    let size = Math.floor(args.length / 2);
+
+    let result = this.value;
+    let keys: CalcitValue[] = null;
+    for (let idx = 0; idx < size; idx++) {
+      let k = args[idx << 1];
+      let v = args[(idx << 1) + 1];
+      if (!ternaryTree.contains(result, k)) {
+        if (keys == null) {
+          keys = this.sortedKeys.slice();
+        }
+        keys.splice(bisectSorted(keys, k)[0], 0, k);
+      }
+      result = assocMap(result, k, v);
+    }
+    return new CalcitSortedMap(result, keys ?? this.sortedKeys);
+  }
+  dissoc(...args: CalcitValue[]): CalcitSortedMap {
+    let ret = this.value;
+    let keys: CalcitValue[] = null;
+    for (let idx = 0; idx < args.length; idx++) {
+      let k = args[idx];
+      if (ternaryTree.contains(ret, k)) {
+        if (keys == null) {
+          keys = this.sortedKeys.slice();
+        }
+        let [pos, found] = bisectSorted(keys, k);
+        if (found) {
+          keys.splice(pos, 1);
+        }
+        ret = dissocMap(ret, k);
+      }
+    }
+    return new CalcitSortedMap(ret, keys ?? this.sortedKeys);
+  }
+  toString(shorter = false, disableJsDataWarning = false) {
+    let itemsCode = "";
+    let pairs = this.pairs();
+    for (let idx = 0; idx < pairs.length; idx++) {
+      let k = pairs[idx][0];
+      let v = pairs[idx][1];
+      if (shorter) {
+        let keyPart = isNestedCalcitData(k) ? tipNestedCalcitData(k) : toString(k, true, disableJsDataWarning);
+        let valuePart = isNestedCalcitData(v) ? tipNestedCalcitData(v) : toString(v, true, disableJsDataWarning);
+        itemsCode = `${itemsCode} ${keyPart} ${valuePart}`;
+      } else {
+        itemsCode = `${itemsCode} ${toString(k, true, disableJsDataWarning)} ${toString(v, true, disableJsDataWarning)}`;
+      }
+    }
+    return `(sorted-map${itemsCode})`;
+  }
+  pairs(): Array<[CalcitValue, CalcitValue]> {
+    return this.sortedKeys.map((k) => [k, mapGetDefault(this.value, k, null)]);
+  }
+  keysArray(): Array<CalcitValue> {
+    return this.sortedKeys.slice();
+  }
+  mergeSkip(ys: CalcitMap | CalcitSliceMap, v: CalcitValue): CalcitSortedMap {
+    if (ys == null) {
+      return this;
+    }
+
+    if (!(ys instanceof CalcitMap || ys instanceof CalcitSliceMap)) {
+      console.error("value:", v);
+      throw new Error("Expected map to merge");
+    }
+
+    let args: CalcitValue[] = [];
+    for (let [k, y] of ys.pairs()) {
+      if (y !== v) {
+        args.push(k, y);
+      }
+    }
+    return this.assoc(...args);
+  }
+  diffNew(ys: CalcitMap | CalcitSliceMap): CalcitSortedMap {
+    if (!(ys instanceof CalcitMap || ys instanceof CalcitSliceMap)) {
+      throw new Error("unknown data to diff");
+    }
+    return this.dissoc(...ys.keysArray());
+  }
+  diffKeys(ys: CalcitMap | CalcitSliceMap): CalcitSortedSet {
+    return CalcitSortedSet.fromSorted(this.sortedKeys.filter((k) => !ys.contains(k)));
+  }
+  commonKeys(ys: CalcitMap | CalcitSliceMap): CalcitSortedSet {
+    return CalcitSortedSet.fromSorted(this.sortedKeys.filter((k) => ys.contains(k)));
+  }
+  first(): [CalcitValue, CalcitValue] {
+    let k = this.sortedKeys[0];
+    return this.sortedKeys.length > 0 ? [k, this.get(k)] : null;
+  }
+  last(): [CalcitValue, CalcitValue] {
+    let k = this.sortedKeys[this.sortedKeys.length - 1];
+    return this.sortedKeys.length > 0 ? [k, this.get(k)] : null;
+  }
+  /** from `from` inclusive to `to` exclusive, `null` for no upper bound */
+  range(from: CalcitValue, to: CalcitValue): CalcitSortedMap {
+    let lo = bisectSorted(this.sortedKeys, from)[0];
+    let hi = to == null ? this.sortedKeys.length : bisectSorted(this.sortedKeys, to)[0];
+    let keys = this.sortedKeys.slice(lo, hi);
+    let pairs: Array<[CalcitValue, CalcitValue]> = keys.map((k) => [k, this.get(k)]);
+    return new CalcitSortedMap(initTernaryTreeMapFromArray(pairs), keys);
+  }
+}
@@ -6,6 +6,8 @@ import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
 import { CalcitSet as CalcitSet } from "./js-set.mjs";
 import { CalcitTuple } from "./js-tuple.mjs";
 import { CalcitCirruQuote, cirru_deep_equal } from "./js-cirru.mjs";
+import { CalcitTransient } from "./js-transient.mjs";
+import { CalcitLazySeq } from "./js-lazy.mjs";
 
 export type CalcitValue =
   | string
@@ -24,6 +26,8 @@ export type CalcitValue =
   | CalcitRecur // should not be exposed to function
   | CalcitRecord
   | CalcitCirruQuote
+  | CalcitTransient
+  | CalcitLazySeq
   | null;
 
 export let isLiteral = (x: CalcitValue): boolean => {
@@ -45,6 +49,7 @@ enum PseudoTypeIndex {
   string,
   ref,
   tuple,
+  buffer,
   recur,
   list,
   set,
@@ -52,6 +57,8 @@ enum PseudoTypeIndex {
   record,
   fn,
   cirru_quote,
+  transient,
+  lazy_seq,
 }
 
 let typeAsInt = (x: CalcitValue): number => {
@@ -65,12 +72,15 @@ let typeAsInt = (x: CalcitValue): number => {
   if (t === "string") return PseudoTypeIndex.string;
   if (x instanceof CalcitRef) return PseudoTypeIndex.ref;
   if (x instanceof CalcitTuple) return PseudoTypeIndex.tuple;
+  if (x instanceof Uint8Array) return PseudoTypeIndex.buffer;
   if (x instanceof CalcitRecur) return PseudoTypeIndex.recur;
   if (x instanceof CalcitList || x instanceof CalcitSliceList) return PseudoTypeIndex.list;
   if (x instanceof CalcitSet) return PseudoTypeIndex.set;
   if (x instanceof CalcitMap || x instanceof CalcitSliceMap) return PseudoTypeIndex.map;
   if (x instanceof CalcitRecord) return PseudoTypeIndex.record;
   if (x instanceof CalcitCirruQuote) return PseudoTypeIndex.cirru_quote;
+  if (x instanceof CalcitTransient) return PseudoTypeIndex.transient;
+  if (x instanceof CalcitLazySeq) return PseudoTypeIndex.lazy_seq;
   // proc, fn, macro, syntax, not distinguished
   if (t === "function") return PseudoTypeIndex.fn;
   throw new Error("unknown type to compare");
@@ -86,6 +96,18 @@ let rawCompare = (x: any, y: any): number => {
   }
 };
 
+/** lexicographical, like slices in Rust */
+let compareItems = (xs: CalcitValue[], ys: CalcitValue[]): number => {
+  let size = Math.min(xs.length, ys.length);
+  for (let idx = 0; idx < size; idx++) {
+    let ret = _$n_compare(xs[idx], ys[idx]);
+    if (ret !== 0) {
+      return ret;
+    }
+  }
+  return rawCompare(xs.length, ys.length);
+};
+
 export let _$n_compare = (a: CalcitValue, b: CalcitValue): number => {
   if (a === b) return 0;
   let ta = typeAsInt(a);
@@ -106,6 +128,32 @@ export let _$n_compare = (a: CalcitValue, b: CalcitValue): number => {
         return rawCompare(a, b);
       case PseudoTypeIndex.ref:
         return rawCompare((a as CalcitRef).path, (b as CalcitRef).path);
+      case PseudoTypeIndex.tuple: {
+        let x = a as CalcitTuple;
+        let y = b as CalcitTuple;
+        let ret = _$n_compare(x.tag, y.tag);
+        return ret !== 0 ? ret : compareItems(x.extra, y.extra);
+      }
+      case PseudoTypeIndex.list: {
+        let xs = a as CalcitList | CalcitSliceList;
+        let ys = b as CalcitList | CalcitSliceList;
+        return compareItems(xs.toArray(), ys.toArray());
+      }
+      case PseudoTypeIndex.buffer: {
+        let xs = a as any as Uint8Array;
+        let ys = b as any as Uint8Array;
+        let size = Math.min(xs.length, ys.length);
+        for (let idx = 0; idx < size; idx++) {
+          if (xs[idx] !== ys[idx]) {
+            return rawCompare(xs[idx], ys[idx]);
+          }
+        }
+        return rawCompare(xs.length, ys.length);
+      }
+      case PseudoTypeIndex.transient:
+        return rawCompare((a as CalcitTransient).id, (b as CalcitTransient).id);
+      case PseudoTypeIndex.lazy_seq:
+        return rawCompare((a as CalcitLazySeq).id, (b as CalcitLazySeq).id);
       case PseudoTypeIndex.cirru_quote:
         return rawCompare(a, b); // TODO not stable
       default:
@@ -122,3 +170,21 @@ export let _$n_compare = (a: CalcitValue, b: CalcitValue): number => {
     return rawCompare(ta, tb);
   }
 };
+
+/** position of `x` in items sorted by `&compare`, or where it should be inserted */
+export let bisectSorted = (xs: CalcitValue[], x: CalcitValue): [number, boolean] => {
+  let lo = 0;
+  let hi = xs.length;
+  while (lo < hi) {
+    let mid = (lo + hi) >> 1;
+    let ret = _$n_compare(xs[mid], x);
+    if (ret === 0) {
+      return [mid, true];
+    } else if (ret < 0) {
+      lo = mid + 1;
+    } else {
+      hi = mid;
+    }
+  }
+  return [lo, false];
+};
@@ -4,13 +4,29 @@ import { newTag, castTag, toString, CalcitTag, getStringName, findInFields } fro
 
 import { CalcitMap, CalcitSliceMap } from "./js-map.mjs";
 
+/** rules of a field when constructing a record, kept in sync with `RecordFieldSchema` in Rust */
+export interface RecordFieldSchema {
+  hasDefault: boolean;
+  default: CalcitValue;
+  optional: boolean;
+  validator: Function;
+}
+
 export class CalcitRecord {
   name: CalcitTag;
   fields: Array<CalcitTag>;
   values: Array<CalcitValue>;
   klass: CalcitValue;
+  /** rules in the same order as fields, records built from a definition share it */
+  schema: Array<RecordFieldSchema>;
   cachedHash: Hash;
-  constructor(name: CalcitTag, fields: Array<CalcitTag>, values?: Array<CalcitValue>, klass?: CalcitValue) {
+  constructor(
+    name: CalcitTag,
+    fields: Array<CalcitTag>,
+    values?: Array<CalcitValue>,
+    klass?: CalcitValue,
+    schema?: Array<RecordFieldSchema>
+  ) {
     this.name = name;
     let fieldNames = fields.map(castTag);
     this.fields = fields;
@@ -24,6 +40,7 @@ export class CalcitRecord {
     }
     this.cachedHash = null;
     this.klass = klass;
+    this.schema = schema;
   }
   get(k: CalcitValue) {
     let field = castTag(k);
@@ -53,7 +70,7 @@ export class CalcitRecord {
         values[idx] = this.values[idx];
       }
     }
-    return new CalcitRecord(this.name, this.fields, values, this.klass);
+    return new CalcitRecord(this.name, this.fields, values, this.klass, this.schema);
   }
   /** return -1 for missing */
   findIndex(k: CalcitValue) {
@@ -74,7 +91,7 @@ export class CalcitRecord {
   }
   withClass(klass: CalcitValue): CalcitRecord {
     if (klass instanceof CalcitRecord) {
-      return new CalcitRecord(this.name, this.fields, this.values, klass);
+      return new CalcitRecord(this.name, this.fields, this.values, klass, this.schema);
     } else {
       throw new Error("Expected a record");
     }
@@ -127,6 +144,20 @@ export let _$n__PCT__$M_ = (proto: CalcitValue, ...xs: Array<CalcitValue>): Calc
     if (xs.length % 2 !== 0) {
       throw new Error("Expected even number of key/value");
     }
+    if (proto.schema != null) {
+      let values: Array<CalcitValue> = new Array(proto.fields.length);
+      let given: Array<boolean> = new Array(proto.fields.length).fill(false);
+      for (let i = 0; i < xs.length; i += 2) {
+        let field = castTag(xs[i]);
+        let idx = findInFields(proto.fields, field);
+        if (idx < 0) {
+          throw new Error(`Cannot find field ${field} among (${proto.fields.join(", ")})`);
+        }
+        values[idx] = xs[i + 1];
+        given[idx] = true;
+      }
+      return new CalcitRecord(proto.name, proto.fields, fillRecordValues(proto, values, given, "&%{}"), proto.klass, proto.schema);
+    }
     if (xs.length !== proto.fields.length * 2) {
       throw new Error("fields size does not match");
     }
@@ -152,12 +183,54 @@ export let _$n__PCT__$M_ = (proto: CalcitValue, ...xs: Array<CalcitValue>): Calc
       values[i] = xs[idx * 2 + 1];
     }
 
-    return new CalcitRecord(proto.name, proto.fields, values, proto.klass);
+    return new CalcitRecord(proto.name, proto.fields, values, proto.klass, proto.schema);
   } else {
     throw new Error("Expected prototype to be a record");
   }
 };
 
+/** takes values of fields in order, missing fields are filled by the schema of the record.
+ * all missing and invalid fields are reported together */
+export let fillRecordValues = (
+  proto: CalcitRecord,
+  values: Array<CalcitValue>,
+  given: Array<boolean>,
+  procName: string
+): Array<CalcitValue> => {
+  let problems: Array<[string, string]> = [];
+  let result: Array<CalcitValue> = new Array(proto.fields.length);
+  for (let idx = 0; idx < proto.fields.length; idx++) {
+    let field = proto.fields[idx];
+    let rule = proto.schema?.[idx];
+    let value: CalcitValue = null;
+    if (given[idx]) {
+      value = values[idx];
+    } else if (rule?.hasDefault) {
+      value = rule.default;
+    } else if (rule?.optional) {
+      result[idx] = null;
+      continue;
+    } else {
+      problems.push([field.value, `missing field ${field}`]);
+      result[idx] = null;
+      continue;
+    }
+    if (rule?.validator != null) {
+      let ret = rule.validator(value);
+      if (ret == null || ret === false) {
+        problems.push([field.value, `invalid field ${field}, got: ${toString(value, true)}`]);
+      }
+    }
+    result[idx] = value;
+  }
+  if (problems.length > 0) {
+    // fields are ordered by names in Rust
+    problems.sort((a, b) => (a[0] < b[0] ? -1 : a[0] > b[0] ? 1 : 0));
+    throw new Error(`${procName} failed to build record ${proto.name}\n  ${problems.map((p) => p[1]).join("\n  ")}`);
+  }
+  return result;
+};
+
 /// update record with new values
 export let _$n_record_$o_with = (proto: CalcitValue, ...xs: Array<CalcitValue>): CalcitValue => {
   if (proto instanceof CalcitRecord) {
@@ -174,7 +247,7 @@ export let _$n_record_$o_with = (proto: CalcitValue, ...xs: Array<CalcitValue>):
       }
       values[idx] = v;
     }
-    return new CalcitRecord(proto.name, proto.fields, values, proto.klass);
+    return new CalcitRecord(proto.name, proto.fields, values, proto.klass, proto.schema);
   } else {
     throw new Error("Expected prototype to be a record");
   }
@@ -206,6 +279,21 @@ export let _$n_record_$o_from_map = (proto: CalcitValue, data: CalcitValue): Cal
       }
       return new CalcitRecord(proto.name, proto.fields, values);
     }
+  } else if ((data instanceof CalcitMap || data instanceof CalcitSliceMap) && proto.schema != null) {
+    let values: Array<CalcitValue> = new Array(proto.fields.length);
+    let given: Array<boolean> = new Array(proto.fields.length).fill(false);
+    let pairs = data.pairs();
+    for (let i = 0; i < pairs.length; i++) {
+      let field = castTag(pairs[i][0]);
+      let idx = findInFields(proto.fields, field);
+      if (idx < 0) {
+        throw new Error(`Cannot find field ${field} among (${proto.fields.join(", ")})`);
+      }
+      values[idx] = pairs[i][1];
+      given[idx] = true;
+    }
+    let filled = fillRecordValues(proto, values, given, "&record:from-map");
+    return new CalcitRecord(proto.name, proto.fields, filled, proto.klass, proto.schema);
   } else if (data instanceof CalcitMap || data instanceof CalcitSliceMap) {
     let pairs_buffer: Array<[CalcitTag, CalcitValue]> = [];
     let pairs = data.pairs();
@@ -298,3 +386,72 @@ export function _$n_record_$o_extend_as(obj: CalcitValue, new_name: CalcitValue,
 
   return new CalcitRecord(new_name_tag, new_fields, new_values);
 }
+
+/** attaches rules for fields, taking a map from field to a map of `:default`, `:optional` and `:validate` */
+export let _$n_record_$o_with_schema = (proto: CalcitValue, rules: CalcitValue): CalcitRecord => {
+  if (!(proto instanceof CalcitRecord)) {
+    throw new Error(`&record:with-schema expected a record, got: ${toString(proto, true)}`);
+  }
+  if (!(rules instanceof CalcitMap || rules instanceof CalcitSliceMap)) {
+    throw new Error(`&record:with-schema expected a map of rules, got: ${toString(rules, true)}`);
+  }
+  let schema: Array<RecordFieldSchema> = proto.fields.map(() => ({ hasDefault: false, default: null, optional: false, validator: null }));
+  for (let [k, options] of rules.pairs()) {
+    let idx = findInFields(proto.fields, castTag(k));
+    if (idx < 0) {
+      throw new Error(`&record:with-schema got unknown field ${toString(k, true)} for (${proto.fields.join(", ")})`);
+    }
+    if (!(options instanceof CalcitMap || options instanceof CalcitSliceMap)) {
+      throw new Error(`&record:with-schema expected a map of options for ${toString(k, true)}, got: ${toString(options, true)}`);
+    }
+    let rule = schema[idx];
+    for (let [option, v] of options.pairs()) {
+      let name = option instanceof CalcitTag ? option.value : null;
+      if (name === "default") {
+        rule.hasDefault = true;
+        rule.default = v;
+      } else if (name === "optional") {
+        if (typeof v !== "boolean") {
+          throw new Error(`&record:with-schema expected a bool for :optional of ${toString(k, true)}, got: ${toString(v, true)}`);
+        }
+        rule.optional = v;
+      } else if (name === "validate") {
+        if (typeof v !== "function") {
+          throw new Error(`&record:with-schema expected a function for :validate of ${toString(k, true)}, got: ${toString(v, true)}`);
+        }
+        rule.validator = v;
+      } else {
+        throw new Error(`&record:with-schema got unknown option ${toString(option, true)} for ${toString(k, true)}`);
+      }
+    }
+  }
+  return new CalcitRecord(proto.name, proto.fields, proto.values, proto.klass, schema);
+};
+
+/** rules of fields in the shape taken by `&record:with-schema`, `nil` for records without a schema */
+export let _$n_record_$o_schema = (x: CalcitValue): CalcitValue => {
+  if (!(x instanceof CalcitRecord)) {
+    throw new Error(`&record:schema expected a record, got: ${toString(x, true)}`);
+  }
+  if (x.schema == null) {
+    return null;
+  }
+  let rules: Array<CalcitValue> = [];
+  for (let idx = 0; idx < x.fields.length; idx++) {
+    let rule = x.schema[idx];
+    let options: Array<CalcitValue> = [];
+    if (rule.hasDefault) {
+      options.push(newTag("default"), rule.default);
+    }
+    if (rule.optional) {
+      options.push(newTag("optional"), true);
+    }
+    if (rule.validator != null) {
+      options.push(newTag("validate"), rule.validator);
+    }
+    if (options.length > 0) {
+      rules.push(x.fields[idx], new CalcitSliceMap(options));
+    }
+  }
+  return new CalcitSliceMap(rules);
+};
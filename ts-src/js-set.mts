@@ -1,4 +1,4 @@
-import { CalcitValue, isLiteral } from "./js-primes.mjs";
+import { CalcitValue, isLiteral, bisectSorted } from "./js-primes.mjs";
 import { toString } from "./calcit-data.mjs";
 import {
   TernaryTreeMap,
@@ -120,3 +120,88 @@ export class CalcitSet {
     return false;
   }
 }
+
+/** set with items kept in the order of `&compare`, tree is still used for lookups and hashing */
+export class CalcitSortedSet extends CalcitSet {
+  items: CalcitValue[];
+  constructor(value: TernaryTreeMap<CalcitValue, boolean>, items: CalcitValue[]) {
+    super(value);
+    this.items = items;
+  }
+  /** items are expected to be sorted and unique */
+  static fromSorted(items: CalcitValue[]): CalcitSortedSet {
+    let pairs: [CalcitValue, boolean][] = items.map((x) => [x, true]);
+    return new CalcitSortedSet(initTernaryTreeMapFromArray(pairs), items);
+  }
+  static from(xs: CalcitValue[]): CalcitSortedSet {
+    let items: CalcitValue[] = [];
+    for (let idx = 0; idx < xs.length; idx++) {
+      let [pos, found] = bisectSorted(items, xs[idx]);
+      if (!found) {
+        items.splice(pos, 0, xs[idx]);
+      }
+    }
+    return CalcitSortedSet.fromSorted(items);
+  }
+  include(y: CalcitValue): CalcitSortedSet {
+    if (this.contains(y)) {
+      return this;
+    }
+    let items = this.items.slice();
+    items.splice(bisectSorted(items, y)[0], 0, y);
+    return new CalcitSortedSet(assocMap(this.value, y, true), items);
+  }
+  exclude(y: CalcitValue): CalcitSortedSet {
+    if (!this.contains(y)) {
+      return this;
+    }
+    let items = this.items.slice();
+    let [pos, found] = bisectSorted(items, y);
+    if (found) {
+      items.splice(pos, 1);
+    }
+    return new CalcitSortedSet(dissocMap(this.value, y), items);
+  }
+  difference(ys: CalcitSet): CalcitSortedSet {
+    return CalcitSortedSet.fromSorted(this.items.filter((x) => !ys.contains(x)));
+  }
+  union(ys: CalcitSet): CalcitSortedSet {
+    let result: CalcitSortedSet = this;
+    for (let y of ys.values()) {
+      result = result.include(y);
+    }
+    return result;
+  }
+  intersection(ys: CalcitSet): CalcitSortedSet {
+    return CalcitSortedSet.fromSorted(this.items.filter((x) => ys.contains(x)));
+  }
+  destruct(): CalcitSliceList {
+    if (this.items.length === 0) {
+      return null;
+    }
+    let x0 = this.items[0];
+    return new CalcitSliceList([x0, this.exclude(x0)]);
+  }
+  first(): CalcitValue {
+    return this.items.length > 0 ? this.items[0] : null;
+  }
+  last(): CalcitValue {
+    return this.items.length > 0 ? this.items[this.items.length - 1] : null;
+  }
+  /** from `from` inclusive to `to` exclusive, `null` for no upper bound */
+  range(from: CalcitValue, to: CalcitValue): CalcitSortedSet {
+    let lo = bisectSorted(this.items, from)[0];
+    let hi = to == null ? this.items.length : bisectSorted(this.items, to)[0];
+    return CalcitSortedSet.fromSorted(this.items.slice(lo, hi));
+  }
+  toString(disableJsDataWarning: boolean = false) {
+    let itemsCode = "";
+    for (let idx = 0; idx < this.items.length; idx++) {
+      itemsCode = `${itemsCode} ${toString(this.items[idx], true, disableJsDataWarning)}`;
+    }
+    return `(sorted-set${itemsCode})`;
+  }
+  values() {
+    return this.items.slice();
+  }
+}
@@ -0,0 +1,457 @@
+import { CalcitValue } from "./js-primes.mjs";
+import { CalcitTag } from "./calcit-data.mjs";
+
+// timestamps are milliseconds since Unix epoch, same as in Rust.
+// calendar fields are computed on a "naive" timestamp, which is the wall-clock time of a zone read with UTC methods.
+
+type TimeZone = { kind: "fixed"; minutes: number } | { kind: "local" };
+
+type TimeUnit = "millisecond" | "second" | "minute" | "hour" | "day" | "week" | "month" | "year";
+
+const fixedUnitMillis: Record<string, number> = {
+  millisecond: 1,
+  second: 1_000,
+  minute: 60_000,
+  hour: 3_600_000,
+  day: 86_400_000,
+  week: 604_800_000,
+};
+
+const weekdayNames = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const monthNames = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+const supportedSpecifiers = "%Y %y %m %d %e %H %I %p %M %S %3f %j %a %A %b %B %z %:z %s %%";
+
+let readUnit = (x: CalcitValue, procName: string): TimeUnit => {
+  let name: string;
+  if (x instanceof CalcitTag) {
+    name = x.value;
+  } else if (typeof x === "string") {
+    name = x;
+  } else {
+    throw new Error(`${procName} expected a unit tag, got: ${x}`);
+  }
+  if (["millisecond", "second", "minute", "hour", "day", "week", "month", "year"].includes(name)) {
+    return name as TimeUnit;
+  }
+  throw new Error(`${procName} got unknown unit \`${name}\`, expected :millisecond :second :minute :hour :day :week :month :year`);
+};
+
+let parseZoneStr = (s: string): TimeZone => {
+  if (s === "Z" || s === "z" || s === "utc" || s === "UTC") {
+    return { kind: "fixed", minutes: 0 };
+  }
+  if (s === "local") {
+    return { kind: "local" };
+  }
+  let m = s.match(/^([+-])(\d{2}):?(\d{2})?$/);
+  if (m == null || (m[3] != null && parseInt(m[3]) >= 60)) {
+    return null;
+  }
+  let sign = m[1] === "+" ? 1 : -1;
+  return { kind: "fixed", minutes: sign * (parseInt(m[2]) * 60 + parseInt(m[3] ?? "0")) };
+};
+
+let readZone = (x: CalcitValue, procName: string): TimeZone => {
+  if (x == null) {
+    return { kind: "fixed", minutes: 0 };
+  }
+  if (typeof x === "number") {
+    if (!Number.isInteger(x) || Math.abs(x) >= 24 * 60) {
+      throw new Error(`${procName} got invalid offset in minutes: ${x}`);
+    }
+    return { kind: "fixed", minutes: x };
+  }
+  let s = x instanceof CalcitTag ? x.value : x;
+  if (typeof s === "string") {
+    let zone = parseZoneStr(s);
+    if (zone == null || (zone.kind === "fixed" && Math.abs(zone.minutes) >= 24 * 60)) {
+      throw new Error(`${procName} got invalid time zone: ${s}`);
+    }
+    return zone;
+  }
+  throw new Error(`${procName} expected a time zone, got: ${x}`);
+};
+
+let readTimestamp = (x: CalcitValue, procName: string): number => {
+  if (typeof x === "number" && Number.isFinite(x)) {
+    return x;
+  }
+  throw new Error(`${procName} expected a timestamp in milliseconds, got: ${x}`);
+};
+
+let offsetMinutes = (t: number, zone: TimeZone): number => {
+  if (zone.kind === "fixed") {
+    return zone.minutes;
+  }
+  return -new Date(t).getTimezoneOffset();
+};
+
+let toNaive = (t: number, zone: TimeZone): number => {
+  return t + offsetMinutes(t, zone) * 60_000;
+};
+
+let fromNaive = (naive: number, zone: TimeZone): number => {
+  if (zone.kind === "fixed") {
+    return naive - zone.minutes * 60_000;
+  }
+  let d = new Date(naive);
+  let local = new Date(0);
+  local.setFullYear(d.getUTCFullYear(), d.getUTCMonth(), d.getUTCDate());
+  local.setHours(d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds(), d.getUTCMilliseconds());
+  return local.getTime();
+};
+
+let naiveFromFields = (year: number, month: number, day: number, hour = 0, minute = 0, second = 0, milli = 0): number => {
+  let d = new Date(0);
+  d.setUTCFullYear(year, month, day);
+  d.setUTCHours(hour, minute, second, milli);
+  return d.getTime();
+};
+
+let pad = (n: number, size: number, fill = "0"): string => {
+  let s = `${Math.abs(n)}`;
+  while (s.length < size) {
+    s = fill + s;
+  }
+  return n < 0 ? `-${s}` : s;
+};
+
+let formatOffset = (minutes: number, colon: boolean): string => {
+  let sign = minutes < 0 ? "-" : "+";
+  let abs = Math.abs(minutes);
+  return `${sign}${pad(Math.floor(abs / 60), 2)}${colon ? ":" : ""}${pad(abs % 60, 2)}`;
+};
+
+let checkPattern = (pattern: string, procName: string): void => {
+  let ok = pattern.replace(/%(:z|3f|[YymdeHIpMSjaAbBzs%])/g, "").indexOf("%") < 0;
+  if (!ok) {
+    throw new Error(`${procName} got unsupported pattern \`${pattern}\`, specifiers are limited to ${supportedSpecifiers}`);
+  }
+};
+
+let dayOfYear = (d: Date): number => {
+  return Math.round((d.getTime() - naiveFromFields(d.getUTCFullYear(), 0, 1)) / 86_400_000) + 1;
+};
+
+/** wall-clock time in milliseconds since Unix epoch */
+export let now_$x_ = (): number => {
+  return Date.now();
+};
+
+/** milliseconds from a monotonic clock, only useful for measuring durations */
+export let monotonic_now_$x_ = (): number => {
+  return performance.now();
+};
+
+export let format_time = (t: CalcitValue, pattern: CalcitValue = null, zoneArg: CalcitValue = null): string => {
+  let time = readTimestamp(t, "format-time");
+  let zone = readZone(zoneArg, "format-time");
+  let offset = offsetMinutes(time, zone);
+  let d = new Date(time + offset * 60_000);
+  if (pattern == null) {
+    let date = `${pad(d.getUTCFullYear(), 4)}-${pad(d.getUTCMonth() + 1, 2)}-${pad(d.getUTCDate(), 2)}`;
+    let clock = `${pad(d.getUTCHours(), 2)}:${pad(d.getUTCMinutes(), 2)}:${pad(d.getUTCSeconds(), 2)}.${pad(d.getUTCMilliseconds(), 3)}`;
+    return `${date}T${clock}${offset === 0 ? "Z" : formatOffset(offset, true)}`;
+  }
+  if (typeof pattern !== "string") {
+    throw new Error(`format-time expected a pattern string, got: ${pattern}`);
+  }
+  checkPattern(pattern, "format-time");
+  return pattern.replace(/%(:z|3f|[YymdeHIpMSjaAbBzs%])/g, (_, spec: string) => {
+    switch (spec) {
+      case "Y":
+        return pad(d.getUTCFullYear(), 4);
+      case "y":
+        return pad(((d.getUTCFullYear() % 100) + 100) % 100, 2);
+      case "m":
+        return pad(d.getUTCMonth() + 1, 2);
+      case "d":
+        return pad(d.getUTCDate(), 2);
+      case "e":
+        return pad(d.getUTCDate(), 2, " ");
+      case "H":
+        return pad(d.getUTCHours(), 2);
+      case "I":
+        return pad(((d.getUTCHours() + 11) % 12) + 1, 2);
+      case "p":
+        return d.getUTCHours() < 12 ? "AM" : "PM";
+      case "M":
+        return pad(d.getUTCMinutes(), 2);
+      case "S":
+        return pad(d.getUTCSeconds(), 2);
+      case "3f":
+        return pad(d.getUTCMilliseconds(), 3);
+      case "j":
+        return pad(dayOfYear(d), 3);
+      case "a":
+        return weekdayNames[d.getUTCDay()].slice(0, 3);
+      case "A":
+        return weekdayNames[d.getUTCDay()];
+      case "b":
+        return monthNames[d.getUTCMonth()].slice(0, 3);
+      case "B":
+        return monthNames[d.getUTCMonth()];
+      case "z":
+        return formatOffset(offset, false);
+      case ":z":
+        return formatOffset(offset, true);
+      case "s":
+        return `${Math.floor(time / 1000)}`;
+      default:
+        return "%";
+    }
+  });
+};
+
+let parseIso = (s: string, zone: TimeZone): number => {
+  let m = s.match(/^(\d{4})-(\d{2})-(\d{2})(?:[Tt ](\d{2}):(\d{2})(?::(\d{2})(?:\.(\d+))?([Zz]|[+-]\d{2}:\d{2})?)?)?$/);
+  if (m == null) {
+    return null;
+  }
+  let milli = m[7] != null ? Math.round(parseFloat(`0.${m[7]}`) * 1000) : 0;
+  let naive = naiveFromFields(parseInt(m[1]), parseInt(m[2]) - 1, parseInt(m[3]), parseInt(m[4] ?? "0"), parseInt(m[5] ?? "0"), parseInt(m[6] ?? "0"), milli);
+  if (m[8] != null) {
+    return fromNaive(naive, parseZoneStr(m[8]));
+  }
+  return fromNaive(naive, zone);
+};
+
+let escapeRegex = (s: string): string => {
+  return s.replace(/[.*+?^${}()|[\]\\]/g, "\\$&");
+};
+
+let parseWithPattern = (s: string, pattern: string, zone: TimeZone): number => {
+  let fields: string[] = [];
+  let source = "";
+  let last = 0;
+  let specRegex = /%(:z|3f|[YymdeHIpMSjaAbBzs%])/g;
+  let matched: RegExpExecArray;
+  while ((matched = specRegex.exec(pattern)) != null) {
+    source += escapeRegex(pattern.slice(last, matched.index));
+    last = matched.index + matched[0].length;
+    let spec = matched[1];
+    let piece: string;
+    switch (spec) {
+      case "Y":
+        piece = "(\\d{4})";
+        break;
+      case "y":
+      case "3f":
+        piece = spec === "y" ? "(\\d{2})" : "(\\d{3})";
+        break;
+      case "j":
+        piece = "(\\d{3})";
+        break;
+      case "e":
+        piece = "\\s?(\\d{1,2})";
+        break;
+      case "a":
+      case "A":
+        piece = `(${weekdayNames.map((x) => (spec === "a" ? x.slice(0, 3) : x)).join("|")})`;
+        break;
+      case "b":
+      case "B":
+        piece = `(${monthNames.map((x) => (spec === "b" ? x.slice(0, 3) : x)).join("|")})`;
+        break;
+      case "p":
+        piece = "(AM|PM|am|pm)";
+        break;
+      case "z":
+        piece = "([+-]\\d{4})";
+        break;
+      case ":z":
+        piece = "([+-]\\d{2}:\\d{2})";
+        break;
+      case "s":
+        piece = "(-?\\d+)";
+        break;
+      case "%":
+        piece = "%";
+        break;
+      default:
+        piece = "(\\d{1,2})";
+    }
+    if (spec !== "%") {
+      fields.push(spec);
+    }
+    source += piece;
+  }
+  source += escapeRegex(pattern.slice(last));
+  let m = s.match(new RegExp(`^${source}$`));
+  if (m == null) {
+    return null;
+  }
+  let values: Record<string, string> = {};
+  fields.forEach((spec, idx) => {
+    values[spec] = m[idx + 1];
+  });
+  if (values.s != null) {
+    return parseInt(values.s) * 1000;
+  }
+  let year = values.Y != null ? parseInt(values.Y) : values.y != null ? (parseInt(values.y) < 70 ? 2000 : 1900) + parseInt(values.y) : null;
+  let monthName = values.B ?? values.b;
+  let month = values.m != null ? parseInt(values.m) - 1 : monthName != null ? monthNames.findIndex((x) => x.startsWith(monthName)) : null;
+  let day = values.d ?? values.e;
+  if (year == null || ((month == null || day == null) && values.j == null)) {
+    return null;
+  }
+  let naiveDay = values.j != null ? naiveFromFields(year, 0, parseInt(values.j)) : naiveFromFields(year, month, parseInt(day));
+  let check = new Date(naiveDay);
+  if (values.j == null && (check.getUTCMonth() !== month || check.getUTCDate() !== parseInt(day))) {
+    return null;
+  }
+  let hour = 0;
+  if (values.H != null) {
+    hour = parseInt(values.H);
+  } else if (values.I != null) {
+    if (values.p == null) {
+      return null;
+    }
+    hour = (parseInt(values.I) % 12) + (values.p.toUpperCase() === "PM" ? 12 : 0);
+  }
+  let hasClock = values.H != null || values.I != null;
+  if (hasClock && values.M == null) {
+    return null;
+  }
+  let minute = parseInt(values.M ?? "0");
+  let second = parseInt(values.S ?? "0");
+  let milli = parseInt(values["3f"] ?? "0");
+  if (hour > 23 || minute > 59 || second > 59) {
+    return null;
+  }
+  let naive = naiveDay + ((hour * 60 + minute) * 60 + second) * 1000 + milli;
+  let offsetText = values[":z"] ?? values.z;
+  if (offsetText != null) {
+    return fromNaive(naive, parseZoneStr(offsetText));
+  }
+  return fromNaive(naive, zone);
+};
+
+export let parse_time = (s: CalcitValue, pattern: CalcitValue = null, zoneArg: CalcitValue = null): number => {
+  if (typeof s !== "string") {
+    throw new Error(`parse-time expected a string, got: ${s}`);
+  }
+  let zone = readZone(zoneArg, "parse-time");
+  if (pattern == null) {
+    let t = parseIso(s, zone);
+    if (t == null) {
+      throw new Error(`parse-time failed to parse \`${s}\` as ISO 8601`);
+    }
+    return t;
+  }
+  if (typeof pattern !== "string") {
+    throw new Error(`parse-time expected a pattern string, got: ${pattern}`);
+  }
+  checkPattern(pattern, "parse-time");
+  let t = parseWithPattern(s, pattern, zone);
+  if (t == null) {
+    throw new Error(`parse-time failed to parse \`${s}\` with pattern \`${pattern}\``);
+  }
+  return t;
+};
+
+/** month overflow clamps to the last day of the target month */
+let addMonths = (naive: number, n: number): number => {
+  let d = new Date(naive);
+  let total = d.getUTCFullYear() * 12 + d.getUTCMonth() + n;
+  let year = Math.floor(total / 12);
+  let month = total - year * 12;
+  let lastDay = new Date(naiveFromFields(year, month + 1, 0)).getUTCDate();
+  let day = Math.min(d.getUTCDate(), lastDay);
+  return naiveFromFields(year, month, day, d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds(), d.getUTCMilliseconds());
+};
+
+export let time_add = (t: CalcitValue, n: CalcitValue, unitArg: CalcitValue, zoneArg: CalcitValue = null): number => {
+  let time = readTimestamp(t, "time-add");
+  if (typeof n !== "number") {
+    throw new Error(`time-add expected an amount in number, got: ${n}`);
+  }
+  let unit = readUnit(unitArg, "time-add");
+  let zone = readZone(zoneArg, "time-add");
+  switch (unit) {
+    case "month":
+    case "year": {
+      let months = unit === "year" ? n * 12 : n;
+      if (!Number.isInteger(months)) {
+        throw new Error(`time-add expected integer for months, cannot extract int from float: ${months}`);
+      }
+      return fromNaive(addMonths(toNaive(time, zone), months), zone);
+    }
+    case "day":
+    case "week": {
+      let days = unit === "week" ? n * 7 : n;
+      if (!Number.isInteger(days)) {
+        throw new Error(`time-add expected integer for days, cannot extract int from float: ${days}`);
+      }
+      return fromNaive(toNaive(time, zone) + days * 86_400_000, zone);
+    }
+    default:
+      return time + n * fixedUnitMillis[unit];
+  }
+};
+
+/** measures `a - b`, months and years are counted in whole calendar months */
+export let time_diff = (a: CalcitValue, b: CalcitValue, unitArg: CalcitValue, zoneArg: CalcitValue = null): number => {
+  let ta = readTimestamp(a, "time-diff");
+  let tb = readTimestamp(b, "time-diff");
+  let unit = readUnit(unitArg, "time-diff");
+  let zone = readZone(zoneArg, "time-diff");
+  if (fixedUnitMillis[unit] != null) {
+    return (ta - tb) / fixedUnitMillis[unit];
+  }
+  let za = toNaive(ta, zone);
+  let zb = toNaive(tb, zone);
+  let da = new Date(za);
+  let db = new Date(zb);
+  let months = (da.getUTCFullYear() - db.getUTCFullYear()) * 12 + (da.getUTCMonth() - db.getUTCMonth());
+  // step back when the last month is not completed
+  if (months > 0 && addMonths(zb, months) > za) {
+    months -= 1;
+  } else if (months < 0 && addMonths(zb, months) < za) {
+    months += 1;
+  }
+  if (unit === "year") {
+    return Math.trunc(months / 12);
+  }
+  return months;
+};
+
+/** moves to the start of the unit, weeks start from Monday */
+export let time_truncate = (t: CalcitValue, unitArg: CalcitValue, zoneArg: CalcitValue = null): number => {
+  let time = readTimestamp(t, "time-truncate");
+  let unit = readUnit(unitArg, "time-truncate");
+  let zone = readZone(zoneArg, "time-truncate");
+  let d = new Date(toNaive(time, zone));
+  let year = d.getUTCFullYear();
+  let month = d.getUTCMonth();
+  let day = d.getUTCDate();
+  let naive: number;
+  switch (unit) {
+    case "millisecond":
+      naive = naiveFromFields(year, month, day, d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds(), d.getUTCMilliseconds());
+      break;
+    case "second":
+      naive = naiveFromFields(year, month, day, d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds());
+      break;
+    case "minute":
+      naive = naiveFromFields(year, month, day, d.getUTCHours(), d.getUTCMinutes());
+      break;
+    case "hour":
+      naive = naiveFromFields(year, month, day, d.getUTCHours());
+      break;
+    case "day":
+      naive = naiveFromFields(year, month, day);
+      break;
+    case "week":
+      naive = naiveFromFields(year, month, day - ((d.getUTCDay() + 6) % 7));
+      break;
+    case "month":
+      naive = naiveFromFields(year, month, 1);
+      break;
+    case "year":
+      naive = naiveFromFields(year, 0, 1);
+      break;
+  }
+  return fromNaive(naive, zone);
+};
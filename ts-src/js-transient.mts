@@ -0,0 +1,258 @@
+import { CalcitValue, _$n_compare } from "./js-primes.mjs";
+import { CalcitList, CalcitSliceList } from "./js-list.mjs";
+import { CalcitMap, CalcitSliceMap, CalcitSortedMap } from "./js-map.mjs";
+import { CalcitSet, CalcitSortedSet } from "./js-set.mjs";
+import { toString, hashFunction, _$n__$e_ } from "./calcit-data.mjs";
+import { initTernaryTreeMapFromArray } from "@calcit/ternary-tree";
+
+// transients, kept in sync with `src/builtins/transients.rs`.
+// items are collected in plain arrays and hash buckets, persistent data is built once in `persistent!`.
+
+let transientIdGen = 0;
+
+type TransientKind = "list" | "map" | "set" | "frozen";
+
+export class CalcitTransient {
+  id: number;
+  kind: TransientKind;
+  /** from a sorted map or a sorted set, sorted again when frozen */
+  sorted: boolean;
+  /** items of a list */
+  items: CalcitValue[];
+  /** entries of maps and sets grouped by hashes of keys, values are `true` for sets */
+  buckets: Map<number, [CalcitValue, CalcitValue][]>;
+  size: number;
+
+  constructor(x: CalcitValue) {
+    this.id = transientIdGen++;
+    this.sorted = x instanceof CalcitSortedMap || x instanceof CalcitSortedSet;
+    this.items = [];
+    this.buckets = new Map();
+    this.size = 0;
+    if (x instanceof CalcitList || x instanceof CalcitSliceList) {
+      this.kind = "list";
+      this.items = x.toArray().slice();
+    } else if (x instanceof CalcitMap || x instanceof CalcitSliceMap) {
+      this.kind = "map";
+      for (let [k, v] of x.pairs()) {
+        this.put(k, v);
+      }
+    } else if (x instanceof CalcitSet) {
+      this.kind = "set";
+      for (let y of x.values()) {
+        this.put(y, true);
+      }
+    } else {
+      throw new Error(`transient expected a list, a map or a set, got: ${toString(x, true)}`);
+    }
+  }
+
+  len(): number {
+    return this.kind === "list" ? this.items.length : this.size;
+  }
+
+  lookup(k: CalcitValue): [CalcitValue, CalcitValue] {
+    let bucket = this.buckets.get(hashFunction(k));
+    if (bucket != null) {
+      for (let entry of bucket) {
+        if (_$n__$e_(entry[0], k)) {
+          return entry;
+        }
+      }
+    }
+    return null;
+  }
+
+  put(k: CalcitValue, v: CalcitValue): void {
+    let h = hashFunction(k);
+    let bucket = this.buckets.get(h);
+    if (bucket == null) {
+      this.buckets.set(h, [[k, v]]);
+      this.size++;
+      return;
+    }
+    for (let entry of bucket) {
+      if (_$n__$e_(entry[0], k)) {
+        entry[1] = v;
+        return;
+      }
+    }
+    bucket.push([k, v]);
+    this.size++;
+  }
+
+  remove(k: CalcitValue): void {
+    let h = hashFunction(k);
+    let bucket = this.buckets.get(h);
+    if (bucket == null) {
+      return;
+    }
+    let idx = bucket.findIndex((entry) => _$n__$e_(entry[0], k));
+    if (idx >= 0) {
+      bucket.splice(idx, 1);
+      this.size--;
+      if (bucket.length === 0) {
+        this.buckets.delete(h);
+      }
+    }
+  }
+
+  entries(): [CalcitValue, CalcitValue][] {
+    let ret: [CalcitValue, CalcitValue][] = [];
+    for (let bucket of this.buckets.values()) {
+      for (let entry of bucket) {
+        ret.push([entry[0], entry[1]]);
+      }
+    }
+    if (this.sorted) {
+      ret.sort((a, b) => _$n_compare(a[0], b[0]));
+    }
+    return ret;
+  }
+
+  /** builds persistent data and leaves the transient frozen */
+  freeze(): CalcitValue {
+    let ret: CalcitValue;
+    switch (this.kind) {
+      case "list":
+        ret = new CalcitSliceList(this.items);
+        break;
+      case "map": {
+        let pairs = this.entries();
+        if (this.sorted) {
+          ret = new CalcitSortedMap(
+            initTernaryTreeMapFromArray(pairs),
+            pairs.map((pair) => pair[0])
+          );
+        } else {
+          ret = new CalcitMap(initTernaryTreeMapFromArray(pairs));
+        }
+        break;
+      }
+      case "set": {
+        let pairs = this.entries().map((pair): [CalcitValue, boolean] => [pair[0], true]);
+        if (this.sorted) {
+          ret = new CalcitSortedSet(
+            initTernaryTreeMapFromArray(pairs),
+            pairs.map((pair) => pair[0])
+          );
+        } else {
+          ret = new CalcitSet(initTernaryTreeMapFromArray(pairs));
+        }
+        break;
+      }
+      case "frozen":
+        throw new Error("persistent! expected a transient in use, it is already persistent");
+    }
+    this.kind = "frozen";
+    this.items = [];
+    this.buckets = new Map();
+    this.size = 0;
+    return ret;
+  }
+
+  toString(): string {
+    return `(&transient ${this.id} :${this.kind} ${this.len()})`;
+  }
+}
+
+/** checks the transient is in use and of expected kinds */
+let readTransient = (x: CalcitValue, procName: string, kinds: TransientKind[], expected: string): CalcitTransient => {
+  if (!(x instanceof CalcitTransient)) {
+    throw new Error(`${procName} expected a transient, got: ${toString(x, true)}`);
+  }
+  if (x.kind === "frozen") {
+    throw new Error(`${procName} expected a transient in use, it is already persistent`);
+  }
+  if (!kinds.includes(x.kind)) {
+    throw new Error(`${procName} expected a transient ${expected}, got a transient ${x.kind}`);
+  }
+  return x;
+};
+
+let readIndex = (x: CalcitValue, procName: string): number => {
+  if (typeof x !== "number") {
+    throw new Error(`${procName} expected an index, got: ${toString(x, true)}`);
+  }
+  if (!Number.isInteger(x)) {
+    throw new Error(`${procName} expected an index, cannot extract usize from float: ${x}`);
+  }
+  if (x < 0) {
+    throw new Error(`${procName} expected an index, usize expected a positive number, but got: ${x}`);
+  }
+  return x;
+};
+
+export let transient = (x: CalcitValue): CalcitTransient => {
+  return new CalcitTransient(x);
+};
+
+export let transient_$q_ = (x: CalcitValue): boolean => {
+  return x instanceof CalcitTransient;
+};
+
+export let persistent_$x_ = (x: CalcitValue): CalcitValue => {
+  if (!(x instanceof CalcitTransient)) {
+    throw new Error(`persistent! expected a transient, got: ${toString(x, true)}`);
+  }
+  return x.freeze();
+};
+
+export let _$n_transient_$o_count = (x: CalcitValue): number => {
+  return readTransient(x, "&transient:count", ["list", "map", "set"], "").len();
+};
+
+export let _$n_transient_$o_get = (x: CalcitValue, k: CalcitValue): CalcitValue => {
+  let t = readTransient(x, "&transient:get", ["list", "map"], "list or map");
+  if (t.kind === "list") {
+    return t.items[readIndex(k, "&transient:get")] ?? null;
+  }
+  let entry = t.lookup(k);
+  return entry == null ? null : entry[1];
+};
+
+export let _$n_transient_$o_append_$x_ = (x: CalcitValue, y: CalcitValue): CalcitTransient => {
+  let t = readTransient(x, "&transient:append!", ["list"], "list");
+  t.items.push(y);
+  return t;
+};
+
+export let _$n_transient_$o_assoc_$x_ = (x: CalcitValue, k: CalcitValue, v: CalcitValue): CalcitTransient => {
+  let t = readTransient(x, "&transient:assoc!", ["list", "map"], "list or map");
+  if (t.kind === "list") {
+    let idx = readIndex(k, "&transient:assoc!");
+    if (idx >= t.items.length) {
+      throw new Error(`&transient:assoc! expected an index within 0~${t.items.length}, got: ${idx}`);
+    }
+    t.items[idx] = v;
+  } else {
+    t.put(k, v);
+  }
+  return t;
+};
+
+export let _$n_transient_$o_dissoc_$x_ = (x: CalcitValue, k: CalcitValue): CalcitTransient => {
+  let t = readTransient(x, "&transient:dissoc!", ["list", "map"], "list or map");
+  if (t.kind === "list") {
+    let idx = readIndex(k, "&transient:dissoc!");
+    if (idx >= t.items.length) {
+      throw new Error(`&transient:dissoc! expected an index within 0~${t.items.length}, got: ${idx}`);
+    }
+    t.items.splice(idx, 1);
+  } else {
+    t.remove(k);
+  }
+  return t;
+};
+
+export let _$n_transient_$o_include_$x_ = (x: CalcitValue, y: CalcitValue): CalcitTransient => {
+  let t = readTransient(x, "&transient:include!", ["set"], "set");
+  t.put(y, true);
+  return t;
+};
+
+export let _$n_transient_$o_exclude_$x_ = (x: CalcitValue, y: CalcitValue): CalcitTransient => {
+  let t = readTransient(x, "&transient:exclude!", ["set"], "set");
+  t.remove(y);
+  return t;
+};